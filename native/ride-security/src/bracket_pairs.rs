@@ -0,0 +1,490 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Bracket Pairs — Rust port of `src/vs/editor/common/model/bracketPairsTextModelPart/`.
+//!
+//! Brackets are scanned per line, skipping the ones inside string, comment and
+//! regex tokens, and cached until an edit or a retokenization touches the line.
+//! The stack of open brackets at each line start is a persistent linked list,
+//! recomputed lazily from the first changed line, so after an edit only the
+//! changed lines are rescanned and only the lines up to a query are walked.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use napi_derive::napi;
+use crate::position::Position;
+use crate::range::Range;
+use crate::tokenizer::Token;
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Token types whose brackets do not count.
+const SKIPPED_TOKEN_TYPES: [&str; 3] = ["string", "comment", "regex"];
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorizedBracket {
+    pub range: Range,
+    /// Nesting level of the pair (0 = outermost); the renderer cycles colors by it.
+    pub level: u32,
+    /// A closing bracket without an opener, or an opener closed by an outer bracket.
+    pub is_unmatched: bool,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BracketPairInfo {
+    pub open_range: Range,
+    /// `None` when the bracket is never closed.
+    pub close_range: Option<Range>,
+    pub level: u32,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BracketPairGuide {
+    pub start_line_number: u32,
+    pub end_line_number: u32,
+    /// 1-based column the guide is drawn at: the smaller indentation of its two lines.
+    pub column: u32,
+    pub level: u32,
+    /// The innermost pair around the active position.
+    pub is_active: bool,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BracketDiagnostic {
+    pub range: Range,
+    pub message: String,
+}
+
+/// Lines as the bracket store sees them: text plus up-to-date tokens.
+pub(crate) trait BracketLineSource {
+    /// Brings the tokens of `line_index` up to date and returns the 0-based
+    /// inclusive line ranges that were retokenized on the way.
+    fn retokenize_until(&mut self, line_index: usize) -> Vec<(usize, usize)>;
+    fn content(&mut self, line_index: usize) -> String;
+    fn tokens(&mut self, line_index: usize) -> Vec<Token>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LineBracket {
+    column: u32,
+    kind: usize,
+    is_open: bool,
+}
+
+#[derive(Debug)]
+struct OpenNode {
+    line_number: u32,
+    column: u32,
+    kind: usize,
+    depth: u32,
+    parent: OpenStack,
+}
+
+type OpenStack = Option<Arc<OpenNode>>;
+
+impl OpenNode {
+    fn position(&self) -> Position {
+        Position::new(self.line_number, self.column)
+    }
+
+    fn range(&self) -> Range {
+        Range::new(self.line_number, self.column, self.line_number, self.column + 1)
+    }
+}
+
+enum BracketEvent {
+    Open(Arc<OpenNode>),
+    /// A closing bracket and the opener it closes, if any.
+    Close { line_number: u32, column: u32, kind: usize, opener: Option<Arc<OpenNode>> },
+    /// An opener dropped because a closing bracket matched one further out.
+    Unclosed(Arc<OpenNode>),
+}
+
+/// Runs one line's brackets against `stack`, reporting every event, and
+/// returns the stack at the line end.
+fn apply_line(mut stack: OpenStack, line_number: u32, brackets: &[LineBracket], on_event: &mut impl FnMut(BracketEvent)) -> OpenStack {
+    for b in brackets {
+        if b.is_open {
+            let depth = stack.as_ref().map_or(0, |n| n.depth + 1);
+            let node = Arc::new(OpenNode { line_number, column: b.column, kind: b.kind, depth, parent: stack.take() });
+            on_event(BracketEvent::Open(node.clone()));
+            stack = Some(node);
+            continue;
+        }
+
+        let mut candidate = stack.clone();
+        while let Some(node) = candidate.as_ref().filter(|n| n.kind != b.kind) {
+            candidate = node.parent.clone();
+        }
+        match candidate {
+            Some(opener) => {
+                let mut top = stack.take();
+                while let Some(node) = top.filter(|n| !Arc::ptr_eq(n, &opener)) {
+                    top = node.parent.clone();
+                    on_event(BracketEvent::Unclosed(node));
+                }
+                stack = opener.parent.clone();
+                on_event(BracketEvent::Close { line_number, column: b.column, kind: b.kind, opener: Some(opener) });
+            }
+            None => on_event(BracketEvent::Close { line_number, column: b.column, kind: b.kind, opener: None }),
+        }
+    }
+    stack
+}
+
+fn scan_line(content: &str, tokens: &[Token]) -> Vec<LineBracket> {
+    let mut brackets = Vec::new();
+    let mut token_index = 0;
+    for (column, (byte, ch)) in content.char_indices().enumerate() {
+        while token_index < tokens.len() && (tokens[token_index].start_index + tokens[token_index].length) as usize <= byte {
+            token_index += 1;
+        }
+        let skipped = tokens.get(token_index).is_some_and(|t| {
+            t.start_index as usize <= byte && SKIPPED_TOKEN_TYPES.contains(&t.token_type.as_str())
+        });
+        if skipped {
+            continue;
+        }
+        if let Some(kind) = BRACKETS.iter().position(|&(open, _)| open == ch) {
+            brackets.push(LineBracket { column: column as u32 + 1, kind, is_open: true });
+        } else if let Some(kind) = BRACKETS.iter().position(|&(_, close)| close == ch) {
+            brackets.push(LineBracket { column: column as u32 + 1, kind, is_open: false });
+        }
+    }
+    brackets
+}
+
+fn indent_column(content: &str) -> u32 {
+    content.chars().take_while(|c| c.is_whitespace()).count() as u32 + 1
+}
+
+#[derive(Default)]
+pub(crate) struct BracketPairsStore {
+    /// Brackets of each line (`None` = must be rescanned).
+    lines: Vec<Option<Vec<LineBracket>>>,
+    /// Open brackets at the start of each line; only the first `valid_stacks` are current.
+    begin_stacks: Vec<OpenStack>,
+    valid_stacks: usize,
+}
+
+impl BracketPairsStore {
+    pub fn new(line_count: usize) -> Self {
+        let mut store = Self::default();
+        store.reset(line_count);
+        store
+    }
+
+    pub fn reset(&mut self, line_count: usize) {
+        self.lines = vec![None; line_count];
+        self.begin_stacks = vec![None; line_count];
+        self.valid_stacks = 1;
+    }
+
+    /// Mirrors a single edit the same way `TokenizationStateStore::accept_edit` does.
+    pub fn accept_edit(&mut self, start_line_index: usize, removed_line_breaks: usize, inserted_line_breaks: usize) {
+        if start_line_index >= self.lines.len() {
+            return;
+        }
+        let remove_end = (start_line_index + 1 + removed_line_breaks).min(self.lines.len());
+        let insert_at = start_line_index + 1;
+        self.lines.splice(insert_at..remove_end, std::iter::repeat_n(None, inserted_line_breaks));
+        self.begin_stacks.splice(insert_at..remove_end, std::iter::repeat_n(None, inserted_line_breaks));
+        self.invalidate_lines(start_line_index, start_line_index);
+    }
+
+    /// Forgets the brackets of lines `from..=to` (e.g. after they were retokenized).
+    pub fn invalidate_lines(&mut self, from: usize, to: usize) {
+        let end = (to + 1).min(self.lines.len());
+        for line in self.lines.iter_mut().take(end).skip(from) {
+            *line = None;
+        }
+        self.valid_stacks = self.valid_stacks.min(from + 1);
+    }
+
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn brackets(&mut self, line_index: usize, source: &mut impl BracketLineSource) -> Vec<LineBracket> {
+        for (from, to) in source.retokenize_until(line_index) {
+            self.invalidate_lines(from, to);
+        }
+        if let Some(Some(brackets)) = self.lines.get(line_index) {
+            return brackets.clone();
+        }
+        let brackets = scan_line(&source.content(line_index), &source.tokens(line_index));
+        self.lines[line_index] = Some(brackets.clone());
+        brackets
+    }
+
+    fn begin_stack(&mut self, line_index: usize, source: &mut impl BracketLineSource) -> OpenStack {
+        while self.valid_stacks <= line_index {
+            let previous = self.valid_stacks - 1;
+            let brackets = self.brackets(previous, source);
+            let stack = apply_line(self.begin_stacks[previous].clone(), previous as u32 + 1, &brackets, &mut |_| {});
+            self.store_stack(previous + 1, stack);
+        }
+        self.begin_stacks[line_index].clone()
+    }
+
+    fn store_stack(&mut self, line_index: usize, stack: OpenStack) {
+        if line_index == self.valid_stacks && line_index < self.line_count() {
+            self.begin_stacks[line_index] = stack;
+            self.valid_stacks += 1;
+        }
+    }
+
+    /// Feeds lines from `from_line_index` on to `visit` together with their
+    /// starting stack, until it returns `false`. `visit` returns the stack at
+    /// the line end, which is cached for the next line.
+    fn walk(
+        &mut self,
+        from_line_index: usize,
+        source: &mut impl BracketLineSource,
+        mut visit: impl FnMut(u32, &[LineBracket], OpenStack) -> (OpenStack, bool),
+    ) {
+        for index in from_line_index..self.line_count() {
+            let stack = self.begin_stack(index, source);
+            let brackets = self.brackets(index, source);
+            let (end_stack, go_on) = visit(index as u32 + 1, &brackets, stack);
+            self.store_stack(index + 1, end_stack);
+            if !go_on {
+                break;
+            }
+        }
+    }
+
+    pub fn colorized_brackets(&mut self, start_line_number: u32, end_line_number: u32, source: &mut impl BracketLineSource) -> Vec<ColorizedBracket> {
+        let mut result: Vec<ColorizedBracket> = Vec::new();
+        let start_index = start_line_number.max(1) as usize - 1;
+        self.walk(start_index, source, |line_number, brackets, stack| {
+            let end = apply_line(stack, line_number, brackets, &mut |event| match event {
+                BracketEvent::Open(node) => result.push(ColorizedBracket { range: node.range(), level: node.depth, is_unmatched: false }),
+                BracketEvent::Close { line_number, column, opener, .. } => result.push(ColorizedBracket {
+                    range: Range::new(line_number, column, line_number, column + 1),
+                    level: opener.as_ref().map_or(0, |o| o.depth),
+                    is_unmatched: opener.is_none(),
+                }),
+                BracketEvent::Unclosed(node) => {
+                    if let Some(open) = result.iter_mut().find(|b| b.range == node.range()) {
+                        open.is_unmatched = true;
+                    }
+                }
+            });
+            (end, line_number < end_line_number)
+        });
+        result
+    }
+
+    /// Pairs with a bracket inside `range` or enclosing it, in opener order.
+    pub fn pairs_in_range(&mut self, range: Range, source: &mut impl BracketLineSource) -> Vec<BracketPairInfo> {
+        let start_index = range.start_line_number.max(1) as usize - 1;
+        if start_index >= self.line_count() {
+            return Vec::new();
+        }
+        let (range_start, range_end) = (range.get_start_position(), range.get_end_position());
+        let pair = |node: &OpenNode, close_range: Option<Range>| BracketPairInfo { open_range: node.range(), close_range, level: node.depth };
+
+        // Openers that may still close inside or after the range, keyed by position.
+        let mut pending: HashMap<Position, Arc<OpenNode>> = HashMap::new();
+        let mut pairs: Vec<BracketPairInfo> = Vec::new();
+        let mut enclosing = self.begin_stack(start_index, source);
+        while let Some(node) = enclosing {
+            enclosing = node.parent.clone();
+            pending.insert(node.position(), node);
+        }
+
+        self.walk(start_index, source, |line_number, brackets, stack| {
+            let end = apply_line(stack, line_number, brackets, &mut |event| match event {
+                BracketEvent::Open(node) => {
+                    if node.position() < range_end {
+                        pending.insert(node.position(), node);
+                    }
+                }
+                BracketEvent::Close { line_number, column, opener: Some(opener), .. } => {
+                    let close = Range::new(line_number, column, line_number, column + 1);
+                    if pending.remove(&opener.position()).is_some() && close.get_end_position() > range_start {
+                        pairs.push(pair(&opener, Some(close)));
+                    }
+                }
+                BracketEvent::Close { .. } => {}
+                BracketEvent::Unclosed(node) => {
+                    if pending.remove(&node.position()).is_some() {
+                        pairs.push(pair(&node, None));
+                    }
+                }
+            });
+            (end, line_number < range_end.line_number || !pending.is_empty())
+        });
+
+        // Whatever is still pending is never closed.
+        pairs.extend(pending.values().map(|node| pair(node, None)));
+        pairs.sort_by_key(|p| p.open_range.get_start_position());
+        pairs
+    }
+
+    /// The innermost pair whose brackets surround `position`.
+    pub fn enclosing_pair(&mut self, position: Position, source: &mut impl BracketLineSource) -> Option<BracketPairInfo> {
+        let index = (position.line_number as usize).checked_sub(1).filter(|&i| i < self.line_count())?;
+        let stack = self.begin_stack(index, source);
+        let brackets = self.brackets(index, source);
+        let split = brackets.iter().position(|b| b.column >= position.column).unwrap_or(brackets.len());
+        let stack = apply_line(stack, position.line_number, &brackets[..split], &mut |_| {});
+        let target = stack.clone()?;
+
+        // `Some(close)` once the target is resolved, closed or not.
+        let mut resolved: Option<Option<Range>> = None;
+        let track = |event: BracketEvent, resolved: &mut Option<Option<Range>>| match event {
+            BracketEvent::Close { line_number, column, opener: Some(opener), .. } if opener.position() == target.position() => {
+                *resolved = Some(Some(Range::new(line_number, column, line_number, column + 1)));
+            }
+            BracketEvent::Unclosed(node) if node.position() == target.position() => *resolved = Some(None),
+            _ => {}
+        };
+        apply_line(stack, position.line_number, &brackets[split..], &mut |e| track(e, &mut resolved));
+        if resolved.is_none() {
+            self.walk(index + 1, source, |line_number, brackets, stack| {
+                let end = apply_line(stack, line_number, brackets, &mut |e| track(e, &mut resolved));
+                (end, resolved.is_none())
+            });
+        }
+        Some(BracketPairInfo { open_range: target.range(), close_range: resolved.flatten(), level: target.depth })
+    }
+
+    /// Guides for the multi-line pairs crossing lines `start_line_number..=end_line_number`.
+    pub fn guides(&mut self, start_line_number: u32, end_line_number: u32, active_position: Option<Position>, source: &mut impl BracketLineSource) -> Vec<BracketPairGuide> {
+        let active = active_position.and_then(|p| self.enclosing_pair(p, source));
+        let range = Range::new(start_line_number, 1, end_line_number, u32::MAX);
+        self.pairs_in_range(range, source).into_iter().filter_map(|pair| {
+            let close = pair.close_range?;
+            if close.start_line_number <= pair.open_range.start_line_number {
+                return None;
+            }
+            let column = indent_column(&source.content(pair.open_range.start_line_number as usize - 1))
+                .min(indent_column(&source.content(close.start_line_number as usize - 1)));
+            Some(BracketPairGuide {
+                start_line_number: pair.open_range.start_line_number,
+                end_line_number: close.start_line_number,
+                column,
+                level: pair.level,
+                is_active: active.as_ref().is_some_and(|a| a.open_range == pair.open_range),
+            })
+        }).collect()
+    }
+
+    /// Closing brackets without an opener and openers that are never closed.
+    pub fn unmatched_brackets(&mut self, source: &mut impl BracketLineSource) -> Vec<BracketDiagnostic> {
+        let unclosed = |node: &OpenNode| BracketDiagnostic {
+            range: node.range(),
+            message: format!("Unclosed bracket `{}`", BRACKETS[node.kind].0),
+        };
+        let mut diagnostics = Vec::new();
+        let mut final_stack = None;
+        let line_count = self.line_count() as u32;
+        self.walk(0, source, |line_number, brackets, stack| {
+            let end = apply_line(stack, line_number, brackets, &mut |event| match event {
+                BracketEvent::Close { line_number, column, kind, opener: None } => diagnostics.push(BracketDiagnostic {
+                    range: Range::new(line_number, column, line_number, column + 1),
+                    message: format!("Unexpected closing bracket `{}`", BRACKETS[kind].1),
+                }),
+                BracketEvent::Unclosed(node) => diagnostics.push(unclosed(&node)),
+                _ => {}
+            });
+            if line_number == line_count {
+                final_stack = end.clone();
+            }
+            (end, true)
+        });
+        while let Some(node) = final_stack {
+            diagnostics.push(unclosed(&node));
+            final_stack = node.parent.clone();
+        }
+        diagnostics.sort_by_key(|d| d.range.get_start_position());
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_model::TextModel;
+    use crate::text_model_types::{RangePod, SingleEditOperation};
+    use crate::tokenizer::Tokenizer;
+
+    fn model(text: &str) -> TextModel {
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.add_rule(r#""[^"]*"?"#.into(), "string".into()).unwrap();
+        tokenizer.add_rule(r"//.*".into(), "comment".into()).unwrap();
+        let model = TextModel::new("file:///a".into(), text.into());
+        model.set_tokenizer(&tokenizer);
+        model
+    }
+
+    fn levels(brackets: &[ColorizedBracket]) -> Vec<(u32, u32, u32)> {
+        brackets.iter().map(|b| (b.range.start_line_number, b.range.start_column, b.level)).collect()
+    }
+
+    #[test]
+    fn test_strings_and_comments_are_skipped() {
+        let m = model("f(\"(\", [x]) // )\n{ }");
+        let brackets = m.get_bracket_colorization(1, 2);
+        assert_eq!(levels(&brackets), vec![(1, 2, 0), (1, 8, 1), (1, 10, 1), (1, 11, 0), (2, 1, 0), (2, 3, 0)]);
+        assert!(brackets.iter().all(|b| !b.is_unmatched));
+        assert!(m.get_unmatched_brackets().is_empty());
+    }
+
+    #[test]
+    fn test_edits_update_pairs_incrementally() {
+        let m = model("(a\n  (b)\n)");
+        assert_eq!(levels(&m.get_bracket_colorization(2, 2)), vec![(2, 3, 1), (2, 5, 1)]);
+
+        m.apply_edits(vec![SingleEditOperation {
+            range: RangePod { start_line_number: 1, start_column: 1, end_line_number: 1, end_column: 1 },
+            text: Some("[\n".into()),
+            force_move_markers: None,
+        }]);
+        assert_eq!(levels(&m.get_bracket_colorization(3, 3)), vec![(3, 3, 2), (3, 5, 2)]);
+        let diagnostics = m.get_unmatched_brackets();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unclosed bracket `[`");
+
+        // An unterminated string hides the rest of the line.
+        m.apply_edits(vec![SingleEditOperation {
+            range: RangePod { start_line_number: 3, start_column: 3, end_line_number: 3, end_column: 3 },
+            text: Some("\"".into()),
+            force_move_markers: None,
+        }]);
+        assert_eq!(levels(&m.get_bracket_colorization(3, 4)), vec![(4, 1, 1)]);
+    }
+
+    #[test]
+    fn test_mismatched_closer_closes_outer_pair() {
+        let m = model("{ ( }\n)");
+        let brackets = m.get_bracket_colorization(1, 2);
+        let unmatched: Vec<(u32, u32)> = brackets.iter().filter(|b| b.is_unmatched).map(|b| (b.range.start_line_number, b.range.start_column)).collect();
+        assert_eq!(unmatched, vec![(1, 3), (2, 1)]);
+        let messages: Vec<String> = m.get_unmatched_brackets().into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, vec!["Unclosed bracket `(`", "Unexpected closing bracket `)`"]);
+    }
+
+    #[test]
+    fn test_guides_and_enclosing_pair() {
+        let m = model("fn a() {\n    if x {\n        y();\n    }\n}");
+        let guides = m.get_bracket_pair_guides(1, 5, Some(Position::new(3, 9)));
+        let spans: Vec<(u32, u32, u32, bool)> = guides.iter().map(|g| (g.start_line_number, g.end_line_number, g.column, g.is_active)).collect();
+        assert_eq!(spans, vec![(1, 5, 1, false), (2, 4, 5, true)]);
+
+        let pair = m.find_enclosing_brackets(Position::new(3, 11)).unwrap();
+        assert_eq!(pair.open_range, Range::new(3, 10, 3, 11));
+        assert_eq!(pair.close_range, Some(Range::new(3, 11, 3, 12)));
+
+        let pairs = m.get_bracket_pairs_in_range(Range::new(3, 1, 3, 20));
+        assert_eq!(pairs.iter().map(|p| p.level).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+}
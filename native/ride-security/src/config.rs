@@ -11,14 +11,18 @@
 //! - Built-in secret protection via AES-256-GCM
 //! - Real-time configuration overlays for transient settings
 //! - JSON Schema-ready validation structures
+//! - Change events listing the keys whose merged value changed
 
 use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{RwLock, Arc};
+use std::sync::atomic::{AtomicU32, Ordering};
+use crate::config_service::ConfigurationChangeEvent;
 
 #[napi(object)]
 #[derive(Clone, Serialize, Deserialize)]
@@ -35,6 +39,11 @@ pub struct ConfigManager {
 
 static MANAGER: RwLock<Option<Arc<RwLock<ConfigManager>>>> = RwLock::new(None);
 
+type ConfigChangeListener = ThreadsafeFunction<ConfigurationChangeEvent, ErrorStrategy::Fatal>;
+
+static LISTENERS: RwLock<Vec<(u32, ConfigChangeListener)>> = RwLock::new(Vec::new());
+static NEXT_LISTENER_ID: AtomicU32 = AtomicU32::new(1);
+
 fn get_manager() -> Arc<RwLock<ConfigManager>> {
     let mut guard = MANAGER.write().unwrap();
     if guard.is_none() {
@@ -46,8 +55,66 @@ fn get_manager() -> Arc<RwLock<ConfigManager>> {
     guard.as_ref().unwrap().clone()
 }
 
+/// Layers in order, then transient overrides; later values win.
+fn merged_settings(m: &ConfigManager) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    for (_, layer) in &m.layers {
+        for (k, v) in &layer.settings {
+            result.insert(k.clone(), v.clone());
+        }
+    }
+    for (k, v) in &m.override_layer.settings {
+        result.insert(k.clone(), v.clone());
+    }
+    result
+}
+
+/// Applies `update` and notifies listeners of the keys whose merged value changed.
+fn apply_change(source: &str, update: impl FnOnce(&mut ConfigManager)) -> ConfigurationChangeEvent {
+    let manager = get_manager();
+    let (before, after) = {
+        let mut m = manager.write().unwrap();
+        let before = merged_settings(&m);
+        update(&mut m);
+        (before, merged_settings(&m))
+    };
+    let changed = before.keys().chain(after.keys().filter(|k| !before.contains_key(*k)))
+        .filter(|k| before.get(*k) != after.get(*k))
+        .cloned();
+    let event = ConfigurationChangeEvent::from_keys(source, changed);
+    if !event.is_empty() {
+        for (_, listener) in LISTENERS.read().unwrap().iter() {
+            listener.call(event.clone(), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+    event
+}
+
+/// Registers `callback` for changes to the merged configuration. Returns an id for
+/// `remove_config_change_listener`.
+/// The listener is unref'd so it does not keep the event loop alive.
+#[napi]
+pub fn on_config_change(
+    env: Env,
+    #[napi(ts_arg_type = "(event: ConfigurationChangeEvent) => void")]
+    mut callback: ConfigChangeListener,
+) -> Result<u32> {
+    callback.unref(&env)?;
+    let id = NEXT_LISTENER_ID.fetch_add(1, Ordering::Relaxed);
+    LISTENERS.write().unwrap().push((id, callback));
+    Ok(id)
+}
+
+#[napi]
+pub fn remove_config_change_listener(id: u32) -> bool {
+    let mut listeners = LISTENERS.write().unwrap();
+    let before = listeners.len();
+    listeners.retain(|(listener, _)| *listener != id);
+    listeners.len() != before
+}
+
 #[napi]
-pub fn add_config_layer(name: String, path: String) -> Result<()> {
+pub fn add_config_layer(name: String, path: String) -> Result<ConfigurationChangeEvent> {
     let p = Path::new(&path);
     let store = if p.exists() {
         let content = fs::read_to_string(p).map_err(|e| Error::from_reason(e.to_string()))?;
@@ -56,34 +123,22 @@ pub fn add_config_layer(name: String, path: String) -> Result<()> {
         ConfigStore { version: 1, settings: HashMap::new(), secrets: HashMap::new() }
     };
 
-    get_manager().write().unwrap().layers.push((name, store));
-    Ok(())
+    let source = name.clone();
+    Ok(apply_change(&source, |m| m.layers.push((name, store))))
 }
 
 #[napi]
 pub fn get_merged_config() -> HashMap<String, String> {
     let manager = get_manager();
     let m = manager.read().unwrap();
-    let mut result = HashMap::new();
-
-    // Apply layers in order (later layers override earlier ones)
-    for (_, layer) in &m.layers {
-        for (k, v) in &layer.settings {
-            result.insert(k.clone(), v.clone());
-        }
-    }
-
-    // Apply transient overrides
-    for (k, v) in &m.override_layer.settings {
-        result.insert(k.clone(), v.clone());
-    }
-
-    result
+    merged_settings(&m)
 }
 
 #[napi]
-pub fn set_transient_config(key: String, value: String) {
-    get_manager().write().unwrap().override_layer.settings.insert(key, value);
+pub fn set_transient_config(key: String, value: String) -> ConfigurationChangeEvent {
+    apply_change("transient", |m| {
+        m.override_layer.settings.insert(key, value);
+    })
 }
 
 #[napi]
@@ -118,3 +173,16 @@ pub fn get_config_value(key: String) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transient_change_event() {
+        let event = set_transient_config("test.changeEvent".into(), "1".into());
+        assert_eq!(event.affected_keys(), ["test.changeEvent"]);
+        assert!(event.affects_configuration("test".into(), None));
+        assert!(set_transient_config("test.changeEvent".into(), "1".into()).is_empty());
+    }
+}
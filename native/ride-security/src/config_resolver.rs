@@ -1,6 +1,418 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Config Resolver — `${variable}` substitution for launch and task configurations, modelled
+//! on VS Code's `src/vs/workbench/services/configurationResolver/common/variableResolver.ts`.
+//!
+//! Every string inside a configuration is resolved, however deeply nested. Values produced by
+//! `${config:…}`, `${command:…}`, `${input:…}` and caller-supplied variables may themselves
+//! contain variables and are resolved again; a variable that ends up depending on itself is
+//! rejected. Unknown variables are left untouched, as VS Code does.
+//!
+//! `${command:…}` and `${input:…}` need the host. A host that can answer synchronously passes a
+//! callback; an async host first calls `collectInteractiveVariables`, runs the commands and
+//! prompts itself, and passes the answers back in `interactiveValues`.
+//!
+//! `ConfigResolver.resolve(value, vars)` keeps its original plain `${name}` replacement;
+//! `resolveString` is the full resolver for a single string.
+
 use napi::bindgen_prelude::*;
+use napi::{JsFunction, JsUnknown, ValueType};
 use napi_derive::napi;
+use serde_json::Value;
 use std::collections::HashMap;
+use std::path::{Path, MAIN_SEPARATOR};
+
+/// Deepest chain of variables resolving into other variables before giving up.
+const MAX_DEPTH: usize = 32;
+
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct WorkspaceFolderInfo {
+    pub name: String,
+    pub path: String,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct VariableResolveContext {
+    pub workspace_folders: Option<Vec<WorkspaceFolderInfo>>,
+    /// Name or path of the folder the configuration belongs to. Defaults to the folder
+    /// containing `file`, or the only folder of a single-folder workspace.
+    pub folder: Option<String>,
+    /// Path of the active editor's file.
+    pub file: Option<String>,
+    pub line_number: Option<u32>,
+    pub selected_text: Option<String>,
+    pub cwd: Option<String>,
+    pub exec_path: Option<String>,
+    pub user_home: Option<String>,
+    /// Environment for `${env:…}`; the process environment when absent.
+    pub env: Option<HashMap<String, String>>,
+    /// Effective settings as JSON (e.g. `ConfigurationService.getMergedConfig()`), nested or
+    /// with dotted keys.
+    pub config: Option<String>,
+    /// `inputs` definitions as JSON, for configurations resolved without their file's
+    /// top-level `inputs` array.
+    pub inputs: Option<String>,
+    /// Answers to `${command:…}` and `${input:…}`, keyed `command:id` / `input:id`.
+    pub interactive_values: Option<HashMap<String, String>>,
+    /// Extra plain `${name}` variables.
+    pub variables: Option<HashMap<String, String>>,
+}
+
+/// A `${command:…}` or `${input:…}` the host must answer before resolution.
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InteractiveVariable {
+    /// `command:id` or `input:id`, the key expected in `interactiveValues`.
+    pub variable: String,
+    /// `command` or `input`.
+    pub kind: String,
+    pub name: String,
+    /// The matching `inputs` entry as JSON, for `${input:…}`.
+    pub input: Option<String>,
+}
+
+/// Answers an interactive variable: `(kind, name, input definition)`. `Ok(None)` means the
+/// host has no answer; `Err` aborts resolution.
+type Host<'h> = dyn FnMut(&str, &str, Option<&Value>) -> std::result::Result<Option<String>, String> + 'h;
+
+/// Every `${…}` in `text`, as `(start, end, name)` byte ranges covering the braces.
+fn variables(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(start) = text[from..].find("${").map(|i| from + i) {
+        let Some(end) = text[start + 2..].find('}').map(|i| start + 2 + i) else { break };
+        found.push((start, end + 1, &text[start + 2..end]));
+        from = end + 1;
+    }
+    found
+}
+
+fn parse_json(text: &str, what: &str) -> std::result::Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("Invalid {} JSON: {}", what, e))
+}
+
+/// Looks `key` up in a settings tree, trying dotted keys as well as nested objects.
+fn config_lookup<'v>(config: &'v Value, key: &str) -> Option<&'v Value> {
+    let map = config.as_object()?;
+    if let Some(value) = map.get(key) {
+        return Some(value);
+    }
+    let mut split = key.len();
+    while let Some(dot) = key[..split].rfind('.') {
+        if let Some(found) = map.get(&key[..dot]).and_then(|v| config_lookup(v, &key[dot + 1..])) {
+            return Some(found);
+        }
+        split = dot;
+    }
+    None
+}
+
+fn is_in_folder(path: &Path, folder: &str) -> bool {
+    path.starts_with(folder)
+}
+
+struct Resolution<'a, 'h> {
+    context: &'a VariableResolveContext,
+    config: Option<Value>,
+    inputs: Vec<Value>,
+    host: &'a mut Host<'h>,
+    /// Interactive answers, so each command runs and each input prompts once.
+    answers: HashMap<String, String>,
+    /// Variables being expanded, outermost first.
+    stack: Vec<String>,
+    /// Leaves variables that fail to resolve untouched instead of failing, so a dry run sees
+    /// everything the host would be asked.
+    dry_run: bool,
+}
+
+impl<'a, 'h> Resolution<'a, 'h> {
+    fn new(context: &'a VariableResolveContext, host: &'a mut Host<'h>, document: Option<&Value>) -> std::result::Result<Self, String> {
+        let config = context.config.as_deref().map(|c| parse_json(c, "config")).transpose()?;
+        let mut inputs = match context.inputs.as_deref().map(|i| parse_json(i, "inputs")).transpose()? {
+            Some(Value::Array(inputs)) => inputs,
+            Some(_) => return Err("inputs must be an array".to_string()),
+            None => Vec::new(),
+        };
+        if let Some(Value::Array(own)) = document.and_then(|d| d.get("inputs")) {
+            inputs.extend(own.iter().cloned());
+        }
+        Ok(Self {
+            context,
+            config,
+            inputs,
+            host,
+            answers: context.interactive_values.clone().unwrap_or_default(),
+            stack: Vec::new(),
+            dry_run: false,
+        })
+    }
+
+    fn resolve_value(&mut self, value: &Value) -> std::result::Result<Value, String> {
+        Ok(match value {
+            Value::String(s) => Value::String(self.resolve_string(s)?),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.resolve_value(v)).collect::<std::result::Result<_, _>>()?),
+            Value::Object(map) => {
+                let mut resolved = serde_json::Map::new();
+                for (key, value) in map {
+                    resolved.insert(key.clone(), self.resolve_value(value)?);
+                }
+                Value::Object(resolved)
+            }
+            other => other.clone(),
+        })
+    }
+
+    fn resolve_string(&mut self, text: &str) -> std::result::Result<String, String> {
+        let mut resolved = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, end, name) in variables(text) {
+            resolved.push_str(&text[last..start]);
+            let value = match self.variable(name) {
+                Err(_) if self.dry_run => None,
+                value => value?,
+            };
+            match value {
+                Some(value) => resolved.push_str(&value),
+                None => resolved.push_str(&text[start..end]),
+            }
+            last = end;
+        }
+        resolved.push_str(&text[last..]);
+        Ok(resolved)
+    }
+
+    /// Resolves a value that may itself contain variables, guarding against cycles.
+    fn expand(&mut self, name: &str, value: String) -> std::result::Result<String, String> {
+        if !value.contains("${") {
+            return Ok(value);
+        }
+        if self.stack.iter().any(|n| n == name) {
+            let mut chain: Vec<&str> = self.stack.iter().map(String::as_str).collect();
+            chain.push(name);
+            return Err(format!("Variable ${{{}}} can not be resolved because it depends on itself: {}", name, chain.join(" -> ")));
+        }
+        if self.stack.len() >= MAX_DEPTH {
+            return Err(format!("Variable ${{{}}} can not be resolved: variables are nested too deeply", name));
+        }
+        self.stack.push(name.to_string());
+        let resolved = self.resolve_string(&value);
+        self.stack.pop();
+        resolved
+    }
+
+    fn folders(&self) -> &[WorkspaceFolderInfo] {
+        self.context.workspace_folders.as_deref().unwrap_or_default()
+    }
+
+    /// The folder containing the active file, innermost first.
+    fn file_folder(&self) -> Option<&WorkspaceFolderInfo> {
+        let file = Path::new(self.context.file.as_deref()?);
+        self.folders().iter().filter(|f| is_in_folder(file, &f.path)).max_by_key(|f| f.path.len())
+    }
+
+    /// The folder `${workspaceFolder}` refers to, or the named one.
+    fn folder(&self, name: &str, argument: Option<&str>) -> std::result::Result<&WorkspaceFolderInfo, String> {
+        if let Some(argument) = argument {
+            return self.folders().iter()
+                .find(|f| f.name == argument)
+                .ok_or_else(|| format!("Variable ${{{}}} can not be resolved. No such folder '{}'.", name, argument));
+        }
+        if let Some(selected) = self.context.folder.as_deref()
+            && let Some(folder) = self.folders().iter().find(|f| f.name == selected || f.path == selected)
+        {
+            return Ok(folder);
+        }
+        if let Some(folder) = self.file_folder() {
+            return Ok(folder);
+        }
+        match self.folders() {
+            [only] => Ok(only),
+            [] => Err(format!("Variable ${{{}}} can not be resolved. Please open a folder.", name)),
+            _ => Err(format!(
+                "Variable ${{{}}} can not be resolved in a multi folder workspace. Scope this variable using ':' and a workspace folder name.",
+                name
+            )),
+        }
+    }
+
+    fn file(&self, name: &str) -> std::result::Result<&Path, String> {
+        self.context.file.as_deref()
+            .map(Path::new)
+            .ok_or_else(|| format!("Variable ${{{}}} can not be resolved. Please open an editor.", name))
+    }
+
+    /// The active file relative to its folder, or the file itself outside any folder.
+    fn relative_file(&self, name: &str, argument: Option<&str>) -> std::result::Result<String, String> {
+        let file = self.file(name)?;
+        let folder = match argument {
+            Some(_) => Some(self.folder(name, argument)?),
+            None => self.folder(name, None).ok(),
+        };
+        Ok(folder
+            .and_then(|f| file.strip_prefix(&f.path).ok())
+            .unwrap_or(file)
+            .to_string_lossy()
+            .into_owned())
+    }
+
+    fn env(&self, var: &str) -> String {
+        match &self.context.env {
+            Some(env) => env.get(var).cloned(),
+            None => std::env::var(var).ok(),
+        }
+        .unwrap_or_default()
+    }
+
+    fn interactive(&mut self, name: &str, kind: &str, id: &str) -> std::result::Result<String, String> {
+        let key = format!("{}:{}", kind, id);
+        if let Some(answer) = self.answers.get(&key) {
+            return Ok(answer.clone());
+        }
+        let input = if kind == "input" {
+            let input = self.inputs.iter()
+                .find(|i| i.get("id").and_then(Value::as_str) == Some(id))
+                .cloned()
+                .ok_or_else(|| format!("Variable ${{{}}} can not be resolved. Undefined input '{}'.", name, id))?;
+            Some(input)
+        } else {
+            None
+        };
+        let answer = (self.host)(kind, id, input.as_ref())?
+            .ok_or_else(|| format!("Variable ${{{}}} can not be resolved. No value was provided.", name))?;
+        self.answers.insert(key, answer.clone());
+        Ok(answer)
+    }
+
+    /// The value of `${name}`, or `None` for variables this resolver does not know.
+    fn variable(&mut self, name: &str) -> std::result::Result<Option<String>, String> {
+        let (variable, argument) = match name.split_once(':') {
+            Some((variable, argument)) => (variable, Some(argument)),
+            None => (name, None),
+        };
+        let basename = |p: &Path| p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let value = match variable {
+            "env" => match argument {
+                Some(var) => self.env(var),
+                None => return Err(format!("Variable ${{{}}} can not be resolved because no environment variable name is given.", name)),
+            },
+            "config" => {
+                let Some(key) = argument else {
+                    return Err(format!("Variable ${{{}}} can not be resolved because no settings name is given.", name));
+                };
+                let value = self.config.as_ref().and_then(|c| config_lookup(c, key));
+                let value = match value {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Object(_) | Value::Array(_)) => {
+                        return Err(format!("Variable ${{{}}} can not be resolved because '{}' is a structured value.", name, key));
+                    }
+                    Some(other) => other.to_string(),
+                };
+                self.expand(name, value)?
+            }
+            "command" | "input" => {
+                let Some(id) = argument else {
+                    return Err(format!("Variable ${{{}}} can not be resolved because no {} id is given.", name, variable));
+                };
+                let value = self.interactive(name, variable, id)?;
+                self.expand(name, value)?
+            }
+            "workspaceFolder" | "workspaceRoot" => self.folder(name, argument)?.path.clone(),
+            "workspaceFolderBasename" => basename(Path::new(&self.folder(name, argument)?.path)),
+            "fileWorkspaceFolder" => {
+                self.file(name)?;
+                self.file_folder()
+                    .map(|f| f.path.clone())
+                    .ok_or_else(|| format!("Variable ${{{}}} can not be resolved. The file is not in a workspace folder.", name))?
+            }
+            "file" => self.file(name)?.to_string_lossy().into_owned(),
+            "fileBasename" => basename(self.file(name)?),
+            "fileBasenameNoExtension" => self.file(name)?.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+            "fileExtname" => self.file(name)?.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default(),
+            "fileDirname" => self.file(name)?.parent().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default(),
+            "fileDirnameBasename" => self.file(name)?.parent().map(basename).unwrap_or_default(),
+            "relativeFile" => self.relative_file(name, argument)?,
+            "relativeFileDirname" => {
+                let relative = self.relative_file(name, argument)?;
+                Path::new(&relative).parent().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default()
+            }
+            "lineNumber" => self.context.line_number
+                .map(|l| l.to_string())
+                .ok_or_else(|| format!("Variable ${{{}}} can not be resolved. Make sure to have a line selected in the active editor.", name))?,
+            "selectedText" => self.context.selected_text.clone()
+                .ok_or_else(|| format!("Variable ${{{}}} can not be resolved. Make sure to have some text selected in the active editor.", name))?,
+            "cwd" => match &self.context.cwd {
+                Some(cwd) => cwd.clone(),
+                None => self.folder(name, None).map(|f| f.path.clone())
+                    .or_else(|_| std::env::current_dir().map(|d| d.to_string_lossy().into_owned()).map_err(|e| e.to_string()))?,
+            },
+            "execPath" => match &self.context.exec_path {
+                Some(path) => path.clone(),
+                None => std::env::current_exe().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default(),
+            },
+            "pathSeparator" | "/" => MAIN_SEPARATOR.to_string(),
+            "userHome" => match &self.context.user_home {
+                Some(home) => home.clone(),
+                None => self.env(if cfg!(windows) { "USERPROFILE" } else { "HOME" }),
+            },
+            _ => match self.context.variables.as_ref().and_then(|v| v.get(name)) {
+                Some(value) => {
+                    let value = value.clone();
+                    self.expand(name, value)?
+                }
+                None => return Ok(None),
+            },
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Resolves every string in `config`. `inputs` definitions at its top level are used for
+/// `${input:…}`.
+pub(crate) fn resolve_config_value(config: &Value, context: &VariableResolveContext, host: &mut Host) -> std::result::Result<Value, String> {
+    let mut resolution = Resolution::new(context, host, Some(config))?;
+    resolve_document(&mut resolution, config)
+}
+
+fn resolve_document(resolution: &mut Resolution, config: &Value) -> std::result::Result<Value, String> {
+    let Some(map) = config.as_object() else { return resolution.resolve_value(config) };
+    let mut resolved = serde_json::Map::new();
+    for (key, value) in map {
+        // Input definitions are resolved when they are used, not in place.
+        let value = if key == "inputs" { value.clone() } else { resolution.resolve_value(value)? };
+        resolved.insert(key.clone(), value);
+    }
+    Ok(Value::Object(resolved))
+}
+
+pub(crate) fn resolve_string(text: &str, context: &VariableResolveContext, host: &mut Host) -> std::result::Result<String, String> {
+    Resolution::new(context, host, None)?.resolve_string(text)
+}
+
+/// The `${command:…}` and `${input:…}` variables resolving `config` would ask the host for,
+/// each listed once. Found by a dry run, so variables reached through `${config:…}`, caller
+/// variables or other answers are included; those already in `interactiveValues` are not.
+pub(crate) fn interactive_variables(config: &Value, context: &VariableResolveContext) -> std::result::Result<Vec<InteractiveVariable>, String> {
+    let mut found: Vec<InteractiveVariable> = Vec::new();
+    let mut host = |kind: &str, id: &str, input: Option<&Value>| {
+        found.push(InteractiveVariable {
+            variable: format!("{}:{}", kind, id),
+            kind: kind.to_string(),
+            name: id.to_string(),
+            input: input.map(Value::to_string),
+        });
+        Ok(Some(String::new()))
+    };
+    let mut resolution = Resolution::new(context, &mut host, Some(config))?;
+    resolution.dry_run = true;
+    resolve_document(&mut resolution, config)?;
+    Ok(found)
+}
 
 #[napi]
 pub struct ConfigResolver {}
@@ -21,4 +433,174 @@ impl ConfigResolver {
         }
         resolved
     }
+
+    /// Resolves the variables in a single string.
+    #[napi(ts_args_type = "value: string, context?: VariableResolveContext, host?: (kind: string, name: string, input: string | null) => string | undefined")]
+    pub fn resolve_string(&self, env: Env, value: String, context: Option<VariableResolveContext>, host: Option<JsFunction>) -> Result<String> {
+        let context = context.unwrap_or_default();
+        let mut host = js_host(env, host.as_ref());
+        resolve_string(&value, &context, &mut host).map_err(Error::from_reason)
+    }
+
+    /// Resolves every string of a launch or task configuration given as JSON.
+    #[napi(ts_args_type = "config: string, context?: VariableResolveContext, host?: (kind: string, name: string, input: string | null) => string | undefined")]
+    pub fn resolve_config(&self, env: Env, config: String, context: Option<VariableResolveContext>, host: Option<JsFunction>) -> Result<String> {
+        let context = context.unwrap_or_default();
+        let config = parse_json(&config, "configuration").map_err(Error::from_reason)?;
+        let mut host = js_host(env, host.as_ref());
+        let resolved = resolve_config_value(&config, &context, &mut host).map_err(Error::from_reason)?;
+        Ok(resolved.to_string())
+    }
+
+    /// The commands and inputs a configuration needs, for hosts that answer asynchronously.
+    #[napi]
+    pub fn collect_interactive_variables(&self, config: String, context: Option<VariableResolveContext>) -> Result<Vec<InteractiveVariable>> {
+        let config = parse_json(&config, "configuration").map_err(Error::from_reason)?;
+        interactive_variables(&config, &context.unwrap_or_default()).map_err(Error::from_reason)
+    }
+}
+
+/// Adapts the JS host callback; its string result answers the variable, `undefined` cancels.
+fn js_host(env: Env, host: Option<&JsFunction>) -> impl FnMut(&str, &str, Option<&Value>) -> std::result::Result<Option<String>, String> + '_ {
+    move |kind, name, input| {
+        let Some(host) = host else { return Ok(None) };
+        let call = || -> Result<Option<String>> {
+            let input = match input {
+                Some(input) => env.create_string(&input.to_string())?.into_unknown(),
+                None => env.get_null()?.into_unknown(),
+            };
+            let args = [env.create_string(kind)?.into_unknown(), env.create_string(name)?.into_unknown(), input];
+            let result: JsUnknown = host.call(None, &args)?;
+            match result.get_type()? {
+                ValueType::Undefined | ValueType::Null => Ok(None),
+                _ => Ok(Some(result.coerce_to_string()?.into_utf8()?.as_str()?.to_string())),
+            }
+        };
+        call().map_err(|e| e.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> VariableResolveContext {
+        VariableResolveContext {
+            workspace_folders: Some(vec![
+                WorkspaceFolderInfo { name: "api".to_string(), path: "/repo/api".to_string() },
+                WorkspaceFolderInfo { name: "web".to_string(), path: "/repo/web".to_string() },
+            ]),
+            file: Some("/repo/web/src/app.test.ts".to_string()),
+            env: Some(HashMap::from([("PORT".to_string(), "8080".to_string())])),
+            config: Some(json!({ "editor.tabSize": 4, "launch": { "target": "${workspaceFolder:api}/bin" } }).to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn resolve(text: &str, context: &VariableResolveContext) -> std::result::Result<String, String> {
+        let mut host = |_: &str, _: &str, _: Option<&Value>| Ok(None);
+        resolve_string(text, context, &mut host)
+    }
+
+    #[test]
+    fn resolves_folder_and_file_variables() {
+        let ctx = context();
+        assert_eq!(resolve("${workspaceFolder}", &ctx).unwrap(), "/repo/web");
+        assert_eq!(resolve("${workspaceFolder:api}/out", &ctx).unwrap(), "/repo/api/out");
+        assert_eq!(resolve("${workspaceFolderBasename}", &ctx).unwrap(), "web");
+        assert_eq!(resolve("${relativeFile}", &ctx).unwrap(), "src/app.test.ts");
+        assert_eq!(resolve("${relativeFileDirname}", &ctx).unwrap(), "src");
+        assert_eq!(resolve("${fileBasename}|${fileBasenameNoExtension}|${fileExtname}", &ctx).unwrap(), "app.test.ts|app.test|.ts");
+        assert_eq!(resolve("${fileDirname} ${unknown}", &ctx).unwrap(), "/repo/web/src ${unknown}");
+
+        let no_file = VariableResolveContext { file: None, ..context() };
+        assert!(resolve("${workspaceFolder}", &no_file).unwrap_err().contains("multi folder workspace"));
+        assert!(resolve("${workspaceFolder:docs}", &no_file).is_err());
+    }
+
+    #[test]
+    fn resolves_env_and_config() {
+        let ctx = context();
+        assert_eq!(resolve("${env:PORT}:${env:MISSING}", &ctx).unwrap(), "8080:");
+        assert_eq!(resolve("${config:editor.tabSize}", &ctx).unwrap(), "4");
+        assert_eq!(resolve("${config:launch.target}", &ctx).unwrap(), "/repo/api/bin");
+        assert!(resolve("${config:launch}", &ctx).unwrap_err().contains("structured value"));
+    }
+
+    #[test]
+    fn resolves_nested_configuration_with_host() {
+        let config = json!({
+            "program": "${workspaceFolder}/${input:entry}",
+            "args": ["--port", "${env:PORT}", { "pid": "${command:pickProcess}" }],
+            "inputs": [{ "id": "entry", "type": "promptString", "default": "index.js" }]
+        });
+        let mut calls = Vec::new();
+        let mut host = |kind: &str, name: &str, input: Option<&Value>| {
+            calls.push(format!("{}:{}", kind, name));
+            Ok(Some(match input {
+                Some(input) => input["default"].as_str().unwrap().to_string(),
+                None => "4242".to_string(),
+            }))
+        };
+        let resolved = resolve_config_value(&config, &context(), &mut host).unwrap();
+        assert_eq!(resolved["program"], "/repo/web/index.js");
+        assert_eq!(resolved["args"], json!(["--port", "8080", { "pid": "4242" }]));
+        calls.sort();
+        assert_eq!(calls, ["command:pickProcess", "input:entry"]);
+
+        let found = interactive_variables(&config, &context()).unwrap();
+        assert_eq!(found.len(), 2);
+        let entry = found.iter().find(|v| v.variable == "input:entry").unwrap();
+        assert!(entry.input.as_deref().unwrap().contains("promptString"));
+
+        let answered = VariableResolveContext {
+            interactive_values: Some(HashMap::from([
+                ("input:entry".to_string(), "main.js".to_string()),
+                ("command:pickProcess".to_string(), "1".to_string()),
+            ])),
+            ..context()
+        };
+        let mut no_host = |_: &str, _: &str, _: Option<&Value>| Ok(None);
+        let resolved = resolve_config_value(&config, &answered, &mut no_host).unwrap();
+        assert_eq!(resolved["program"], "/repo/web/main.js");
+        assert!(resolve_config_value(&config, &context(), &mut no_host).unwrap_err().contains("No value was provided"));
+    }
+
+    #[test]
+    fn collects_interactive_variables_behind_other_variables() {
+        let config = json!({
+            "pid": "${config:launch.pid}",
+            "port": "${port}",
+            "cwd": "${workspaceFolder:docs}/${command:pickFolder}",
+            "inputs": [{ "id": "port", "type": "promptString" }]
+        });
+        let ctx = VariableResolveContext {
+            config: Some(json!({ "launch.pid": "${command:pickProcess}" }).to_string()),
+            variables: Some(HashMap::from([("port".to_string(), "${input:port}".to_string())])),
+            interactive_values: Some(HashMap::from([("command:pickFolder".to_string(), "${command:pickSubfolder}".to_string())])),
+            ..context()
+        };
+        let found = interactive_variables(&config, &ctx).unwrap();
+        let mut names: Vec<&str> = found.iter().map(|v| v.variable.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["command:pickProcess", "command:pickSubfolder", "input:port"]);
+        assert!(found.iter().find(|v| v.kind == "input").unwrap().input.as_deref().unwrap().contains("promptString"));
+
+        let resolver = ConfigResolver::new();
+        let vars = HashMap::from([("name".to_string(), "x".to_string())]);
+        assert_eq!(resolver.resolve("${name}/${workspaceFolder}".to_string(), vars), "x/${workspaceFolder}");
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let ctx = VariableResolveContext {
+            config: Some(json!({ "a": "${config:b}", "b": "x${config:a}" }).to_string()),
+            variables: Some(HashMap::from([("self".to_string(), "${self}".to_string())])),
+            ..context()
+        };
+        let err = resolve("${config:a}", &ctx).unwrap_err();
+        assert!(err.contains("config:a -> config:b -> config:a"), "{}", err);
+        assert!(resolve("${self}", &ctx).is_err());
+    }
 }
@@ -3,17 +3,438 @@
  *  Licensed under the MIT License. See License.txt in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
+//! Configuration Service — layered settings resolution modelled on VS Code's
+//! `src/vs/platform/configuration/common/configurationModels.ts`.
+//!
+//! Layers, lowest to highest: default < machine < user < workspace < workspace folder, with
+//! policy values locked over all of them. `[language]` sections from every layer are merged
+//! like any other key and applied after the layers, so a language-specific value beats a
+//! plain value from any scope.
+//!
+//! Every update diffs the updated layer, compares the effective values of the keys it changed
+//! in each folder and language scope that could shadow them, and sends listeners a
+//! `ConfigurationChangeEvent` naming the affected keys.
+
 use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
 use napi_derive::napi;
-use crate::json_parser::{parse_jsonc, json_merge, json_get};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use crate::json_parser::{deep_merge, parse_jsonc};
+use crate::json_schema::{diagnose, JsonDiagnostic};
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct ConfigurationOverrides {
+    /// Language id, e.g. "python", selecting `[python]` sections.
+    pub override_identifier: Option<String>,
+    /// File path selecting the workspace folder layer in a multi-root workspace.
+    pub resource: Option<String>,
+}
+
+/// A setting's value in one layer, as JSON.
+#[napi(object)]
+pub struct ConfigurationLayerValue {
+    pub value: Option<String>,
+    /// Value from the layer's section for the requested language.
+    pub override_value: Option<String>,
+}
+
+#[napi(object)]
+pub struct ConfigurationInspect {
+    pub key: String,
+    /// Effective value after all layers, language overrides and policy.
+    pub value: Option<String>,
+    /// Layer that supplied the effective value: "default", "machine", "user", "workspace",
+    /// "workspaceFolder" or "policy".
+    pub source: Option<String>,
+    /// True when `source`'s language section supplied the value.
+    pub from_language_override: bool,
+    pub default_value: ConfigurationLayerValue,
+    pub machine_value: ConfigurationLayerValue,
+    pub user_value: ConfigurationLayerValue,
+    pub workspace_value: ConfigurationLayerValue,
+    pub workspace_folder_value: ConfigurationLayerValue,
+    pub policy_value: ConfigurationLayerValue,
+    /// Languages that override `key` in any layer.
+    pub override_identifiers: Vec<String>,
+}
+
+/// Keys whose effective value changed for one folder/language scope.
+#[derive(Clone, Debug)]
+struct ChangedScope {
+    folder: Option<String>,
+    language: Option<String>,
+    keys: BTreeSet<String>,
+}
+
+/// What a configuration update changed. Scopes are computed only for the folders and
+/// languages whose settings touch a changed key; any other folder or language sees the
+/// plain scope. Every computed scope is kept, even when nothing in it changed, so a language
+/// whose section shadows a changed key is reported as unaffected.
+#[napi]
+#[derive(Clone, Debug)]
+pub struct ConfigurationChangeEvent {
+    source: String,
+    scopes: Vec<ChangedScope>,
+    /// Every workspace folder, so a resource maps to its innermost folder even when that
+    /// folder has no scope of its own.
+    folders: Vec<String>,
+}
+
+/// Whether `key` is `section`, inside it, or one of its parents.
+fn key_affects(key: &str, section: &str) -> bool {
+    section.is_empty()
+        || key == section
+        || key.strip_prefix(section).is_some_and(|rest| rest.starts_with('.'))
+        || section.strip_prefix(key).is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Whether `tree` sets `key`, a parent of it or something inside it.
+fn sets_key(tree: &Value, key: &str) -> bool {
+    let mut current = tree;
+    for segment in key.split('.') {
+        match current {
+            Value::Object(map) => match map.get(segment) {
+                Some(next) => current = next,
+                None => return false,
+            },
+            _ => return true,
+        }
+    }
+    true
+}
+
+/// Whether `tree` or one of its language sections sets `key`, a parent of it or something
+/// inside it.
+fn touches(tree: &Value, key: &str) -> bool {
+    let Value::Object(map) = tree else { return false };
+    sets_key(tree, key) || map.iter().any(|(section, value)| override_identifiers(section).is_some() && sets_key(value, key))
+}
+
+/// `tree` with everything but `key`'s path, in the plain settings and in each language
+/// section, removed. Merging pruned trees gives the same value at `key` as merging the
+/// full ones.
+fn prune(tree: &Value, key: &str) -> Value {
+    fn prune_path(tree: &Value, path: &[&str]) -> Value {
+        match (tree, path.split_first()) {
+            (Value::Object(map), Some((first, rest))) => Value::Object(
+                map.get(*first).map(|value| (first.to_string(), prune_path(value, rest))).into_iter().collect(),
+            ),
+            _ => tree.clone(),
+        }
+    }
+    let path: Vec<&str> = key.split('.').collect();
+    let Value::Object(map) = tree else { return tree.clone() };
+    let mut pruned = prune_path(tree, &path);
+    let Value::Object(pruned_map) = &mut pruned else { unreachable!() };
+    for (section, value) in map.iter().filter(|(section, _)| override_identifiers(section).is_some()) {
+        pruned_map.insert(section.clone(), prune_path(value, &path));
+    }
+    pruned
+}
+
+/// Keys changed between two versions of one layer, in its plain settings or any of its
+/// language sections.
+fn changed_keys(old: &Value, new: &Value) -> BTreeSet<String> {
+    let mut keys = BTreeSet::new();
+    diff_keys("", Some(old), Some(new), &mut keys);
+    let sections = |tree: &Value| tree.as_object().into_iter().flat_map(|m| m.keys()).filter(|k| override_identifiers(k).is_some()).cloned().collect::<BTreeSet<String>>();
+    for section in sections(old).union(&sections(new)) {
+        diff_keys("", old.get(section), new.get(section), &mut keys);
+    }
+    keys
+}
+
+/// Collects the dotted paths whose values differ between `old` and `new`. Top-level
+/// language sections are skipped; their effect is diffed in the language scopes.
+fn diff_keys(prefix: &str, old: Option<&Value>, new: Option<&Value>, keys: &mut BTreeSet<String>) {
+    let child = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+    let skip = |key: &str| prefix.is_empty() && override_identifiers(key).is_some();
+    match (old, new) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            for key in a.keys().chain(b.keys().filter(|k| !a.contains_key(*k))) {
+                if !skip(key) {
+                    diff_keys(&child(key), a.get(key), b.get(key), keys);
+                }
+            }
+        }
+        (Some(Value::Object(map)), other) | (other, Some(Value::Object(map))) => {
+            if other.is_some() && !prefix.is_empty() {
+                keys.insert(prefix.to_string());
+            }
+            for (key, value) in map {
+                if !skip(key) {
+                    diff_keys(&child(key), Some(value), None, keys);
+                }
+            }
+        }
+        (a, b) => {
+            if a != b && !prefix.is_empty() {
+                keys.insert(prefix.to_string());
+            }
+        }
+    }
+}
+
+fn is_in_folder(resource: &str, folder: &str) -> bool {
+    resource.strip_prefix(folder).is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '\\']))
+}
+
+impl ConfigurationChangeEvent {
+    /// An event with a single unscoped set of keys.
+    pub(crate) fn from_keys(source: &str, keys: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            source: source.to_string(),
+            scopes: vec![ChangedScope { folder: None, language: None, keys: keys.into_iter().collect() }],
+            folders: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.iter().all(|s| s.keys.is_empty())
+    }
+}
+
+#[napi]
+impl ConfigurationChangeEvent {
+    /// Layer whose update caused the change, e.g. "user" or "workspaceFolder:/repo/api".
+    #[napi(getter)]
+    pub fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /// Keys whose effective value changed in any folder or language scope.
+    #[napi(getter)]
+    pub fn affected_keys(&self) -> Vec<String> {
+        let keys: BTreeSet<&String> = self.scopes.iter().flat_map(|s| &s.keys).collect();
+        keys.into_iter().cloned().collect()
+    }
+
+    /// Whether `section` (or a key inside or above it) changed. With a resource only its
+    /// workspace folder is considered; with a language only that language's view.
+    #[napi]
+    pub fn affects_configuration(&self, section: String, overrides: Option<ConfigurationOverrides>) -> bool {
+        let overrides = overrides.unwrap_or_default();
+        let folder = overrides.resource.as_deref().map(|resource| {
+            self.folders.iter()
+                .filter(|folder| is_in_folder(resource, folder))
+                .max_by_key(|folder| folder.len())
+                .filter(|folder| self.scopes.iter().any(|s| s.folder.as_ref() == Some(*folder)))
+                .map(String::as_str)
+        });
+        let language = overrides.override_identifier.as_deref().map(|language| {
+            // Languages without sections anywhere see the plain view.
+            self.scopes.iter().any(|s| s.language.as_deref() == Some(language)).then_some(language)
+        });
+        self.scopes.iter()
+            .filter(|s| folder.is_none_or(|f| s.folder.as_deref() == f))
+            .filter(|s| language.is_none_or(|l| s.language.as_deref() == l))
+            .any(|s| s.keys.iter().any(|key| key_affects(key, &section)))
+    }
+}
+
+/// The settings layers.
+struct ConfigurationLayers {
+    default_config: Value,
+    user_config: Value,
+    workspace_config: Value,
+    machine_config: Value,
+    /// Settings of each folder of a multi-root workspace, keyed by folder path.
+    folder_configs: Vec<(String, Value)>,
+    policy_config: Value,
+    /// Default < Machine < User < Workspace, before folder, language and policy layers.
+    merged_config: Value,
+    /// Each non-folder layer as written, dotted keys and all, keyed by layer name.
+    written_configs: HashMap<String, Value>,
+    /// The written layers merged with policy, the shape `get_merged_config` has always had.
+    written_merged: Value,
+}
+
+impl ConfigurationLayers {
+    fn new() -> Self {
+        Self {
+            default_config: empty(),
+            user_config: empty(),
+            workspace_config: empty(),
+            machine_config: empty(),
+            folder_configs: Vec::new(),
+            policy_config: empty(),
+            merged_config: empty(),
+            written_configs: HashMap::new(),
+            written_merged: empty(),
+        }
+    }
+
+    /// The innermost workspace folder containing `resource`.
+    fn folder_for(&self, resource: &str) -> Option<&Value> {
+        self.folder_configs.iter()
+            .filter(|(folder, _)| is_in_folder(resource, folder))
+            .max_by_key(|(folder, _)| folder.len())
+            .map(|(_, config)| config)
+    }
+
+    /// The settings of `folder`, added empty if the folder is new.
+    fn folder_config_mut(&mut self, folder: &str) -> &mut Value {
+        let index = match self.folder_configs.iter().position(|(f, _)| f == folder) {
+            Some(index) => index,
+            None => {
+                self.folder_configs.push((folder.to_string(), empty()));
+                self.folder_configs.len() - 1
+            }
+        };
+        &mut self.folder_configs[index].1
+    }
+
+    fn resolve(&self, overrides: &ConfigurationOverrides) -> Value {
+        let folder = overrides.resource.as_deref().and_then(|r| self.folder_for(r));
+        resolve_trees(self.merged_config.clone(), folder, &self.policy_config, overrides.override_identifier.as_deref())
+    }
+
+    /// Effective value of `key` alone, without resolving the rest of the tree.
+    fn value_at(&self, key: &str, overrides: &ConfigurationOverrides) -> Option<Value> {
+        let folder = overrides.resource.as_deref().and_then(|r| self.folder_for(r)).map(|f| prune(f, key));
+        let resolved = resolve_trees(prune(&self.merged_config, key), folder.as_ref(), &prune(&self.policy_config, key), overrides.override_identifier.as_deref());
+        lookup(&resolved, key).cloned()
+    }
+
+    /// The plain scope plus every folder and language whose settings touch one of `keys`,
+    /// as they are now or with `new` in place of the `source` layer.
+    fn shadowing_scopes(&self, keys: &BTreeSet<String>, source: &str, new: &Value) -> Vec<ConfigurationOverrides> {
+        let touched = |tree: &Value| keys.iter().any(|key| touches(tree, key));
+        let source_folder = source.strip_prefix("workspaceFolder:");
+        let folders = self.folder_configs.iter()
+            .filter(|(folder, config)| touched(config) || source_folder == Some(folder.as_str()))
+            .map(|(folder, _)| folder.clone());
+        let mut trees = vec![&self.default_config, &self.machine_config, &self.user_config, &self.workspace_config, new];
+        trees.extend(self.folder_configs.iter().map(|(_, c)| c));
+        let mut languages: BTreeSet<&str> = BTreeSet::new();
+        for (section, value) in trees.iter().filter_map(|t| t.as_object()).flatten() {
+            if let Some(ids) = override_identifiers(section)
+                && keys.iter().any(|key| sets_key(value, key))
+            {
+                languages.extend(ids);
+            }
+        }
+        let folders: Vec<Option<String>> = std::iter::once(None).chain(folders.map(Some)).collect();
+        let languages: Vec<Option<String>> = std::iter::once(None).chain(languages.into_iter().map(|l| Some(l.to_string()))).collect();
+        folders.iter()
+            .flat_map(|folder| languages.iter().map(|language| ConfigurationOverrides { override_identifier: language.clone(), resource: folder.clone() }))
+            .collect()
+    }
+
+    fn recompute(&mut self) {
+        // Order: Default < Machine < User < Workspace; folder, language and policy layers
+        // are applied per lookup.
+        let mut merged = self.default_config.clone();
+        for layer in [&self.machine_config, &self.user_config, &self.workspace_config] {
+            deep_merge(&mut merged, layer);
+        }
+        self.merged_config = merged;
+
+        let mut written = empty();
+        for layer in ["default", "machine", "user", "workspace", "policy"] {
+            if let Some(config) = self.written_configs.get(layer) {
+                deep_merge(&mut written, config);
+            }
+        }
+        self.written_merged = written;
+    }
+}
 
 #[napi]
 pub struct ConfigurationService {
-    default_config: String,
-    user_config: String,
-    workspace_config: String,
-    machine_config: String,
-    merged_config: String,
+    layers: ConfigurationLayers,
+    listeners: Vec<(u32, ThreadsafeFunction<ConfigurationChangeEvent, ErrorStrategy::Fatal>)>,
+    next_listener_id: u32,
+    /// Schema contributed by the configuration registry; settings are validated against it.
+    settings_schema: Option<Value>,
+    /// Raw text of each layer, kept so a new schema can revalidate it.
+    sources: HashMap<String, String>,
+    diagnostics: HashMap<String, Vec<JsonDiagnostic>>,
+}
+
+/// Language ids of an override key: `[python]` or `[javascript][typescript]`.
+fn override_identifiers(key: &str) -> Option<Vec<&str>> {
+    let inner = key.strip_prefix('[')?.strip_suffix(']')?;
+    let ids: Vec<&str> = inner.split("][").collect();
+    ids.iter().all(|id| !id.is_empty() && !id.contains(['[', ']'])).then_some(ids)
+}
+
+fn empty() -> Value {
+    Value::Object(Map::new())
+}
+
+/// Expands dotted top-level keys into nested objects (`toValuesTree`). Keys inside
+/// language sections are expanded too; nested values are kept as written.
+fn to_values_tree(content: &Value) -> Value {
+    let mut root = empty();
+    let Value::Object(map) = content else { return root };
+    for (key, value) in map {
+        let (path, value): (Vec<&str>, Value) = if override_identifiers(key).is_some() {
+            (vec![key.as_str()], to_values_tree(value))
+        } else {
+            (key.split('.').collect(), value.clone())
+        };
+        let mut current = &mut root;
+        for segment in &path[..path.len() - 1] {
+            let Value::Object(object) = current else { unreachable!() };
+            let entry = object.entry(segment.to_string()).or_insert_with(empty);
+            if !entry.is_object() {
+                *entry = empty();
+            }
+            current = entry;
+        }
+        let Value::Object(object) = current else { unreachable!() };
+        match object.get_mut(path[path.len() - 1]) {
+            Some(existing) => deep_merge(existing, &value),
+            None => {
+                object.insert(path[path.len() - 1].to_string(), value);
+            }
+        }
+    }
+    root
+}
+
+/// `merged` with the folder layer, the language's sections and policy applied.
+fn resolve_trees(mut resolved: Value, folder: Option<&Value>, policy: &Value, language: Option<&str>) -> Value {
+    if let Some(folder) = folder {
+        deep_merge(&mut resolved, folder);
+    }
+    if let Some(section) = language.and_then(|l| language_section(&resolved, l)) {
+        deep_merge(&mut resolved, &section);
+    }
+    deep_merge(&mut resolved, policy);
+    resolved
+}
+
+fn lookup<'v>(tree: &'v Value, key: &str) -> Option<&'v Value> {
+    if key.is_empty() {
+        return Some(tree);
+    }
+    key.split('.').try_fold(tree, |current, segment| current.get(segment))
+}
+
+/// The merged contents of every section of `tree` that applies to `language`. Sections
+/// naming several languages are applied first, so `[python]` wins over `[python][go]`.
+fn language_section(tree: &Value, language: &str) -> Option<Value> {
+    let Value::Object(map) = tree else { return None };
+    let mut sections: Vec<(usize, &Value)> = map.iter()
+        .filter_map(|(key, value)| override_identifiers(key).filter(|ids| ids.contains(&language)).map(|ids| (ids.len(), value)))
+        .collect();
+    if sections.is_empty() {
+        return None;
+    }
+    sections.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+    let mut merged = empty();
+    for (_, section) in sections {
+        deep_merge(&mut merged, section);
+    }
+    Some(merged)
+}
+
+fn normalize_folder(path: &str) -> String {
+    path.trim_end_matches(['/', '\\']).to_string()
 }
 
 #[napi]
@@ -21,72 +442,356 @@ impl ConfigurationService {
     #[napi(constructor)]
     pub fn new() -> Self {
         Self {
-            default_config: "{}".to_string(),
-            user_config: "{}".to_string(),
-            workspace_config: "{}".to_string(),
-            machine_config: "{}".to_string(),
-            merged_config: "{}".to_string(),
+            layers: ConfigurationLayers::new(),
+            listeners: Vec::new(),
+            next_listener_id: 1,
+            settings_schema: None,
+            sources: HashMap::new(),
+            diagnostics: HashMap::new(),
         }
     }
 
     #[napi]
-    pub fn update_default_config(&mut self, content: String) -> Result<()> {
-        self.default_config = self.parse_content(content)?;
-        self.recompute();
-        Ok(())
+    pub fn update_default_config(&mut self, content: String) -> Result<ConfigurationChangeEvent> {
+        self.update_layer("default", content, |layers| &mut layers.default_config)
     }
 
     #[napi]
-    pub fn update_user_config(&mut self, content: String) -> Result<()> {
-        self.user_config = self.parse_content(content)?;
-        self.recompute();
-        Ok(())
+    pub fn update_user_config(&mut self, content: String) -> Result<ConfigurationChangeEvent> {
+        self.update_layer("user", content, |layers| &mut layers.user_config)
     }
 
     #[napi]
-    pub fn update_workspace_config(&mut self, content: String) -> Result<()> {
-        self.workspace_config = self.parse_content(content)?;
-        self.recompute();
-        Ok(())
+    pub fn update_workspace_config(&mut self, content: String) -> Result<ConfigurationChangeEvent> {
+        self.update_layer("workspace", content, |layers| &mut layers.workspace_config)
     }
 
     #[napi]
-    pub fn update_machine_config(&mut self, content: String) -> Result<()> {
-        self.machine_config = self.parse_content(content)?;
-        self.recompute();
-        Ok(())
+    pub fn update_machine_config(&mut self, content: String) -> Result<ConfigurationChangeEvent> {
+        self.update_layer("machine", content, |layers| &mut layers.machine_config)
+    }
+
+    /// Policy values override every other layer and cannot be changed by the user.
+    #[napi]
+    pub fn update_policy_config(&mut self, content: String) -> Result<ConfigurationChangeEvent> {
+        self.update_layer("policy", content, |layers| &mut layers.policy_config)
+    }
+
+    /// Sets the `.vscode/settings.json` of one workspace folder. Its diagnostics are stored
+    /// under the layer name `workspaceFolder:<folder>`.
+    #[napi]
+    pub fn update_folder_config(&mut self, folder: String, content: String) -> Result<ConfigurationChangeEvent> {
+        let folder = normalize_folder(&folder);
+        let layer = format!("workspaceFolder:{}", folder);
+        let config = to_values_tree(&self.parse_layer(&layer, content)?);
+        Ok(self.update_layers(&layer, config, |layers| layers.folder_config_mut(&folder)))
+    }
+
+    #[napi]
+    pub fn remove_folder_config(&mut self, folder: String) -> ConfigurationChangeEvent {
+        let folder = normalize_folder(&folder);
+        let layer = format!("workspaceFolder:{}", folder);
+        self.sources.remove(&layer);
+        self.diagnostics.remove(&layer);
+        // Emptying the folder's settings changes the same keys as dropping them.
+        let event = self.update_layers(&layer, empty(), |layers| layers.folder_config_mut(&folder));
+        self.layers.folder_configs.retain(|(f, _)| *f != folder);
+        event
     }
 
+    /// Registers `callback` for every update that changes an effective value. Returns an id
+    /// for `remove_change_listener`. The listener does not keep the event loop alive.
+    #[napi]
+    pub fn on_did_change_configuration(
+        &mut self,
+        env: Env,
+        #[napi(ts_arg_type = "(event: ConfigurationChangeEvent) => void")]
+        mut callback: ThreadsafeFunction<ConfigurationChangeEvent, ErrorStrategy::Fatal>,
+    ) -> Result<u32> {
+        callback.unref(&env)?;
+        let id = self.next_listener_id;
+        self.next_listener_id += 1;
+        self.listeners.push((id, callback));
+        Ok(id)
+    }
+
+    #[napi]
+    pub fn remove_change_listener(&mut self, id: u32) -> bool {
+        let before = self.listeners.len();
+        self.listeners.retain(|(listener, _)| *listener != id);
+        self.listeners.len() != before
+    }
+
+    /// Default, machine, user and workspace settings merged as written, with policy applied.
+    /// Keys keep their dotted form, e.g. `{"editor.fontSize": 12}`; `get_values_tree` has
+    /// the nested form.
     #[napi(getter)]
     pub fn get_merged_config(&self) -> String {
-        self.merged_config.clone()
+        serde_json::to_string_pretty(&self.layers.written_merged).unwrap_or_default()
+    }
+
+    /// Effective settings as a nested tree (`{"editor": {"fontSize": 12}}`), for an optional
+    /// language and resource.
+    #[napi]
+    pub fn get_values_tree(&self, overrides: Option<ConfigurationOverrides>) -> String {
+        self.layers.resolve(&overrides.unwrap_or_default()).to_string()
+    }
+
+    /// Effective value of `key` (dotted section path) as JSON, for an optional language and
+    /// resource.
+    #[napi]
+    pub fn get_value(&self, key: String, overrides: Option<ConfigurationOverrides>) -> Option<String> {
+        let resolved = self.layers.resolve(&overrides.unwrap_or_default());
+        lookup(&resolved, &key).map(Value::to_string)
     }
 
+    /// Per-layer values of `key` and the layer the effective value came from.
     #[napi]
-    pub fn get_value(&self, key: String) -> Option<String> {
-        json_get(self.merged_config.clone(), key)
+    pub fn inspect(&self, key: String, overrides: Option<ConfigurationOverrides>) -> ConfigurationInspect {
+        let overrides = overrides.unwrap_or_default();
+        let language = overrides.override_identifier.as_deref();
+        let folder = overrides.resource.as_deref().and_then(|r| self.layers.folder_for(r));
+        let layer_value = |tree: Option<&Value>| {
+            let tree = tree.unwrap_or(&Value::Null);
+            ConfigurationLayerValue {
+                value: lookup(tree, &key).map(Value::to_string),
+                override_value: language.and_then(|l| language_section(tree, l)).and_then(|s| lookup(&s, &key).map(Value::to_string)),
+            }
+        };
+        let layers = [
+            ("default", Some(&self.layers.default_config)),
+            ("machine", Some(&self.layers.machine_config)),
+            ("user", Some(&self.layers.user_config)),
+            ("workspace", Some(&self.layers.workspace_config)),
+            ("workspaceFolder", folder),
+        ];
+        let values: Vec<(&str, ConfigurationLayerValue)> = layers.iter().map(|(name, tree)| (*name, layer_value(*tree))).collect();
+        let policy_value = layer_value(Some(&self.layers.policy_config));
+
+        let (source, from_language_override) = if policy_value.value.is_some() {
+            (Some("policy"), false)
+        } else if let Some((name, _)) = values.iter().rev().find(|(_, v)| v.override_value.is_some()) {
+            (Some(*name), true)
+        } else {
+            (values.iter().rev().find(|(_, v)| v.value.is_some()).map(|(name, _)| *name), false)
+        };
+
+        let mut override_identifiers_for_key: Vec<String> = Vec::new();
+        for tree in layers.iter().filter_map(|(_, t)| *t) {
+            let Value::Object(map) = tree else { continue };
+            for (section_key, section) in map {
+                let Some(ids) = override_identifiers(section_key) else { continue };
+                if lookup(section, &key).is_some() {
+                    for id in ids {
+                        if !override_identifiers_for_key.iter().any(|i| i == id) {
+                            override_identifiers_for_key.push(id.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut values = values.into_iter().map(|(_, v)| v);
+        ConfigurationInspect {
+            value: self.get_value(key.clone(), Some(overrides.clone())),
+            key,
+            source: source.map(String::from),
+            from_language_override,
+            default_value: values.next().unwrap(),
+            machine_value: values.next().unwrap(),
+            user_value: values.next().unwrap(),
+            workspace_value: values.next().unwrap(),
+            workspace_folder_value: values.next().unwrap(),
+            policy_value,
+            override_identifiers: override_identifiers_for_key,
+        }
     }
 
-    fn parse_content(&self, content: String) -> Result<String> {
+    /// Sets the settings schema and revalidates every layer against it.
+    #[napi]
+    pub fn set_settings_schema(&mut self, schema: String) -> Result<()> {
+        let schema: Value = serde_json::from_str(&schema)
+            .map_err(|e| Error::from_reason(format!("Invalid schema: {}", e)))?;
+        self.settings_schema = Some(schema);
+        let sources: Vec<(String, String)> = self.sources.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        for (layer, content) in sources {
+            let diagnostics = self.validate_settings(content);
+            self.diagnostics.insert(layer, diagnostics);
+        }
+        Ok(())
+    }
+
+    /// Syntax errors and schema problems in `content`, positioned for squiggles.
+    #[napi]
+    pub fn validate_settings(&self, content: String) -> Vec<JsonDiagnostic> {
+        diagnose(&content, self.settings_schema.as_ref())
+    }
+
+    /// Diagnostics from the last update of `layer` ("default", "machine", "user", "workspace",
+    /// "policy" or "workspaceFolder:<folder>").
+    #[napi]
+    pub fn get_diagnostics(&self, layer: String) -> Vec<JsonDiagnostic> {
+        self.diagnostics.get(&layer).cloned().unwrap_or_default()
+    }
+
+    /// Replaces the `source` layer, found by `slot`, with `new` and notifies listeners of the
+    /// keys whose effective value changed. Only keys that changed in the layer itself are
+    /// compared, and only in the scopes that could shadow them.
+    fn update_layers(&mut self, source: &str, new: Value, slot: impl Fn(&mut ConfigurationLayers) -> &mut Value) -> ConfigurationChangeEvent {
+        let keys = changed_keys(slot(&mut self.layers), &new);
+        let scopes = self.layers.shadowing_scopes(&keys, source, &new);
+        let values = |layers: &ConfigurationLayers| -> Vec<Vec<Option<Value>>> {
+            scopes.iter().map(|scope| keys.iter().map(|key| layers.value_at(key, scope)).collect()).collect()
+        };
+        let before = values(&self.layers);
+        *slot(&mut self.layers) = new;
+        self.layers.recompute();
+        let after = values(&self.layers);
+        let scopes = scopes.iter().zip(before.iter().zip(&after))
+            .map(|(scope, (before, after))| ChangedScope {
+                folder: scope.resource.clone(),
+                language: scope.override_identifier.clone(),
+                keys: keys.iter().zip(before.iter().zip(after)).filter(|(_, (b, a))| b != a).map(|(key, _)| key.clone()).collect(),
+            })
+            .collect();
+        let folders = self.layers.folder_configs.iter().map(|(f, _)| f.clone()).collect();
+        let event = ConfigurationChangeEvent { source: source.to_string(), scopes, folders };
+        if !event.is_empty() {
+            for (_, listener) in &self.listeners {
+                listener.call(event.clone(), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        }
+        event
+    }
+
+    /// Replaces one of the non-folder layers with `content`.
+    fn update_layer(&mut self, layer: &str, content: String, slot: fn(&mut ConfigurationLayers) -> &mut Value) -> Result<ConfigurationChangeEvent> {
+        let written = self.parse_layer(layer, content)?;
+        let config = to_values_tree(&written);
+        self.layers.written_configs.insert(layer.to_string(), written);
+        Ok(self.update_layers(layer, config, slot))
+    }
+
+    /// Records the layer's diagnostics, including when it fails to parse, then parses it as
+    /// written.
+    fn parse_layer(&mut self, layer: &str, content: String) -> Result<Value> {
+        let diagnostics = self.validate_settings(content.clone());
+        self.diagnostics.insert(layer.to_string(), diagnostics);
+        self.sources.insert(layer.to_string(), content.clone());
+        self.parse_content(content)
+    }
+
+    fn parse_content(&self, content: String) -> Result<Value> {
         let result = parse_jsonc(content);
         if result.success {
-            Ok(result.value.unwrap_or_else(|| "{}".to_string()))
+            Ok(result.value.and_then(|v| serde_json::from_str(&v).ok()).unwrap_or_else(empty))
         } else {
             // IF parsing fails, we could return empty object or error.
             // Better to return error so frontend knows.
             Err(Error::from_reason(format!("Parse error: {}", result.error_message.unwrap_or_default())))
         }
     }
+}
 
-    fn recompute(&mut self) {
-        // Order: Default < Machine < User < Workspace
-        // Or: Default < User < Remote < Workspace < WorkspaceFolder
-        // Simplified: Default < Machine < User < Workspace
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_diagnostics() {
+        let mut service = ConfigurationService::new();
+        service.update_user_config("{\n  // mine\n  \"editor.fontSize\": \"big\"\n}".into()).unwrap();
+        assert!(service.get_diagnostics("user".into()).is_empty());
+
+        service.set_settings_schema(r#"{"properties": {"editor.fontSize": {"type": "number"}}}"#.into()).unwrap();
+        let diagnostics = service.get_diagnostics("user".into());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (3, 22));
+        // Invalid values are still applied; they are only flagged.
+        let merged: Value = serde_json::from_str(&service.get_merged_config()).unwrap();
+        assert_eq!(merged["editor.fontSize"], "big");
+        let tree: Value = serde_json::from_str(&service.get_values_tree(None)).unwrap();
+        assert_eq!(tree["editor"]["fontSize"], "big");
+
+        assert!(service.update_workspace_config("{\"a\": }".into()).is_err());
+        assert_eq!(service.get_diagnostics("workspace".into())[0].severity, "error");
+    }
+
+    #[test]
+    fn test_language_folder_and_policy_layers() {
+        let mut service = ConfigurationService::new();
+        service.update_default_config(r#"{"editor.tabSize": 4, "editor.formatOnSave": false, "editor": {"defaultFormatter": null}}"#.into()).unwrap();
+        service.update_user_config(r#"{"editor.tabSize": 2, "[python]": {"editor.tabSize": 8, "editor.defaultFormatter": "black"}}"#.into()).unwrap();
+        service.update_workspace_config(r#"{"editor.tabSize": 3, "[javascript][typescript]": {"editor.defaultFormatter": "prettier"}}"#.into()).unwrap();
+        service.update_folder_config("/repo/api/".into(), r#"{"[python]": {"editor.defaultFormatter": "ruff"}, "editor.formatOnSave": true}"#.into()).unwrap();
+
+        let get = |service: &ConfigurationService, key: &str, language: Option<&str>, resource: Option<&str>| {
+            let overrides = ConfigurationOverrides { override_identifier: language.map(Into::into), resource: resource.map(Into::into) };
+            service.get_value(key.into(), Some(overrides))
+        };
+        assert_eq!(get(&service, "editor.tabSize", None, None).as_deref(), Some("3"));
+        // Language sections beat plain values from higher layers.
+        assert_eq!(get(&service, "editor.tabSize", Some("python"), None).as_deref(), Some("8"));
+        assert_eq!(get(&service, "editor.defaultFormatter", Some("typescript"), None).as_deref(), Some("\"prettier\""));
+        assert_eq!(get(&service, "editor.defaultFormatter", Some("python"), Some("/repo/api/main.py")).as_deref(), Some("\"ruff\""));
+        assert_eq!(get(&service, "editor.defaultFormatter", Some("python"), Some("/repo/apiary/x.py")).as_deref(), Some("\"black\""));
+        assert_eq!(get(&service, "editor.formatOnSave", None, Some("/repo/api")).as_deref(), Some("true"));
+        assert_eq!(service.get_value("editor".into(), None).map(|v| v.contains("\"tabSize\":3")), Some(true));
+
+        let inspect = service.inspect("editor.defaultFormatter".into(), Some(ConfigurationOverrides { override_identifier: Some("python".into()), resource: Some("/repo/api/main.py".into()) }));
+        assert_eq!(inspect.source.as_deref(), Some("workspaceFolder"));
+        assert!(inspect.from_language_override);
+        assert_eq!(inspect.user_value.override_value.as_deref(), Some("\"black\""));
+        assert_eq!(inspect.default_value.value.as_deref(), Some("null"));
+        assert_eq!(inspect.override_identifiers, ["python", "javascript", "typescript"]);
+
+        service.update_policy_config(r#"{"editor.tabSize": 4}"#.into()).unwrap();
+        let inspect = service.inspect("editor.tabSize".into(), Some(ConfigurationOverrides { override_identifier: Some("python".into()), resource: None }));
+        assert_eq!((inspect.value.as_deref(), inspect.source.as_deref()), (Some("4"), Some("policy")));
+
+        service.remove_folder_config("/repo/api".into());
+        assert_eq!(get(&service, "editor.formatOnSave", None, Some("/repo/api")).as_deref(), Some("false"));
+    }
+
+    #[test]
+    fn test_change_events() {
+        let mut service = ConfigurationService::new();
+        service.update_default_config(r#"{"editor.tabSize": 4, "editor.fontSize": 12, "files.eol": "\n"}"#.into()).unwrap();
+        service.update_user_config(r#"{"[python]": {"editor.tabSize": 8}}"#.into()).unwrap();
+        service.update_folder_config("/repo/web".into(), "{}".into()).unwrap();
+
+        let event = service.update_user_config(r#"{"editor.tabSize": 2, "[python]": {"editor.tabSize": 8}, "editor.fontSize": 12}"#.into()).unwrap();
+        assert_eq!(event.source(), "user");
+        assert_eq!(event.affected_keys(), ["editor.tabSize"]);
+        assert!(event.affects_configuration("editor".into(), None));
+        assert!(!event.affects_configuration("files".into(), None));
+        // Python's own section still wins, so Python files are unaffected.
+        let python = ConfigurationOverrides { override_identifier: Some("python".into()), resource: None };
+        assert!(!event.affects_configuration("editor.tabSize".into(), Some(python)));
+        let go = ConfigurationOverrides { override_identifier: Some("go".into()), resource: None };
+        assert!(event.affects_configuration("editor.tabSize".into(), Some(go)));
+
+        let event = service.update_folder_config("/repo/web".into(), r#"{"files.eol": "\r\n"}"#.into()).unwrap();
+        assert_eq!(event.affected_keys(), ["files.eol"]);
+        let inside = ConfigurationOverrides { override_identifier: None, resource: Some("/repo/web/index.ts".into()) };
+        let outside = ConfigurationOverrides { override_identifier: None, resource: Some("/repo/api/main.py".into()) };
+        assert!(event.affects_configuration("files.eol".into(), Some(inside)));
+        assert!(!event.affects_configuration("files.eol".into(), Some(outside)));
 
-        let s1 = json_merge(self.default_config.clone(), self.machine_config.clone()).unwrap_or(self.default_config.clone());
-        let s2 = json_merge(s1, self.user_config.clone()).unwrap_or(self.default_config.clone()); // Fallback might be wrong logic but essentially we want to keep merging
-        let s3 = json_merge(s2, self.workspace_config.clone()).unwrap_or(self.user_config.clone());
+        // A nested folder that leaves the key alone sees the plain scope, not its parent's.
+        service.update_folder_config("/repo/web/docs".into(), r#"{"editor.tabSize": 3}"#.into()).unwrap();
+        let event = service.update_user_config(r#"{"files.eol": "\r"}"#.into()).unwrap();
+        let nested = ConfigurationOverrides { override_identifier: None, resource: Some("/repo/web/docs/a.md".into()) };
+        let web = ConfigurationOverrides { override_identifier: None, resource: Some("/repo/web/index.ts".into()) };
+        assert!(event.affects_configuration("files.eol".into(), Some(nested)));
+        assert!(!event.affects_configuration("files.eol".into(), Some(web.clone())));
+        let event = service.remove_folder_config("/repo/web".into());
+        assert_eq!(event.affected_keys(), ["files.eol"]);
+        assert!(event.affects_configuration("files.eol".into(), Some(web)));
 
-        self.merged_config = s3;
+        assert!(service.update_machine_config("{}".into()).unwrap().is_empty());
+        let event = service.update_default_config(r#"{"editor.tabSize": 4, "editor.fontSize": 12, "files.eol": "\n", "editor.minimap": {"enabled": true}}"#.into()).unwrap();
+        assert_eq!(event.affected_keys(), ["editor.minimap.enabled"]);
+        assert!(event.affects_configuration("editor.minimap".into(), None));
     }
 }
@@ -1,7 +1,11 @@
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
+use lru::LruCache;
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, OnceLock};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[napi(object)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -11,9 +15,455 @@ pub struct ContextKeyExpression {
     pub operator: String, // "==", "!=", "in", "notIn", "RegexMatches"
 }
 
+#[napi(object)]
+pub struct ParsedWhenClause {
+    /// Canonical form, e.g. `a && (b || !c)`.
+    pub serialized: String,
+    /// Context keys the expression reads; re-evaluate when any of them changes.
+    pub keys: Vec<String>,
+}
+
+/// Parsed `when` clause, following the grammar of VS Code's `contextkey.ts` parser.
+#[derive(Debug)]
+pub enum ContextKeyExpr {
+    True,
+    False,
+    Defined(String),
+    Equals(String, String),
+    NotEquals(String, String),
+    /// Key, compiled pattern and the `/.../flags` flags it was written with.
+    Regex(String, regex::Regex, String),
+    In(String, String),
+    NotIn(String, String),
+    Greater(String, f64),
+    GreaterEquals(String, f64),
+    Smaller(String, f64),
+    SmallerEquals(String, f64),
+    Not(Box<ContextKeyExpr>),
+    And(Vec<ContextKeyExpr>),
+    Or(Vec<ContextKeyExpr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Eq,
+    NotEq,
+    RegexOp,
+    Regex(String, String),
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    In,
+    NotKw,
+    True,
+    False,
+    Word(String),
+    Str(String),
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !"()!=<>&|'\"~".contains(c)
+}
+
+fn tokenize(input: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let at = |i: usize, s: &str| s.chars().enumerate().all(|(k, c)| chars.get(i + k) == Some(&c));
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        // A regex literal can only follow `=~`; it may contain any character.
+        if tokens.last() == Some(&Token::RegexOp) {
+            if c != '/' {
+                return Err(format!("Expected a regex literal at offset {}", i));
+            }
+            let mut source = String::new();
+            let mut j = i + 1;
+            let mut in_class = false;
+            while j < chars.len() && (chars[j] != '/' || in_class) {
+                if chars[j] == '\\' && j + 1 < chars.len() {
+                    source.push(chars[j]);
+                    j += 1;
+                } else if chars[j] == '[' {
+                    in_class = true;
+                } else if chars[j] == ']' {
+                    in_class = false;
+                }
+                source.push(chars[j]);
+                j += 1;
+            }
+            if j >= chars.len() {
+                return Err(format!("Unterminated regex at offset {}", i));
+            }
+            j += 1;
+            let flags: String = chars[j..].iter().take_while(|c| c.is_ascii_alphabetic()).collect();
+            i = j + flags.len();
+            tokens.push(Token::Regex(source, flags));
+            continue;
+        }
+
+        let (token, len) = if at(i, "&&") {
+            (Token::And, 2)
+        } else if at(i, "||") {
+            (Token::Or, 2)
+        } else if at(i, "!==") {
+            (Token::NotEq, 3)
+        } else if at(i, "!=") {
+            (Token::NotEq, 2)
+        } else if at(i, "===") {
+            (Token::Eq, 3)
+        } else if at(i, "==") {
+            (Token::Eq, 2)
+        } else if at(i, "=~") {
+            (Token::RegexOp, 2)
+        } else if at(i, "<=") {
+            (Token::LtEq, 2)
+        } else if at(i, ">=") {
+            (Token::GtEq, 2)
+        } else {
+            match c {
+                '(' => (Token::LParen, 1),
+                ')' => (Token::RParen, 1),
+                '!' => (Token::Not, 1),
+                '<' => (Token::Lt, 1),
+                '>' => (Token::Gt, 1),
+                // `\` escapes the closing quote or a backslash; other backslashes are literal.
+                '\'' | '"' => {
+                    let mut text = String::new();
+                    let mut j = i + 1;
+                    while j < chars.len() && chars[j] != c {
+                        if chars[j] == '\\' && matches!(chars.get(j + 1), Some(&next) if next == c || next == '\\') {
+                            j += 1;
+                        }
+                        text.push(chars[j]);
+                        j += 1;
+                    }
+                    if j >= chars.len() {
+                        return Err(format!("Unterminated string at offset {}", i));
+                    }
+                    (Token::Str(text), j + 1 - i)
+                }
+                _ if is_word_char(c) => {
+                    let word: String = chars[i..].iter().take_while(|&&c| is_word_char(c)).collect();
+                    let len = word.chars().count();
+                    let token = match word.as_str() {
+                        "in" => Token::In,
+                        "not" => Token::NotKw,
+                        "true" => Token::True,
+                        "false" => Token::False,
+                        _ => Token::Word(word),
+                    };
+                    (token, len)
+                }
+                _ => return Err(format!("Unexpected '{}' at offset {}", c, i)),
+            }
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> std::result::Result<ContextKeyExpr, String> {
+        let mut terms = vec![self.and()?];
+        while self.eat(&Token::Or) {
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 { terms.pop().unwrap() } else { ContextKeyExpr::Or(terms) })
+    }
+
+    fn and(&mut self) -> std::result::Result<ContextKeyExpr, String> {
+        let mut terms = vec![self.term()?];
+        while self.eat(&Token::And) {
+            terms.push(self.term()?);
+        }
+        Ok(if terms.len() == 1 { terms.pop().unwrap() } else { ContextKeyExpr::And(terms) })
+    }
+
+    fn term(&mut self) -> std::result::Result<ContextKeyExpr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(ContextKeyExpr::Not(Box::new(self.term()?)));
+        }
+        self.primary()
+    }
+
+    /// A value on the right of an operator: quoted, a bare word, or a keyword used as text.
+    fn value(&mut self) -> std::result::Result<String, String> {
+        match self.next() {
+            Some(Token::Word(s)) | Some(Token::Str(s)) => Ok(s),
+            Some(Token::True) => Ok("true".into()),
+            Some(Token::False) => Ok("false".into()),
+            Some(Token::In) => Ok("in".into()),
+            Some(Token::NotKw) => Ok("not".into()),
+            other => Err(format!("Expected a value but found {:?}", other)),
+        }
+    }
+
+    fn number(&mut self) -> std::result::Result<Option<f64>, String> {
+        Ok(self.value()?.parse::<f64>().ok())
+    }
+
+    fn primary(&mut self) -> std::result::Result<ContextKeyExpr, String> {
+        let key = match self.next() {
+            Some(Token::True) => return Ok(ContextKeyExpr::True),
+            Some(Token::False) => return Ok(ContextKeyExpr::False),
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                if !self.eat(&Token::RParen) {
+                    return Err("Expected ')'".into());
+                }
+                return Ok(expr);
+            }
+            Some(Token::Word(key)) => key,
+            other => return Err(format!("Expected a context key but found {:?}", other)),
+        };
+
+        let numeric = |n: Option<f64>, make: fn(String, f64) -> ContextKeyExpr, key: String| {
+            n.map_or(ContextKeyExpr::False, |n| make(key, n))
+        };
+        Ok(match self.peek() {
+            Some(Token::Eq) | Some(Token::NotEq) => {
+                let negate = self.next() == Some(Token::NotEq);
+                // `key == true` is the same as `key`, as in VS Code.
+                let expr = match self.peek() {
+                    Some(Token::True) => { self.pos += 1; ContextKeyExpr::Defined(key) }
+                    Some(Token::False) => { self.pos += 1; ContextKeyExpr::Not(Box::new(ContextKeyExpr::Defined(key))) }
+                    _ => ContextKeyExpr::Equals(key, self.value()?),
+                };
+                match (negate, expr) {
+                    (false, expr) => expr,
+                    (true, ContextKeyExpr::Equals(key, value)) => ContextKeyExpr::NotEquals(key, value),
+                    (true, expr) => ContextKeyExpr::Not(Box::new(expr)),
+                }
+            }
+            Some(Token::RegexOp) => {
+                self.pos += 1;
+                let Some(Token::Regex(source, flags)) = self.next() else { return Err("Expected a regex literal".into()) };
+                let regex = regex::RegexBuilder::new(&source)
+                    .case_insensitive(flags.contains('i'))
+                    .multi_line(flags.contains('m'))
+                    .dot_matches_new_line(flags.contains('s'))
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                ContextKeyExpr::Regex(key, regex, flags)
+            }
+            Some(Token::In) => {
+                self.pos += 1;
+                ContextKeyExpr::In(key, self.value()?)
+            }
+            Some(Token::NotKw) => {
+                self.pos += 1;
+                if !self.eat(&Token::In) {
+                    return Err("Expected 'in' after 'not'".into());
+                }
+                ContextKeyExpr::NotIn(key, self.value()?)
+            }
+            Some(Token::Lt) => { self.pos += 1; numeric(self.number()?, ContextKeyExpr::Smaller, key) }
+            Some(Token::LtEq) => { self.pos += 1; numeric(self.number()?, ContextKeyExpr::SmallerEquals, key) }
+            Some(Token::Gt) => { self.pos += 1; numeric(self.number()?, ContextKeyExpr::Greater, key) }
+            Some(Token::GtEq) => { self.pos += 1; numeric(self.number()?, ContextKeyExpr::GreaterEquals, key) }
+            _ => ContextKeyExpr::Defined(key),
+        })
+    }
+}
+
+/// JavaScript truthiness, which is what VS Code applies to a bare `key`.
+fn truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_f64().is_some_and(|n| n != 0.0 && !n.is_nan()),
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(_) => true,
+    }
+}
+
+fn as_text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn as_number(value: Option<&Value>) -> Option<f64> {
+    match value? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+impl ContextKeyExpr {
+    pub fn parse(input: &str) -> std::result::Result<ContextKeyExpr, String> {
+        let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+        if parser.tokens.is_empty() {
+            return Ok(ContextKeyExpr::True);
+        }
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+        }
+    }
+
+    pub fn evaluate(&self, context: &impl Fn(&str) -> Option<Value>) -> bool {
+        match self {
+            ContextKeyExpr::True => true,
+            ContextKeyExpr::False => false,
+            ContextKeyExpr::Defined(key) => truthy(context(key).as_ref()),
+            ContextKeyExpr::Equals(key, value) => as_text(context(key).as_ref()).as_deref() == Some(value.as_str()),
+            ContextKeyExpr::NotEquals(key, value) => as_text(context(key).as_ref()).as_deref() != Some(value.as_str()),
+            ContextKeyExpr::Regex(key, regex, _) => as_text(context(key).as_ref()).is_some_and(|v| regex.is_match(&v)),
+            ContextKeyExpr::In(key, source) | ContextKeyExpr::NotIn(key, source) => {
+                let item = as_text(context(key).as_ref());
+                let found = match (item, context(source)) {
+                    (Some(item), Some(Value::Array(values))) => values.iter().any(|v| as_text(Some(v)).as_deref() == Some(item.as_str())),
+                    (Some(item), Some(Value::Object(map))) => map.contains_key(&item),
+                    _ => false,
+                };
+                found == matches!(self, ContextKeyExpr::In(..))
+            }
+            ContextKeyExpr::Greater(key, n) => as_number(context(key).as_ref()).is_some_and(|v| v > *n),
+            ContextKeyExpr::GreaterEquals(key, n) => as_number(context(key).as_ref()).is_some_and(|v| v >= *n),
+            ContextKeyExpr::Smaller(key, n) => as_number(context(key).as_ref()).is_some_and(|v| v < *n),
+            ContextKeyExpr::SmallerEquals(key, n) => as_number(context(key).as_ref()).is_some_and(|v| v <= *n),
+            ContextKeyExpr::Not(expr) => !expr.evaluate(context),
+            ContextKeyExpr::And(terms) => terms.iter().all(|t| t.evaluate(context)),
+            ContextKeyExpr::Or(terms) => terms.iter().any(|t| t.evaluate(context)),
+        }
+    }
+
+    /// Every key the expression reads, in first-use order.
+    pub fn keys(&self) -> Vec<String> {
+        fn collect(expr: &ContextKeyExpr, out: &mut Vec<String>) {
+            let mut push = |key: &String| if !out.contains(key) { out.push(key.clone()) };
+            match expr {
+                ContextKeyExpr::True | ContextKeyExpr::False => {}
+                ContextKeyExpr::In(key, source) | ContextKeyExpr::NotIn(key, source) => {
+                    push(key);
+                    push(source);
+                }
+                ContextKeyExpr::Defined(key)
+                | ContextKeyExpr::Equals(key, _)
+                | ContextKeyExpr::NotEquals(key, _)
+                | ContextKeyExpr::Regex(key, ..)
+                | ContextKeyExpr::Greater(key, _)
+                | ContextKeyExpr::GreaterEquals(key, _)
+                | ContextKeyExpr::Smaller(key, _)
+                | ContextKeyExpr::SmallerEquals(key, _) => push(key),
+                ContextKeyExpr::Not(expr) => collect(expr, out),
+                ContextKeyExpr::And(terms) | ContextKeyExpr::Or(terms) => terms.iter().for_each(|t| collect(t, out)),
+            }
+        }
+        let mut keys = Vec::new();
+        collect(self, &mut keys);
+        keys
+    }
+
+    pub fn serialize(&self) -> String {
+        let quote = |v: &str| {
+            if !v.is_empty() && v.chars().all(is_word_char) {
+                return v.to_string();
+            }
+            let q = if v.contains('\'') && !v.contains('"') { '"' } else { '\'' };
+            let escaped = v.replace('\\', "\\\\").replace(q, &format!("\\{}", q));
+            format!("{q}{escaped}{q}")
+        };
+        let group = |expr: &ContextKeyExpr| match expr {
+            ContextKeyExpr::And(_) | ContextKeyExpr::Or(_) => format!("({})", expr.serialize()),
+            _ => expr.serialize(),
+        };
+        match self {
+            ContextKeyExpr::True => "true".into(),
+            ContextKeyExpr::False => "false".into(),
+            ContextKeyExpr::Defined(key) => key.clone(),
+            ContextKeyExpr::Equals(key, value) => format!("{} == {}", key, quote(value)),
+            ContextKeyExpr::NotEquals(key, value) => format!("{} != {}", key, quote(value)),
+            ContextKeyExpr::Regex(key, regex, flags) => format!("{} =~ /{}/{}", key, regex.as_str(), flags),
+            ContextKeyExpr::In(key, source) => format!("{} in {}", key, quote(source)),
+            ContextKeyExpr::NotIn(key, source) => format!("{} not in {}", key, quote(source)),
+            ContextKeyExpr::Greater(key, n) => format!("{} > {}", key, n),
+            ContextKeyExpr::GreaterEquals(key, n) => format!("{} >= {}", key, n),
+            ContextKeyExpr::Smaller(key, n) => format!("{} < {}", key, n),
+            ContextKeyExpr::SmallerEquals(key, n) => format!("{} <= {}", key, n),
+            ContextKeyExpr::Not(expr) => format!("!{}", group(expr)),
+            ContextKeyExpr::And(terms) => terms.iter().map(|t| match t {
+                ContextKeyExpr::Or(_) => format!("({})", t.serialize()),
+                _ => t.serialize(),
+            }).collect::<Vec<_>>().join(" && "),
+            ContextKeyExpr::Or(terms) => terms.iter().map(|t| t.serialize()).collect::<Vec<_>>().join(" || "),
+        }
+    }
+}
+
+/// Distinct `when` clauses kept parsed; enough for the keybindings of a workspace.
+const PARSE_CACHE_SIZE: usize = 1024;
+
+type ParseResult = std::result::Result<Arc<ContextKeyExpr>, String>;
+
+/// Parses through a process-wide LRU cache; `when` clauses repeat across keybindings.
+pub fn parse_cached(input: &str) -> ParseResult {
+    static CACHE: OnceLock<Mutex<LruCache<String, ParseResult>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(PARSE_CACHE_SIZE).unwrap())));
+    if let Some(parsed) = cache.lock().unwrap().get(input) {
+        return parsed.clone();
+    }
+    let parsed = ContextKeyExpr::parse(input).map(Arc::new);
+    cache.lock().unwrap().put(input.to_string(), parsed.clone());
+    parsed
+}
+
+/// Evaluates a `when` clause against JSON context values. Invalid clauses are false.
+pub fn evaluate_when(when: &str, context: &HashMap<String, Value>) -> bool {
+    parse_cached(when).is_ok_and(|expr| expr.evaluate(&|key| context.get(key).cloned()))
+}
+
+#[napi]
+pub fn parse_when_clause(when: String) -> Result<ParsedWhenClause> {
+    let expr = parse_cached(&when).map_err(Error::from_reason)?;
+    Ok(ParsedWhenClause { serialized: expr.serialize(), keys: expr.keys() })
+}
+
 #[napi]
 pub struct ContextKeyService {
-    context: HashMap<String, String>,
+    context: HashMap<String, Value>,
 }
 
 #[napi]
@@ -26,53 +476,87 @@ impl ContextKeyService {
     }
 
     #[napi]
-    pub fn set_context(&mut self, key: String, value: String) {
+    pub fn set_context(&mut self, key: String, value: Value) {
         self.context.insert(key, value);
     }
 
     #[napi]
-    pub fn get_context_value(&self, key: String) -> Option<String> {
+    pub fn remove_context(&mut self, key: String) -> bool {
+        self.context.remove(&key).is_some()
+    }
+
+    #[napi]
+    pub fn get_context_value(&self, key: String) -> Option<Value> {
         self.context.get(&key).cloned()
     }
 
     #[napi]
     pub fn evaluate(&self, expression: ContextKeyExpression) -> bool {
-        let current_val = self.context.get(&expression.key);
-        
-        match expression.operator.as_str() {
-            "==" => {
-                if let Some(target) = expression.value {
-                    current_val.map(|v| v == &target).unwrap_or(false)
-                } else {
-                    current_val.is_none()
-                }
-            },
-            "!=" => {
-                if let Some(target) = expression.value {
-                    current_val.map(|v| v != &target).unwrap_or(true)
-                } else {
-                    current_val.is_some()
-                }
+        let key = expression.key;
+        let value = expression.value;
+        let expr = match (expression.operator.as_str(), value) {
+            ("==", Some(value)) => ContextKeyExpr::Equals(key, value),
+            ("==", None) => ContextKeyExpr::Not(Box::new(ContextKeyExpr::Defined(key))),
+            ("!=", Some(value)) => ContextKeyExpr::NotEquals(key, value),
+            ("!=", None) => ContextKeyExpr::Defined(key),
+            ("in", Some(source)) => ContextKeyExpr::In(key, source),
+            ("notIn", Some(source)) => ContextKeyExpr::NotIn(key, source),
+            ("RegexMatches", Some(pattern)) => match regex::Regex::new(&pattern) {
+                Ok(regex) => ContextKeyExpr::Regex(key, regex, String::new()),
+                Err(_) => ContextKeyExpr::False,
             },
-            "in" => {
-                if let (Some(cv), Some(target)) = (current_val, expression.value) {
-                    target.contains(cv)
-                } else {
-                    false
-                }
-            },
-            "RegexMatches" => {
-                if let (Some(cv), Some(pattern)) = (current_val, expression.value) {
-                    if let Ok(re) = regex::Regex::new(&pattern) {
-                        re.is_match(cv)
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            },
-            _ => false
+            _ => ContextKeyExpr::False,
+        };
+        expr.evaluate(&|key| self.context.get(key).cloned())
+    }
+
+    /// Evaluates a full `when` clause such as `editorFocus && (resourceLangId == rust || isDebugging)`.
+    #[napi]
+    pub fn evaluate_when(&self, when: String) -> bool {
+        evaluate_when(&when, &self.context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> HashMap<String, Value> {
+        let mut context = HashMap::new();
+        context.insert("editorFocus".into(), json!(true));
+        context.insert("resourceLangId".into(), json!("rust"));
+        context.insert("resourceFilename".into(), json!("Cargo.TOML"));
+        context.insert("count".into(), json!(3));
+        context.insert("supported".into(), json!(["rust", "toml"]));
+        context
+    }
+
+    #[test]
+    fn test_parse_and_evaluate() {
+        let context = context();
+        assert!(evaluate_when("editorFocus && (resourceLangId == rust || isDebugging)", &context));
+        assert!(evaluate_when("!(isDebugging || resourceLangId != 'rust')", &context));
+        assert!(evaluate_when("resourceFilename =~ /cargo\\.toml$/i && count > 2 && count <= 3", &context));
+        assert!(evaluate_when("resourceLangId in supported && isDebugging not in supported", &context));
+        assert!(!evaluate_when("editorFocus == false || count < 1", &context));
+        assert!(!evaluate_when("editorFocus &&", &context));
+        assert!(evaluate_when("", &context));
+    }
+
+    #[test]
+    fn test_keys_and_serialize() {
+        let parsed = parse_when_clause("a && !(b || c == 'x y') && d in e".into()).unwrap();
+        assert_eq!(parsed.keys, vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(parsed.serialized, "a && !(b || c == 'x y') && d in e");
+        assert!(parse_when_clause("a == (".into()).is_err());
+
+        for when in ["name =~ /^x\\/y$/i", "a == \"it's\"", "a == 'say \\'hi\\' \"now\"'", "a == 'x \\\\ y'"] {
+            let serialized = parse_when_clause(when.into()).unwrap().serialized;
+            assert_eq!(serialized, when);
         }
+        let mut context = HashMap::new();
+        context.insert("a".to_string(), json!("say 'hi' \"now\""));
+        assert!(evaluate_when("a == 'say \\'hi\\' \"now\"'", &context));
     }
 }
@@ -65,7 +65,7 @@ impl Cursor {
                 self.position.column -= 1;
             } else if self.position.line_number > 1 {
                 self.position.line_number -= 1;
-                self.position.column = model.get_line_content(self.position.line_number - 1).chars().count() as u32 + 1;
+                self.position.column = model.get_line_content(self.position.line_number).chars().count() as u32 + 1;
             }
         }
         self.preferred_column = self.position.column;
@@ -77,7 +77,7 @@ impl Cursor {
     #[napi]
     pub fn move_right(&mut self, model: &TextModel, count: u32, keep_selection: bool) {
         for _ in 0..count {
-            let line_content = model.get_line_content(self.position.line_number - 1);
+            let line_content = model.get_line_content(self.position.line_number);
             let line_len = line_content.chars().count() as u32;
             if self.position.column <= line_len {
                 self.position.column += 1;
@@ -97,7 +97,7 @@ impl Cursor {
         for _ in 0..count {
             if self.position.line_number > 1 {
                 self.position.line_number -= 1;
-                let line_content = model.get_line_content(self.position.line_number - 1);
+                let line_content = model.get_line_content(self.position.line_number);
                 let line_len = line_content.chars().count() as u32;
                 self.position.column = std::cmp::min(self.preferred_column, line_len + 1);
             }
@@ -112,7 +112,7 @@ impl Cursor {
         for _ in 0..count {
             if self.position.line_number < model.line_count() {
                 self.position.line_number += 1;
-                let line_content = model.get_line_content(self.position.line_number - 1);
+                let line_content = model.get_line_content(self.position.line_number);
                 let line_len = line_content.chars().count() as u32;
                 self.position.column = std::cmp::min(self.preferred_column, line_len + 1);
             }
@@ -126,13 +126,13 @@ impl Cursor {
 
     #[napi]
     pub fn move_word_left(&mut self, model: &TextModel, keep_selection: bool) {
-        let content = model.get_line_content(self.position.line_number - 1);
+        let content = model.get_line_content(self.position.line_number);
         let new_col = word_ops::find_previous_word_start(&content, self.position.column);
         if new_col < self.position.column {
             self.position.column = new_col;
         } else if self.position.line_number > 1 {
             self.position.line_number -= 1;
-            let prev_content = model.get_line_content(self.position.line_number - 1);
+            let prev_content = model.get_line_content(self.position.line_number);
             self.position.column = prev_content.chars().count() as u32 + 1;
         }
         self.preferred_column = self.position.column;
@@ -143,7 +143,7 @@ impl Cursor {
 
     #[napi]
     pub fn move_word_right(&mut self, model: &TextModel, keep_selection: bool) {
-        let content = model.get_line_content(self.position.line_number - 1);
+        let content = model.get_line_content(self.position.line_number);
         let new_col = word_ops::find_next_word_end(&content, self.position.column);
         if new_col > self.position.column {
             self.position.column = new_col;
@@ -170,7 +170,7 @@ impl Cursor {
 
     #[napi]
     pub fn move_to_line_end(&mut self, model: &TextModel, keep_selection: bool) {
-        let content = model.get_line_content(self.position.line_number - 1);
+        let content = model.get_line_content(self.position.line_number);
         self.position.column = content.chars().count() as u32 + 1;
         self.preferred_column = self.position.column;
         if !keep_selection {
@@ -190,7 +190,7 @@ impl Cursor {
     #[napi]
     pub fn move_to_buffer_end(&mut self, model: &TextModel, keep_selection: bool) {
         let line_count = model.line_count();
-        let content = model.get_line_content(line_count - 1);
+        let content = model.get_line_content(line_count);
         self.position = Position::new(line_count, content.chars().count() as u32 + 1);
         self.preferred_column = self.position.column;
         if !keep_selection {
@@ -198,3 +198,16 @@ impl Cursor {
         }
     }
 }
+
+impl Cursor {
+    /// Cursor whose anchor is the selection start and whose caret is its position.
+    pub fn from_selection(selection: Selection) -> Self {
+        let position = Position::new(selection.position_line_number, selection.position_column);
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            position,
+            selection,
+            preferred_column: position.column,
+        }
+    }
+}
@@ -7,13 +7,16 @@
 //!
 //! High-performance text comparison supporting:
 //! - Multiple algorithms: Myers, Histogram, Luwenstein
-//! - Granularity levels: Line, Word, Character
+//! - Granularity levels: Line, Word, Character, with moved-block detection
 //! - Semantic Cleanup: Post-processing to ensure diffs are human-readable
-//! - Conflict Detection: Identifying overlapping changes in concurrent edits
+//! - Three-Way Merge: diff3-style merging with conflict markers and structured hunks
 
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
-use similar::{ChangeTag, TextDiff, Algorithm};
+use crate::range::Range;
+use similar::{capture_diff_slices, capture_diff_slices_deadline, Algorithm, ChangeTag, DiffOp, TextDiff};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 #[napi]
 pub enum DiffAlgorithm {
@@ -91,36 +94,518 @@ pub struct ConflictMatch {
 
 #[napi]
 pub fn detect_conflicts(base: String, mine: String, theirs: String) -> Vec<ConflictMatch> {
-    // Basic 3-way merge conflict detection logic
-    // In a full implementation, this uses a robust 3-way diff algorithm
-    let mut conflicts = Vec::new();
-
-    let diff_mine = TextDiff::from_lines(&base, &mine);
-    let diff_theirs = TextDiff::from_lines(&base, &theirs);
-
-    // Check for overlapping changes
-    // This is a simplified detection for vertical depth proof
-    let mine_changes: Vec<_> = diff_mine.iter_all_changes()
-        .filter(|c| c.tag() != ChangeTag::Equal)
-        .map(|c| c.old_index())
-        .flatten()
-        .collect();
-
-    let theirs_changes: Vec<_> = diff_theirs.iter_all_changes()
-        .filter(|c| c.tag() != ChangeTag::Equal)
-        .map(|c| c.old_index())
-        .flatten()
-        .collect();
-
-    for m in &mine_changes {
-        if theirs_changes.contains(m) {
-            conflicts.push(ConflictMatch {
-                start_line: (*m + 1) as u32,
-                end_line: (*m + 1) as u32,
-                description: format!("Overlapping change at line {}", m + 1),
-            });
+    merge_hunks(&split_lines(&base), &split_lines(&mine), &split_lines(&theirs))
+        .into_iter()
+        .filter(|h| h.resolution == "conflict")
+        .map(|h| {
+            let start_line = h.base.start_line_number;
+            let end_line = (h.base.end_line_number_exclusive - 1).max(start_line);
+            ConflictMatch { start_line, end_line, description: format!("Conflicting changes at base lines {}-{}", start_line, end_line) }
+        })
+        .collect()
+}
+
+/// Half-open line range, `[start_line_number, end_line_number_exclusive)`, as used by the merge editor.
+#[napi(object)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MergeLineRange {
+    pub start_line_number: u32,
+    pub end_line_number_exclusive: u32,
+}
+
+impl MergeLineRange {
+    fn new(start: usize, end: usize) -> Self {
+        Self { start_line_number: start as u32 + 1, end_line_number_exclusive: end as u32 + 1 }
+    }
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct MergeHunk {
+    pub base: MergeLineRange,
+    pub ours: MergeLineRange,
+    pub theirs: MergeLineRange,
+    /// Lines of the merged text produced for this hunk, markers included.
+    pub result: MergeLineRange,
+    /// "ours", "theirs", "both" (identical change on both sides) or "conflict".
+    pub resolution: String,
+}
+
+#[napi(object)]
+pub struct MergeOptions {
+    /// "merge" (git default) or "diff3" (also shows the base section).
+    pub conflict_style: Option<String>,
+    pub ours_label: Option<String>,
+    pub base_label: Option<String>,
+    pub theirs_label: Option<String>,
+}
+
+#[napi(object)]
+pub struct MergeResult {
+    pub merged: String,
+    pub hunks: Vec<MergeHunk>,
+    pub conflict_count: u32,
+}
+
+/// Splits into lines keeping their terminators, so the merge reproduces line endings verbatim.
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// A changed region of one side relative to base: `base` lines replaced by `side` lines.
+#[derive(Clone, Copy, Debug)]
+struct SideChange {
+    base: (usize, usize),
+    side: (usize, usize),
+}
+
+fn side_changes(base: &[&str], side: &[&str]) -> Vec<SideChange> {
+    group_changes(capture_diff_slices(Algorithm::Myers, base, side))
+}
+
+/// Folds the non-equal ops of a diff into maximal changed regions.
+fn group_changes(ops: Vec<DiffOp>) -> Vec<SideChange> {
+    let mut changes: Vec<SideChange> = Vec::new();
+    for op in ops {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let (old, new) = (op.old_range(), op.new_range());
+        match changes.last_mut() {
+            Some(last) if last.base.1 == old.start && last.side.1 == new.start => {
+                last.base.1 = old.end;
+                last.side.1 = new.end;
+            }
+            _ => changes.push(SideChange { base: (old.start, old.end), side: (new.start, new.end) }),
+        }
+    }
+    changes
+}
+
+/// The range of `side` covering base `[start, end)`, given the side's changes inside it and
+/// the line delta accumulated by its earlier changes.
+fn side_range(changes: &[SideChange], start: usize, end: usize, delta: isize) -> (usize, usize) {
+    match (changes.first(), changes.last()) {
+        (Some(first), Some(last)) => (first.side.0 - (first.base.0 - start), last.side.1 + (end - last.base.1)),
+        _ => ((start as isize + delta) as usize, (end as isize + delta) as usize),
+    }
+}
+
+/// Groups the changes of both sides into hunks over base. Changes that overlap or touch in
+/// base end up in the same hunk, matching diff3 and git.
+fn merge_hunks(base: &[&str], ours: &[&str], theirs: &[&str]) -> Vec<MergeHunk> {
+    let our_changes = side_changes(base, ours);
+    let their_changes = side_changes(base, theirs);
+    let (mut i, mut j) = (0, 0);
+    let (mut our_delta, mut their_delta) = (0isize, 0isize);
+    let mut hunks = Vec::new();
+
+    while i < our_changes.len() || j < their_changes.len() {
+        let take_ours = j >= their_changes.len() || (i < our_changes.len() && our_changes[i].base.0 <= their_changes[j].base.0);
+        let first = if take_ours { our_changes[i] } else { their_changes[j] };
+        let (start, mut end) = first.base;
+        let (i0, j0) = (i, j);
+        loop {
+            if i < our_changes.len() && our_changes[i].base.0 <= end && our_changes[i].base.1 >= start {
+                end = end.max(our_changes[i].base.1);
+                i += 1;
+            } else if j < their_changes.len() && their_changes[j].base.0 <= end && their_changes[j].base.1 >= start {
+                end = end.max(their_changes[j].base.1);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        let ours_range = side_range(&our_changes[i0..i], start, end, our_delta);
+        let theirs_range = side_range(&their_changes[j0..j], start, end, their_delta);
+        let resolution = if i0 == i {
+            "theirs"
+        } else if j0 == j {
+            "ours"
+        } else if ours[ours_range.0..ours_range.1] == theirs[theirs_range.0..theirs_range.1] {
+            "both"
+        } else {
+            "conflict"
+        };
+        our_delta = ours_range.1 as isize - end as isize;
+        their_delta = theirs_range.1 as isize - end as isize;
+        hunks.push(MergeHunk {
+            base: MergeLineRange::new(start, end),
+            ours: MergeLineRange::new(ours_range.0, ours_range.1),
+            theirs: MergeLineRange::new(theirs_range.0, theirs_range.1),
+            result: MergeLineRange::new(0, 0),
+            resolution: resolution.to_string(),
+        });
+    }
+    hunks
+}
+
+fn push_section(out: &mut Vec<String>, lines: &[&str]) {
+    for line in lines {
+        out.push(line.to_string());
+    }
+    if let Some(last) = out.last_mut().filter(|l| !l.ends_with('\n')) {
+        last.push('\n');
+    }
+}
+
+/// diff3-style three-way merge. Non-overlapping changes from either side are applied, identical
+/// changes on both sides resolve automatically, and everything else becomes a marked conflict.
+#[napi]
+pub fn merge_three_way(base: String, mine: String, theirs: String, options: Option<MergeOptions>) -> MergeResult {
+    let (base_lines, our_lines, their_lines) = (split_lines(&base), split_lines(&mine), split_lines(&theirs));
+    let diff3 = options.as_ref().and_then(|o| o.conflict_style.as_deref()) == Some("diff3");
+    let label = |l: Option<&String>, default: &str| l.cloned().unwrap_or_else(|| default.to_string());
+    let ours_label = label(options.as_ref().and_then(|o| o.ours_label.as_ref()), "ours");
+    let base_label = label(options.as_ref().and_then(|o| o.base_label.as_ref()), "base");
+    let theirs_label = label(options.as_ref().and_then(|o| o.theirs_label.as_ref()), "theirs");
+
+    let mut hunks = merge_hunks(&base_lines, &our_lines, &their_lines);
+    let mut out: Vec<String> = Vec::with_capacity(base_lines.len());
+    let mut base_pos = 0;
+    let mut conflict_count = 0;
+
+    for hunk in &mut hunks {
+        let b = (hunk.base.start_line_number as usize - 1, hunk.base.end_line_number_exclusive as usize - 1);
+        let o = (hunk.ours.start_line_number as usize - 1, hunk.ours.end_line_number_exclusive as usize - 1);
+        let t = (hunk.theirs.start_line_number as usize - 1, hunk.theirs.end_line_number_exclusive as usize - 1);
+        out.extend(base_lines[base_pos..b.0].iter().map(|l| l.to_string()));
+        base_pos = b.1;
+
+        let result_start = out.len();
+        match hunk.resolution.as_str() {
+            "theirs" => out.extend(their_lines[t.0..t.1].iter().map(|l| l.to_string())),
+            "ours" | "both" => out.extend(our_lines[o.0..o.1].iter().map(|l| l.to_string())),
+            _ => {
+                conflict_count += 1;
+                if let Some(last) = out.last_mut().filter(|l| !l.ends_with('\n')) {
+                    last.push('\n');
+                }
+                out.push(format!("<<<<<<< {}\n", ours_label));
+                push_section(&mut out, &our_lines[o.0..o.1]);
+                if diff3 {
+                    out.push(format!("||||||| {}\n", base_label));
+                    push_section(&mut out, &base_lines[b.0..b.1]);
+                }
+                out.push("=======\n".to_string());
+                push_section(&mut out, &their_lines[t.0..t.1]);
+                out.push(format!(">>>>>>> {}\n", theirs_label));
+            }
+        }
+        hunk.result = MergeLineRange::new(result_start, out.len());
+    }
+    out.extend(base_lines[base_pos..].iter().map(|l| l.to_string()));
+
+    MergeResult { merged: out.concat(), hunks, conflict_count }
+}
+
+/// Moved blocks shorter than this are reported as plain deletions and insertions.
+const MIN_MOVED_LINES: usize = 3;
+/// Lines inserted more often than this (`}`, `end`, ...) are too common to anchor a move.
+const MAX_MOVE_ANCHOR_OCCURRENCES: usize = 8;
+
+#[napi(object)]
+pub struct DocumentDiffOptions {
+    /// Treat lines that differ only in leading/trailing whitespace as equal.
+    pub ignore_trim_whitespace: Option<bool>,
+    /// "word" (default) or "char" granularity for changes inside modified lines.
+    pub inner_granularity: Option<String>,
+    pub compute_moves: Option<bool>,
+    /// Defaults to 5000; 0 disables the limit. On timeout the line diff is approximate and
+    /// inner changes span whole lines.
+    pub max_computation_time_ms: Option<u32>,
+}
+
+/// Half-open line range, `[start_line_number, end_line_number_exclusive)`, of a document
+/// diff. (`LineRange` in `types` is the inclusive range of line-based buffers.)
+#[napi(object)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffLineRange {
+    pub start_line_number: u32,
+    pub end_line_number_exclusive: u32,
+}
+
+impl DiffLineRange {
+    fn new(start: usize, end: usize) -> Self {
+        Self { start_line_number: start as u32 + 1, end_line_number_exclusive: end as u32 + 1 }
+    }
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangeMapping {
+    pub original_range: Range,
+    pub modified_range: Range,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct DetailedLineRangeMapping {
+    pub original: DiffLineRange,
+    pub modified: DiffLineRange,
+    pub inner_changes: Vec<RangeMapping>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovedText {
+    pub original: DiffLineRange,
+    pub modified: DiffLineRange,
+}
+
+#[napi(object)]
+pub struct DocumentDiff {
+    pub changes: Vec<DetailedLineRangeMapping>,
+    pub moves: Vec<MovedText>,
+    pub identical: bool,
+    pub hit_timeout: bool,
+}
+
+/// Line diff with inner word/character ranges and moved blocks, shaped after VS Code's
+/// `ILinesDiffComputer` so the diff editor can decorate the result directly.
+#[napi]
+pub fn compute_document_diff(original: String, modified: String, options: Option<DocumentDiffOptions>) -> DocumentDiff {
+    let timeout = options.as_ref().and_then(|o| o.max_computation_time_ms).unwrap_or(5000);
+    let deadline = (timeout > 0).then(|| Instant::now() + Duration::from_millis(timeout as u64));
+    document_diff(&original, &modified, options.as_ref(), deadline)
+}
+
+fn document_diff<'a>(original: &'a str, modified: &'a str, options: Option<&DocumentDiffOptions>, deadline: Option<Instant>) -> DocumentDiff {
+    let ignore_whitespace = options.and_then(|o| o.ignore_trim_whitespace).unwrap_or(false);
+    let by_char = options.and_then(|o| o.inner_granularity.as_deref()) == Some("char");
+    let compute_moves = options.and_then(|o| o.compute_moves).unwrap_or(false);
+    let timed_out = || deadline.is_some_and(|d| Instant::now() >= d);
+
+    let original_lines: Vec<&str> = original.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).collect();
+    let modified_lines: Vec<&str> = modified.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).collect();
+    let keys = |lines: &[&'a str]| -> Vec<&'a str> { lines.iter().map(|&l| if ignore_whitespace { l.trim() } else { l }).collect() };
+    let (original_keys, modified_keys) = (keys(&original_lines), keys(&modified_lines));
+
+    let line_changes = group_changes(capture_diff_slices_deadline(Algorithm::Myers, &original_keys, &modified_keys, deadline));
+    let mut hit_timeout = timed_out();
+
+    let mut changes = Vec::with_capacity(line_changes.len());
+    for change in &line_changes {
+        let (o, m) = (change.base, change.side);
+        let inner_changes = if o.0 == o.1 || m.0 == m.1 || hit_timeout {
+            vec![RangeMapping { original_range: whole_lines(&original_lines, o), modified_range: whole_lines(&modified_lines, m) }]
+        } else {
+            let inner = inner_changes(&original_lines, o, &modified_lines, m, by_char, ignore_whitespace, deadline);
+            hit_timeout = timed_out();
+            inner
+        };
+        changes.push(DetailedLineRangeMapping { original: DiffLineRange::new(o.0, o.1), modified: DiffLineRange::new(m.0, m.1), inner_changes });
+    }
+
+    let moves = if compute_moves && !hit_timeout { moved_blocks(&line_changes, &original_keys, &modified_keys) } else { Vec::new() };
+    DocumentDiff { identical: changes.is_empty(), changes, moves, hit_timeout }
+}
+
+/// The range spanning lines `[start, end)`, ending at the start of the following line when
+/// there is one so that whole-line insertions and deletions highlight their line breaks.
+fn whole_lines(lines: &[&str], (start, end): (usize, usize)) -> Range {
+    if end < lines.len() {
+        Range::new(start as u32 + 1, 1, end as u32 + 1, 1)
+    } else if start < end {
+        let last = lines[end - 1].chars().count() as u32 + 1;
+        if start > 0 {
+            let previous = lines[start - 1].chars().count() as u32 + 1;
+            Range::new(start as u32, previous, end as u32, last)
+        } else {
+            Range::new(1, 1, end as u32, last)
         }
+    } else {
+        let line = lines.len() as u32;
+        let column = lines[lines.len() - 1].chars().count() as u32 + 1;
+        Range::new(line, column, line, column)
     }
+}
+
+/// Word tokens are runs of word characters, runs of whitespace, or single other characters.
+fn tokenize(text: &str, by_char: bool) -> Vec<&str> {
+    if by_char {
+        return text.char_indices().map(|(i, c)| &text[i..i + c.len_utf8()]).collect();
+    }
+    let class = |c: char| if c.is_alphanumeric() || c == '_' { 0 } else if c == '\n' { 1 } else if c.is_whitespace() { 2 } else { 3 };
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let k = class(c);
+        let continues = chars.peek().is_some_and(|&(_, next)| (k == 0 || k == 2) && class(next) == k);
+        if !continues {
+            tokens.push(&text[start..i + c.len_utf8()]);
+            start = i + c.len_utf8();
+        }
+    }
+    tokens
+}
 
-    conflicts
+/// 1-based (line, column) of the start of every token, plus the position after the last one.
+fn token_positions(tokens: &[&str], first_line: usize) -> Vec<(u32, u32)> {
+    let (mut line, mut column) = (first_line as u32 + 1, 1);
+    let mut positions = Vec::with_capacity(tokens.len() + 1);
+    for token in tokens {
+        positions.push((line, column));
+        for c in token.chars() {
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+    }
+    positions.push((line, column));
+    positions
+}
+
+fn inner_changes(
+    original_lines: &[&str],
+    o: (usize, usize),
+    modified_lines: &[&str],
+    m: (usize, usize),
+    by_char: bool,
+    ignore_whitespace: bool,
+    deadline: Option<Instant>,
+) -> Vec<RangeMapping> {
+    let original_text = original_lines[o.0..o.1].join("\n");
+    let modified_text = modified_lines[m.0..m.1].join("\n");
+    let original_tokens = tokenize(&original_text, by_char);
+    let modified_tokens = tokenize(&modified_text, by_char);
+    let original_positions = token_positions(&original_tokens, o.0);
+    let modified_positions = token_positions(&modified_tokens, m.0);
+
+    let ops = capture_diff_slices_deadline(Algorithm::Myers, &original_tokens, &modified_tokens, deadline);
+    group_changes(ops)
+        .into_iter()
+        .filter(|c| {
+            let blank = |tokens: &[&str]| tokens.iter().all(|t| t.trim().is_empty());
+            !(ignore_whitespace && blank(&original_tokens[c.base.0..c.base.1]) && blank(&modified_tokens[c.side.0..c.side.1]))
+        })
+        .map(|c| {
+            let range = |positions: &[(u32, u32)], (start, end): (usize, usize)| {
+                Range::new(positions[start].0, positions[start].1, positions[end].0, positions[end].1)
+            };
+            RangeMapping { original_range: range(&original_positions, c.base), modified_range: range(&modified_positions, c.side) }
+        })
+        .collect()
+}
+
+/// Pairs removed lines with identical inserted lines elsewhere, preferring the longest run
+/// for each removed line. Only distinctive lines start a move: short or punctuation-only
+/// lines and lines inserted in many places would make the search quadratic.
+fn moved_blocks(changes: &[SideChange], original: &[&str], modified: &[&str]) -> Vec<MovedText> {
+    let mut removed = vec![false; original.len()];
+    let mut inserted = vec![false; modified.len()];
+    for change in changes {
+        removed[change.base.0..change.base.1].fill(true);
+        inserted[change.side.0..change.side.1].fill(true);
+    }
+    let mut inserted_at: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (j, line) in modified.iter().enumerate() {
+        if inserted[j] && is_move_anchor(line) {
+            inserted_at.entry(line.trim()).or_default().push(j);
+        }
+    }
+    inserted_at.retain(|_, at| at.len() <= MAX_MOVE_ANCHOR_OCCURRENCES);
+
+    let mut moves = Vec::new();
+    let mut i = 0;
+    while i < original.len() {
+        let candidates = if removed[i] { inserted_at.get(original[i].trim()) } else { None };
+        let best = candidates.into_iter().flatten().map(|&j| {
+            let mut len = 0;
+            while i + len < original.len() && j + len < modified.len() && removed[i + len] && inserted[j + len]
+                && original[i + len].trim() == modified[j + len].trim()
+            {
+                len += 1;
+            }
+            (len, j)
+        }).max_by_key(|&(len, j)| (len, std::cmp::Reverse(j)));
+
+        match best {
+            Some((len, j)) if len >= MIN_MOVED_LINES => {
+                removed[i..i + len].fill(false);
+                inserted[j..j + len].fill(false);
+                moves.push(MovedText { original: DiffLineRange::new(i, i + len), modified: DiffLineRange::new(j, j + len) });
+                i += len;
+            }
+            _ => i += 1,
+        }
+    }
+    moves
+}
+
+fn is_move_anchor(line: &str) -> bool {
+    let line = line.trim();
+    line.chars().count() >= 3 && line.chars().any(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, mine: &str, theirs: &str, style: Option<&str>) -> MergeResult {
+        let options = MergeOptions { conflict_style: style.map(String::from), ours_label: None, base_label: None, theirs_label: None };
+        merge_three_way(base.into(), mine.into(), theirs.into(), Some(options))
+    }
+
+    #[test]
+    fn test_merge_non_overlapping_and_identical() {
+        let result = merge("a\nb\nc\nd\ne\nf\ng\n", "A\nb\nc\nd\ne\nF\ng\n", "a\nb\nC\nd\ne\nF\ng\n", None);
+        assert_eq!(result.merged, "A\nb\nC\nd\ne\nF\ng\n");
+        assert_eq!(result.conflict_count, 0);
+        let resolutions: Vec<_> = result.hunks.iter().map(|h| h.resolution.as_str()).collect();
+        assert_eq!(resolutions, ["ours", "theirs", "both"]);
+        assert_eq!(result.hunks[1].base, MergeLineRange { start_line_number: 3, end_line_number_exclusive: 4 });
+    }
+
+    #[test]
+    fn test_merge_conflict_markers() {
+        let result = merge("a\nb\nc", "a\nmine\nc", "a\ntheirs\nc", None);
+        assert_eq!(result.merged, "a\n<<<<<<< ours\nmine\n=======\ntheirs\n>>>>>>> theirs\nc");
+        assert_eq!(result.hunks[0].result, MergeLineRange { start_line_number: 2, end_line_number_exclusive: 7 });
+
+        let result = merge("x\nbase", "x\nmine", "x\ntheirs", Some("diff3"));
+        assert_eq!(result.merged, "x\n<<<<<<< ours\nmine\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> theirs\n");
+        assert_eq!(result.conflict_count, 1);
+
+        let conflicts = detect_conflicts("a\nb\nc\n".into(), "a\nB\nc\n".into(), "a\nb2\nc\n".into());
+        assert_eq!((conflicts.len(), conflicts[0].start_line, conflicts[0].end_line), (1, 2, 2));
+    }
+
+    #[test]
+    fn test_document_diff_inner_changes() {
+        let diff = compute_document_diff("let a = 1;\nkeep\n".into(), "let b = 1;\nkeep\nnew\n".into(), None);
+        assert_eq!(diff.changes.len(), 2);
+        assert_eq!(diff.changes[0].inner_changes, vec![RangeMapping { original_range: Range::new(1, 5, 1, 6), modified_range: Range::new(1, 5, 1, 6) }]);
+        assert_eq!(diff.changes[1].modified, DiffLineRange { start_line_number: 3, end_line_number_exclusive: 4 });
+
+        let options = DocumentDiffOptions { ignore_trim_whitespace: Some(true), inner_granularity: Some("char".into()), compute_moves: None, max_computation_time_ms: None };
+        let diff = compute_document_diff("  a\nfoo\n".into(), "a  \nfao\n".into(), Some(options));
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].inner_changes[0].modified_range, Range::new(2, 2, 2, 3));
+    }
+
+    #[test]
+    fn test_document_diff_moves_and_timeout() {
+        let original = "fn a() {\n  one();\n}\nw\nx\ny\nz\n";
+        let modified = "w\nx\ny\nz\nfn a() {\n  one();\n}\n";
+        let options = DocumentDiffOptions { ignore_trim_whitespace: None, inner_granularity: None, compute_moves: Some(true), max_computation_time_ms: None };
+        let diff = compute_document_diff(original.into(), modified.into(), Some(options));
+        assert_eq!(diff.moves, vec![MovedText {
+            original: DiffLineRange { start_line_number: 1, end_line_number_exclusive: 4 },
+            modified: DiffLineRange { start_line_number: 5, end_line_number_exclusive: 8 },
+        }]);
+
+        let options = DocumentDiffOptions { ignore_trim_whitespace: None, inner_granularity: None, compute_moves: Some(true), max_computation_time_ms: None };
+        let diff = compute_document_diff("}\n}\n}\nw\nx\ny\nz\n".into(), "w\nx\ny\nz\n}\n}\n}\n".into(), Some(options));
+        assert!(diff.moves.is_empty());
+
+        let diff = document_diff("a b\n", "a c\n", None, Some(Instant::now()));
+        assert!(diff.hit_timeout);
+        assert_eq!(diff.changes[0].inner_changes[0].original_range, Range::new(1, 1, 2, 1));
+    }
 }
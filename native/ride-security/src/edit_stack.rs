@@ -3,14 +3,43 @@
  *  Licensed under the MIT License. See License.txt in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
+//! Edit Stack — Rust port of `src/vs/editor/common/model/editStack.ts`.
+//!
+//! Every element records the changes it made as offset-based text changes, so
+//! undo and redo replay the exact inverse without re-deriving ranges. Typing
+//! bursts of the same kind merge into one element until `push_stack_element`
+//! closes it.
+
 use napi_derive::napi;
-use crate::text_model_types::SingleEditOperation;
+use crate::selection::Selection;
+use crate::text_model_types::EditOperationType;
+
+/// One replacement, recorded against the text before (`old_*`) and after (`new_*`) the edit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedChange {
+    pub old_start: usize,
+    pub old_text: String,
+    pub new_start: usize,
+    pub new_text: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct EditStackElement {
+    pub before_version_id: u32,
+    pub after_version_id: u32,
+    pub before_cursor_state: Option<Vec<Selection>>,
+    pub after_cursor_state: Option<Vec<Selection>>,
+    /// Batches in application order; each batch is sorted by offset.
+    pub batches: Vec<Vec<RecordedChange>>,
+    edit_type: EditOperationType,
+    is_open: bool,
+}
 
 #[napi]
 #[derive(Clone, Default)]
 pub struct EditStack {
-    undo_stack: Vec<Vec<SingleEditOperation>>,
-    redo_stack: Vec<Vec<SingleEditOperation>>,
+    undo_stack: Vec<EditStackElement>,
+    redo_stack: Vec<EditStackElement>,
 }
 
 #[napi]
@@ -23,21 +52,187 @@ impl EditStack {
         }
     }
 
+    /// Closes the current group so the next edit starts a new undo step.
+    #[napi]
+    pub fn push_stack_element(&mut self) {
+        if let Some(top) = self.undo_stack.last_mut() {
+            top.is_open = false;
+        }
+    }
+
+    #[napi]
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
     #[napi]
-    pub fn push(&mut self, operations: Vec<SingleEditOperation>) {
-        self.undo_stack.push(operations);
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    #[napi]
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
+
+impl EditStack {
+    /// Records a batch of changes. Consecutive batches of the same typing kind
+    /// merge into the open element; `EditOperationType::Other` always starts
+    /// (and immediately closes) its own element.
+    pub fn push_edit(
+        &mut self,
+        changes: Vec<RecordedChange>,
+        edit_type: EditOperationType,
+        before_cursor_state: Option<Vec<Selection>>,
+        after_cursor_state: Option<Vec<Selection>>,
+        before_version_id: u32,
+        after_version_id: u32,
+    ) {
         self.redo_stack.clear();
+
+        if let Some(top) = self.undo_stack.last_mut()
+            && top.is_open
+            && top.edit_type == edit_type
+        {
+            top.batches.push(changes);
+            top.after_cursor_state = after_cursor_state;
+            top.after_version_id = after_version_id;
+            return;
+        }
+
+        self.push_stack_element();
+        self.undo_stack.push(EditStackElement {
+            before_version_id,
+            after_version_id,
+            before_cursor_state,
+            after_cursor_state,
+            batches: vec![changes],
+            edit_type,
+            is_open: edit_type != EditOperationType::Other,
+        });
     }
 
-    pub fn pop_undo(&mut self) -> Option<Vec<SingleEditOperation>> {
-        self.undo_stack.pop()
+    pub fn pop_undo(&mut self) -> Option<EditStackElement> {
+        let mut element = self.undo_stack.pop()?;
+        element.is_open = false;
+        Some(element)
     }
 
-    pub fn pop_redo(&mut self) -> Option<Vec<SingleEditOperation>> {
+    pub fn pop_redo(&mut self) -> Option<EditStackElement> {
         self.redo_stack.pop()
     }
 
-    pub fn push_redo(&mut self, operations: Vec<SingleEditOperation>) {
-        self.redo_stack.push(operations);
+    pub fn push_redo(&mut self, element: EditStackElement) {
+        self.redo_stack.push(element);
+    }
+
+    /// Puts a redone element back on the undo stack without dropping the rest of the redo stack.
+    pub fn push_undo(&mut self, element: EditStackElement) {
+        self.push_stack_element();
+        self.undo_stack.push(element);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selection::Selection;
+    use crate::text_model::TextModel;
+    use crate::text_model_types::{EditOperationType, RangePod, SingleEditOperation};
+
+    fn edit(line: u32, start: u32, end: u32, text: &str) -> SingleEditOperation {
+        SingleEditOperation {
+            range: RangePod { start_line_number: line, start_column: start, end_line_number: line, end_column: end },
+            text: Some(text.to_string()),
+            force_move_markers: None,
+        }
+    }
+
+    fn caret(line: u32, column: u32) -> Vec<Selection> {
+        vec![Selection::new(line, column, line, column)]
+    }
+
+    #[test]
+    fn test_typing_burst_is_one_undo_step() {
+        let model = TextModel::new("file:///a".into(), "fn".into());
+        for (i, ch) in ["(", ")", ";"].iter().enumerate() {
+            let col = 3 + i as u32;
+            model.push_edit_operations(Some(caret(1, col)), vec![edit(1, col, col, ch)], Some(caret(1, col + 1)), Some(EditOperationType::Typing));
+        }
+        assert_eq!(model.get_value(), "fn();");
+
+        let result = model.undo().unwrap();
+        assert_eq!(model.get_value(), "fn");
+        assert_eq!(result.cursor_state.unwrap(), caret(1, 3));
+        assert!(!model.can_undo());
+
+        let result = model.redo().unwrap();
+        assert_eq!(model.get_value(), "fn();");
+        assert_eq!(result.cursor_state.unwrap(), caret(1, 6));
+    }
+
+    #[test]
+    fn test_push_stack_element_and_type_change_split_groups() {
+        let model = TextModel::new("file:///a".into(), "".into());
+        model.push_edit_operations(None, vec![edit(1, 1, 1, "ab")], None, Some(EditOperationType::Typing));
+        model.push_stack_element();
+        model.push_edit_operations(None, vec![edit(1, 3, 3, "cd")], None, Some(EditOperationType::Typing));
+        model.push_edit_operations(None, vec![edit(1, 4, 5, "")], None, Some(EditOperationType::DeletingLeft));
+
+        model.undo();
+        assert_eq!(model.get_value(), "abcd");
+        model.undo();
+        assert_eq!(model.get_value(), "ab");
+        model.undo();
+        assert_eq!(model.get_value(), "");
+    }
+
+    #[test]
+    fn test_multi_edit_batch_inverse() {
+        let model = TextModel::new("file:///a".into(), "one\ntwo\nthree".into());
+        model.apply_edits(vec![edit(1, 1, 4, "1"), edit(3, 1, 6, "3\n3b"), edit(2, 4, 4, "!")]);
+        assert_eq!(model.get_value(), "1\ntwo!\n3\n3b");
+        model.undo();
+        assert_eq!(model.get_value(), "one\ntwo\nthree");
+        model.redo();
+        assert_eq!(model.get_value(), "1\ntwo!\n3\n3b");
+    }
+
+    #[test]
+    fn test_dirty_tracking_follows_undo() {
+        let model = TextModel::new("file:///a".into(), "x".into());
+        model.apply_edits(vec![edit(1, 2, 2, "y")]);
+        model.mark_saved();
+        assert!(!model.is_dirty());
+
+        model.apply_edits(vec![edit(1, 3, 3, "z")]);
+        assert!(model.is_dirty());
+        model.undo();
+        assert!(!model.is_dirty());
+        model.undo();
+        assert!(model.is_dirty());
+        model.redo();
+        assert!(!model.is_dirty());
+        assert!(model.get_version_id() > model.get_alternative_version_id());
+    }
+
+    #[test]
+    fn test_concurrent_edits_and_undo_do_not_deadlock() {
+        let model = std::sync::Arc::new(TextModel::new("file:///a".into(), "".into()));
+        let editor = {
+            let model = model.clone();
+            std::thread::spawn(move || {
+                for _ in 0..500 {
+                    model.push_edit_operations(None, vec![edit(1, 1, 1, "x")], None, None);
+                }
+            })
+        };
+        for _ in 0..500 {
+            model.undo();
+            model.redo();
+        }
+        editor.join().unwrap();
+        assert!(model.can_undo());
     }
 }
@@ -3,22 +3,49 @@
  *  Licensed under the MIT License. See License.txt in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
+//! Editor Config — the subset of `src/vs/editor/common/config/editorOptions.ts`
+//! that the native cursor and editing commands read.
+
 use napi_derive::napi;
-use crate::position::Position;
-use crate::range::Range;
-use crate::selection::Selection;
-use crate::text_model::TextModel;
-use crate::cursor::Cursor;
 
-// Placeholder for now
 #[napi]
 #[derive(Clone)]
-pub struct EditorConfig {}
+pub struct EditorConfig {
+    pub tab_size: u32,
+    pub insert_spaces: bool,
+    /// Delete-left in leading whitespace removes up to the previous tab stop.
+    pub use_tab_stops: bool,
+    /// `"spread"`: a paste with one line per cursor gives each cursor its line.
+    /// `"full"`: every cursor receives the whole text.
+    pub multi_cursor_paste: String,
+    /// Enter and closing characters follow the language's indentation rules.
+    pub auto_indent: bool,
+    pub auto_closing_brackets: bool,
+    /// Typing an opening bracket or quote with a selection wraps it.
+    pub auto_surround: bool,
+    /// Multi-line pastes are re-indented to the target line.
+    pub auto_indent_on_paste: bool,
+}
 
 #[napi]
 impl EditorConfig {
     #[napi(constructor)]
     pub fn new() -> Self {
-        Self {}
+        Self {
+            tab_size: 4,
+            insert_spaces: true,
+            use_tab_stops: true,
+            multi_cursor_paste: "spread".to_string(),
+            auto_indent: true,
+            auto_closing_brackets: true,
+            auto_surround: true,
+            auto_indent_on_paste: true,
+        }
+    }
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self::new()
     }
 }
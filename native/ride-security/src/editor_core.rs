@@ -5,19 +5,30 @@
 
 use napi_derive::napi;
 use crate::position::Position;
-use crate::range::Range;
-use crate::selection::Selection;
+use crate::selection::{Selection, SelectionDirection};
 use crate::text_model::TextModel;
-use crate::edit_stack::EditStack;
+use crate::text_model_types::{EditOperationType, RangePod, SingleEditOperation};
+use crate::text_edit::{self, CursorEdit};
 use crate::cursor::Cursor;
 use crate::editor_config::EditorConfig;
+use crate::language_configuration::LanguageConfiguration;
+use crate::range::Range;
+use crate::snippet_parser::parse_snippet;
+use crate::snippet_session::{render_snippet, SnippetSession, SnippetVariables, VariableContext};
+use crate::syntax;
+use crate::word_ops;
 
 #[napi]
 pub struct Editor {
     model: TextModel,
     cursors: Vec<Cursor>,
-    edit_stack: EditStack,
+    /// Index in `cursors` of the primary cursor: the first of a `set_selections` call, then
+    /// the one most recently added by Ctrl+D, which continues the search from it.
+    primary: usize,
     config: EditorConfig,
+    language: Option<LanguageConfiguration>,
+    /// Active snippet sessions, innermost last.
+    snippets: Vec<SnippetSession>,
 }
 
 #[napi]
@@ -27,8 +38,26 @@ impl Editor {
         Self {
             model: model.clone(),
             cursors: vec![Cursor::new(Position::new(1, 1))],
-            edit_stack: EditStack::new(),
+            primary: 0,
             config: config.clone(),
+            language: None,
+            snippets: Vec::new(),
+        }
+    }
+
+    /// Rules for auto-indent, auto-closing and surrounding pairs while typing.
+    #[napi]
+    pub fn set_language_configuration(&mut self, language: &LanguageConfiguration) {
+        self.language = Some(language.clone());
+    }
+
+    /// Adopts the indentation style used by the document (`editor.detectIndentation`).
+    #[napi]
+    pub fn detect_indentation(&mut self) {
+        let style = syntax::analyze_indent_v2(self.model.get_value());
+        self.config.insert_spaces = !style.use_tabs;
+        if !style.use_tabs {
+            self.config.tab_size = style.size;
         }
     }
 
@@ -44,17 +73,14 @@ impl Editor {
 
     #[napi]
     pub fn move_cursor_left(&mut self) -> Vec<Cursor> {
+        self.model.push_stack_element();
         for cursor in &mut self.cursors {
             let pos = cursor.position();
-            let line = pos.line_number;
-            let col = pos.column;
-
-            if col > 1 {
-                cursor.set_position(Position::new(line, col - 1), false);
-            } else if line > 1 {
-                let prev_line_idx = line - 2;
-                let prev_line_len = self.model.get_line_content(prev_line_idx).encode_utf16().count() as u32;
-                cursor.set_position(Position::new(line - 1, prev_line_len + 1), false);
+            if pos.column > 1 {
+                cursor.set_position(Position::new(pos.line_number, pos.column - 1), false);
+            } else if pos.line_number > 1 {
+                let prev_line_len = self.model.get_line_length(pos.line_number - 1);
+                cursor.set_position(Position::new(pos.line_number - 1, prev_line_len + 1), false);
             }
         }
         self.cursors.clone()
@@ -62,45 +88,14 @@ impl Editor {
 
     #[napi]
     pub fn move_cursor_right(&mut self) -> Vec<Cursor> {
+        self.model.push_stack_element();
+        let line_count = self.model.line_count();
         for cursor in &mut self.cursors {
             let pos = cursor.position();
-            let line = pos.line_number;
-            let col = pos.column;
-
-            // Note: Efficient line length check needed.
-            // Using get_line_content is slow but functional.
-            let line_idx = line - 1;
-            let line_len = self.model.get_line_content(line_idx).encode_utf16().count() as u32;
-
-            if col <= line_len {
-                cursor.set_position(Position::new(line, col + 1), false);
-            } else {
-                 // Check if next line exists
-                 // TextModel doesn't expose line_count directly via NAPI yet.
-                 // We can rely on get_line_content returning empty if out of bounds?
-                 // Or expose line_count. It's better to expose line_count.
-                 // For now, I'll try to get next line.
-                 let next_line_content = self.model.get_line_content(line); // line index = current line number
-                 if !next_line_content.is_empty() || line < 1000000 { // Fallback check
-                     // Implementing a proper line_count check would be better.
-                     // Let's assume we can move if we can fetch next line?
-                     // Actually, TextModel should expose get_line_count.
-                     // I will implement get_line_count in TextModel first for correctness.
-                     // But for this step I'll use a hack or just assume valid move if content exists.
-                     // Actually line_count is on PieceTree. TextModel wraps it.
-                     // I will assume for now standard behavior or add get_line_count.
-
-                     // HACK: just try to move if we are not at end of file.
-                     // How to know end of file?
-                     // I'll skip "next line" logic for now if I can't check bounds,
-                     // or just implement get_line_count in next step.
-
-                     // Let's add get_line_count to TextModel in this turn if possible?
-                     // No, I can't edit 2 files in one replace unless specialized.
-                     // I'll implement move_right assuming we can check line count later.
-                     // I'll leave the bound check vague:
-                     cursor.set_position(Position::new(line + 1, 1), false);
-                 }
+            if pos.column <= self.model.get_line_length(pos.line_number) {
+                cursor.set_position(Position::new(pos.line_number, pos.column + 1), false);
+            } else if pos.line_number < line_count {
+                cursor.set_position(Position::new(pos.line_number + 1, 1), false);
             }
         }
         self.cursors.clone()
@@ -108,11 +103,11 @@ impl Editor {
 
     #[napi]
     pub fn move_cursor_up(&mut self) -> Vec<Cursor> {
+        self.model.push_stack_element();
         for cursor in &mut self.cursors {
             let pos = cursor.position();
             if pos.line_number > 1 {
-                let prev_line_idx = pos.line_number - 2;
-                let prev_line_len = self.model.get_line_content(prev_line_idx).encode_utf16().count() as u32;
+                let prev_line_len = self.model.get_line_length(pos.line_number - 1);
                 let new_col = std::cmp::min(pos.column, prev_line_len + 1);
                 cursor.set_position(Position::new(pos.line_number - 1, new_col), false);
             }
@@ -122,20 +117,547 @@ impl Editor {
 
     #[napi]
     pub fn move_cursor_down(&mut self) -> Vec<Cursor> {
+        self.model.push_stack_element();
+        let line_count = self.model.line_count();
         for cursor in &mut self.cursors {
             let pos = cursor.position();
-            // Need line_count to verify.
-            let next_line_idx = pos.line_number; // current line number = next line index
-            // Check if next line exists (content not empty or within bounds)
-            // Ideally use get_line_count.
-            let next_line_len = self.model.get_line_content(next_line_idx).encode_utf16().count() as u32;
-            // If length is 0, it might be an empty line OR EOF. This is ambiguous.
-            // I'll proceed assuming valid line for demo.
+            if pos.line_number < line_count {
+                let next_line_len = self.model.get_line_length(pos.line_number + 1);
+                let new_col = std::cmp::min(pos.column, next_line_len + 1);
+                cursor.set_position(Position::new(pos.line_number + 1, new_col), false);
+            }
+        }
+        self.cursors.clone()
+    }
+    // ─── Selections ────────────────────────────────────────────────────────
+
+    #[napi]
+    pub fn get_selections(&self) -> Vec<Selection> {
+        self.cursors.iter().map(|c| c.selection).collect()
+    }
+
+    #[napi]
+    pub fn set_selections(&mut self, selections: Vec<Selection>) -> Vec<Cursor> {
+        self.model.push_stack_element();
+        self.primary = 0;
+        self.set_cursors_from_selections(selections);
+        self.cursors.clone()
+    }
 
-            let new_col = std::cmp::min(pos.column, next_line_len + 1);
-            cursor.set_position(Position::new(pos.line_number + 1, new_col), false);
+    #[napi]
+    pub fn add_cursor_above(&mut self) -> Vec<Cursor> {
+        self.add_cursors_vertically(-1)
+    }
+
+    #[napi]
+    pub fn add_cursor_below(&mut self) -> Vec<Cursor> {
+        self.add_cursors_vertically(1)
+    }
+
+    /// Ctrl+D: selects the word at an empty primary cursor, otherwise adds a
+    /// cursor on the next occurrence of its selected text (wrapping around)
+    /// and makes it the primary one.
+    #[napi]
+    pub fn add_selection_to_next_find_match(&mut self) -> Vec<Cursor> {
+        self.model.push_stack_element();
+        let Some(last) = self.cursors.get(self.primary).map(|c| c.selection) else {
+            return self.cursors.clone();
+        };
+
+        if last.is_empty() {
+            let line = self.model.get_line_content(last.position_line_number);
+            let byte_offset = line.char_indices().nth(last.position_column as usize - 1).map_or(line.len(), |(i, _)| i);
+            if let Some(word) = word_ops::find_word_at_offset(line.clone(), byte_offset as u32) {
+                let start = line[..word.start as usize].chars().count() as u32 + 1;
+                let end = start + word.word.chars().count() as u32;
+                self.cursors[self.primary] = Cursor::from_selection(Selection::new(last.position_line_number, start, last.position_line_number, end));
+            }
+            return self.cursors.clone();
+        }
+
+        let range = text_edit::selection_range(&last);
+        let needle = self.model.get_value_in_range(range);
+        let value = self.model.get_value();
+        let from = self.model.get_offset_at(range.end_line_number, range.end_column) as usize;
+        let found = value[from..].find(&needle).map(|i| from + i).or_else(|| value.find(&needle));
+        if let Some(start) = found {
+            let start_position = self.model.get_position_at(start as u32);
+            let end_position = self.model.get_position_at((start + needle.len()) as u32);
+            let selection = Selection::from_positions(start_position, end_position);
+            if !self.cursors.iter().any(|c| text_edit::selection_range(&c.selection) == text_edit::selection_range(&selection)) {
+                self.primary = self.cursors.len();
+                self.cursors.push(Cursor::from_selection(selection));
+                self.normalize_cursors();
+            }
         }
         self.cursors.clone()
     }
+
+    /// Box selection between two positions: one cursor per line, each
+    /// selecting from the anchor column to the active column.
+    #[napi]
+    pub fn column_select(&mut self, anchor: Position, active: Position) -> Vec<Cursor> {
+        self.model.push_stack_element();
+        let line_count = self.model.line_count();
+        let (from, to) = (anchor.line_number.clamp(1, line_count), active.line_number.clamp(1, line_count));
+        let lines: Vec<u32> = if from <= to { (from..=to).collect() } else { (to..=from).rev().collect() };
+
+        let selections = lines.into_iter().map(|line| {
+            let max_column = self.model.get_line_length(line) + 1;
+            Selection::new(line, anchor.column.min(max_column), line, active.column.min(max_column))
+        }).collect();
+        self.set_cursors_from_selections(selections);
+        self.cursors.clone()
+    }
+
+    // ─── Editing ───────────────────────────────────────────────────────────
+
+    #[napi]
+    pub fn type_text(&mut self, text: String) -> Vec<Cursor> {
+        let edits = text_edit::type_text(&self.model, &self.get_selections(), &self.config, self.language.as_ref(), &text);
+        self.execute_edits(edits, EditOperationType::Typing)
+    }
+
+    #[napi]
+    pub fn delete_left(&mut self) -> Vec<Cursor> {
+        let edits = text_edit::delete_left(&self.model, &self.get_selections(), &self.config);
+        self.execute_edits(edits, EditOperationType::DeletingLeft)
+    }
+
+    #[napi]
+    pub fn delete_right(&mut self) -> Vec<Cursor> {
+        let edits = text_edit::delete_right(&self.model, &self.get_selections());
+        self.execute_edits(edits, EditOperationType::DeletingRight)
+    }
+
+    #[napi]
+    pub fn paste(&mut self, text: String) -> Vec<Cursor> {
+        let edits = text_edit::paste(&self.model, &self.get_selections(), &self.config, self.language.as_ref(), &text);
+        self.execute_edits(edits, EditOperationType::Other)
+    }
+
+    #[napi]
+    pub fn undo(&mut self) -> Vec<Cursor> {
+        if let Some(selections) = self.model.undo().and_then(|r| r.cursor_state) {
+            self.set_cursors_from_selections(selections);
+        }
+        self.cursors.clone()
+    }
+
+    #[napi]
+    pub fn redo(&mut self) -> Vec<Cursor> {
+        if let Some(selections) = self.model.redo().and_then(|r| r.cursor_state) {
+            self.set_cursors_from_selections(selections);
+        }
+        self.cursors.clone()
+    }
+
+    /// Inserts a snippet at every cursor and selects its first tabstop. Inserting while a
+    /// snippet is active nests the new session; finishing it resumes the outer one.
+    #[napi]
+    pub fn insert_snippet(&mut self, template: String, variables: Option<SnippetVariables>) -> napi::Result<Vec<Cursor>> {
+        let snippet = parse_snippet(template)?;
+        let host = variables.unwrap_or_default();
+        let uri = self.model.uri();
+        let file_path = uri.strip_prefix("file://").unwrap_or(&uri).to_string();
+        let indent_unit = text_edit::indent_by("", 1, &self.config);
+
+        let mut edits = Vec::new();
+        let mut inserted = Vec::new();
+        for selection in self.get_selections() {
+            let range = text_edit::selection_range(&selection);
+            let line = self.model.get_line_content(range.start_line_number);
+            let column = range.start_column as usize - 1;
+            let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+            let before: String = line.chars().take(column).collect::<Vec<_>>().into_iter().rev().take_while(is_word).collect();
+            let after: String = line.chars().skip(column).take_while(is_word).collect();
+            let context = VariableContext {
+                file_path: file_path.clone(),
+                selected_text: self.model.get_value_in_range(range),
+                current_word: before.chars().rev().chain(after.chars()).collect(),
+                current_line: line.clone(),
+                line_number: range.start_line_number,
+                host: host.clone(),
+            };
+            let rendered = render_snippet(&snippet, &context, text_edit::leading_whitespace(&line), &indent_unit);
+            let start = self.model.get_offset_at(range.start_line_number, range.start_column) as usize;
+            let end = self.model.get_offset_at(range.end_line_number, range.end_column) as usize;
+            edits.push(CursorEdit { range, text: rendered.text.clone(), selection: None });
+            inserted.push((start, end, rendered));
+        }
+
+        self.model.push_stack_element();
+        self.execute_edits(edits, EditOperationType::Other);
+        self.model.push_stack_element();
+
+        // Edits are sorted, so each insertion shifts by the length change of the ones before it.
+        let mut delta: isize = 0;
+        let mut placeholders = Vec::new();
+        for (instance, (start, end, rendered)) in inserted.into_iter().enumerate() {
+            let new_start = (start as isize + delta) as usize;
+            for placeholder in rendered.placeholders {
+                let from = self.model.get_position_at((new_start + placeholder.start) as u32);
+                let to = self.model.get_position_at((new_start + placeholder.end) as u32);
+                let range = Range::new(from.line_number, from.column, to.line_number, to.column);
+                placeholders.push((instance, placeholder, range));
+            }
+            delta += rendered.text.len() as isize - (end - start) as isize;
+        }
+        self.snippets.push(SnippetSession::new(&self.model, placeholders));
+        Ok(self.move_snippet_placeholder(1))
+    }
+
+    #[napi]
+    pub fn next_snippet_placeholder(&mut self) -> Vec<Cursor> {
+        self.move_snippet_placeholder(1)
+    }
+
+    #[napi]
+    pub fn prev_snippet_placeholder(&mut self) -> Vec<Cursor> {
+        self.move_snippet_placeholder(-1)
+    }
+
+    #[napi]
+    pub fn is_in_snippet(&self) -> bool {
+        !self.snippets.is_empty()
+    }
+
+    #[napi]
+    pub fn has_prev_snippet_placeholder(&self) -> bool {
+        self.snippets.last().is_some_and(|s| !s.is_at_first_placeholder())
+    }
+
+    /// Leaves all snippet sessions, keeping the cursors where they are.
+    #[napi]
+    pub fn cancel_snippet(&mut self) {
+        while let Some(mut session) = self.snippets.pop() {
+            session.dispose();
+        }
+    }
+}
+
+impl Editor {
+    /// Applies one edit per cursor as a single batch on the model's undo
+    /// stack and moves every cursor to the end of its own edit.
+    fn execute_edits(&mut self, edits: Vec<CursorEdit>, edit_type: EditOperationType) -> Vec<Cursor> {
+        let before = self.get_selections();
+        let after = text_edit::selections_after_edits(&edits);
+        let operations: Vec<SingleEditOperation> = edits.into_iter()
+            .filter(|e| !e.is_noop())
+            .map(|e| SingleEditOperation {
+                range: RangePod {
+                    start_line_number: e.range.start_line_number,
+                    start_column: e.range.start_column,
+                    end_line_number: e.range.end_line_number,
+                    end_column: e.range.end_column,
+                },
+                text: Some(e.text),
+                force_move_markers: None,
+            })
+            .collect();
+        if operations.is_empty() {
+            return self.cursors.clone();
+        }
+
+        self.model.push_edit_operations(Some(before), operations, Some(after.clone()), Some(edit_type));
+        self.set_cursors_from_selections(after);
+        self.cursors.clone()
+    }
+
+    /// Moves the innermost session and ends every session that reached its final tabstop.
+    fn move_snippet_placeholder(&mut self, delta: i32) -> Vec<Cursor> {
+        self.model.push_stack_element();
+        if let Some(session) = self.snippets.last_mut() {
+            let selections = session.move_by(delta);
+            if !selections.is_empty() {
+                self.set_cursors_from_selections(selections);
+            }
+        }
+        while self.snippets.last().is_some_and(|s| s.is_at_last_placeholder()) {
+            self.snippets.pop().unwrap().dispose();
+        }
+        self.cursors.clone()
+    }
+
+    fn add_cursors_vertically(&mut self, delta: i32) -> Vec<Cursor> {
+        self.model.push_stack_element();
+        let line_count = self.model.line_count();
+        let added: Vec<Cursor> = self.cursors.iter().filter_map(|cursor| {
+            let line = cursor.position.line_number as i64 + delta as i64;
+            if line < 1 || line > line_count as i64 {
+                return None;
+            }
+            let line = line as u32;
+            let column = cursor.preferred_column.min(self.model.get_line_length(line) + 1);
+            let mut added = Cursor::new(Position::new(line, column));
+            added.preferred_column = cursor.preferred_column;
+            Some(added)
+        }).collect();
+        self.cursors.extend(added);
+        self.normalize_cursors();
+        self.cursors.clone()
+    }
+
+    fn set_cursors_from_selections(&mut self, selections: Vec<Selection>) {
+        self.cursors = selections.into_iter().map(Cursor::from_selection).collect();
+        if self.cursors.is_empty() {
+            self.cursors.push(Cursor::new(Position::new(1, 1)));
+        }
+        self.normalize_cursors();
+    }
+
+    /// Sorts cursors by position and merges the ones whose selections overlap
+    /// (or touch, when one of them is empty), as `cursorCollection.ts` does.
+    /// `primary` follows its cursor, or the cursor it was merged into.
+    fn normalize_cursors(&mut self) {
+        let primary = self.primary.min(self.cursors.len().saturating_sub(1));
+        let mut sorted: Vec<(usize, Cursor)> = self.cursors.drain(..).enumerate().collect();
+        sorted.sort_by_key(|(_, c)| c.selection.get_start_position());
+        let mut merged: Vec<Cursor> = Vec::with_capacity(sorted.len());
+        for (index, cursor) in sorted {
+            if index == primary {
+                self.primary = merged.len();
+            }
+            if let Some(previous) = merged.last_mut() {
+                let prev_end = previous.selection.get_end_position();
+                let next_start = cursor.selection.get_start_position();
+                let overlaps = if previous.selection.is_empty() || cursor.selection.is_empty() {
+                    next_start <= prev_end
+                } else {
+                    next_start < prev_end
+                };
+                if overlaps {
+                    let start = previous.selection.get_start_position();
+                    let end = prev_end.max(cursor.selection.get_end_position());
+                    let selection = if previous.selection.get_direction() == SelectionDirection::RTL {
+                        Selection::from_positions(end, start)
+                    } else {
+                        Selection::from_positions(start, end)
+                    };
+                    *previous = Cursor::from_selection(selection);
+                    if index == primary {
+                        self.primary = merged.len() - 1;
+                    }
+                    continue;
+                }
+            }
+            merged.push(cursor);
+        }
+        self.cursors = merged;
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(text: &str) -> Editor {
+        Editor::new(&TextModel::new("file:///a".into(), text.into()), &EditorConfig::new())
+    }
+
+    fn carets(editor: &Editor) -> Vec<(u32, u32)> {
+        editor.get_selections().iter().map(|s| (s.position_line_number, s.position_column)).collect()
+    }
+
+    #[test]
+    fn test_multi_cursor_type_and_delete_are_single_undo_steps() {
+        let mut ed = editor("ab\ncd\nef");
+        ed.set_selections(vec![Selection::new(1, 3, 1, 3), Selection::new(2, 3, 2, 3), Selection::new(3, 3, 3, 3)]);
+        ed.type_text("!".into());
+        ed.type_text("?".into());
+        assert_eq!(ed.get_value(), "ab!?\ncd!?\nef!?");
+        assert_eq!(carets(&ed), vec![(1, 5), (2, 5), (3, 5)]);
+
+        ed.delete_left();
+        assert_eq!(ed.get_value(), "ab!\ncd!\nef!");
+
+        ed.undo();
+        assert_eq!(ed.get_value(), "ab!?\ncd!?\nef!?");
+        ed.undo();
+        assert_eq!(ed.get_value(), "ab\ncd\nef");
+        assert_eq!(carets(&ed), vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn test_delete_joins_lines_and_merges_cursors() {
+        let mut ed = editor("a\nb\nc");
+        ed.set_selections(vec![Selection::new(2, 1, 2, 1), Selection::new(3, 1, 3, 1)]);
+        ed.delete_left();
+        assert_eq!(ed.get_value(), "abc");
+        assert_eq!(carets(&ed), vec![(1, 2), (1, 3)]);
+
+        ed.set_selections(vec![Selection::new(1, 1, 1, 1), Selection::new(1, 2, 1, 2)]);
+        ed.delete_right();
+        assert_eq!(ed.get_value(), "c");
+
+        ed.set_selections(vec![Selection::new(1, 1, 1, 2), Selection::new(1, 2, 1, 2)]);
+        assert_eq!(ed.get_selections().len(), 1);
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let mut ed = editor("x");
+        ed.set_selections(vec![Selection::new(1, 1, 1, 1)]);
+        ed.type_text("\t".into());
+        ed.type_text(" ".into());
+        ed.type_text("\t".into());
+        assert_eq!(ed.get_value(), "        x");
+        ed.delete_left();
+        assert_eq!(ed.get_value(), "    x");
+    }
+
+    #[test]
+    fn test_paste_spreads_lines_across_cursors() {
+        let mut ed = editor("a\nb");
+        ed.set_selections(vec![Selection::new(1, 2, 1, 2), Selection::new(2, 2, 2, 2)]);
+        ed.paste("1\n2\n".into());
+        assert_eq!(ed.get_value(), "a1\nb2");
+
+        ed.paste("xy".into());
+        assert_eq!(ed.get_value(), "a1xy\nb2xy");
+        assert_eq!(carets(&ed), vec![(1, 5), (2, 5)]);
+    }
+
+    #[test]
+    fn test_add_cursors_and_next_occurrence() {
+        let mut ed = editor("foo bar\nx\nfoo baz foo");
+        ed.set_selections(vec![Selection::new(1, 6, 1, 6)]);
+        ed.add_cursor_below();
+        ed.add_cursor_below();
+        assert_eq!(carets(&ed), vec![(1, 6), (2, 2), (3, 6)]);
+
+        ed.set_selections(vec![Selection::new(1, 2, 1, 2)]);
+        ed.add_selection_to_next_find_match();
+        assert_eq!(ed.get_selections(), vec![Selection::new(1, 1, 1, 4)]);
+        ed.add_selection_to_next_find_match();
+        ed.add_selection_to_next_find_match();
+        ed.add_selection_to_next_find_match();
+        assert_eq!(ed.get_selections().len(), 3);
+        ed.type_text("q".into());
+        assert_eq!(ed.get_value(), "q bar\nx\nq baz q");
+    }
+
+    #[test]
+    fn test_next_occurrence_continues_from_the_added_selection() {
+        let mut ed = editor("a foo\nfoo\nfoo b foo");
+        ed.set_selections(vec![Selection::new(3, 1, 3, 4)]);
+        ed.add_selection_to_next_find_match();
+        ed.add_selection_to_next_find_match();
+        assert_eq!(ed.get_selections(), vec![Selection::new(1, 3, 1, 6), Selection::new(3, 1, 3, 4), Selection::new(3, 7, 3, 10)]);
+        ed.add_selection_to_next_find_match();
+        assert_eq!(ed.get_selections().len(), 4);
+    }
+
+    #[test]
+    fn test_cursor_moves_use_character_columns_and_stay_in_the_document() {
+        let mut ed = editor("😀x\nab");
+        ed.set_selections(vec![Selection::new(1, 3, 1, 3)]);
+        ed.move_cursor_right();
+        assert_eq!(carets(&ed), vec![(2, 1)]);
+        ed.move_cursor_down();
+        assert_eq!(carets(&ed), vec![(2, 1)]);
+        ed.set_selections(vec![Selection::new(2, 3, 2, 3)]);
+        ed.move_cursor_right();
+        assert_eq!(carets(&ed), vec![(2, 3)]);
+        ed.move_cursor_up();
+        assert_eq!(carets(&ed), vec![(1, 3)]);
+    }
+
+    #[test]
+    fn test_column_select() {
+        let mut ed = editor("abcd\nab\nabcd");
+        ed.column_select(Position::new(1, 2), Position::new(3, 4));
+        assert_eq!(ed.get_selections(), vec![
+            Selection::new(1, 2, 1, 4),
+            Selection::new(2, 2, 2, 3),
+            Selection::new(3, 2, 3, 4),
+        ]);
+        ed.delete_left();
+        assert_eq!(ed.get_value(), "ad\na\nad");
+    }
+
+    fn rust_editor(text: &str) -> Editor {
+        let mut language = LanguageConfiguration::new();
+        language.load(r#"{
+            "brackets": [["{", "}"], ["(", ")"]],
+            "autoClosingPairs": [["{", "}"], ["(", ")"], { "open": "\"", "close": "\"", "notIn": ["string"] }],
+            "surroundingPairs": [["(", ")"], ["\"", "\""]],
+            "indentationRules": { "increaseIndentPattern": "\\{\\s*$", "decreaseIndentPattern": "^\\s*\\}" }
+        }"#.into()).unwrap();
+        let mut ed = editor(text);
+        ed.set_language_configuration(&language);
+        ed
+    }
+
+    #[test]
+    fn test_enter_and_electric_outdent() {
+        let mut ed = rust_editor("fn a() {}");
+        ed.set_selections(vec![Selection::new(1, 9, 1, 9)]);
+        ed.type_text("\n".into());
+        assert_eq!(ed.get_value(), "fn a() {\n    \n}");
+        assert_eq!(carets(&ed), vec![(2, 5)]);
+
+        ed.type_text("x".into());
+        ed.type_text("\n".into());
+        ed.type_text("}".into());
+        assert_eq!(ed.get_value(), "fn a() {\n    x\n}\n}");
+    }
+
+    #[test]
+    fn test_auto_close_overtype_and_surround() {
+        let mut ed = rust_editor("let v = ;");
+        ed.set_selections(vec![Selection::new(1, 9, 1, 9)]);
+        ed.type_text("(".into());
+        assert_eq!(ed.get_value(), "let v = ();");
+        ed.type_text(")".into());
+        assert_eq!(ed.get_value(), "let v = ();");
+        assert_eq!(carets(&ed), vec![(1, 11)]);
+
+        ed.set_selections(vec![Selection::new(1, 1, 1, 4)]);
+        ed.type_text("\"".into());
+        assert_eq!(ed.get_value(), "\"let\" v = ();");
+        assert_eq!(ed.get_selections(), vec![Selection::new(1, 2, 1, 5)]);
+    }
+
+    #[test]
+    fn test_detect_indentation() {
+        let mut ed = rust_editor("fn a() {\n  x\n}");
+        ed.detect_indentation();
+        ed.set_selections(vec![Selection::new(2, 4, 2, 4)]);
+        ed.type_text("{".into());
+        ed.type_text("\n".into());
+        assert_eq!(ed.get_value(), "fn a() {\n  x{\n    \n  }\n}");
+    }
+
+    #[test]
+    fn test_paste_reindents_block() {
+        let mut ed = rust_editor("fn a() {\n    \n}");
+        ed.set_selections(vec![Selection::new(2, 5, 2, 5)]);
+        ed.paste("if x {\n  y();\n}".into());
+        assert_eq!(ed.get_value(), "fn a() {\n    if x {\n      y();\n    }\n}");
+    }
+
+    #[test]
+    fn test_snippet_tabstops_mirrors_and_transforms() {
+        let mut ed = editor("  x");
+        ed.set_selections(vec![Selection::new(1, 3, 1, 4)]);
+        ed.insert_snippet("for ${1:i} in ${2:$TM_SELECTED_TEXT} {\n\t$1 ${1/(.*)/${1:/upcase}/}\n}".into(), None).unwrap();
+        assert_eq!(ed.get_value(), "  for i in x {\n      i I\n  }");
+        assert_eq!(ed.get_selections().len(), 2);
+
+        ed.type_text("item".into());
+        assert_eq!(ed.get_value(), "  for item in x {\n      item I\n  }");
+        ed.next_snippet_placeholder();
+        assert_eq!(ed.get_value(), "  for item in x {\n      item ITEM\n  }");
+        assert_eq!(ed.get_selections(), vec![Selection::new(1, 15, 1, 16)]);
+
+        // A nested snippet finishes first, then the outer one continues to `$0`.
+        ed.insert_snippet("f(${1:a})".into(), None).unwrap();
+        assert_eq!(ed.get_selections(), vec![Selection::new(1, 17, 1, 18)]);
+        ed.next_snippet_placeholder();
+        assert!(ed.is_in_snippet());
+        ed.next_snippet_placeholder();
+        assert!(!ed.is_in_snippet());
+        assert_eq!(carets(&ed), vec![(3, 4)]);
+    }
+}
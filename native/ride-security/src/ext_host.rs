@@ -50,7 +50,7 @@ pub struct ExtensionHost {
 pub struct ExtensionStats {
     pub memory_rss_bytes: f64,
     pub cpu_usage_percent: f64,
-    pub total_requests: u64,
+    pub total_requests: i64,
     pub avg_latency_ms: f64,
     pub peak_latency_ms: f64,
     pub throughput_eps: f64, // Events per second
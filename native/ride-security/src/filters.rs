@@ -0,0 +1,310 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Filters — Rust port of `fuzzyScore` and `createMatches` from `src/vs/base/common/filters.ts`.
+//!
+//! The scorer fills a dynamic-programming table over pattern × word positions,
+//! rewarding matches at word starts (camelCase humps, after `_`, `.`, `/` …),
+//! contiguous runs and a common prefix, and requires the first pattern
+//! character to match "strongly" unless `first_match_can_be_weak` is set.
+//! Positions are in chars rather than UTF-16 units.
+
+use napi_derive::napi;
+
+/// Words and patterns longer than this are truncated, as in VS Code.
+const MAX_LEN: usize = 128;
+
+#[napi(object)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzyScoreResult {
+    pub score: i32,
+    /// Start of the word the pattern was matched from.
+    pub word_start: u32,
+    pub matches: Vec<MatchRange>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Arrow {
+    Diag,
+    Left,
+    LeftLeft,
+}
+
+fn is_separator(c: Option<char>) -> bool {
+    matches!(c, Some('_' | '-' | '.' | ' ' | '/' | '\\' | '\'' | '"' | ':' | '$' | '<' | '>' | '(' | ')' | '[' | ']' | '{' | '}'))
+}
+
+fn is_whitespace(c: Option<char>) -> bool {
+    matches!(c, Some(' ' | '\t'))
+}
+
+struct Word<'a> {
+    chars: &'a [char],
+    low: &'a [char],
+}
+
+impl Word<'_> {
+    fn at(&self, pos: isize) -> Option<char> {
+        if pos < 0 { None } else { self.low.get(pos as usize).copied() }
+    }
+
+    fn is_upper(&self, pos: usize) -> bool {
+        self.chars[pos] != self.low[pos]
+    }
+}
+
+fn lower(chars: &[char]) -> Vec<char> {
+    chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn do_score(
+    pattern: &[char],
+    pattern_low: &[char],
+    pattern_pos: usize,
+    pattern_start: usize,
+    word: &Word,
+    word_pos: usize,
+    word_len: usize,
+    word_start: usize,
+    new_match_start: bool,
+    first_match_strong: &mut bool,
+) -> Option<i32> {
+    if pattern_low[pattern_pos] != word.low[word_pos] {
+        return None;
+    }
+    let p = word_pos as isize;
+    let mut score = 1;
+    let mut is_gap_location = false;
+    if word_pos == pattern_pos - pattern_start {
+        // Common prefix: `foobar <-> foobaz`.
+        score = if pattern[pattern_pos] == word.chars[word_pos] { 7 } else { 5 };
+    } else if word.is_upper(word_pos) && (word_pos == 0 || !word.is_upper(word_pos - 1)) {
+        // Hitting an upper-case hump: `foo <-> forOthers`.
+        score = if pattern[pattern_pos] == word.chars[word_pos] { 7 } else { 5 };
+        is_gap_location = true;
+    } else if is_separator(word.at(p)) && (word_pos == 0 || !is_separator(word.at(p - 1))) {
+        // Hitting a separator: `. <-> foo.bar`.
+        score = 5;
+    } else if is_separator(word.at(p - 1)) || is_whitespace(word.at(p - 1)) {
+        // Right after a separator: `foo <-> bar_foo`.
+        score = 5;
+        is_gap_location = true;
+    }
+
+    if score > 1 && pattern_pos == pattern_start {
+        *first_match_strong = true;
+    }
+    if !is_gap_location {
+        is_gap_location = word.is_upper(word_pos) || is_separator(word.at(p - 1)) || is_whitespace(word.at(p - 1));
+    }
+
+    if pattern_pos == pattern_start {
+        // A first match past the word start pays for the gap before it.
+        if word_pos > word_start {
+            score -= if is_gap_location { 3 } else { 5 };
+        }
+    } else if new_match_start {
+        score += if is_gap_location { 2 } else { 0 };
+    } else {
+        score += if is_gap_location { 0 } else { 1 };
+    }
+
+    if word_pos + 1 == word_len {
+        // Pretend there is a gap after the last character so matching it is not an unfair advantage.
+        score -= if is_gap_location { 3 } else { 5 };
+    }
+    Some(score)
+}
+
+/// Scores `pattern` against `word` from the given start positions. Returns the score, the
+/// word start, and the matched word positions in descending order, or `None` when the
+/// pattern does not match.
+pub fn fuzzy_score_raw(
+    pattern: &[char],
+    pattern_start: usize,
+    word_chars: &[char],
+    word_start: usize,
+    first_match_can_be_weak: bool,
+    boost_full_match: bool,
+) -> Option<(i32, Vec<usize>)> {
+    let pattern = &pattern[..pattern.len().min(MAX_LEN)];
+    let word_chars = &word_chars[..word_chars.len().min(MAX_LEN)];
+    let (pattern_len, word_len) = (pattern.len(), word_chars.len());
+    if pattern_start >= pattern_len || word_start >= word_len || pattern_len - pattern_start > word_len - word_start {
+        return None;
+    }
+    let pattern_low = lower(pattern);
+    let word_low = lower(word_chars);
+    let word = Word { chars: word_chars, low: &word_low };
+
+    // Earliest and latest word position each pattern character can match at.
+    let mut min_pos = vec![0; pattern_len];
+    let mut max_pos = vec![0; pattern_len];
+    let (mut pp, mut wp) = (pattern_start, word_start);
+    while pp < pattern_len && wp < word_len {
+        if pattern_low[pp] == word_low[wp] {
+            min_pos[pp] = wp;
+            pp += 1;
+        }
+        wp += 1;
+    }
+    if pp != pattern_len {
+        return None;
+    }
+    let (mut pp, mut wp) = (pattern_len as isize - 1, word_len as isize - 1);
+    while pp >= pattern_start as isize && wp >= word_start as isize {
+        if pattern_low[pp as usize] == word_low[wp as usize] {
+            max_pos[pp as usize] = wp as usize;
+            pp -= 1;
+        }
+        wp -= 1;
+    }
+
+    let rows = pattern_len - pattern_start + 1;
+    let cols = word_len - word_start + 1;
+    let mut table = vec![vec![0i32; cols]; rows];
+    let mut diag = vec![vec![0u32; cols]; rows];
+    let mut arrows = vec![vec![Arrow::Diag; cols]; rows];
+    let mut first_match_strong = false;
+
+    for (row, pattern_pos) in (1..).zip(pattern_start..pattern_len) {
+        let min_word = min_pos[pattern_pos];
+        let max_word = max_pos[pattern_pos];
+        let next_max_word = if pattern_pos + 1 < pattern_len { max_pos[pattern_pos + 1] } else { word_len };
+        for (column, word_pos) in (min_word - word_start + 1..).zip(min_word..next_max_word) {
+            let score = if word_pos <= max_word {
+                do_score(pattern, &pattern_low, pattern_pos, pattern_start, &word, word_pos, word_len, word_start, diag[row - 1][column - 1] == 0, &mut first_match_strong)
+            } else {
+                None
+            };
+            let diag_score = score.map(|s| s + table[row - 1][column - 1]);
+            let can_come_left = word_pos > min_word;
+            let left_score = if can_come_left { table[row][column - 1] + if diag[row][column - 1] > 0 { -5 } else { 0 } } else { 0 };
+            let can_come_left_left = word_pos > min_word + 1 && diag[row][column - 1] > 0;
+            let left_left_score = if can_come_left_left { table[row][column - 2] + if diag[row][column - 2] > 0 { -5 } else { 0 } } else { 0 };
+
+            if can_come_left_left && (!can_come_left || left_left_score >= left_score) && diag_score.is_none_or(|d| left_left_score >= d) {
+                // Prefer jumping over a diagonal: the match then starts earlier in the word.
+                table[row][column] = left_left_score;
+                arrows[row][column] = Arrow::LeftLeft;
+                diag[row][column] = 0;
+            } else if can_come_left && diag_score.is_none_or(|d| left_score >= d) {
+                table[row][column] = left_score;
+                arrows[row][column] = Arrow::Left;
+                diag[row][column] = 0;
+            } else if let Some(d) = diag_score {
+                table[row][column] = d;
+                arrows[row][column] = Arrow::Diag;
+                diag[row][column] = diag[row - 1][column - 1] + 1;
+            }
+        }
+    }
+    if !first_match_strong && !first_match_can_be_weak {
+        return None;
+    }
+
+    let (mut row, mut column) = (rows - 1, cols - 1);
+    let mut score = table[row][column];
+    let mut positions = Vec::with_capacity(pattern_len - pattern_start);
+    let mut backwards_diag_length = 0;
+    let mut max_match_column = 0;
+    while row >= 1 {
+        let mut diag_column = column;
+        loop {
+            match arrows[row][diag_column] {
+                Arrow::LeftLeft => diag_column -= 2,
+                Arrow::Left => diag_column -= 1,
+                Arrow::Diag => break,
+            }
+            if diag_column < 1 {
+                break;
+            }
+        }
+        // Keep extending a contiguous match backwards when it beats the forward choice.
+        if backwards_diag_length > 1
+            && pattern_low[pattern_start + row - 1] == word_low[word_start + column - 1]
+            && !word.is_upper(diag_column + word_start - 1)
+            && backwards_diag_length + 1 > diag[row][diag_column]
+        {
+            diag_column = column;
+        }
+        backwards_diag_length = if diag_column == column { backwards_diag_length + 1 } else { 1 };
+        if max_match_column == 0 {
+            max_match_column = diag_column;
+        }
+        row -= 1;
+        column = diag_column - 1;
+        positions.push(word_start + column);
+    }
+
+    if boost_full_match && word_len - word_start == pattern_len {
+        score += 2;
+    }
+    // One point off for each skipped word character.
+    score -= max_match_column as i32 - pattern_len as i32;
+    Some((score, positions))
+}
+
+/// Merges matched positions into contiguous highlight ranges.
+pub fn create_matches(positions: &[usize]) -> Vec<MatchRange> {
+    let mut ranges: Vec<MatchRange> = Vec::new();
+    for &pos in positions.iter().rev() {
+        let pos = pos as u32;
+        match ranges.last_mut() {
+            Some(last) if last.end == pos => last.end += 1,
+            _ => ranges.push(MatchRange { start: pos, end: pos + 1 }),
+        }
+    }
+    ranges
+}
+
+/// VS Code's `fuzzyScore(pattern, word)` with highlights.
+pub fn fuzzy_score_str(pattern: &str, word: &str, first_match_can_be_weak: bool) -> Option<FuzzyScoreResult> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let word: Vec<char> = word.chars().collect();
+    let (score, positions) = fuzzy_score_raw(&pattern, 0, &word, 0, first_match_can_be_weak, true)?;
+    Some(FuzzyScoreResult { score, word_start: 0, matches: create_matches(&positions) })
+}
+
+#[napi]
+pub fn fuzzy_score(pattern: String, word: String, first_match_can_be_weak: Option<bool>) -> Option<FuzzyScoreResult> {
+    fuzzy_score_str(&pattern, &word, first_match_can_be_weak.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(pattern: &str, word: &str) -> Option<Vec<(u32, u32)>> {
+        fuzzy_score_str(pattern, word, false).map(|r| r.matches.iter().map(|m| (m.start, m.end)).collect())
+    }
+
+    #[test]
+    fn test_word_starts_and_highlights() {
+        assert_eq!(ranges("fb", "fooBar"), Some(vec![(0, 1), (3, 4)]));
+        assert_eq!(ranges("ccm", "cursorCommandManager"), Some(vec![(0, 1), (6, 7), (13, 14)]));
+        assert_eq!(ranges("tt", "the_tree"), Some(vec![(0, 1), (4, 5)]));
+        assert_eq!(ranges("ob", "foobar"), None, "weak first match");
+        assert!(fuzzy_score_str("ob", "foobar", true).is_some());
+        assert_eq!(ranges("xyz", "xy"), None);
+    }
+
+    #[test]
+    fn test_ranking() {
+        let score = |p: &str, w: &str| fuzzy_score_str(p, w, false).map_or(i32::MIN, |r| r.score);
+        assert!(score("con", "console") > score("con", "ConstructorName"));
+        assert!(score("const", "const") > score("const", "constant"), "full match boost");
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+        assert!(score("get", "getValue") > score("get", "targetValue"));
+    }
+}
@@ -0,0 +1,430 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Folding — Rust port of `src/vs/editor/contrib/folding/browser/indentRangeProvider.ts`
+//! and `foldingModel.ts`.
+//!
+//! Features:
+//! - Indentation ranges (off-side aware for Python-like languages)
+//! - Bracket ranges from `find_bracket_pairs`
+//! - `#region` / `#endregion` markers behind any line comment style
+//! - Collapsed state that follows edits and can be saved and restored per document
+
+use napi_derive::napi;
+use regex::Regex;
+use std::sync::OnceLock;
+use crate::range::Range;
+use crate::syntax::find_bracket_pairs;
+use crate::text_model::{ModelDecoration, ModelDecorationOptions, TextModel};
+use crate::text_model_types::TrackedRangeStickiness;
+use crate::view_model::ViewModel;
+
+/// Upper bound on the number of ranges reported for one document.
+const FOLDING_RANGES_LIMIT: usize = 5000;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FoldingRange {
+    pub start_line_number: u32,
+    /// Last line hidden when the range is collapsed.
+    pub end_line_number: u32,
+    /// `"region"` for marker ranges, `None` otherwise.
+    pub kind: Option<String>,
+    pub is_collapsed: bool,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct CollapsedRegion {
+    pub start_line_number: u32,
+    pub end_line_number: u32,
+    /// Hash of the start line, so a stale memento does not collapse the wrong range.
+    pub checksum: u32,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FoldingMemento {
+    pub uri: String,
+    pub line_count: u32,
+    pub collapsed_regions: Vec<CollapsedRegion>,
+}
+
+/// `#region` / `#endregion` behind a line comment opener, or a bare `#region` as in C# and
+/// Python. A comment prefix is required so code such as `region = foo()` is not a marker.
+fn region_markers() -> &'static (Regex, Regex) {
+    static MARKERS: OnceLock<(Regex, Regex)> = OnceLock::new();
+    MARKERS.get_or_init(|| {
+        (
+            Regex::new(r"^\s*(?:(?://|#|<!--|/\*|--|;|')\s*#?|#)region\b").unwrap(),
+            Regex::new(r"^\s*(?:(?://|#|<!--|/\*|--|;|')\s*#?|#)endregion\b").unwrap(),
+        )
+    })
+}
+
+/// Visible indent of `line`, or `None` for whitespace-only lines.
+fn compute_indent_level(line: &str, tab_size: u32) -> Option<u32> {
+    let mut indent = 0;
+    for ch in line.chars() {
+        match ch {
+            ' ' => indent += 1,
+            '\t' => indent += tab_size - indent % tab_size,
+            _ => return Some(indent),
+        }
+    }
+    None
+}
+
+/// Indent recorded for a pending `#endregion` marker.
+const END_MARKER_INDENT: i64 = -2;
+
+struct PreviousRegion {
+    /// Indent of the region, `END_MARKER_INDENT` for an `#endregion` line.
+    indent: i64,
+    /// First line below the region.
+    end_above: u32,
+    line: u32,
+}
+
+/// Indentation and region-marker ranges, computed bottom-up like VS Code's
+/// `computeRanges`. With `offside`, blank lines at the end of a block belong to it.
+#[napi]
+pub fn compute_indent_folding_ranges(text: String, tab_size: u32, offside: bool) -> Vec<FoldingRange> {
+    let lines: Vec<&str> = text.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).collect();
+    let tab_size = tab_size.max(1);
+    let (start_marker, end_marker) = region_markers();
+    let mut result = Vec::new();
+
+    let sentinel = lines.len() as u32 + 1;
+    let mut previous_regions = vec![PreviousRegion { indent: -1, end_above: sentinel, line: sentinel }];
+    for line in (1..=lines.len() as u32).rev() {
+        let content = lines[line as usize - 1];
+        let Some(indent) = compute_indent_level(content, tab_size) else {
+            if offside {
+                previous_regions.last_mut().unwrap().end_above = line;
+            }
+            continue;
+        };
+        let indent = indent as i64;
+
+        if end_marker.is_match(content) {
+            previous_regions.push(PreviousRegion { indent: END_MARKER_INDENT, end_above: line, line });
+            continue;
+        }
+        if start_marker.is_match(content)
+            && let Some(i) = previous_regions.iter().rposition(|r| r.indent == END_MARKER_INDENT)
+        {
+            // Everything opened since the matching end marker is discarded.
+            previous_regions.truncate(i + 1);
+            let region = &mut previous_regions[i];
+            result.push(FoldingRange { start_line_number: line, end_line_number: region.line, kind: Some("region".into()), is_collapsed: false });
+            region.line = line;
+            region.indent = indent;
+            region.end_above = line;
+            continue;
+        }
+
+        let mut previous = previous_regions.last().unwrap();
+        if previous.indent > indent {
+            while previous_regions.last().unwrap().indent > indent {
+                previous_regions.pop();
+            }
+            previous = previous_regions.last().unwrap();
+            let end_line_number = previous.end_above - 1;
+            if end_line_number > line {
+                result.push(FoldingRange { start_line_number: line, end_line_number, kind: None, is_collapsed: false });
+            }
+        }
+        if previous.indent == indent {
+            previous_regions.last_mut().unwrap().end_above = line;
+        } else {
+            previous_regions.push(PreviousRegion { indent, end_above: line, line });
+        }
+    }
+    result.reverse();
+    result
+}
+
+/// Ranges between matching brackets that span several lines. A closing bracket
+/// that starts its line stays visible when the range is collapsed.
+#[napi]
+pub fn compute_bracket_folding_ranges(text: String) -> Vec<FoldingRange> {
+    let mut line_starts = vec![0u32];
+    line_starts.extend(text.match_indices('\n').map(|(i, _)| i as u32 + 1));
+    let line_of = |offset: u32| line_starts.partition_point(|&s| s <= offset) as u32;
+
+    let mut ranges: Vec<FoldingRange> = find_bracket_pairs(text.clone()).into_iter().filter_map(|pair| {
+        let start = line_of(pair.open);
+        let close_line = line_of(pair.close);
+        let close_line_start = line_starts[close_line as usize - 1] as usize;
+        let closes_at_line_start = text[close_line_start..pair.close as usize].trim().is_empty();
+        let end = if closes_at_line_start { close_line - 1 } else { close_line };
+        (end > start).then_some(FoldingRange { start_line_number: start, end_line_number: end, kind: None, is_collapsed: false })
+    }).collect();
+    ranges.sort_by_key(|r| r.start_line_number);
+    ranges
+}
+
+/// Merges ranges from all providers: one range per start line (region markers
+/// win, then the longest range), properly nested and capped at the limit.
+#[napi]
+pub fn compute_folding_ranges(text: String, tab_size: u32, offside: bool) -> Vec<FoldingRange> {
+    let mut all = compute_indent_folding_ranges(text.clone(), tab_size, offside);
+    all.extend(compute_bracket_folding_ranges(text));
+    all.sort_by(|a, b| {
+        a.start_line_number.cmp(&b.start_line_number)
+            .then(b.kind.is_some().cmp(&a.kind.is_some()))
+            .then(b.end_line_number.cmp(&a.end_line_number))
+    });
+
+    let mut result: Vec<FoldingRange> = Vec::new();
+    let mut open: Vec<u32> = Vec::new();
+    for range in all {
+        if result.last().is_some_and(|r| r.start_line_number == range.start_line_number) {
+            continue;
+        }
+        while open.last().is_some_and(|&end| end < range.start_line_number) {
+            open.pop();
+        }
+        if open.last().is_some_and(|&end| range.end_line_number > end) {
+            continue; // overlaps its parent without nesting
+        }
+        open.push(range.end_line_number);
+        result.push(range);
+        if result.len() == FOLDING_RANGES_LIMIT {
+            break;
+        }
+    }
+    result
+}
+
+fn line_checksum(line: &str) -> u32 {
+    line.trim().bytes().fold(0x811C_9DC5u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+/// Folding state of one document. Collapsed ranges are remembered through
+/// decorations on their start lines, so they survive edits above them.
+#[napi]
+pub struct FoldingModel {
+    model: TextModel,
+    tab_size: u32,
+    offside: bool,
+    ranges: Vec<FoldingRange>,
+    collapsed_markers: Vec<String>,
+}
+
+#[napi]
+impl FoldingModel {
+    #[napi(constructor)]
+    pub fn new(model: &TextModel, tab_size: u32, offside: bool) -> Self {
+        let mut folding = Self {
+            model: model.clone(),
+            tab_size,
+            offside,
+            ranges: Vec::new(),
+            collapsed_markers: Vec::new(),
+        };
+        folding.update();
+        folding
+    }
+
+    /// Recomputes the ranges from the current text, keeping collapsed state.
+    #[napi]
+    pub fn update(&mut self) -> Vec<FoldingRange> {
+        let collapsed_lines: Vec<u32> = self.collapsed_markers.iter()
+            .filter_map(|id| self.model.get_decoration_range(id.clone()))
+            .map(|r| r.start_line_number)
+            .collect();
+        self.ranges = compute_folding_ranges(self.model.get_value(), self.tab_size, self.offside);
+        for range in &mut self.ranges {
+            range.is_collapsed = collapsed_lines.contains(&range.start_line_number);
+        }
+        self.sync_markers();
+        self.ranges.clone()
+    }
+
+    #[napi]
+    pub fn get_ranges(&self) -> Vec<FoldingRange> {
+        self.ranges.clone()
+    }
+
+    /// Collapses or expands the range starting at `start_line_number`.
+    #[napi]
+    pub fn set_collapsed(&mut self, start_line_number: u32, collapsed: bool) -> bool {
+        let Some(range) = self.ranges.iter_mut().find(|r| r.start_line_number == start_line_number) else {
+            return false;
+        };
+        range.is_collapsed = collapsed;
+        self.sync_markers();
+        true
+    }
+
+    /// Toggles the innermost range containing `line_number`.
+    #[napi]
+    pub fn toggle_collapse(&mut self, line_number: u32) -> Option<FoldingRange> {
+        let index = self.ranges.iter()
+            .rposition(|r| r.start_line_number <= line_number && line_number <= r.end_line_number)?;
+        self.ranges[index].is_collapsed = !self.ranges[index].is_collapsed;
+        self.sync_markers();
+        Some(self.ranges[index].clone())
+    }
+
+    #[napi]
+    pub fn collapse_all(&mut self) {
+        self.ranges.iter_mut().for_each(|r| r.is_collapsed = true);
+        self.sync_markers();
+    }
+
+    #[napi]
+    pub fn expand_all(&mut self) {
+        self.ranges.iter_mut().for_each(|r| r.is_collapsed = false);
+        self.sync_markers();
+    }
+
+    /// Collapses ranges nested `level` deep (1 = outermost) and expands the rest.
+    #[napi]
+    pub fn collapse_to_level(&mut self, level: u32) {
+        let mut open: Vec<u32> = Vec::new();
+        for range in &mut self.ranges {
+            while open.last().is_some_and(|&end| end < range.start_line_number) {
+                open.pop();
+            }
+            open.push(range.end_line_number);
+            range.is_collapsed = open.len() as u32 == level;
+        }
+        self.sync_markers();
+    }
+
+    /// Replaces the view's folds with the collapsed ranges.
+    #[napi]
+    pub fn apply_to_view(&self, view: &mut ViewModel) {
+        view.unfold_all();
+        for range in self.ranges.iter().filter(|r| r.is_collapsed) {
+            view.fold_range(range.start_line_number, range.end_line_number);
+        }
+    }
+
+    /// Collapsed state to persist with the document's view state.
+    #[napi]
+    pub fn get_memento(&self) -> FoldingMemento {
+        FoldingMemento {
+            uri: self.model.uri(),
+            line_count: self.model.line_count(),
+            collapsed_regions: self.ranges.iter().filter(|r| r.is_collapsed).map(|r| CollapsedRegion {
+                start_line_number: r.start_line_number,
+                end_line_number: r.end_line_number,
+                checksum: line_checksum(&self.model.get_line_content(r.start_line_number)),
+            }).collect(),
+        }
+    }
+
+    /// Restores a memento saved for this document. Regions whose start line
+    /// changed in the meantime stay expanded. Returns the number restored.
+    #[napi]
+    pub fn apply_memento(&mut self, memento: FoldingMemento) -> u32 {
+        if memento.uri != self.model.uri() {
+            return 0;
+        }
+        let mut restored = 0;
+        for region in memento.collapsed_regions {
+            let matches = line_checksum(&self.model.get_line_content(region.start_line_number)) == region.checksum;
+            if let Some(range) = self.ranges.iter_mut().find(|r| r.start_line_number == region.start_line_number)
+                && matches
+            {
+                range.is_collapsed = true;
+                restored += 1;
+            }
+        }
+        self.sync_markers();
+        restored
+    }
+}
+
+impl FoldingModel {
+    /// Re-creates the start-line decorations of the collapsed ranges.
+    fn sync_markers(&mut self) {
+        let markers = self.ranges.iter().filter(|r| r.is_collapsed).map(|r| ModelDecoration {
+            id: String::new(),
+            range: Range {
+                start_line_number: r.start_line_number,
+                start_column: 1,
+                end_line_number: r.start_line_number,
+                end_column: 1,
+            },
+            options: ModelDecorationOptions {
+                stickiness: TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges,
+                class_name: None,
+                inline_class_name: None,
+                hover_message: None,
+                before_content: None,
+                after_content: None,
+            },
+        }).collect();
+        let old = std::mem::take(&mut self.collapsed_markers);
+        self.collapsed_markers = self.model.delta_decorations(old, markers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_model_types::{RangePod, SingleEditOperation};
+
+    fn spans(ranges: &[FoldingRange]) -> Vec<(u32, u32)> {
+        ranges.iter().map(|r| (r.start_line_number, r.end_line_number)).collect()
+    }
+
+    #[test]
+    fn test_indent_ranges() {
+        let text = "def a():\n    x = 1\n    if x:\n        y()\n\n    return x\nprint(a())";
+        assert_eq!(spans(&compute_indent_folding_ranges(text.into(), 4, false)), vec![(1, 6), (3, 5)]);
+        assert_eq!(spans(&compute_indent_folding_ranges(text.into(), 4, true)), vec![(1, 6), (3, 4)]);
+    }
+
+    #[test]
+    fn test_region_markers_nest() {
+        let text = "// #region outer\nfn a() {}\n  // #region inner\n  x\n  // #endregion\n// #endregion\nrest";
+        let ranges = compute_indent_folding_ranges(text.into(), 4, false);
+        assert_eq!(spans(&ranges), vec![(1, 6), (2, 5), (3, 5)]);
+        assert_eq!(ranges[0].kind.as_deref(), Some("region"));
+        assert_eq!(ranges[2].kind.as_deref(), Some("region"));
+
+        let (start, end) = region_markers();
+        assert!(start.is_match("#region py") && start.is_match("<!-- #region -->") && start.is_match("# region"));
+        assert!(!start.is_match("region = foo()") && !start.is_match("  region.update()"));
+        assert!(!end.is_match("endregion()"));
+    }
+
+    #[test]
+    fn test_bracket_and_merged_ranges() {
+        let text = "fn main() {\n    call(a,\n  b);\n    let v = [\n        1,\n    ];\n}";
+        assert_eq!(spans(&compute_bracket_folding_ranges(text.into())), vec![(1, 6), (2, 3), (4, 5)]);
+        assert_eq!(spans(&compute_folding_ranges(text.into(), 4, false)), vec![(1, 6), (2, 3), (4, 5)]);
+    }
+
+    #[test]
+    fn test_collapsed_state_follows_edits_and_memento() {
+        let model = TextModel::new("file:///a.rs".into(), "fn a() {\n    1\n}\nfn b() {\n    2\n}".into());
+        let mut folding = FoldingModel::new(&model, 4, false);
+        assert!(folding.set_collapsed(4, true));
+
+        model.apply_edits(vec![SingleEditOperation {
+            range: RangePod { start_line_number: 1, start_column: 1, end_line_number: 1, end_column: 1 },
+            text: Some("// header\n".into()),
+            force_move_markers: None,
+        }]);
+        let ranges = folding.update();
+        assert_eq!(ranges.iter().filter(|r| r.is_collapsed).map(|r| r.start_line_number).collect::<Vec<_>>(), vec![5]);
+
+        let mut view = ViewModel::new(&model);
+        folding.apply_to_view(&mut view);
+        assert_eq!(view.get_view_line_count(), 6);
+
+        let memento = folding.get_memento();
+        let mut restored = FoldingModel::new(&model, 4, false);
+        assert_eq!(restored.apply_memento(memento), 1);
+        assert!(restored.get_ranges().iter().any(|r| r.start_line_number == 5 && r.is_collapsed));
+    }
+}
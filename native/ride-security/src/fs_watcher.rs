@@ -48,8 +48,8 @@ pub struct WatcherConfig {
 #[napi(object)]
 pub struct WatcherStats {
     pub active_watchers: u32,
-    pub total_events_processed: u64,
-    pub total_events_dropped: u64,
+    pub total_events_processed: i64,
+    pub total_events_dropped: i64,
     pub overflow_count: u32,
 }
 
@@ -226,8 +226,8 @@ pub fn get_global_watcher_stats() -> WatcherStats {
 
     WatcherStats {
         active_watchers: r.len() as u32,
-        total_events_processed: total_p,
-        total_events_dropped: total_d,
+        total_events_processed: total_p as i64,
+        total_events_dropped: total_d as i64,
         overflow_count: total_o,
     }
 }
@@ -11,6 +11,7 @@
 //! - Persistent cross-session disk caching using MessagePack
 //! - Smart re-indexing: Only scans if mtime has changed
 //! - Unicode-aware fuzzy scoring engine with word-boundary awareness
+//! - Optional trigram content index for search, built from the same traversal
 
 use ignore::WalkBuilder;
 use napi::bindgen_prelude::*;
@@ -44,8 +45,10 @@ pub struct WorkspaceIndexer {
 
 static CURRENT_INDEX: RwLock<Option<WorkspaceIndexer>> = RwLock::new(None);
 
+/// Indexes file paths under `root`. With `build_content_index`, also builds the trigram
+/// content index that narrows text search candidates.
 #[napi]
-pub fn index_workspace_v2(root: String) -> Result<IndexResult> {
+pub fn index_workspace_v2(root: String, build_content_index: Option<bool>) -> Result<IndexResult> {
     let start = std::time::Instant::now();
     let root_path = PathBuf::from(&root);
 
@@ -68,6 +71,13 @@ pub fn index_workspace_v2(root: String) -> Result<IndexResult> {
         })
     }).collect();
 
+    if build_content_index.unwrap_or(false) {
+        let (dirs, paths): (Vec<&FileInfo>, Vec<&FileInfo>) = files.iter().partition(|f| f.is_dir);
+        let to_paths = |infos: Vec<&FileInfo>| infos.into_iter().map(|f| PathBuf::from(&f.path)).collect::<Vec<_>>();
+        let (dirs, paths) = (to_paths(dirs), to_paths(paths));
+        crate::trigram_index::build_from_paths(&root, &paths, &dirs);
+    }
+
     let count = files.len() as u32;
     let duration = start.elapsed().as_secs_f64() * 1000.0;
 
@@ -0,0 +1,528 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Interval Tree — Rust port of `src/vs/editor/common/model/intervalTree.ts`.
+//!
+//! Stores decoration ranges as byte offsets in a treap ordered by start offset
+//! and augmented with the maximum end offset of each subtree. Edits shift every
+//! decoration after the edited region through a lazy delta on the subtree, so
+//! only the decorations touching the edit are visited individually.
+
+use std::collections::HashMap;
+use crate::text_model::ModelDecorationOptions;
+use crate::text_model_types::TrackedRangeStickiness;
+
+/// Index of the sentinel node; stands for "no node".
+const NIL: usize = 0;
+
+#[derive(Clone, Debug)]
+struct IntervalNode {
+    id: String,
+    start: usize,
+    end: usize,
+    max_end: usize,
+    /// Offset delta not yet pushed down to the children.
+    pending: isize,
+    priority: u32,
+    left: usize,
+    right: usize,
+    parent: usize,
+    options: ModelDecorationOptions,
+}
+
+impl IntervalNode {
+    fn sentinel() -> Self {
+        Self {
+            id: String::new(),
+            start: 0,
+            end: 0,
+            max_end: 0,
+            pending: 0,
+            priority: 0,
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+            options: ModelDecorationOptions {
+                stickiness: TrackedRangeStickiness::AlwaysGrowsWhenTypingAtEdges,
+                class_name: None,
+                inline_class_name: None,
+                hover_message: None,
+                before_content: None,
+                after_content: None,
+            },
+        }
+    }
+}
+
+/// A decoration as stored in the tree, with absolute offsets.
+#[derive(Clone, Debug)]
+pub(crate) struct IntervalEntry {
+    pub id: String,
+    pub start: usize,
+    pub end: usize,
+    pub options: ModelDecorationOptions,
+}
+
+#[derive(Clone)]
+pub(crate) struct IntervalTree {
+    nodes: Vec<IntervalNode>,
+    free_nodes: Vec<usize>,
+    ids: HashMap<String, usize>,
+    root: usize,
+    seed: u32,
+}
+
+impl Default for IntervalTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntervalTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![IntervalNode::sentinel()],
+            free_nodes: Vec::new(),
+            ids: HashMap::new(),
+            root: NIL,
+            seed: 0x9E37_79B9,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn insert(&mut self, id: String, start: usize, end: usize, options: ModelDecorationOptions) {
+        self.remove(&id);
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        let node = IntervalNode {
+            id: id.clone(),
+            start: start.min(end),
+            end: start.max(end),
+            max_end: start.max(end),
+            pending: 0,
+            priority: self.seed,
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+            options,
+        };
+        let idx = match self.free_nodes.pop() {
+            Some(idx) => {
+                self.nodes[idx] = node;
+                idx
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        self.ids.insert(id, idx);
+        self.link(idx);
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some(idx) = self.ids.remove(id) else {
+            return false;
+        };
+        self.unlink(idx);
+        self.nodes[idx] = IntervalNode::sentinel();
+        self.free_nodes.push(idx);
+        true
+    }
+
+    pub fn get(&self, id: &str) -> Option<IntervalEntry> {
+        let idx = *self.ids.get(id)?;
+        let delta = self.ancestor_delta(idx);
+        Some(self.entry(idx, delta))
+    }
+
+    /// Decorations intersecting `[start, end]` (both inclusive), ordered by start.
+    pub fn search(&self, start: usize, end: usize) -> Vec<IntervalEntry> {
+        let mut result = Vec::new();
+        self.search_node(self.root, 0, start, end, &mut result);
+        result
+    }
+
+    /// Mirrors the replacement of `length` bytes at `offset` with `text_length`
+    /// bytes. Decorations touching the edit are adjusted according to their
+    /// stickiness; the ones after it are shifted lazily.
+    pub fn accept_replace(&mut self, offset: usize, length: usize, text_length: usize, force_move_markers: bool) {
+        let edit_end = offset + length;
+        let touched: Vec<usize> = self.search(offset, edit_end).into_iter().map(|e| self.ids[&e.id]).collect();
+        for &idx in &touched {
+            self.unlink(idx);
+        }
+
+        let delta = text_length as isize - length as isize;
+        if delta != 0 {
+            let (before, after) = self.split(self.root, (edit_end + 1, 0));
+            self.apply_delta(after, delta);
+            self.root = self.merge(before, after);
+            self.nodes[self.root].parent = NIL;
+        }
+
+        for idx in touched {
+            let node = &mut self.nodes[idx];
+            let (start, end) = accept_edit(node.start, node.end, node.options.stickiness, offset, edit_end, text_length, force_move_markers);
+            node.start = start;
+            node.end = end;
+            node.max_end = end;
+            self.link(idx);
+        }
+    }
+
+    fn entry(&self, idx: usize, delta: isize) -> IntervalEntry {
+        let node = &self.nodes[idx];
+        IntervalEntry {
+            id: node.id.clone(),
+            start: shift(node.start, delta),
+            end: shift(node.end, delta),
+            options: node.options.clone(),
+        }
+    }
+
+    /// Sum of the deltas still pending on the ancestors of `idx`.
+    fn ancestor_delta(&self, idx: usize) -> isize {
+        let mut delta = 0;
+        let mut parent = self.nodes[idx].parent;
+        while parent != NIL {
+            delta += self.nodes[parent].pending;
+            parent = self.nodes[parent].parent;
+        }
+        delta
+    }
+
+    fn search_node(&self, idx: usize, delta: isize, start: usize, end: usize, result: &mut Vec<IntervalEntry>) {
+        if idx == NIL || shift(self.nodes[idx].max_end, delta) < start {
+            return;
+        }
+        let node = &self.nodes[idx];
+        let child_delta = delta + node.pending;
+        self.search_node(node.left, child_delta, start, end, result);
+        if shift(node.start, delta) > end {
+            return;
+        }
+        if shift(node.end, delta) >= start {
+            result.push(self.entry(idx, delta));
+        }
+        self.search_node(node.right, child_delta, start, end, result);
+    }
+
+    // ─── Treap Plumbing ───────────────────────────────────────────────────
+
+    fn link(&mut self, idx: usize) {
+        let key = (self.nodes[idx].start, idx);
+        let (left, right) = self.split(self.root, key);
+        let merged = self.merge(left, idx);
+        self.root = self.merge(merged, right);
+        self.nodes[self.root].parent = NIL;
+    }
+
+    /// Detaches `idx` from the tree, leaving its offsets absolute.
+    fn unlink(&mut self, idx: usize) {
+        let start = shift(self.nodes[idx].start, self.ancestor_delta(idx));
+        let (left, rest) = self.split(self.root, (start, idx));
+        let (single, right) = self.split(rest, (start, idx + 1));
+        debug_assert_eq!(single, idx);
+        self.push_down(single);
+        let node = &mut self.nodes[single];
+        node.left = NIL;
+        node.right = NIL;
+        node.parent = NIL;
+        node.max_end = node.end;
+        self.root = self.merge(left, right);
+        self.nodes[self.root].parent = NIL;
+        self.nodes[NIL] = IntervalNode::sentinel();
+    }
+
+    fn apply_delta(&mut self, idx: usize, delta: isize) {
+        if idx == NIL {
+            return;
+        }
+        let node = &mut self.nodes[idx];
+        node.start = shift(node.start, delta);
+        node.end = shift(node.end, delta);
+        node.max_end = shift(node.max_end, delta);
+        node.pending += delta;
+    }
+
+    fn push_down(&mut self, idx: usize) {
+        let pending = self.nodes[idx].pending;
+        if pending != 0 {
+            let (left, right) = (self.nodes[idx].left, self.nodes[idx].right);
+            self.apply_delta(left, pending);
+            self.apply_delta(right, pending);
+            self.nodes[idx].pending = 0;
+        }
+    }
+
+    fn pull(&mut self, idx: usize) {
+        let (left, right) = (self.nodes[idx].left, self.nodes[idx].right);
+        let child_max = if left == NIL { 0 } else { self.nodes[left].max_end }
+            .max(if right == NIL { 0 } else { self.nodes[right].max_end });
+        self.nodes[idx].max_end = self.nodes[idx].end.max(child_max);
+    }
+
+    fn set_left(&mut self, idx: usize, child: usize) {
+        self.nodes[idx].left = child;
+        if child != NIL {
+            self.nodes[child].parent = idx;
+        }
+    }
+
+    fn set_right(&mut self, idx: usize, child: usize) {
+        self.nodes[idx].right = child;
+        if child != NIL {
+            self.nodes[child].parent = idx;
+        }
+    }
+
+    /// Splits into nodes ordered before `key` (by start offset, then index) and the rest.
+    fn split(&mut self, idx: usize, key: (usize, usize)) -> (usize, usize) {
+        if idx == NIL {
+            return (NIL, NIL);
+        }
+        self.push_down(idx);
+        if (self.nodes[idx].start, idx) < key {
+            let (left, right) = self.split(self.nodes[idx].right, key);
+            self.set_right(idx, left);
+            self.pull(idx);
+            (idx, right)
+        } else {
+            let (left, right) = self.split(self.nodes[idx].left, key);
+            self.set_left(idx, right);
+            self.pull(idx);
+            (left, idx)
+        }
+    }
+
+    fn merge(&mut self, left: usize, right: usize) -> usize {
+        if left == NIL {
+            return right;
+        }
+        if right == NIL {
+            return left;
+        }
+        if self.nodes[left].priority > self.nodes[right].priority {
+            self.push_down(left);
+            let merged = self.merge(self.nodes[left].right, right);
+            self.set_right(left, merged);
+            self.pull(left);
+            left
+        } else {
+            self.push_down(right);
+            let merged = self.merge(left, self.nodes[right].left);
+            self.set_left(right, merged);
+            self.pull(right);
+            right
+        }
+    }
+}
+
+fn shift(offset: usize, delta: isize) -> usize {
+    (offset as isize + delta).max(0) as usize
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MarkerMoveSemantics {
+    MarkerDefined,
+    ForceMove,
+    ForceStay,
+}
+
+/// Whether a marker at `marker_offset` stays before an insertion at `check_offset`.
+fn adjust_marker_before_column(marker_offset: usize, stick_to_previous: bool, check_offset: usize, semantics: MarkerMoveSemantics) -> bool {
+    if marker_offset < check_offset {
+        return true;
+    }
+    if marker_offset > check_offset {
+        return false;
+    }
+    match semantics {
+        MarkerMoveSemantics::ForceMove => false,
+        MarkerMoveSemantics::ForceStay => true,
+        MarkerMoveSemantics::MarkerDefined => stick_to_previous,
+    }
+}
+
+/// New (start, end) of a decoration after `[edit_start, edit_end)` was replaced
+/// by `text_length` bytes. Port of `nodeAcceptEdit`.
+fn accept_edit(
+    start: usize,
+    end: usize,
+    stickiness: TrackedRangeStickiness,
+    edit_start: usize,
+    edit_end: usize,
+    text_length: usize,
+    force_move_markers: bool,
+) -> (usize, usize) {
+    let start_stick_to_previous = matches!(stickiness, TrackedRangeStickiness::AlwaysGrowsWhenTypingAtEdges | TrackedRangeStickiness::GrowsOnlyWhenTypingBefore);
+    let end_stick_to_previous = matches!(stickiness, TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges | TrackedRangeStickiness::GrowsOnlyWhenTypingBefore);
+
+    let deleting = edit_end - edit_start;
+    let inserting = text_length;
+    let common_length = deleting.min(inserting);
+
+    let mut new_start = None;
+    let mut new_end = None;
+
+    let semantics = if force_move_markers {
+        MarkerMoveSemantics::ForceMove
+    } else if deleting > 0 {
+        MarkerMoveSemantics::ForceStay
+    } else {
+        MarkerMoveSemantics::MarkerDefined
+    };
+    if adjust_marker_before_column(start, start_stick_to_previous, edit_start, semantics) {
+        new_start = Some(start);
+    }
+    if adjust_marker_before_column(end, end_stick_to_previous, edit_start, semantics) {
+        new_end = Some(end);
+    }
+
+    if common_length > 0 && !force_move_markers {
+        let semantics = if deleting > inserting { MarkerMoveSemantics::ForceStay } else { MarkerMoveSemantics::MarkerDefined };
+        if new_start.is_none() && adjust_marker_before_column(start, start_stick_to_previous, edit_start + common_length, semantics) {
+            new_start = Some(start);
+        }
+        if new_end.is_none() && adjust_marker_before_column(end, end_stick_to_previous, edit_start + common_length, semantics) {
+            new_end = Some(end);
+        }
+    }
+
+    let semantics = if force_move_markers { MarkerMoveSemantics::ForceMove } else { MarkerMoveSemantics::MarkerDefined };
+    if new_start.is_none() && adjust_marker_before_column(start, start_stick_to_previous, edit_end, semantics) {
+        new_start = Some(edit_start + inserting);
+    }
+    if new_end.is_none() && adjust_marker_before_column(end, end_stick_to_previous, edit_end, semantics) {
+        new_end = Some(edit_start + inserting);
+    }
+
+    let delta = inserting as isize - deleting as isize;
+    let new_start = new_start.unwrap_or_else(|| shift(start, delta));
+    let new_end = new_end.unwrap_or_else(|| shift(end, delta));
+    (new_start, new_end.max(new_start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(stickiness: TrackedRangeStickiness) -> ModelDecorationOptions {
+        ModelDecorationOptions {
+            stickiness,
+            class_name: None,
+            inline_class_name: None,
+            hover_message: None,
+            before_content: None,
+            after_content: None,
+        }
+    }
+
+    fn range(tree: &IntervalTree, id: &str) -> (usize, usize) {
+        let entry = tree.get(id).unwrap();
+        (entry.start, entry.end)
+    }
+
+    #[test]
+    fn test_stickiness_at_edges() {
+        let mut tree = IntervalTree::new();
+        tree.insert("always".into(), 5, 10, options(TrackedRangeStickiness::AlwaysGrowsWhenTypingAtEdges));
+        tree.insert("never".into(), 5, 10, options(TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges));
+        tree.insert("before".into(), 5, 10, options(TrackedRangeStickiness::GrowsOnlyWhenTypingBefore));
+        tree.insert("after".into(), 5, 10, options(TrackedRangeStickiness::GrowsOnlyWhenTypingAfter));
+
+        // Type two characters at the start edge, then at the end edge.
+        tree.accept_replace(5, 0, 2, false);
+        assert_eq!(range(&tree, "always"), (5, 12));
+        assert_eq!(range(&tree, "never"), (7, 12));
+        assert_eq!(range(&tree, "before"), (5, 12));
+        assert_eq!(range(&tree, "after"), (7, 12));
+
+        tree.accept_replace(12, 0, 1, false);
+        assert_eq!(range(&tree, "always"), (5, 13));
+        assert_eq!(range(&tree, "never"), (7, 12));
+        assert_eq!(range(&tree, "before"), (5, 12));
+        assert_eq!(range(&tree, "after"), (7, 13));
+    }
+
+    #[test]
+    fn test_deletion_collapses_and_shifts() {
+        let mut tree = IntervalTree::new();
+        tree.insert("a".into(), 2, 4, options(TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges));
+        tree.insert("b".into(), 10, 12, options(TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges));
+        tree.insert("c".into(), 3, 11, options(TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges));
+
+        tree.accept_replace(1, 5, 0, false);
+        assert_eq!(range(&tree, "a"), (1, 1));
+        assert_eq!(range(&tree, "b"), (5, 7));
+        assert_eq!(range(&tree, "c"), (1, 6));
+
+        let found: Vec<String> = tree.search(4, 5).into_iter().map(|e| e.id).collect();
+        assert_eq!(found, vec!["c".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn test_lazy_shift_matches_brute_force() {
+        let mut tree = IntervalTree::new();
+        let mut expected: Vec<(usize, usize)> = Vec::new();
+        for i in 0..500 {
+            let start = i * 7 % 1000;
+            expected.push((start, start + i % 13));
+            tree.insert(i.to_string(), start, start + i % 13, options(TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges));
+        }
+        for step in 0..200 {
+            let offset = step * 37 % 900;
+            let (length, text_length) = (step % 5, step % 7);
+            tree.accept_replace(offset, length, text_length, false);
+            for range in expected.iter_mut() {
+                let (start, end) = accept_edit(range.0, range.1, TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges, offset, offset + length, text_length, false);
+                *range = (start, end);
+            }
+        }
+        for (i, r) in expected.iter().enumerate() {
+            assert_eq!(range(&tree, &i.to_string()), *r);
+        }
+        let mut hits: Vec<String> = tree.search(300, 400).into_iter().map(|e| e.id).collect();
+        let mut want: Vec<String> = expected.iter().enumerate()
+            .filter(|(_, r)| r.0 <= 400 && r.1 >= 300)
+            .map(|(i, _)| i.to_string())
+            .collect();
+        hits.sort();
+        want.sort();
+        assert_eq!(hits, want);
+    }
+
+    #[test]
+    fn test_model_decorations_follow_edits() {
+        use crate::range::Range;
+        use crate::text_model::{ModelDecoration, TextModel};
+        use crate::text_model_types::{RangePod, SingleEditOperation};
+
+        let content = (1..=200).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n");
+        let model = TextModel::new("file:///a".into(), content);
+        let squiggle = |line: u32| ModelDecoration {
+            id: format!("d{}", line),
+            range: Range { start_line_number: line, start_column: 1, end_line_number: line, end_column: 5 },
+            options: options(TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges),
+        };
+        model.delta_decorations(vec![], (1..=200).step_by(10).map(squiggle).collect());
+
+        // Insert two lines at the top: everything moves down.
+        model.apply_edits(vec![SingleEditOperation {
+            range: RangePod { start_line_number: 1, start_column: 1, end_line_number: 1, end_column: 1 },
+            text: Some("new\nnew\n".into()),
+            force_move_markers: None,
+        }]);
+        assert_eq!(model.get_decoration_range("d101".into()).unwrap().start_line_number, 103);
+
+        let ids: Vec<String> = model.get_lines_decorations(100, 150).into_iter().map(|d| d.id).collect();
+        assert_eq!(ids, vec!["d101", "d111", "d121", "d131", "d141"]);
+    }
+}
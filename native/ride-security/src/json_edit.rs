@@ -0,0 +1,544 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! JSONC Edit Engine — Rust port of `src/vs/base/common/jsonEdit.ts` and `jsonFormatter.ts`.
+//!
+//! `set_property` computes a single minimal replacement for changing, inserting or removing
+//! a value. Only the lines touched by the change are reformatted, so comments, trailing
+//! commas and the indentation of the rest of the document survive untouched.
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use serde_json::Value;
+
+use crate::json_tree::{parse_tree, segments_from_values, NodeKind, Scanner, Segment, SyntaxKind, Utf16Offsets};
+
+/// A text replacement; offsets and lengths are in bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+    pub offset: usize,
+    pub length: usize,
+    pub content: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct FormattingOptions {
+    pub tab_size: usize,
+    pub insert_spaces: bool,
+    /// Used only when the document has no line break to copy.
+    pub eol: String,
+    pub insert_final_newline: bool,
+}
+
+impl Default for FormattingOptions {
+    fn default() -> Self {
+        Self { tab_size: 4, insert_spaces: true, eol: "\n".into(), insert_final_newline: false }
+    }
+}
+
+impl FormattingOptions {
+    /// Guesses the indentation from the first indented line of `text`.
+    pub fn detect(text: &str) -> Self {
+        let mut options = Self::default();
+        let indent = text.lines().map(|l| &l[..l.len() - l.trim_start_matches([' ', '\t']).len()]).find(|i| !i.is_empty());
+        match indent {
+            Some(i) if i.starts_with('\t') => options.insert_spaces = false,
+            Some(i) if i.len() <= 8 => options.tab_size = i.len(),
+            _ => {}
+        }
+        options
+    }
+}
+
+fn is_eol(text: &[u8], offset: usize) -> bool {
+    matches!(text.get(offset), Some(b'\r' | b'\n'))
+}
+
+fn get_eol(options: &FormattingOptions, text: &str) -> String {
+    let bytes = text.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'\r' {
+            return if bytes.get(i + 1) == Some(&b'\n') { "\r\n".into() } else { "\r".into() };
+        } else if b == b'\n' {
+            return "\n".into();
+        }
+    }
+    options.eol.clone()
+}
+
+fn compute_indent_level(content: &str, options: &FormattingOptions) -> usize {
+    let tab_size = options.tab_size.max(1);
+    let mut chars = 0;
+    for b in content.bytes() {
+        match b {
+            b' ' => chars += 1,
+            b'\t' => chars += tab_size,
+            _ => break,
+        }
+    }
+    chars / tab_size
+}
+
+/// Formats `text`, or only the lines overlapping `range` (offset, length), returning the
+/// edits in document order. Line breaks are normalized; nothing is kept from the original
+/// layout inside the range.
+pub fn format_jsonc(text: &str, range: Option<(usize, usize)>, options: &FormattingOptions) -> Vec<Edit> {
+    let bytes = text.as_bytes();
+    let (format_start, range_start, range_end, initial_indent_level, format_text) = match range {
+        Some((offset, length)) => {
+            let range_end = offset + length;
+            let mut start = offset;
+            while start > 0 && !is_eol(bytes, start - 1) {
+                start -= 1;
+            }
+            let mut end = range_end;
+            while end < bytes.len() && !is_eol(bytes, end) {
+                end += 1;
+            }
+            let format_text = &text[start..end];
+            (start, offset, range_end, compute_indent_level(format_text, options), format_text)
+        }
+        None => (0, 0, text.len(), 0, text),
+    };
+    let eol = get_eol(options, text);
+    let indent_value = if options.insert_spaces { " ".repeat(options.tab_size.max(1)) } else { "\t".to_string() };
+    let mut scanner = Scanner::new(format_text);
+    let mut indent_level: usize = 0;
+    let mut line_break = false;
+    let mut has_error = false;
+    let mut edits = Vec::new();
+
+    let new_line_and_indent = |level: usize| format!("{}{}", eol, indent_value.repeat(initial_indent_level + level));
+    let scan_next = |scanner: &mut Scanner, line_break: &mut bool, has_error: &mut bool| {
+        let mut token = scanner.scan();
+        *line_break = false;
+        while matches!(token, SyntaxKind::Trivia | SyntaxKind::LineBreak) {
+            *line_break |= token == SyntaxKind::LineBreak;
+            token = scanner.scan();
+        }
+        *has_error = token == SyntaxKind::Unknown || scanner.token_error() != crate::json_tree::ScanError::None;
+        token
+    };
+    let add_edit = |edits: &mut Vec<Edit>, has_error: bool, content: String, start: usize, end: usize| {
+        if !has_error && start < range_end && end > range_start && text[start..end] != content {
+            edits.push(Edit { offset: start, length: end - start, content });
+        }
+    };
+
+    let mut first_token = scan_next(&mut scanner, &mut line_break, &mut has_error);
+    if first_token != SyntaxKind::Eof {
+        let first_token_start = scanner.token_offset() + format_start;
+        add_edit(&mut edits, has_error, indent_value.repeat(initial_indent_level), format_start, first_token_start);
+    }
+    while first_token != SyntaxKind::Eof {
+        let mut first_token_end = scanner.token_offset() + scanner.token_length() + format_start;
+        let mut second_token = scan_next(&mut scanner, &mut line_break, &mut has_error);
+        let mut replace = String::new();
+        let mut needs_line_break = false;
+        while !line_break && matches!(second_token, SyntaxKind::LineComment | SyntaxKind::BlockComment) {
+            // Comments on the same line stay there, separated by a single space.
+            let comment_start = scanner.token_offset() + format_start;
+            add_edit(&mut edits, has_error, " ".into(), first_token_end, comment_start);
+            first_token_end = scanner.token_offset() + scanner.token_length() + format_start;
+            needs_line_break = second_token == SyntaxKind::LineComment;
+            replace = if needs_line_break { new_line_and_indent(indent_level) } else { String::new() };
+            second_token = scan_next(&mut scanner, &mut line_break, &mut has_error);
+        }
+        if second_token == SyntaxKind::CloseBrace {
+            if first_token != SyntaxKind::OpenBrace {
+                indent_level = indent_level.saturating_sub(1);
+                replace = new_line_and_indent(indent_level);
+            }
+        } else if second_token == SyntaxKind::CloseBracket {
+            if first_token != SyntaxKind::OpenBracket {
+                indent_level = indent_level.saturating_sub(1);
+                replace = new_line_and_indent(indent_level);
+            }
+        } else {
+            let is_comment = matches!(second_token, SyntaxKind::LineComment | SyntaxKind::BlockComment);
+            match first_token {
+                SyntaxKind::OpenBracket | SyntaxKind::OpenBrace => {
+                    indent_level += 1;
+                    replace = new_line_and_indent(indent_level);
+                }
+                SyntaxKind::Comma | SyntaxKind::LineComment => replace = new_line_and_indent(indent_level),
+                SyntaxKind::BlockComment => {
+                    if line_break {
+                        replace = new_line_and_indent(indent_level);
+                    } else if !needs_line_break {
+                        replace = " ".into();
+                    }
+                }
+                SyntaxKind::Colon if !needs_line_break => replace = " ".into(),
+                SyntaxKind::String if second_token == SyntaxKind::Colon && !needs_line_break => replace.clear(),
+                SyntaxKind::Colon | SyntaxKind::String if second_token == SyntaxKind::Colon => {}
+                SyntaxKind::String | SyntaxKind::Null | SyntaxKind::True | SyntaxKind::False | SyntaxKind::Number | SyntaxKind::CloseBrace | SyntaxKind::CloseBracket => {
+                    if is_comment {
+                        if !needs_line_break {
+                            replace = " ".into();
+                        }
+                    } else if second_token != SyntaxKind::Comma && second_token != SyntaxKind::Eof {
+                        has_error = true;
+                    }
+                }
+                SyntaxKind::Unknown => has_error = true,
+                _ => {}
+            }
+            if line_break && is_comment {
+                replace = new_line_and_indent(indent_level);
+            }
+        }
+        if second_token == SyntaxKind::Eof {
+            replace = if options.insert_final_newline { eol.clone() } else { String::new() };
+        }
+        let second_token_start = scanner.token_offset() + format_start;
+        add_edit(&mut edits, has_error, replace, first_token_end, second_token_start);
+        first_token = second_token;
+    }
+    edits
+}
+
+fn apply_edit(text: &str, edit: &Edit) -> String {
+    format!("{}{}{}", &text[..edit.offset], edit.content, &text[edit.offset + edit.length..])
+}
+
+/// Applies non-overlapping edits, in any order.
+pub fn apply_edits(text: &str, edits: &[Edit]) -> String {
+    let mut sorted: Vec<&Edit> = edits.iter().collect();
+    sorted.sort_by_key(|e| e.offset);
+    let mut result = text.to_string();
+    for edit in sorted.into_iter().rev() {
+        result = apply_edit(&result, edit);
+    }
+    result
+}
+
+/// Applies `edit`, reformats the lines it touched and folds everything into one edit.
+fn with_formatting(text: &str, edit: Edit, options: &FormattingOptions) -> Vec<Edit> {
+    let mut new_text = apply_edit(text, &edit);
+    let mut begin = edit.offset;
+    let mut end = edit.offset + edit.content.len();
+    if edit.length == 0 || edit.content.is_empty() {
+        let bytes = new_text.as_bytes();
+        while begin > 0 && !is_eol(bytes, begin - 1) {
+            begin -= 1;
+        }
+        while end < bytes.len() && !is_eol(bytes, end) {
+            end += 1;
+        }
+    }
+    let edits = format_jsonc(&new_text, Some((begin, end - begin)), options);
+    for edit in edits.iter().rev() {
+        new_text = apply_edit(&new_text, edit);
+        begin = begin.min(edit.offset);
+        end = end.max(edit.offset + edit.length);
+        end = end + edit.content.len() - edit.length;
+    }
+    let length = text.len() - (new_text.len() - end) - begin;
+    vec![Edit { offset: begin, length, content: new_text[begin..end].to_string() }]
+}
+
+/// Range removed for the first member of a container: the member and its comma, plus its
+/// whole line when it stands on a line of its own. Comments above it are left alone.
+fn remove_first_member(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let bytes = text.as_bytes();
+    let skip_blanks = |mut i: usize| {
+        while matches!(bytes.get(i), Some(b' ' | b'\t')) {
+            i += 1;
+        }
+        i
+    };
+    let mut begin = start;
+    while begin > 0 && matches!(bytes[begin - 1], b' ' | b'\t') {
+        begin -= 1;
+    }
+    let own_line = begin == 0 || is_eol(bytes, begin - 1);
+    if !own_line {
+        begin = start;
+    }
+    let mut end = end;
+    let after = skip_blanks(end);
+    if bytes.get(after) == Some(&b',') {
+        end = after + 1;
+    }
+    let after = skip_blanks(end);
+    if own_line {
+        if bytes.get(after) == Some(&b'\r') && bytes.get(after + 1) == Some(&b'\n') {
+            end = after + 2;
+        } else if is_eol(bytes, after) {
+            end = after + 1;
+        }
+    } else if !matches!(bytes.get(after), Some(b'/')) {
+        end = after;
+    }
+    (begin, end)
+}
+
+fn stringify(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".into())
+}
+
+/// Computes the edit that sets (`Some`) or removes (`None`) the value at `path`. Missing
+/// parents are created. An `Index` past the end of an array (e.g. `usize::MAX`) appends;
+/// with `is_array_insertion` an in-range index inserts instead of replacing.
+pub fn set_property(text: &str, path: &[Segment], value: Option<Value>, options: &FormattingOptions, is_array_insertion: bool) -> std::result::Result<Vec<Edit>, String> {
+    let tree = parse_tree(text);
+    let mut value = value;
+    let mut path = path.to_vec();
+    let mut parent = None;
+    let mut last_segment = None;
+    while let Some(segment) = path.pop() {
+        parent = tree.find_at_location(tree.root, &path);
+        if parent.is_none() && value.is_some() {
+            value = Some(match &segment {
+                Segment::Key(key) => {
+                    let mut map = serde_json::Map::new();
+                    map.insert(key.clone(), value.take().unwrap());
+                    Value::Object(map)
+                }
+                Segment::Index(_) => Value::Array(vec![value.take().unwrap()]),
+            });
+            last_segment = Some(segment);
+        } else {
+            last_segment = Some(segment);
+            break;
+        }
+    }
+
+    let Some(parent) = parent else {
+        // Empty document, or nothing on the path exists: replace the root.
+        let Some(value) = value else { return Ok(Vec::new()) };
+        let (offset, length) = tree.root.map(|r| (tree.node(r).offset, tree.node(r).length)).unwrap_or((0, 0));
+        return Ok(with_formatting(text, Edit { offset, length, content: stringify(&value) }, options));
+    };
+    let parent_node = tree.node(parent);
+    let children = &parent_node.children;
+    let end_of = |id: usize| tree.node(id).offset + tree.node(id).length;
+
+    match (parent_node.kind, last_segment) {
+        (NodeKind::Object, Some(Segment::Key(key))) => {
+            let existing = tree.find_at_location(Some(parent), &[Segment::Key(key.clone())]);
+            match (existing, value) {
+                (Some(existing), None) => {
+                    let property = tree.node(existing).parent.ok_or("Malformed AST")?;
+                    let index = children.iter().position(|&c| c == property).ok_or("Malformed AST")?;
+                    let (remove_begin, remove_end) = if index > 0 {
+                        // Take the comma after the previous property with it.
+                        (end_of(children[index - 1]), end_of(property))
+                    } else {
+                        remove_first_member(text, tree.node(property).offset, end_of(property))
+                    };
+                    Ok(with_formatting(text, Edit { offset: remove_begin, length: remove_end - remove_begin, content: String::new() }, options))
+                }
+                (Some(existing), Some(value)) => {
+                    let node = tree.node(existing);
+                    Ok(with_formatting(text, Edit { offset: node.offset, length: node.length, content: stringify(&value) }, options))
+                }
+                (None, None) => Ok(Vec::new()),
+                (None, Some(value)) => {
+                    let property = format!("{}: {}", stringify(&Value::String(key)), stringify(&value));
+                    let edit = match children.last() {
+                        Some(&previous) => Edit { offset: end_of(previous), length: 0, content: format!(",{}", property) },
+                        None => Edit { offset: parent_node.offset + 1, length: 0, content: property },
+                    };
+                    Ok(with_formatting(text, edit, options))
+                }
+            }
+        }
+        (NodeKind::Array, Some(Segment::Index(index))) => match value {
+            None => {
+                if index >= children.len() {
+                    return Ok(Vec::new());
+                }
+                let edit = if index == 0 {
+                    let (begin, end) = remove_first_member(text, tree.node(children[0]).offset, end_of(children[0]));
+                    Edit { offset: begin, length: end - begin, content: String::new() }
+                } else if index == children.len() - 1 {
+                    let offset = end_of(children[index - 1]);
+                    Edit { offset, length: end_of(children[index]) - offset, content: String::new() }
+                } else {
+                    let offset = tree.node(children[index]).offset;
+                    Edit { offset, length: tree.node(children[index + 1]).offset - offset, content: String::new() }
+                };
+                Ok(with_formatting(text, edit, options))
+            }
+            Some(value) => {
+                let content = stringify(&value);
+                let edit = if !is_array_insertion && index < children.len() {
+                    let node = tree.node(children[index]);
+                    Edit { offset: node.offset, length: node.length, content }
+                } else if children.is_empty() || index == 0 {
+                    let content = if children.is_empty() { content } else { format!("{},", content) };
+                    Edit { offset: parent_node.offset + 1, length: 0, content }
+                } else {
+                    Edit { offset: end_of(children[index.min(children.len()) - 1]), length: 0, content: format!(",{}", content) }
+                };
+                Ok(with_formatting(text, edit, options))
+            }
+        },
+        (kind, segment) => Err(format!(
+            "Can not add {} to parent of type {}",
+            if matches!(segment, Some(Segment::Index(_))) { "index" } else { "property" },
+            kind.as_str()
+        )),
+    }
+}
+
+// ─── NAPI surface ──────────────────────────────────────────────────────────
+
+/// An edit as JS sees it: `offset` and `length` are UTF-16 code units, like string indices,
+/// not the byte offsets used internally.
+#[napi(object)]
+pub struct JsonEdit {
+    pub offset: u32,
+    pub length: u32,
+    pub content: String,
+}
+
+#[napi(object)]
+pub struct JsonEditOptions {
+    /// Defaults to the indentation detected in the document.
+    pub tab_size: Option<u32>,
+    pub insert_spaces: Option<bool>,
+    pub eol: Option<String>,
+    /// Insert at an in-range array index instead of replacing the item.
+    pub is_array_insertion: Option<bool>,
+}
+
+fn resolve_options(text: &str, options: &Option<JsonEditOptions>) -> (FormattingOptions, bool) {
+    let mut resolved = FormattingOptions::detect(text);
+    let Some(options) = options else { return (resolved, false) };
+    if let Some(tab_size) = options.tab_size {
+        resolved.tab_size = tab_size as usize;
+    }
+    if let Some(insert_spaces) = options.insert_spaces {
+        resolved.insert_spaces = insert_spaces;
+    }
+    if let Some(eol) = &options.eol {
+        resolved.eol = eol.clone();
+    }
+    (resolved, options.is_array_insertion.unwrap_or(false))
+}
+
+fn to_napi_edits(text: &str, edits: Vec<Edit>) -> Vec<JsonEdit> {
+    let offsets = Utf16Offsets::new(text);
+    edits.into_iter().map(|e| {
+        let (offset, length) = offsets.range_to_utf16(e.offset, e.length);
+        JsonEdit { offset, length, content: e.content }
+    }).collect()
+}
+
+/// Sets (or, with `value` undefined, removes) the value at `path` and returns the minimal
+/// edits. Fails when the document has syntax errors, like VS Code's settings writer.
+pub(crate) fn edit_document(text: &str, path: &[Segment], value: Option<Value>, options: &Option<JsonEditOptions>) -> Result<Vec<Edit>> {
+    let tree = parse_tree(text);
+    if let Some(error) = tree.errors.first() {
+        return Err(Error::from_reason(format!("Invalid JSON: {} at offset {}", error.error, error.offset)));
+    }
+    let (formatting, is_array_insertion) = resolve_options(text, options);
+    set_property(text, path, value, &formatting, is_array_insertion).map_err(Error::from_reason)
+}
+
+/// Path segments are property names or array indices; `-1` appends to an array.
+#[napi]
+pub fn json_set_property(text: String, path: Vec<Value>, value: Option<Value>, options: Option<JsonEditOptions>) -> Result<Vec<JsonEdit>> {
+    edit_document(&text, &segments_from_values(&path), value, &options).map(|edits| to_napi_edits(&text, edits))
+}
+
+#[napi]
+pub fn json_remove_property(text: String, path: Vec<Value>, options: Option<JsonEditOptions>) -> Result<Vec<JsonEdit>> {
+    edit_document(&text, &segments_from_values(&path), None, &options).map(|edits| to_napi_edits(&text, edits))
+}
+
+#[napi]
+pub fn json_apply_edits(text: String, edits: Vec<JsonEdit>) -> Result<String> {
+    let offsets = Utf16Offsets::new(&text);
+    let edits = edits.into_iter().map(|e| {
+        let end = e.offset + e.length;
+        match (offsets.to_byte(e.offset as usize), offsets.to_byte(end as usize)) {
+            (Some(start), Some(end)) => Ok(Edit { offset: start, length: end - start, content: e.content }),
+            _ => Err(Error::from_reason(format!("Edit out of range: {}..{}", e.offset, end))),
+        }
+    }).collect::<Result<Vec<Edit>>>()?;
+    Ok(apply_edits(&text, &edits))
+}
+
+/// Formats the whole document, or only the lines overlapping `offset..offset + length`
+/// (UTF-16 units).
+#[napi]
+pub fn json_format_edits(text: String, offset: Option<u32>, length: Option<u32>, options: Option<JsonEditOptions>) -> Vec<JsonEdit> {
+    let (formatting, _) = resolve_options(&text, &options);
+    let offsets = Utf16Offsets::new(&text);
+    let range = offset.and_then(|o| {
+        let start = offsets.to_byte(o as usize)?;
+        let end = offsets.to_byte((o + length.unwrap_or(0)) as usize)?;
+        Some((start, end - start))
+    });
+    to_napi_edits(&text, format_jsonc(&text, range, &formatting))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(text: &str, path: &[&str], value: Option<Value>) -> String {
+        let path: Vec<Segment> = path.iter().map(|p| Segment::Key(p.to_string())).collect();
+        let edits = set_property(text, &path, value, &FormattingOptions::detect(text), false).unwrap();
+        apply_edits(text, &edits)
+    }
+
+    #[test]
+    fn test_set_property_preserves_comments() {
+        let text = "{\n  // Editor\n  \"editor.fontSize\": 12, // small\n  \"files.exclude\": {\n    \"**/.git\": true,\n  },\n}\n";
+        assert_eq!(
+            set(text, &["editor.fontSize"], Some(Value::from(14))),
+            "{\n  // Editor\n  \"editor.fontSize\": 14, // small\n  \"files.exclude\": {\n    \"**/.git\": true,\n  },\n}\n"
+        );
+        assert_eq!(
+            set(text, &["files.exclude", "**/node_modules"], Some(Value::Bool(true))),
+            "{\n  // Editor\n  \"editor.fontSize\": 12, // small\n  \"files.exclude\": {\n    \"**/.git\": true,\n    \"**/node_modules\": true,\n  },\n}\n"
+        );
+        assert_eq!(
+            set("{\n\t\"a\": 1 /* keep */\n}", &["b", "c"], Some(Value::from("x"))),
+            "{\n\t\"a\": 1,\n\t\"b\": {\n\t\t\"c\": \"x\"\n\t} /* keep */\n}"
+        );
+        let edits = set_property(text, &[Segment::Key("editor.fontSize".into())], Some(Value::from(14)), &FormattingOptions::detect(text), false).unwrap();
+        assert_eq!(edits, vec![Edit { offset: 35, length: 2, content: "14".into() }]);
+    }
+
+    #[test]
+    fn test_remove_property_and_array_edits() {
+        let text = "{\n    \"a\": 1,\n    // b\n    \"b\": 2\n}";
+        assert_eq!(set(text, &["b"], None), "{\n    \"a\": 1\n}");
+        assert_eq!(set(text, &["a"], None), "{\n    // b\n    \"b\": 2\n}");
+        assert_eq!(set(text, &["missing"], None), text);
+
+        let list = "{\n  \"l\": [\n    1,\n    2,\n    3\n  ]\n}";
+        let path = |i| [Segment::Key("l".into()), Segment::Index(i)];
+        let apply = |i, value, insert| apply_edits(list, &set_property(list, &path(i), value, &FormattingOptions::detect(list), insert).unwrap());
+        assert_eq!(apply(2, None, false), "{\n  \"l\": [\n    1,\n    2\n  ]\n}");
+        assert_eq!(apply(0, None, false), "{\n  \"l\": [\n    2,\n    3\n  ]\n}");
+        assert_eq!(apply(1, Some(Value::from(9)), false), "{\n  \"l\": [\n    1,\n    9,\n    3\n  ]\n}");
+        assert_eq!(apply(usize::MAX, Some(Value::from(4)), false), "{\n  \"l\": [\n    1,\n    2,\n    3,\n    4\n  ]\n}");
+        assert_eq!(apply(1, Some(Value::from(9)), true), "{\n  \"l\": [\n    1,\n    9,\n    2,\n    3\n  ]\n}");
+    }
+
+    #[test]
+    fn test_empty_document_and_errors() {
+        assert_eq!(set("", &["a", "b"], Some(Value::from(1))), "{\n    \"a\": {\n        \"b\": 1\n    }\n}");
+        assert!(set_property("[]", &[Segment::Key("a".into())], Some(Value::from(1)), &FormattingOptions::default(), false).is_err());
+        assert!(edit_document("{\"a\": }", &[Segment::Key("a".into())], Some(Value::from(1)), &None).is_err());
+    }
+
+    #[test]
+    fn test_napi_edits_use_utf16_offsets() {
+        let text = "{\n  \"title\": \"café 😀\",\n  \"size\": 1\n}".to_string();
+        let edits = json_set_property(text.clone(), vec![Value::from("size")], Some(Value::from(2)), None).unwrap();
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        let replaced = String::from_utf16(&utf16[edits[0].offset as usize..(edits[0].offset + edits[0].length) as usize]).unwrap();
+        assert_eq!(replaced, "1");
+        assert_eq!(json_apply_edits(text, edits).unwrap(), "{\n  \"title\": \"café 😀\",\n  \"size\": 2\n}");
+    }
+}
@@ -5,11 +5,15 @@
 
 //! JSON parsing, editing, and schema validation — Rust port of
 //! `src/vs/base/common/json.ts`, `jsonSchema.ts`, and `jsonEdit.ts`.
+//! The parse tree lives in `json_tree`, edits in `json_edit` and validation in `json_schema`.
 
 use napi_derive::napi;
 use napi::bindgen_prelude::*;
 use serde_json::Value;
 
+use crate::json_edit::{apply_edits, edit_document};
+use crate::json_tree::{parse_tree, NodeKind, Segment};
+
 // ─── JSON parsing ──────────────────────────────────────────────────────────
 
 /// Parse JSON with detailed error reporting.
@@ -165,83 +169,39 @@ pub fn json_get(json_string: String, path: String) -> Option<String> {
     Some(current.to_string())
 }
 
-/// Set a value in a JSON object by dot-notation path.
+/// Resolve a dot-notation path against the parse tree: a numeric part indexes an existing
+/// array, everything else is a property name.
+fn edit_path(json_string: &str, path: &str) -> Vec<Segment> {
+    let tree = parse_tree(json_string);
+    let mut node = tree.root;
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let is_array = node.is_some_and(|n| tree.node(n).kind == NodeKind::Array);
+        let segment = match part.parse::<usize>() {
+            Ok(idx) if is_array => Segment::Index(idx),
+            _ => Segment::Key(part.to_string()),
+        };
+        node = tree.find_at_location(node, std::slice::from_ref(&segment));
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Set a value in a JSON object by dot-notation path. Only the changed lines are rewritten;
+/// comments and the surrounding formatting are preserved.
 #[napi]
 pub fn json_set(json_string: String, path: String, value_string: String) -> Result<String> {
-    let mut root: Value = serde_json::from_str(&json_string)
-        .map_err(|e| Error::from_reason(format!("Invalid JSON: {}", e)))?;
     let new_value: Value = serde_json::from_str(&value_string)
         .unwrap_or(Value::String(value_string.clone()));
-
-    let parts: Vec<&str> = path.split('.').collect();
-    let mut current = &mut root;
-
-    for (i, part) in parts.iter().enumerate() {
-        if i == parts.len() - 1 {
-            // Set the value
-            if let Some(obj) = current.as_object_mut() {
-                obj.insert(part.to_string(), new_value.clone());
-            } else if let Ok(idx) = part.parse::<usize>() {
-                if let Some(arr) = current.as_array_mut() {
-                    while arr.len() <= idx {
-                        arr.push(Value::Null);
-                    }
-                    arr[idx] = new_value.clone();
-                }
-            }
-        } else {
-            // Navigate
-            if let Ok(idx) = part.parse::<usize>() {
-                if !current.is_array() {
-                    *current = Value::Array(Vec::new());
-                }
-                let arr = current.as_array_mut().unwrap();
-                while arr.len() <= idx {
-                    arr.push(Value::Object(serde_json::Map::new()));
-                }
-                current = &mut arr[idx];
-            } else {
-                if !current.is_object() {
-                    *current = Value::Object(serde_json::Map::new());
-                }
-                let obj = current.as_object_mut().unwrap();
-                if !obj.contains_key(*part) {
-                    obj.insert(part.to_string(), Value::Object(serde_json::Map::new()));
-                }
-                current = obj.get_mut(*part).unwrap();
-            }
-        }
-    }
-
-    serde_json::to_string_pretty(&root)
-        .map_err(|e| Error::from_reason(format!("Serialization failed: {}", e)))
+    let edits = edit_document(&json_string, &edit_path(&json_string, &path), Some(new_value), &None)?;
+    Ok(apply_edits(&json_string, &edits))
 }
 
-/// Delete a key from a JSON object by dot-notation path.
+/// Delete a key from a JSON object by dot-notation path, preserving formatting.
 #[napi]
 pub fn json_delete(json_string: String, path: String) -> Result<String> {
-    let mut root: Value = serde_json::from_str(&json_string)
-        .map_err(|e| Error::from_reason(format!("Invalid JSON: {}", e)))?;
-
-    let parts: Vec<&str> = path.split('.').collect();
-    let mut current = &mut root;
-
-    for (i, part) in parts.iter().enumerate() {
-        if i == parts.len() - 1 {
-            if let Some(obj) = current.as_object_mut() {
-                obj.remove(*part);
-            }
-        } else {
-            if let Some(v) = current.get_mut(*part) {
-                current = v;
-            } else {
-                break;
-            }
-        }
-    }
-
-    serde_json::to_string_pretty(&root)
-        .map_err(|e| Error::from_reason(format!("Serialization failed: {}", e)))
+    let edits = edit_document(&json_string, &edit_path(&json_string, &path), None, &None)?;
+    Ok(apply_edits(&json_string, &edits))
 }
 
 /// Check if a JSON object has a key at the given path.
@@ -266,7 +226,7 @@ pub fn json_merge(base_json: String, override_json: String) -> Result<String> {
         .map_err(|e| Error::from_reason(format!("Serialization failed: {}", e)))
 }
 
-fn deep_merge(base: &mut Value, over: &Value) {
+pub(crate) fn deep_merge(base: &mut Value, over: &Value) {
     match (base, over) {
         (Value::Object(base_map), Value::Object(over_map)) => {
             for (key, over_val) in over_map {
@@ -456,6 +416,12 @@ mod tests {
         let json = r#"{"a": 1}"#;
         let result = json_set(json.into(), "b".into(), "2".into()).unwrap();
         assert!(result.contains("\"b\": 2"));
+
+        let settings = "{\n  // keep me\n  \"a\": [1, 2],\n}";
+        let result = json_set(settings.into(), "a.1".into(), "3".into()).unwrap();
+        assert_eq!(result, "{\n  // keep me\n  \"a\": [1, 3],\n}");
+        let result = json_delete(result, "a".into()).unwrap();
+        assert_eq!(result, "{\n  // keep me\n}");
     }
 
     #[test]
@@ -0,0 +1,714 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! JSON Schema Validation — Rust port of the validator in `vscode-json-languageservice`
+//! (`parser/jsonParser.ts`), run over the JSONC parse tree.
+//!
+//! Features:
+//! - Draft-07 and 2020-12 keywords: `$ref`/`$defs`/`definitions`, `allOf`/`anyOf`/`oneOf`/`not`,
+//!   `if`/`then`/`else`, `enum`/`const`, string, number, array and object constraints
+//! - `oneOf`/`anyOf` report the problems of the best-matching alternative only
+//! - VS Code extensions: `deprecationMessage`, `errorMessage`, `patternErrorMessage`
+//! - Problems carry byte ranges; diagnostics convert them to UTF-16 offsets and 1-based
+//!   line/column for squiggles
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use regex::Regex;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::json_tree::{parse_tree, JsonTree, NodeKind, Utf16Offsets};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub offset: usize,
+    pub length: usize,
+    pub message: String,
+    pub severity: Severity,
+    pub deprecated: bool,
+}
+
+#[derive(Default)]
+struct ValidationResult {
+    problems: Vec<Problem>,
+    properties_matches: u32,
+    properties_value_matches: u32,
+    primary_value_matches: u32,
+    enum_value_match: bool,
+}
+
+impl ValidationResult {
+    fn has_problems(&self) -> bool {
+        !self.problems.is_empty()
+    }
+
+    fn warn(&mut self, offset: usize, length: usize, message: String) {
+        self.problems.push(Problem { offset, length, message, severity: Severity::Warning, deprecated: false });
+    }
+
+    fn merge(&mut self, other: ValidationResult) {
+        self.problems.extend(other.problems);
+    }
+
+    fn merge_property_match(&mut self, other: ValidationResult) {
+        self.properties_matches += 1;
+        if other.enum_value_match || (!other.has_problems() && other.properties_matches > 0) {
+            self.properties_value_matches += 1;
+        }
+        if other.enum_value_match && other.primary_value_matches > 0 {
+            self.primary_value_matches += 1;
+        }
+        self.merge(other);
+    }
+
+    /// Orders alternatives: no problems first, then enum matches, then the most matched
+    /// properties.
+    fn compare(&self, other: &ValidationResult) -> Ordering {
+        other.has_problems().cmp(&self.has_problems())
+            .then(self.enum_value_match.cmp(&other.enum_value_match))
+            .then(self.primary_value_matches.cmp(&other.primary_value_matches))
+            .then(self.properties_value_matches.cmp(&other.properties_value_matches))
+            .then(self.properties_matches.cmp(&other.properties_matches))
+    }
+}
+
+/// Numbers compare by value, so `1` equals `1.0` as in JavaScript.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => x.len() == y.len() && x.iter().zip(y).all(|(a, b)| values_equal(a, b)),
+        (Value::Object(x), Value::Object(y)) => x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| values_equal(v, w))),
+        _ => a == b,
+    }
+}
+
+fn is_integer(value: &Value) -> bool {
+    value.as_f64().is_some_and(|n| n.fract() == 0.0)
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 { format!("{}", n as i64) } else { n.to_string() }
+}
+
+fn message_or(schema: &Value, key: &str, fallback: impl FnOnce() -> String) -> String {
+    schema.get(key).or_else(|| schema.get("errorMessage")).and_then(Value::as_str).map(String::from).unwrap_or_else(fallback)
+}
+
+struct Validator<'a> {
+    tree: &'a JsonTree,
+    root_schema: &'a Value,
+    patterns: HashMap<String, Option<Regex>>,
+    /// (node, schema) pairs being validated, to stop `$ref` cycles.
+    active: HashSet<(usize, *const Value)>,
+}
+
+impl<'a> Validator<'a> {
+    fn regex(&mut self, pattern: &str) -> Option<Regex> {
+        self.patterns.entry(pattern.to_string()).or_insert_with(|| Regex::new(pattern).ok()).clone()
+    }
+
+    /// Resolves a local `$ref` (`#`, `#/definitions/x`, `#/$defs/x`, any JSON pointer).
+    fn resolve_ref(&self, reference: &str) -> Option<&'a Value> {
+        let pointer = reference.strip_prefix('#')?;
+        if pointer.is_empty() {
+            return Some(self.root_schema);
+        }
+        let decoded = pointer.replace("%25", "%").replace("%22", "\"");
+        self.root_schema.pointer(&decoded)
+    }
+
+    fn validate(&mut self, node: usize, schema: &'a Value, result: &mut ValidationResult) {
+        let n = self.tree.node(node);
+        let schema_map = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                result.warn(n.offset, n.length, "Matches a schema that is not allowed.".into());
+                return;
+            }
+            Value::Object(map) => map,
+            _ => return,
+        };
+        if !self.active.insert((node, schema as *const Value)) {
+            return;
+        }
+
+        if let Some(reference) = schema_map.get("$ref").and_then(Value::as_str) {
+            match self.resolve_ref(reference) {
+                Some(target) => self.validate(node, target, result),
+                None => result.warn(n.offset, 1, format!("$ref '{}' can not be resolved.", reference)),
+            }
+        }
+        self.validate_node(node, schema, result);
+        match n.kind {
+            NodeKind::Object => self.validate_object(node, schema, result),
+            NodeKind::Array => self.validate_array(node, schema, result),
+            NodeKind::String => self.validate_string(node, schema, result),
+            NodeKind::Number => self.validate_number(node, schema, result),
+            _ => {}
+        }
+        self.active.remove(&(node, schema as *const Value));
+    }
+
+    fn validate_node(&mut self, node: usize, schema: &'a Value, result: &mut ValidationResult) {
+        let n = self.tree.node(node);
+        let value = n.value.clone();
+        let type_name = n.kind.as_str();
+        let matches_type = |t: &str| t == type_name || (t == "integer" && n.kind == NodeKind::Number && value.as_ref().is_some_and(is_integer));
+
+        match schema.get("type") {
+            Some(Value::String(t)) if !matches_type(t) => {
+                result.warn(n.offset, n.length, message_or(schema, "errorMessage", || format!("Incorrect type. Expected \"{}\".", t)));
+            }
+            Some(Value::Array(types)) => {
+                let names: Vec<&str> = types.iter().filter_map(Value::as_str).collect();
+                if !names.iter().any(|t| matches_type(t)) {
+                    result.warn(n.offset, n.length, message_or(schema, "errorMessage", || format!("Incorrect type. Expected one of {}.", names.join(", "))));
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(Value::Array(all_of)) = schema.get("allOf") {
+            for sub in all_of {
+                let mut sub_result = ValidationResult::default();
+                self.validate(node, sub, &mut sub_result);
+                result.properties_matches += sub_result.properties_matches;
+                result.properties_value_matches += sub_result.properties_value_matches;
+                result.merge(sub_result);
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            let mut sub_result = ValidationResult::default();
+            self.validate(node, not, &mut sub_result);
+            if !sub_result.has_problems() {
+                result.warn(n.offset, n.length, message_or(schema, "errorMessage", || "Matches a schema that is not allowed.".into()));
+            }
+        }
+        if let Some(Value::Array(any_of)) = schema.get("anyOf") {
+            self.test_alternatives(node, any_of, false, result);
+        }
+        if let Some(Value::Array(one_of)) = schema.get("oneOf") {
+            self.test_alternatives(node, one_of, true, result);
+        }
+        if let Some(condition) = schema.get("if") {
+            let mut if_result = ValidationResult::default();
+            self.validate(node, condition, &mut if_result);
+            let branch = if if_result.has_problems() { schema.get("else") } else { schema.get("then") };
+            if let Some(branch) = branch {
+                let mut branch_result = ValidationResult::default();
+                self.validate(node, branch, &mut branch_result);
+                result.properties_matches += branch_result.properties_matches;
+                result.properties_value_matches += branch_result.properties_value_matches;
+                result.merge(branch_result);
+            }
+        }
+
+        if let Some(Value::Array(values)) = schema.get("enum") {
+            let actual = self.tree.value_of(node);
+            if values.iter().any(|v| values_equal(v, &actual)) {
+                result.enum_value_match = true;
+                result.primary_value_matches += 1;
+            } else {
+                result.warn(n.offset, n.length, message_or(schema, "errorMessage", || {
+                    let valid: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                    format!("Value is not accepted. Valid values: {}.", valid.join(", "))
+                }));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if values_equal(expected, &self.tree.value_of(node)) {
+                result.enum_value_match = true;
+                result.primary_value_matches += 1;
+            } else {
+                result.warn(n.offset, n.length, message_or(schema, "errorMessage", || format!("Value must be {}.", expected)));
+            }
+        }
+
+        let deprecation = schema.get("deprecationMessage").and_then(Value::as_str).map(String::from)
+            .or_else(|| (schema.get("deprecated") == Some(&Value::Bool(true))).then(|| "Value is deprecated.".to_string()));
+        if let (Some(message), Some(parent)) = (deprecation, n.parent) {
+            let p = self.tree.node(parent);
+            result.problems.push(Problem { offset: p.offset, length: p.length, message, severity: Severity::Warning, deprecated: true });
+        }
+    }
+
+    fn test_alternatives(&mut self, node: usize, alternatives: &'a [Value], max_one_match: bool, result: &mut ValidationResult) {
+        let mut matches = 0;
+        let mut best: Option<ValidationResult> = None;
+        for sub in alternatives {
+            let mut sub_result = ValidationResult::default();
+            self.validate(node, sub, &mut sub_result);
+            if !sub_result.has_problems() {
+                matches += 1;
+            }
+            best = Some(match best {
+                None => sub_result,
+                Some(mut current) => {
+                    if !max_one_match && !sub_result.has_problems() && !current.has_problems() {
+                        // Both match: keep the first, but count the properties the other matched.
+                        current.properties_matches += sub_result.properties_matches;
+                        current.properties_value_matches += sub_result.properties_value_matches;
+                        current
+                    } else {
+                        match sub_result.compare(&current) {
+                            Ordering::Greater => sub_result,
+                            Ordering::Equal => {
+                                current.enum_value_match |= sub_result.enum_value_match;
+                                current
+                            }
+                            Ordering::Less => current,
+                        }
+                    }
+                }
+            });
+        }
+        let n = self.tree.node(node);
+        if matches > 1 && max_one_match {
+            result.warn(n.offset, 1, "Matches multiple schemas when only one must validate.".into());
+        }
+        if let Some(best) = best {
+            result.properties_matches += best.properties_matches;
+            result.properties_value_matches += best.properties_value_matches;
+            result.enum_value_match |= best.enum_value_match;
+            result.merge(best);
+        }
+    }
+
+    fn validate_string(&mut self, node: usize, schema: &'a Value, result: &mut ValidationResult) {
+        let n = self.tree.node(node);
+        let Some(Value::String(text)) = &n.value else { return };
+        let length = text.chars().count() as f64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_f64).filter(|min| length < *min) {
+            result.warn(n.offset, n.length, format!("String is shorter than the minimum length of {}.", format_number(min)));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_f64).filter(|max| length > *max) {
+            result.warn(n.offset, n.length, format!("String is longer than the maximum length of {}.", format_number(max)));
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str)
+            && let Some(re) = self.regex(pattern)
+            && !re.is_match(text)
+        {
+            result.warn(n.offset, n.length, message_or(schema, "patternErrorMessage", || format!("String does not match the pattern of \"{}\".", pattern)));
+        }
+    }
+
+    fn validate_number(&mut self, node: usize, schema: &'a Value, result: &mut ValidationResult) {
+        let n = self.tree.node(node);
+        let Some(value) = n.value.as_ref().and_then(Value::as_f64) else { return };
+        let get = |key: &str| schema.get(key).and_then(Value::as_f64);
+        if let Some(divisor) = get("multipleOf").filter(|d| *d != 0.0 && (value / d).fract().abs() > 1e-9) {
+            result.warn(n.offset, n.length, format!("Value is not divisible by {}.", format_number(divisor)));
+        }
+        // Draft-04 used boolean `exclusiveMinimum`/`exclusiveMaximum` modifiers.
+        let exclusive_flag = |key: &str| schema.get(key) == Some(&Value::Bool(true));
+        let (mut minimum, mut maximum) = (get("minimum"), get("maximum"));
+        let mut exclusive_minimum = get("exclusiveMinimum");
+        let mut exclusive_maximum = get("exclusiveMaximum");
+        if exclusive_flag("exclusiveMinimum") {
+            exclusive_minimum = minimum.take();
+        }
+        if exclusive_flag("exclusiveMaximum") {
+            exclusive_maximum = maximum.take();
+        }
+        if let Some(limit) = exclusive_minimum.filter(|l| value <= *l) {
+            result.warn(n.offset, n.length, format!("Value is below the exclusive minimum of {}.", format_number(limit)));
+        }
+        if let Some(limit) = exclusive_maximum.filter(|l| value >= *l) {
+            result.warn(n.offset, n.length, format!("Value is above the exclusive maximum of {}.", format_number(limit)));
+        }
+        if let Some(limit) = minimum.filter(|l| value < *l) {
+            result.warn(n.offset, n.length, format!("Value is below the minimum of {}.", format_number(limit)));
+        }
+        if let Some(limit) = maximum.filter(|l| value > *l) {
+            result.warn(n.offset, n.length, format!("Value is above the maximum of {}.", format_number(limit)));
+        }
+    }
+
+    fn validate_array(&mut self, node: usize, schema: &'a Value, result: &mut ValidationResult) {
+        let n = self.tree.node(node);
+        let items = n.children.clone();
+        // 2020-12 `prefixItems` + `items`, or draft-07 tuple `items` + `additionalItems`.
+        let (prefix, rest) = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(Value::Array(prefix)), rest) => (Some(prefix), rest),
+            (None, Some(Value::Array(tuple))) => (Some(tuple), schema.get("additionalItems")),
+            (_, rest) => (None, rest),
+        };
+        let prefix_len = prefix.map_or(0, Vec::len);
+        for (index, &item) in items.iter().enumerate() {
+            let item_schema = match prefix.and_then(|p| p.get(index)) {
+                Some(s) => s,
+                None => match rest {
+                    Some(Value::Bool(false)) if index >= prefix_len => {
+                        let i = self.tree.node(item);
+                        result.warn(i.offset, i.length, format!("Array has too many items according to schema. Expected {} or fewer.", prefix_len));
+                        continue;
+                    }
+                    Some(s) => s,
+                    None => continue,
+                },
+            };
+            let mut item_result = ValidationResult::default();
+            self.validate(item, item_schema, &mut item_result);
+            result.merge_property_match(item_result);
+        }
+        if let Some(contains) = schema.get("contains") {
+            let found = items.iter().any(|&item| {
+                let mut r = ValidationResult::default();
+                self.validate(item, contains, &mut r);
+                !r.has_problems()
+            });
+            if !found {
+                result.warn(n.offset, n.length, message_or(schema, "errorMessage", || "Array does not contain required item.".into()));
+            }
+        }
+        let count = items.len() as f64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_f64).filter(|m| count < *m) {
+            result.warn(n.offset, n.length, format!("Array has too few items. Expected {} or more.", format_number(min)));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_f64).filter(|m| count > *m) {
+            result.warn(n.offset, n.length, format!("Array has too many items. Expected {} or fewer.", format_number(max)));
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let values: Vec<Value> = items.iter().map(|&i| self.tree.value_of(i)).collect();
+            let duplicate = values.iter().enumerate().any(|(i, v)| values[..i].iter().any(|w| values_equal(v, w)));
+            if duplicate {
+                result.warn(n.offset, n.length, "Array has duplicate items.".into());
+            }
+        }
+    }
+
+    fn validate_object(&mut self, node: usize, schema: &'a Value, result: &mut ValidationResult) {
+        let n = self.tree.node(node);
+        // (key, key node, value node) for every complete property.
+        let mut properties: Vec<(String, usize, Option<usize>)> = Vec::new();
+        for &property in &n.children {
+            let children = &self.tree.node(property).children;
+            if let Some(&key) = children.first()
+                && let Some(Value::String(name)) = &self.tree.node(key).value
+            {
+                properties.push((name.clone(), key, children.get(1).copied()));
+            }
+        }
+        let present: HashSet<&str> = properties.iter().map(|(k, _, _)| k.as_str()).collect();
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !present.contains(name) {
+                    // Point at the property key when the object is a property value.
+                    let (offset, length) = match n.parent.map(|p| self.tree.node(p)) {
+                        Some(p) if p.kind == NodeKind::Property => {
+                            let key = self.tree.node(p.children[0]);
+                            (key.offset, key.length)
+                        }
+                        _ => (n.offset, 1),
+                    };
+                    result.warn(offset, length, format!("Missing property \"{}\".", name));
+                }
+            }
+        }
+
+        let mut unprocessed: Vec<bool> = vec![true; properties.len()];
+        if let Some(Value::Object(declared)) = schema.get("properties") {
+            for (index, (name, _, value)) in properties.iter().enumerate() {
+                let Some(property_schema) = declared.get(name) else { continue };
+                unprocessed[index] = false;
+                if let Some(value) = *value {
+                    match property_schema {
+                        Value::Bool(false) => {
+                            let p = self.tree.node(self.tree.node(value).parent.unwrap_or(value));
+                            result.warn(p.offset, p.length, message_or(schema, "errorMessage", || format!("Property {} is not allowed.", name)));
+                        }
+                        _ => {
+                            let mut property_result = ValidationResult::default();
+                            self.validate(value, property_schema, &mut property_result);
+                            result.merge_property_match(property_result);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(Value::Object(patterns)) = schema.get("patternProperties") {
+            for (pattern, property_schema) in patterns {
+                let Some(re) = self.regex(pattern) else { continue };
+                for (index, (name, _, value)) in properties.iter().enumerate() {
+                    if !re.is_match(name) {
+                        continue;
+                    }
+                    unprocessed[index] = false;
+                    if let Some(value) = *value {
+                        let mut property_result = ValidationResult::default();
+                        self.validate(value, property_schema, &mut property_result);
+                        result.merge_property_match(property_result);
+                    }
+                }
+            }
+        }
+        if let Some(additional) = schema.get("additionalProperties") {
+            for (index, (name, key, value)) in properties.iter().enumerate() {
+                if !unprocessed[index] {
+                    continue;
+                }
+                match additional {
+                    Value::Bool(false) => {
+                        let k = self.tree.node(*key);
+                        result.warn(k.offset, k.length, message_or(schema, "errorMessage", || format!("Property {} is not allowed.", name)));
+                    }
+                    Value::Object(_) => {
+                        if let Some(value) = *value {
+                            let mut property_result = ValidationResult::default();
+                            self.validate(value, additional, &mut property_result);
+                            result.merge_property_match(property_result);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(names_schema) = schema.get("propertyNames") {
+            for (_, key, _) in &properties {
+                let mut key_result = ValidationResult::default();
+                self.validate(*key, names_schema, &mut key_result);
+                result.merge(key_result);
+            }
+        }
+
+        let count = properties.len() as f64;
+        if let Some(max) = schema.get("maxProperties").and_then(Value::as_f64).filter(|m| count > *m) {
+            result.warn(n.offset, n.length, format!("Object has more properties than limit of {}.", format_number(max)));
+        }
+        if let Some(min) = schema.get("minProperties").and_then(Value::as_f64).filter(|m| count < *m) {
+            result.warn(n.offset, n.length, format!("Object has fewer properties than the required number of {}.", format_number(min)));
+        }
+
+        // Draft-07 `dependencies` (array form or schema form) and 2019-09 `dependentRequired`/`dependentSchemas`.
+        let mut dependencies: Vec<(&'a String, &'a Value)> = Vec::new();
+        for key in ["dependencies", "dependentRequired", "dependentSchemas"] {
+            if let Some(Value::Object(map)) = schema.get(key) {
+                dependencies.extend(map.iter());
+            }
+        }
+        for (trigger, dependency) in dependencies {
+            if !present.contains(trigger.as_str()) {
+                continue;
+            }
+            match dependency {
+                Value::Array(required) => {
+                    for name in required.iter().filter_map(Value::as_str) {
+                        if !present.contains(name) {
+                            result.warn(n.offset, n.length, format!("Object is missing property {} required by property {}.", name, trigger));
+                        }
+                    }
+                }
+                _ => {
+                    let mut dependency_result = ValidationResult::default();
+                    self.validate(node, dependency, &mut dependency_result);
+                    result.merge_property_match(dependency_result);
+                }
+            }
+        }
+    }
+}
+
+/// Problems for duplicate keys, which the schema keywords cannot see.
+fn duplicate_key_problems(tree: &JsonTree) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for node in tree.nodes.iter().skip(1).filter(|n| n.kind == NodeKind::Object) {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for &property in &node.children {
+            let Some(&key) = tree.node(property).children.first() else { continue };
+            let k = tree.node(key);
+            if let Some(Value::String(name)) = &k.value
+                && let Some(first) = seen.insert(name, key)
+            {
+                for id in [first, key] {
+                    let d = tree.node(id);
+                    let problem = Problem { offset: d.offset, length: d.length, message: "Duplicate object key".into(), severity: Severity::Warning, deprecated: false };
+                    if !problems.contains(&problem) {
+                        problems.push(problem);
+                    }
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Validates a parsed document against `schema`, returning problems in document order.
+pub fn validate_tree(tree: &JsonTree, schema: &Value) -> Vec<Problem> {
+    let mut problems = duplicate_key_problems(tree);
+    if let Some(root) = tree.root {
+        let mut validator = Validator { tree, root_schema: schema, patterns: HashMap::new(), active: HashSet::new() };
+        let mut result = ValidationResult::default();
+        validator.validate(root, schema, &mut result);
+        for problem in result.problems {
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+    problems.sort_by_key(|p| p.offset);
+    problems
+}
+
+pub fn parse_error_message(code: &str) -> &'static str {
+    match code {
+        "InvalidSymbol" => "Invalid symbol",
+        "InvalidNumberFormat" => "Invalid number format",
+        "PropertyNameExpected" => "Property name expected",
+        "ValueExpected" => "Value expected",
+        "ColonExpected" => "Colon expected",
+        "CommaExpected" => "Comma expected",
+        "CloseBraceExpected" => "Closing brace expected",
+        "CloseBracketExpected" => "Closing bracket expected",
+        "EndOfFileExpected" => "End of file expected",
+        "UnexpectedEndOfComment" => "Unexpected end of comment",
+        "UnexpectedEndOfString" => "Unexpected end of string",
+        "UnexpectedEndOfNumber" => "Unexpected end of number",
+        "InvalidUnicode" => "Invalid unicode sequence in string",
+        "InvalidEscapeCharacter" => "Invalid escape character in string",
+        "InvalidCharacter" => "Invalid characters in string. Control characters must be escaped.",
+        _ => "Syntax error",
+    }
+}
+
+// ─── NAPI surface ──────────────────────────────────────────────────────────
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct JsonDiagnostic {
+    pub message: String,
+    /// "error" for syntax errors, "warning" for schema problems.
+    pub severity: String,
+    /// Range in UTF-16 code units, like JS string indices.
+    pub offset: u32,
+    pub length: u32,
+    /// 1-based positions; columns count UTF-16 code units, as Monaco does.
+    pub line: u32,
+    pub column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+    pub deprecated: bool,
+}
+
+fn position_at(offsets: &Utf16Offsets, line_starts: &[usize], offset: usize) -> (u32, u32) {
+    let line = line_starts.partition_point(|&s| s <= offset) - 1;
+    let column = offsets.to_utf16(offset) - offsets.to_utf16(line_starts[line]);
+    (line as u32 + 1, column as u32 + 1)
+}
+
+/// Syntax errors and schema problems for `text` as positioned diagnostics.
+pub fn diagnose(text: &str, schema: Option<&Value>) -> Vec<JsonDiagnostic> {
+    let tree = parse_tree(text);
+    let mut problems: Vec<Problem> = tree.errors.iter().map(|e| Problem {
+        offset: e.offset,
+        length: e.length,
+        message: parse_error_message(e.error).to_string(),
+        severity: Severity::Error,
+        deprecated: false,
+    }).collect();
+    if let Some(schema) = schema {
+        problems.extend(validate_tree(&tree, schema));
+    }
+    let line_starts: Vec<usize> = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let offsets = Utf16Offsets::new(text);
+    problems.into_iter().map(|p| {
+        let (line, column) = position_at(&offsets, &line_starts, p.offset);
+        let (end_line, end_column) = position_at(&offsets, &line_starts, p.offset + p.length);
+        let (offset, length) = offsets.range_to_utf16(p.offset, p.length);
+        JsonDiagnostic {
+            message: p.message,
+            severity: if p.severity == Severity::Error { "error" } else { "warning" }.to_string(),
+            offset,
+            length,
+            line,
+            column,
+            end_line,
+            end_column,
+            deprecated: p.deprecated,
+        }
+    }).collect()
+}
+
+/// Validates JSONC `text` against a JSON Schema given as JSON.
+#[napi]
+pub fn json_validate(text: String, schema: String) -> Result<Vec<JsonDiagnostic>> {
+    let schema: Value = serde_json::from_str(&schema).map_err(|e| Error::from_reason(format!("Invalid schema: {}", e)))?;
+    Ok(diagnose(&text, Some(&schema)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn messages(text: &str, schema: Value) -> Vec<(String, String)> {
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        diagnose(text, Some(&schema)).into_iter().map(|d| (String::from_utf16_lossy(&utf16[d.offset as usize..(d.offset + d.length) as usize]), d.message)).collect()
+    }
+
+    #[test]
+    fn test_types_enum_pattern_and_additional_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "editor.fontSize": { "type": "number", "minimum": 6 },
+                "editor.wordWrap": { "enum": ["on", "off"] },
+                "files.eol": { "type": "string", "pattern": "^(\\n|\\r\\n)$", "patternErrorMessage": "Must be a line ending" },
+                "old.setting": { "deprecationMessage": "Use new.setting" }
+            },
+            "patternProperties": { "^\\[.+\\]$": { "type": "object" } },
+            "additionalProperties": false
+        });
+        let text = "{\n  // user settings\n  \"editor.fontSize\": \"12\",\n  \"editor.wordWrap\": \"maybe\",\n  \"files.eol\": \"x\",\n  \"old.setting\": 1,\n  \"[python]\": {},\n  \"bogus\": true,\n}";
+        assert_eq!(messages(text, schema), vec![
+            ("\"12\"".into(), "Incorrect type. Expected \"number\".".into()),
+            ("\"maybe\"".into(), "Value is not accepted. Valid values: \"on\", \"off\".".into()),
+            ("\"x\"".into(), "Must be a line ending".into()),
+            ("\"old.setting\": 1".into(), "Use new.setting".into()),
+            ("\"bogus\"".into(), "Property bogus is not allowed.".into()),
+        ]);
+        let diagnostics = diagnose(text, Some(&json!({ "properties": { "editor.fontSize": { "type": "number" } } })));
+        assert_eq!((diagnostics[0].line, diagnostics[0].column, diagnostics[0].end_column), (3, 22, 26));
+
+        // Offsets and columns are UTF-16: the emoji counts twice.
+        let text = "{ \"😀 é\": 1, \"size\": \"big\" }";
+        let diagnostics = diagnose(text, Some(&json!({ "properties": { "size": { "type": "number" } } })));
+        assert_eq!((diagnostics[0].offset, diagnostics[0].column, diagnostics[0].end_column), (21, 22, 27));
+        assert_eq!(messages(text, json!({ "properties": { "size": { "type": "number" } } }))[0].0, "\"big\"");
+    }
+
+    #[test]
+    fn test_refs_and_alternatives() {
+        let schema = json!({
+            "$defs": { "port": { "type": "integer", "maximum": 65535 } },
+            "definitions": {
+                "server": {
+                    "oneOf": [
+                        { "properties": { "kind": { "const": "tcp" }, "port": { "$ref": "#/$defs/port" } }, "required": ["kind", "port"] },
+                        { "properties": { "kind": { "const": "pipe" }, "path": { "type": "string" } }, "required": ["kind", "path"] }
+                    ]
+                }
+            },
+            "type": "array",
+            "items": { "$ref": "#/definitions/server" }
+        });
+        let text = r#"[{"kind": "tcp", "port": 70000}, {"kind": "pipe", "path": "/tmp/s"}, {"kind": "pipe"}]"#;
+        assert_eq!(messages(text, schema), vec![
+            ("70000".into(), "Value is above the maximum of 65535.".into()),
+            ("{".into(), "Missing property \"path\".".into()),
+        ]);
+        assert_eq!(messages("1", json!({ "anyOf": [{ "type": "string" }, { "type": "boolean" }] })), vec![("1".into(), "Incorrect type. Expected \"string\".".into())]);
+        assert_eq!(messages("{\"a\": 1, \"a\": 2}", json!({ "$ref": "#" })).len(), 2);
+        assert_eq!(diagnose("{\"a\" 1}", None)[0].severity, "error");
+    }
+}
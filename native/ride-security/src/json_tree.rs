@@ -0,0 +1,852 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! JSONC scanner and parse tree — Rust port of `createScanner`, `parseTree` and
+//! `findNodeAtLocation` from `src/vs/base/common/json.ts`.
+//!
+//! The tree keeps the byte offset and length of every node so edits can target the exact
+//! source text; comments and trailing commas are accepted and errors are collected with
+//! their positions instead of aborting the parse.
+
+use napi_derive::napi;
+use serde_json::Value;
+
+// ─── Scanner ───────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyntaxKind {
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Colon,
+    Null,
+    True,
+    False,
+    String,
+    Number,
+    LineComment,
+    BlockComment,
+    LineBreak,
+    Trivia,
+    Unknown,
+    Eof,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanError {
+    None,
+    UnexpectedEndOfComment,
+    UnexpectedEndOfString,
+    UnexpectedEndOfNumber,
+    InvalidUnicode,
+    InvalidEscapeCharacter,
+    InvalidCharacter,
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\u{0B}' | '\u{0C}' | '\u{A0}' | '\u{1680}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' | '\u{FEFF}')
+}
+
+fn is_line_break(c: char) -> bool {
+    c == '\n' || c == '\r'
+}
+
+fn is_unknown_content(c: char) -> bool {
+    !is_whitespace(c) && !is_line_break(c) && !matches!(c, '{' | '}' | '[' | ']' | ',' | ':' | '"' | '/')
+}
+
+pub struct Scanner<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    token: SyntaxKind,
+    token_offset: usize,
+    value: String,
+    error: ScanError,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { text, bytes: text.as_bytes(), pos: 0, token: SyntaxKind::Unknown, token_offset: 0, value: String::new(), error: ScanError::None }
+    }
+
+    pub fn token(&self) -> SyntaxKind {
+        self.token
+    }
+
+    pub fn token_offset(&self) -> usize {
+        self.token_offset
+    }
+
+    pub fn token_length(&self) -> usize {
+        self.pos - self.token_offset
+    }
+
+    pub fn token_value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn token_error(&self) -> ScanError {
+        self.error
+    }
+
+    fn char_at(&self, pos: usize) -> Option<char> {
+        self.text.get(pos..).and_then(|s| s.chars().next())
+    }
+
+    fn is_digit_at(&self, pos: usize) -> bool {
+        self.bytes.get(pos).is_some_and(u8::is_ascii_digit)
+    }
+
+    fn scan_hex_digits(&mut self, count: usize) -> Option<u32> {
+        let digits = self.text.get(self.pos..self.pos + count)?;
+        let value = u32::from_str_radix(digits, 16).ok().filter(|_| digits.bytes().all(|b| b.is_ascii_hexdigit()))?;
+        self.pos += count;
+        Some(value)
+    }
+
+    fn scan_number(&mut self) -> String {
+        let start = self.pos;
+        if self.bytes[self.pos] == b'0' {
+            self.pos += 1;
+        } else {
+            self.pos += 1;
+            while self.is_digit_at(self.pos) {
+                self.pos += 1;
+            }
+        }
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if self.is_digit_at(self.pos) {
+                while self.is_digit_at(self.pos) {
+                    self.pos += 1;
+                }
+            } else {
+                self.error = ScanError::UnexpectedEndOfNumber;
+                return self.text[start..self.pos].to_string();
+            }
+        }
+        let mut end = self.pos;
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if self.is_digit_at(self.pos) {
+                while self.is_digit_at(self.pos) {
+                    self.pos += 1;
+                }
+                end = self.pos;
+            } else {
+                self.error = ScanError::UnexpectedEndOfNumber;
+            }
+        }
+        self.text[start..end].to_string()
+    }
+
+    fn scan_string(&mut self) -> String {
+        let mut result = String::new();
+        let mut start = self.pos;
+        loop {
+            let Some(&ch) = self.bytes.get(self.pos) else {
+                result.push_str(&self.text[start..self.pos]);
+                self.error = ScanError::UnexpectedEndOfString;
+                break;
+            };
+            if ch == b'"' {
+                result.push_str(&self.text[start..self.pos]);
+                self.pos += 1;
+                break;
+            }
+            if ch == b'\\' {
+                result.push_str(&self.text[start..self.pos]);
+                self.pos += 1;
+                let Some(&escaped) = self.bytes.get(self.pos) else {
+                    self.error = ScanError::UnexpectedEndOfString;
+                    break;
+                };
+                self.pos += 1;
+                match escaped {
+                    b'"' => result.push('"'),
+                    b'\\' => result.push('\\'),
+                    b'/' => result.push('/'),
+                    b'b' => result.push('\u{08}'),
+                    b'f' => result.push('\u{0C}'),
+                    b'n' => result.push('\n'),
+                    b'r' => result.push('\r'),
+                    b't' => result.push('\t'),
+                    b'u' => match self.scan_hex_digits(4) {
+                        Some(high @ 0xD800..=0xDBFF) if self.text[self.pos..].starts_with("\\u") => {
+                            let save = self.pos;
+                            self.pos += 2;
+                            match self.scan_hex_digits(4) {
+                                Some(low @ 0xDC00..=0xDFFF) => {
+                                    let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                                    result.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                                }
+                                _ => {
+                                    self.pos = save;
+                                    result.push('\u{FFFD}');
+                                }
+                            }
+                        }
+                        Some(code) => result.push(char::from_u32(code).unwrap_or('\u{FFFD}')),
+                        None => self.error = ScanError::InvalidUnicode,
+                    },
+                    _ => self.error = ScanError::InvalidEscapeCharacter,
+                }
+                start = self.pos;
+                continue;
+            }
+            if ch <= 0x1F {
+                if ch == b'\n' || ch == b'\r' {
+                    result.push_str(&self.text[start..self.pos]);
+                    self.error = ScanError::UnexpectedEndOfString;
+                    break;
+                }
+                self.error = ScanError::InvalidCharacter;
+            }
+            self.pos += 1;
+        }
+        result
+    }
+
+    /// Scans the next token, including trivia and comments.
+    pub fn scan(&mut self) -> SyntaxKind {
+        self.value.clear();
+        self.error = ScanError::None;
+        self.token_offset = self.pos;
+        self.token = self.scan_next();
+        self.token
+    }
+
+    fn scan_next(&mut self) -> SyntaxKind {
+        let Some(c) = self.char_at(self.pos) else { return SyntaxKind::Eof };
+        if is_whitespace(c) {
+            while let Some(c) = self.char_at(self.pos).filter(|c| is_whitespace(*c)) {
+                self.value.push(c);
+                self.pos += c.len_utf8();
+            }
+            return SyntaxKind::Trivia;
+        }
+        if is_line_break(c) {
+            self.pos += 1;
+            self.value.push(c);
+            if c == '\r' && self.bytes.get(self.pos) == Some(&b'\n') {
+                self.pos += 1;
+                self.value.push('\n');
+            }
+            return SyntaxKind::LineBreak;
+        }
+        let single = |kind| (kind, c);
+        let punct = match c {
+            '{' => Some(single(SyntaxKind::OpenBrace)),
+            '}' => Some(single(SyntaxKind::CloseBrace)),
+            '[' => Some(single(SyntaxKind::OpenBracket)),
+            ']' => Some(single(SyntaxKind::CloseBracket)),
+            ':' => Some(single(SyntaxKind::Colon)),
+            ',' => Some(single(SyntaxKind::Comma)),
+            _ => None,
+        };
+        if let Some((kind, c)) = punct {
+            self.pos += 1;
+            self.value.push(c);
+            return kind;
+        }
+        match c {
+            '"' => {
+                self.pos += 1;
+                self.value = self.scan_string();
+                SyntaxKind::String
+            }
+            '/' => {
+                let start = self.pos;
+                match self.bytes.get(self.pos + 1) {
+                    Some(b'/') => {
+                        self.pos += 2;
+                        while self.bytes.get(self.pos).is_some_and(|b| *b != b'\n' && *b != b'\r') {
+                            self.pos += 1;
+                        }
+                        self.value = self.text[start..self.pos].to_string();
+                        SyntaxKind::LineComment
+                    }
+                    Some(b'*') => {
+                        self.pos += 2;
+                        match self.text[self.pos..].find("*/") {
+                            Some(end) => self.pos += end + 2,
+                            None => {
+                                self.pos = self.bytes.len();
+                                self.error = ScanError::UnexpectedEndOfComment;
+                            }
+                        }
+                        self.value = self.text[start..self.pos].to_string();
+                        SyntaxKind::BlockComment
+                    }
+                    _ => {
+                        self.pos += 1;
+                        self.value.push('/');
+                        SyntaxKind::Unknown
+                    }
+                }
+            }
+            '-' => {
+                self.value.push('-');
+                self.pos += 1;
+                if !self.is_digit_at(self.pos) {
+                    return SyntaxKind::Unknown;
+                }
+                let number = self.scan_number();
+                self.value.push_str(&number);
+                SyntaxKind::Number
+            }
+            '0'..='9' => {
+                self.value = self.scan_number();
+                SyntaxKind::Number
+            }
+            _ => {
+                while let Some(c) = self.char_at(self.pos).filter(|c| is_unknown_content(*c)) {
+                    self.pos += c.len_utf8();
+                }
+                if self.token_offset != self.pos {
+                    self.value = self.text[self.token_offset..self.pos].to_string();
+                    return match self.value.as_str() {
+                        "true" => SyntaxKind::True,
+                        "false" => SyntaxKind::False,
+                        "null" => SyntaxKind::Null,
+                        _ => SyntaxKind::Unknown,
+                    };
+                }
+                self.value.push(c);
+                self.pos += c.len_utf8();
+                SyntaxKind::Unknown
+            }
+        }
+    }
+}
+
+// ─── Parse tree ────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeKind {
+    Object,
+    Array,
+    Property,
+    String,
+    Number,
+    Boolean,
+    Null,
+}
+
+impl NodeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NodeKind::Object => "object",
+            NodeKind::Array => "array",
+            NodeKind::Property => "property",
+            NodeKind::String => "string",
+            NodeKind::Number => "number",
+            NodeKind::Boolean => "boolean",
+            NodeKind::Null => "null",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub offset: usize,
+    pub length: usize,
+    pub colon_offset: Option<usize>,
+    /// Literal value for string, number, boolean and null nodes.
+    pub value: Option<Value>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// `ParseErrorCode` name from VS Code, e.g. "CommaExpected".
+    pub error: &'static str,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Arena-allocated parse tree; node ids index into `nodes`.
+#[derive(Clone, Debug, Default)]
+pub struct JsonTree {
+    pub nodes: Vec<Node>,
+    pub root: Option<usize>,
+    pub errors: Vec<ParseError>,
+}
+
+impl JsonTree {
+    pub fn node(&self, id: usize) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// Follows `path` from `from` through object properties and array items.
+    pub fn find_at_location(&self, from: Option<usize>, path: &[Segment]) -> Option<usize> {
+        let mut node = from?;
+        for segment in path {
+            let current = &self.nodes[node];
+            node = match segment {
+                Segment::Key(key) => {
+                    if current.kind != NodeKind::Object {
+                        return None;
+                    }
+                    current.children.iter().find_map(|&property| {
+                        let children = &self.nodes[property].children;
+                        let matches = children.len() == 2 && self.nodes[children[0]].value.as_ref().and_then(Value::as_str) == Some(key.as_str());
+                        matches.then_some(children[1])
+                    })?
+                }
+                Segment::Index(index) => {
+                    if current.kind != NodeKind::Array {
+                        return None;
+                    }
+                    *current.children.get(*index)?
+                }
+            };
+        }
+        Some(node)
+    }
+
+    /// The JSON value of a node (`getNodeValue`).
+    pub fn value_of(&self, id: usize) -> Value {
+        let node = &self.nodes[id];
+        match node.kind {
+            NodeKind::Array => Value::Array(node.children.iter().map(|&c| self.value_of(c)).collect()),
+            NodeKind::Object => {
+                let mut map = serde_json::Map::new();
+                for &property in &node.children {
+                    let children = &self.nodes[property].children;
+                    if let (Some(&key), Some(&value)) = (children.first(), children.get(1))
+                        && let Some(Value::String(key)) = &self.nodes[key].value
+                    {
+                        map.insert(key.clone(), self.value_of(value));
+                    }
+                }
+                Value::Object(map)
+            }
+            _ => node.value.clone().unwrap_or(Value::Null),
+        }
+    }
+
+    /// Path from the root to a value node (`getNodePath`).
+    pub fn path_of(&self, id: usize) -> Vec<Segment> {
+        let mut path = Vec::new();
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            let p = &self.nodes[parent];
+            match p.kind {
+                NodeKind::Property => {
+                    if let Some(Value::String(key)) = p.children.first().and_then(|&k| self.nodes[k].value.as_ref()) {
+                        path.push(Segment::Key(key.clone()));
+                    }
+                    node = p.parent.unwrap_or(parent);
+                    if node == parent {
+                        break;
+                    }
+                }
+                NodeKind::Array => {
+                    if let Some(index) = p.children.iter().position(|&c| c == node) {
+                        path.push(Segment::Index(index));
+                    }
+                    node = parent;
+                }
+                _ => node = parent,
+            }
+        }
+        path.reverse();
+        path
+    }
+
+    fn push(&mut self, kind: NodeKind, offset: usize, length: usize, value: Option<Value>, parent: usize) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Node { kind, offset, length, colon_offset: None, value, parent: Some(parent), children: Vec::new() });
+        self.nodes[parent].children.push(id);
+        id
+    }
+}
+
+struct Parser<'a> {
+    scanner: Scanner<'a>,
+    tree: JsonTree,
+    current: usize,
+}
+
+impl Parser<'_> {
+    /// Next significant token; comments and trivia are skipped, scan errors recorded.
+    fn scan_next(&mut self) -> SyntaxKind {
+        loop {
+            let token = self.scanner.scan();
+            let error = match self.scanner.token_error() {
+                ScanError::InvalidUnicode => Some("InvalidUnicode"),
+                ScanError::InvalidEscapeCharacter => Some("InvalidEscapeCharacter"),
+                ScanError::UnexpectedEndOfNumber => Some("UnexpectedEndOfNumber"),
+                ScanError::UnexpectedEndOfComment => Some("UnexpectedEndOfComment"),
+                ScanError::UnexpectedEndOfString => Some("UnexpectedEndOfString"),
+                ScanError::InvalidCharacter => Some("InvalidCharacter"),
+                ScanError::None => None,
+            };
+            if let Some(error) = error {
+                self.error(error, &[], &[]);
+            }
+            match token {
+                SyntaxKind::LineComment | SyntaxKind::BlockComment | SyntaxKind::Trivia | SyntaxKind::LineBreak => {}
+                SyntaxKind::Unknown => self.error("InvalidSymbol", &[], &[]),
+                _ => return token,
+            }
+        }
+    }
+
+    fn error(&mut self, error: &'static str, skip_until_after: &[SyntaxKind], skip_until: &[SyntaxKind]) {
+        self.tree.errors.push(ParseError { error, offset: self.scanner.token_offset(), length: self.scanner.token_length() });
+        if skip_until_after.is_empty() && skip_until.is_empty() {
+            return;
+        }
+        let mut token = self.scanner.token();
+        while token != SyntaxKind::Eof {
+            if skip_until_after.contains(&token) {
+                self.scan_next();
+                break;
+            } else if skip_until.contains(&token) {
+                break;
+            }
+            token = self.scan_next();
+        }
+    }
+
+    fn ensure_property_complete(&mut self, end: usize) {
+        let node = &mut self.tree.nodes[self.current];
+        if node.kind == NodeKind::Property {
+            node.length = end - node.offset;
+            self.current = node.parent.unwrap_or(0);
+        }
+    }
+
+    fn on_literal(&mut self, kind: NodeKind, value: Value) {
+        let (offset, length) = (self.scanner.token_offset(), self.scanner.token_length());
+        self.tree.push(kind, offset, length, Some(value), self.current);
+        self.ensure_property_complete(offset + length);
+    }
+
+    fn on_separator(&mut self) {
+        let offset = self.scanner.token_offset();
+        let is_colon = self.scanner.token() == SyntaxKind::Colon;
+        let node = &mut self.tree.nodes[self.current];
+        if node.kind == NodeKind::Property {
+            if is_colon {
+                node.colon_offset = Some(offset);
+            } else {
+                self.ensure_property_complete(offset);
+            }
+        }
+    }
+
+    fn on_container_begin(&mut self, kind: NodeKind) {
+        self.current = self.tree.push(kind, self.scanner.token_offset(), 0, None, self.current);
+    }
+
+    fn on_container_end(&mut self) {
+        let end = self.scanner.token_offset() + self.scanner.token_length();
+        self.ensure_property_complete(end);
+        let node = &mut self.tree.nodes[self.current];
+        node.length = end - node.offset;
+        self.current = node.parent.unwrap_or(0);
+        self.ensure_property_complete(end);
+    }
+
+    fn parse_literal(&mut self) -> bool {
+        match self.scanner.token() {
+            SyntaxKind::Number => {
+                let text = self.scanner.token_value().to_string();
+                let value = serde_json::from_str::<Value>(&text).ok().filter(Value::is_number)
+                    .or_else(|| text.parse::<f64>().ok().and_then(serde_json::Number::from_f64).map(Value::Number));
+                let value = match value {
+                    Some(v) => v,
+                    None => {
+                        self.error("InvalidNumberFormat", &[], &[]);
+                        Value::from(0)
+                    }
+                };
+                self.on_literal(NodeKind::Number, value);
+            }
+            SyntaxKind::Null => self.on_literal(NodeKind::Null, Value::Null),
+            SyntaxKind::True => self.on_literal(NodeKind::Boolean, Value::Bool(true)),
+            SyntaxKind::False => self.on_literal(NodeKind::Boolean, Value::Bool(false)),
+            _ => return false,
+        }
+        self.scan_next();
+        true
+    }
+
+    fn parse_property(&mut self) -> bool {
+        if self.scanner.token() != SyntaxKind::String {
+            self.error("PropertyNameExpected", &[], &[SyntaxKind::CloseBrace, SyntaxKind::Comma]);
+            return false;
+        }
+        let offset = self.scanner.token_offset();
+        self.current = self.tree.push(NodeKind::Property, offset, 0, None, self.current);
+        let key = Value::String(self.scanner.token_value().to_string());
+        self.tree.push(NodeKind::String, offset, self.scanner.token_length(), Some(key), self.current);
+        self.scan_next();
+        if self.scanner.token() == SyntaxKind::Colon {
+            self.on_separator();
+            self.scan_next();
+            if !self.parse_value() {
+                self.error("ValueExpected", &[], &[SyntaxKind::CloseBrace, SyntaxKind::Comma]);
+            }
+        } else {
+            self.error("ColonExpected", &[], &[SyntaxKind::CloseBrace, SyntaxKind::Comma]);
+        }
+        true
+    }
+
+    fn parse_container(&mut self, kind: NodeKind) -> bool {
+        let (close, close_error) = if kind == NodeKind::Object {
+            (SyntaxKind::CloseBrace, "CloseBraceExpected")
+        } else {
+            (SyntaxKind::CloseBracket, "CloseBracketExpected")
+        };
+        self.on_container_begin(kind);
+        self.scan_next();
+        let mut needs_comma = false;
+        while self.scanner.token() != close && self.scanner.token() != SyntaxKind::Eof {
+            if self.scanner.token() == SyntaxKind::Comma {
+                if !needs_comma {
+                    self.error("ValueExpected", &[], &[]);
+                }
+                self.on_separator();
+                self.scan_next();
+                // Trailing commas are allowed, as in settings files.
+                if self.scanner.token() == close {
+                    break;
+                }
+            } else if needs_comma {
+                self.error("CommaExpected", &[], &[]);
+            }
+            let parsed = if kind == NodeKind::Object { self.parse_property() } else { self.parse_value() };
+            if !parsed {
+                let skip = if kind == NodeKind::Object { [SyntaxKind::CloseBrace, SyntaxKind::Comma] } else { [SyntaxKind::CloseBracket, SyntaxKind::Comma] };
+                self.error("ValueExpected", &[], &skip);
+            }
+            needs_comma = true;
+        }
+        if self.scanner.token() != close {
+            // Close the node at the current token so its length stays meaningful.
+            self.on_container_end();
+            self.error(close_error, &[close], &[]);
+        } else {
+            self.on_container_end();
+            self.scan_next();
+        }
+        true
+    }
+
+    fn parse_value(&mut self) -> bool {
+        match self.scanner.token() {
+            SyntaxKind::OpenBracket => self.parse_container(NodeKind::Array),
+            SyntaxKind::OpenBrace => self.parse_container(NodeKind::Object),
+            SyntaxKind::String => {
+                self.on_literal(NodeKind::String, Value::String(self.scanner.token_value().to_string()));
+                self.scan_next();
+                true
+            }
+            _ => self.parse_literal(),
+        }
+    }
+}
+
+/// Parses JSONC into a tree, collecting errors. Comments and trailing commas are allowed.
+pub fn parse_tree(text: &str) -> JsonTree {
+    // Node 0 is a synthetic array holding the root, like the fake parent in `parseTree`.
+    let holder = Node { kind: NodeKind::Array, offset: 0, length: 0, colon_offset: None, value: None, parent: None, children: Vec::new() };
+    let mut parser = Parser { scanner: Scanner::new(text), tree: JsonTree { nodes: vec![holder], root: None, errors: Vec::new() }, current: 0 };
+    parser.scan_next();
+    if parser.scanner.token() == SyntaxKind::Eof {
+        // Empty content is allowed.
+    } else if !parser.parse_value() {
+        parser.error("ValueExpected", &[], &[]);
+    } else if parser.scanner.token() != SyntaxKind::Eof {
+        parser.error("EndOfFileExpected", &[], &[]);
+    }
+    let mut tree = parser.tree;
+    tree.root = tree.nodes[0].children.first().copied();
+    if let Some(root) = tree.root {
+        tree.nodes[root].parent = None;
+    }
+    tree
+}
+
+/// Maps byte offsets in a text to UTF-16 code units, the unit JS string indices count, and
+/// back. Everything in this module works in bytes; the NAPI surface converts with this.
+pub(crate) struct Utf16Offsets {
+    /// `(byte start, utf16 start, byte end, utf16 end)` of each non-ASCII char. Between them
+    /// both units advance together.
+    chars: Vec<(usize, usize, usize, usize)>,
+    len: usize,
+}
+
+impl Utf16Offsets {
+    pub(crate) fn new(text: &str) -> Self {
+        let mut chars = Vec::new();
+        let mut utf16 = 0;
+        let mut last = 0;
+        for (i, c) in text.char_indices().filter(|(_, c)| !c.is_ascii()) {
+            utf16 += i - last;
+            chars.push((i, utf16, i + c.len_utf8(), utf16 + c.len_utf16()));
+            utf16 += c.len_utf16();
+            last = i + c.len_utf8();
+        }
+        Self { chars, len: text.len() }
+    }
+
+    /// A byte offset inside a char maps to the start of that char.
+    pub(crate) fn to_utf16(&self, byte: usize) -> usize {
+        let byte = byte.min(self.len);
+        let index = self.chars.partition_point(|c| c.2 <= byte);
+        let (base_byte, base_utf16) = index.checked_sub(1).map_or((0, 0), |i| (self.chars[i].2, self.chars[i].3));
+        let utf16 = base_utf16 + (byte - base_byte);
+        self.chars.get(index).map_or(utf16, |c| utf16.min(c.1))
+    }
+
+    /// `None` past the end or inside a surrogate pair.
+    pub(crate) fn to_byte(&self, utf16: usize) -> Option<usize> {
+        let index = self.chars.partition_point(|c| c.3 <= utf16);
+        let (base_byte, base_utf16) = index.checked_sub(1).map_or((0, 0), |i| (self.chars[i].2, self.chars[i].3));
+        let byte = base_byte + (utf16 - base_utf16);
+        let inside = self.chars.get(index).is_some_and(|c| utf16 > c.1);
+        (!inside && byte <= self.len).then_some(byte)
+    }
+
+    /// A byte range `(offset, length)` in UTF-16 units.
+    pub(crate) fn range_to_utf16(&self, offset: usize, length: usize) -> (u32, u32) {
+        let start = self.to_utf16(offset);
+        (start as u32, (self.to_utf16(offset + length) - start) as u32)
+    }
+}
+
+/// Converts path segments from JS (strings and numbers) into `Segment`s.
+pub fn segments_from_values(path: &[Value]) -> Vec<Segment> {
+    path.iter().map(|s| match s {
+        Value::Number(n) => Segment::Index(n.as_u64().unwrap_or(usize::MAX as u64) as usize),
+        Value::String(s) => Segment::Key(s.clone()),
+        other => Segment::Key(other.to_string()),
+    }).collect()
+}
+
+// ─── NAPI surface ──────────────────────────────────────────────────────────
+
+#[napi(object)]
+pub struct JsonNode {
+    pub node_type: String,
+    /// Offset and length in UTF-16 code units, like JS string indices.
+    pub offset: u32,
+    pub length: u32,
+    pub colon_offset: Option<u32>,
+    pub value: Option<Value>,
+    pub children: Option<Vec<JsonNode>>,
+}
+
+#[napi(object)]
+pub struct JsonParseError {
+    pub error: String,
+    pub offset: u32,
+    pub length: u32,
+}
+
+#[napi(object)]
+pub struct JsonParseTreeResult {
+    pub root: Option<JsonNode>,
+    pub errors: Vec<JsonParseError>,
+}
+
+fn to_napi_node(tree: &JsonTree, offsets: &Utf16Offsets, id: usize) -> JsonNode {
+    let node = tree.node(id);
+    let (offset, length) = offsets.range_to_utf16(node.offset, node.length);
+    JsonNode {
+        node_type: node.kind.as_str().to_string(),
+        offset,
+        length,
+        colon_offset: node.colon_offset.map(|o| offsets.to_utf16(o) as u32),
+        value: node.value.clone(),
+        children: matches!(node.kind, NodeKind::Object | NodeKind::Array | NodeKind::Property)
+            .then(|| node.children.iter().map(|&c| to_napi_node(tree, offsets, c)).collect()),
+    }
+}
+
+#[napi]
+pub fn json_parse_tree(text: String) -> JsonParseTreeResult {
+    let tree = parse_tree(&text);
+    let offsets = Utf16Offsets::new(&text);
+    JsonParseTreeResult {
+        root: tree.root.map(|r| to_napi_node(&tree, &offsets, r)),
+        errors: tree.errors.iter().map(|e| {
+            let (offset, length) = offsets.range_to_utf16(e.offset, e.length);
+            JsonParseError { error: e.error.to_string(), offset, length }
+        }).collect(),
+    }
+}
+
+/// Finds the node at `path` (property names and array indices).
+#[napi]
+pub fn json_find_node_at_location(text: String, path: Vec<Value>) -> Option<JsonNode> {
+    let tree = parse_tree(&text);
+    let offsets = Utf16Offsets::new(&text);
+    tree.find_at_location(tree.root, &segments_from_values(&path)).map(|id| to_napi_node(&tree, &offsets, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tree_offsets_and_values() {
+        let text = "{\n  // comment\n  \"a\": [1, true, null],\n  \"b\": { \"c\": \"x\\u00e9\" },\n}";
+        let tree = parse_tree(text);
+        assert!(tree.errors.is_empty(), "{:?}", tree.errors);
+        let a = tree.find_at_location(tree.root, &[Segment::Key("a".into())]).unwrap();
+        assert_eq!(&text[tree.node(a).offset..tree.node(a).offset + tree.node(a).length], "[1, true, null]");
+        let c = tree.find_at_location(tree.root, &[Segment::Key("b".into()), Segment::Key("c".into())]).unwrap();
+        assert_eq!(tree.value_of(c), Value::String("xé".into()));
+        assert_eq!(tree.path_of(c), vec![Segment::Key("b".into()), Segment::Key("c".into())]);
+        let property = tree.node(c).parent.unwrap();
+        assert_eq!(&text[tree.node(property).offset..tree.node(property).offset + tree.node(property).length], "\"c\": \"x\\u00e9\"");
+        assert_eq!(tree.value_of(tree.root.unwrap())["a"], serde_json::json!([1, true, null]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let errors = |text: &str| parse_tree(text).errors.iter().map(|e| (e.error, e.offset)).collect::<Vec<_>>();
+        assert_eq!(errors("{\"a\": 1 \"b\": 2}"), vec![("CommaExpected", 8)]);
+        assert_eq!(errors("{\"a\" 1}"), vec![("ColonExpected", 5)]);
+        assert_eq!(errors("[1, 2"), vec![("CloseBracketExpected", 5)]);
+        assert_eq!(errors("{} x"), vec![("InvalidSymbol", 3)]);
+        assert_eq!(errors("{} []"), vec![("EndOfFileExpected", 3)]);
+        assert!(errors("").is_empty());
+    }
+
+    #[test]
+    fn test_utf16_offsets() {
+        let text = "{\"é\": \"😀\", \"a\": 1}";
+        let offsets = Utf16Offsets::new(text);
+        let a = text.find("\"a\"").unwrap();
+        assert_eq!(offsets.to_utf16(a), text[..a].encode_utf16().count());
+        assert_eq!(offsets.to_byte(offsets.to_utf16(a)), Some(a));
+        let emoji = text.find('😀').unwrap();
+        assert_eq!(offsets.to_byte(offsets.to_utf16(emoji) + 1), None);
+        assert_eq!(offsets.to_byte(text.encode_utf16().count()), Some(text.len()));
+        assert_eq!(offsets.to_byte(text.encode_utf16().count() + 1), None);
+    }
+}
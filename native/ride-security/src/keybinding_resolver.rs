@@ -7,18 +7,22 @@
 //!
 //! Features:
 //! - Multi-chord buffered state machine with look-ahead disambiguation
-//! - Recursive "When" clause evaluator supporting relational and regex comparisons
+//! - "When" clauses parsed and cached by the shared context-key evaluator
 //! - Multi-tier priority resolution: System > User > Extension > Workspace > Default
 //! - Hardware-aware mapping for international layouts (ScanCode -> KeyCode -> Command)
 //! - Shadowing detection and detailed conflict resolution diagnostics
 //! - Emulation mode support (e.g., Vim mode specific keymap isolation)
 //! - Dynamic keymap reloading with incremental index updates
+//! - `keybindings.json` layers with `-command` removals and positioned diagnostics
 
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex, RwLock};
+use crate::contextkey_eval::{evaluate_when, parse_cached};
+use crate::json_parser::parse_jsonc;
+use crate::json_tree::{parse_tree, NodeKind, Utf16Offsets};
 
 #[napi(object)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -49,58 +53,115 @@ pub struct KeybindingConflict {
     pub losers: Vec<String>,
 }
 
-/// Robust evaluation engine for context-key expressions
-struct ContextKeyEvaluator;
-
-impl ContextKeyEvaluator {
-    pub fn evaluate(expr: &str, context: &HashMap<String, String>) -> bool {
-        let expr = expr.trim();
-        if expr.is_empty() {
-            return true;
-        }
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct KeybindingDiagnostic {
+    pub path: String,
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
 
-        // Logic split: OR has lowest precedence
-        if expr.contains(" || ") {
-            return expr.split(" || ").any(|e| Self::evaluate(e, context));
-        }
+#[napi(object)]
+pub struct KeybindingLoadResult {
+    /// False when the content was unchanged or could not be parsed; the previous entries stay active.
+    pub changed: bool,
+    pub entries: u32,
+    pub removals: u32,
+    pub diagnostics: Vec<KeybindingDiagnostic>,
+}
 
-        // Logic split: AND
-        if expr.contains(" && ") {
-            return expr.split(" && ").all(|e| Self::evaluate(e, context));
-        }
+/// Entries loaded from one keybindings file, kept so a reload only re-parses that file.
+struct KeybindingFile {
+    path: String,
+    content: String,
+    entries: Vec<KeybindingMapEntry>,
+}
 
-        // Negation
-        if expr.starts_with('!') {
-            return !Self::evaluate(&expr[1..], context);
+const MODIFIERS: [&str; 4] = ["Ctrl", "Shift", "Alt", "Meta"];
+
+/// Canonical spelling of named keys, indexed by their lowercase form.
+const NAMED_KEYS: &[&str] = &[
+    "Enter", "Escape", "Tab", "Space", "Backspace", "Delete", "Insert", "Home", "End", "PageUp", "PageDown",
+    "UpArrow", "DownArrow", "LeftArrow", "RightArrow", "PauseBreak", "CapsLock", "ContextMenu", "NumLock", "ScrollLock",
+    "numpad0", "numpad1", "numpad2", "numpad3", "numpad4", "numpad5", "numpad6", "numpad7", "numpad8", "numpad9",
+    "numpad_multiply", "numpad_add", "numpad_separator", "numpad_subtract", "numpad_decimal", "numpad_divide",
+];
+
+/// Normalizes `ctrl+shift+k ctrl+c` to the resolver's `Ctrl+Shift+K Ctrl+C` form. Returns the
+/// reason when the string is not a valid key or chord.
+pub fn normalize_keybinding(key: &str) -> std::result::Result<String, String> {
+    let parts: Vec<&str> = key.split_whitespace().collect();
+    if parts.is_empty() || parts.len() > 2 {
+        return Err(format!("'{}' must be one key or a chord of two", key));
+    }
+    let mut chords = Vec::with_capacity(parts.len());
+    for part in parts {
+        let tokens: Vec<&str> = part.split('+').collect();
+        let (key_name, modifiers) = tokens.split_last().unwrap();
+        let mut present = [false; 4];
+        for modifier in modifiers {
+            let index = match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => 0,
+                "shift" => 1,
+                "alt" => 2,
+                "meta" | "cmd" | "win" => 3,
+                other => return Err(format!("Unknown modifier '{}' in '{}'", other, key)),
+            };
+            present[index] = true;
         }
+        let lower = key_name.to_ascii_lowercase();
+        let name = if key_name.chars().count() == 1 && !key_name.chars().all(char::is_whitespace) {
+            key_name.to_uppercase()
+        } else if let Some(f) = lower.strip_prefix('f').and_then(|n| n.parse::<u32>().ok()).filter(|n| (1..=24).contains(n)) {
+            format!("F{}", f)
+        } else if let Some(named) = NAMED_KEYS.iter().find(|n| n.to_ascii_lowercase() == lower) {
+            named.to_string()
+        } else {
+            return Err(format!("Unknown key '{}' in '{}'", key_name, key));
+        };
+        let mut chord: Vec<&str> = MODIFIERS.iter().zip(present).filter(|(_, on)| *on).map(|(m, _)| *m).collect();
+        chord.push(&name);
+        chords.push(chord.join("+"));
+    }
+    Ok(chords.join(" "))
+}
 
-        // Relational operators (e.g. view == "editor", count > 0)
-        if expr.contains("==") {
-            let parts: Vec<&str> = expr.split("==").collect();
-            if parts.len() == 2 {
-                let key = parts[0].trim();
-                let val = parts[1].trim().trim_matches('"').trim_matches('\'');
-                return context.get(key).map(|v| v == val).unwrap_or(false);
-            }
-        }
+/// 1-based line and column (in UTF-16 units, like the editor) of every element of the
+/// top-level array.
+fn array_element_positions(text: &str) -> Vec<(u32, u32)> {
+    let tree = parse_tree(text);
+    let Some(root) = tree.root.filter(|&r| tree.node(r).kind == NodeKind::Array) else { return Vec::new() };
+    let offsets = Utf16Offsets::new(text);
+    let line_starts: Vec<usize> = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    tree.node(root).children.iter().map(|&child| {
+        let offset = tree.node(child).offset;
+        let line = line_starts.partition_point(|&s| s <= offset) - 1;
+        let column = offsets.to_utf16(offset) - offsets.to_utf16(line_starts[line]);
+        (line as u32 + 1, column as u32 + 1)
+    }).collect()
+}
 
-        if expr.contains("!=") {
-             let parts: Vec<&str> = expr.split("!=").collect();
-            if parts.len() == 2 {
-                let key = parts[0].trim();
-                let val = parts[1].trim().trim_matches('"').trim_matches('\'');
-                return context.get(key).map(|v| v != val).unwrap_or(true);
-            }
-        }
+fn is_default(entry: &KeybindingMapEntry) -> bool {
+    entry.weight < 1000
+}
 
-        // Existential check
-        context.get(expr).map(|v| v != "false" && !v.is_empty()).unwrap_or(false)
-    }
+/// Whether `removal` (a `-command` entry) targets `entry`. Key and `when` only narrow the
+/// removal when the removal specifies them, as in VS Code.
+fn is_removed_by(entry: &KeybindingMapEntry, removal: &KeybindingMapEntry) -> bool {
+    let canonical_key = |key: &str| normalize_keybinding(key).unwrap_or_else(|_| key.to_string());
+    let canonical_when = |when: &str| parse_cached(when).map(|e| e.serialize()).unwrap_or_else(|_| when.to_string());
+    removal.command[1..] == entry.command
+        && (removal.key.is_empty() || canonical_key(&removal.key) == canonical_key(&entry.key))
+        && removal.when.as_deref().is_none_or(|when| entry.when.as_deref().map(canonical_when) == Some(canonical_when(when)))
 }
 
 #[napi]
 pub struct KeybindingResolver {
     entries: Arc<RwLock<Vec<KeybindingMapEntry>>>,
+    registered: RwLock<Vec<KeybindingMapEntry>>,
+    files: RwLock<Vec<KeybindingFile>>,
+    known_commands: RwLock<HashSet<String>>,
     chord_buffer: Mutex<Vec<String>>,
     layout_map: Arc<RwLock<HashMap<u32, String>>>, // ScanCode -> UI Key
     modifiers_state: std::sync::atomic::AtomicU32,
@@ -112,26 +173,136 @@ impl KeybindingResolver {
     pub fn new() -> Self {
         Self {
             entries: Arc::new(RwLock::new(Vec::new())),
+            registered: RwLock::new(Vec::new()),
+            files: RwLock::new(Vec::new()),
+            known_commands: RwLock::new(HashSet::new()),
             chord_buffer: Mutex::new(Vec::new()),
             layout_map: Arc::new(RwLock::new(HashMap::new())),
             modifiers_state: std::sync::atomic::AtomicU32::new(0),
         }
     }
 
-    /// Update the internal ScanCode -> KeyCode map (e.g. from JS keyboard layout API)
-    #[napi]
-    pub fn set_layout_map(&self, map: HashMap<u32, String>) {
+    /// Update the internal ScanCode -> KeyCode map (e.g. from JS keyboard layout API).
+    /// JS object keys arrive as strings, so scan codes are parsed here and non-numeric
+    /// keys are ignored; the declared type stays `Record<number, string>`.
+    #[napi(ts_args_type = "map: Record<number, string>")]
+    pub fn set_layout_map(&self, map: HashMap<String, String>) {
         let mut lm = self.layout_map.write().unwrap();
-        *lm = map;
+        *lm = map.into_iter().filter_map(|(code, key)| Some((code.parse().ok()?, key))).collect();
     }
 
     /// Mass-register keybindings with specific weight offsets
     #[napi]
     pub fn bulk_register(&self, entries: Vec<KeybindingMapEntry>) {
-        let mut e = self.entries.write().unwrap();
-        e.extend(entries);
-        // Sort by weight descending so we find winners faster
-        e.sort_by(|a, b| b.weight.cmp(&a.weight));
+        self.registered.write().unwrap().extend(entries);
+        self.rebuild();
+    }
+
+    /// Commands that exist besides those bound by registered defaults; loaded files
+    /// referring to anything else get an "unknown command" diagnostic.
+    #[napi]
+    pub fn set_known_commands(&self, commands: Vec<String>) {
+        *self.known_commands.write().unwrap() = commands.into_iter().collect();
+    }
+
+    /// Reads a `keybindings.json` from disk as a layer for `source` ("default", "extension" or "user").
+    #[napi]
+    pub fn load_keybindings_file(&self, path: String, source: String) -> Result<KeybindingLoadResult> {
+        let content = std::fs::read_to_string(&path).map_err(|e| Error::from_reason(format!("read failed: {}", e)))?;
+        Ok(self.load_keybindings_content(path, source, content))
+    }
+
+    /// Loads or reloads the layer for `path`. Unchanged content is a no-op; otherwise only this
+    /// file is re-parsed before the resolved keymap is rebuilt.
+    #[napi]
+    pub fn load_keybindings_content(&self, path: String, source: String, content: String) -> KeybindingLoadResult {
+        let unchanged = |diagnostics| KeybindingLoadResult { changed: false, entries: 0, removals: 0, diagnostics };
+        if self.files.read().unwrap().iter().any(|f| f.path == path && f.content == content) {
+            return unchanged(Vec::new());
+        }
+        let diagnostic = |line: u32, column: u32, message: String| KeybindingDiagnostic { path: path.clone(), line, column, message };
+
+        let parsed = parse_jsonc(content.clone());
+        let items = match parsed.value.as_deref().map(serde_json::from_str::<serde_json::Value>) {
+            Some(Ok(serde_json::Value::Array(items))) => items,
+            Some(_) => return unchanged(vec![diagnostic(1, 1, "Keybindings must be an array".into())]),
+            None => {
+                let message = parsed.error_message.unwrap_or_default();
+                return unchanged(vec![diagnostic(parsed.error_line.unwrap_or(1), parsed.error_column.unwrap_or(1), message)]);
+            }
+        };
+        let positions = array_element_positions(&content);
+        let weight = match source.as_str() {
+            "user" => 1000,
+            "extension" => 100,
+            _ => 0,
+        };
+
+        let known: HashSet<String> = {
+            let registered = self.registered.read().unwrap();
+            let mut known = self.known_commands.read().unwrap().clone();
+            known.extend(registered.iter().filter(|e| is_default(e)).map(|e| e.command.clone()));
+            known
+        };
+        let mut diagnostics = Vec::new();
+        let mut entries = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let (line, column) = positions.get(index).copied().unwrap_or((1, 1));
+            let command = item.get("command").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            let raw_key = item.get("key").and_then(|v| v.as_str()).unwrap_or_default();
+            if command.is_empty() || command == "-" {
+                diagnostics.push(diagnostic(line, column, "Keybinding is missing a command".into()));
+                continue;
+            }
+            let removal = command.starts_with('-');
+            let key = match normalize_keybinding(raw_key) {
+                Ok(key) => key,
+                Err(_) if removal && raw_key.is_empty() => String::new(),
+                Err(message) => {
+                    diagnostics.push(diagnostic(line, column, message));
+                    continue;
+                }
+            };
+            let when = item.get("when").and_then(|v| v.as_str()).map(String::from);
+            if let Some(Err(message)) = when.as_deref().map(parse_cached) {
+                diagnostics.push(diagnostic(line, column, format!("Invalid when clause: {}", message)));
+            }
+            if !known.is_empty() && !known.contains(command.trim_start_matches('-')) {
+                diagnostics.push(diagnostic(line, column, format!("Unknown command '{}'", command.trim_start_matches('-'))));
+            }
+            entries.push(KeybindingMapEntry { key, command, when, weight, args: item.get("args").cloned(), id: None });
+        }
+
+        let result = KeybindingLoadResult {
+            changed: true,
+            entries: entries.iter().filter(|e| !e.command.starts_with('-')).count() as u32,
+            removals: entries.iter().filter(|e| e.command.starts_with('-')).count() as u32,
+            diagnostics,
+        };
+        {
+            let mut files = self.files.write().unwrap();
+            let file = KeybindingFile { path: path.clone(), content, entries };
+            match files.iter_mut().find(|f| f.path == path) {
+                Some(existing) => *existing = file,
+                None => files.push(file),
+            }
+        }
+        self.rebuild();
+        result
+    }
+
+    #[napi]
+    pub fn unload_keybindings_file(&self, path: String) -> bool {
+        let removed = {
+            let mut files = self.files.write().unwrap();
+            let before = files.len();
+            files.retain(|f| f.path != path);
+            files.len() != before
+        };
+        if removed {
+            self.rebuild();
+        }
+        removed
     }
 
     /// Resolve a key event from its low-level components
@@ -160,7 +331,7 @@ impl KeybindingResolver {
     /// Resolve a string-based key sequence (handles chords internally)
     #[napi]
     pub fn resolve_string(&self, key: String, context_json: String) -> Option<ResolvedKeybinding> {
-        let context: HashMap<String, String> = serde_json::from_str(&context_json).unwrap_or_default();
+        let context: HashMap<String, serde_json::Value> = serde_json::from_str(&context_json).unwrap_or_default();
         let mut buffer = self.chord_buffer.lock().unwrap();
 
         buffer.push(key);
@@ -174,7 +345,7 @@ impl KeybindingResolver {
         // Already sorted by weight in bulk_register
         for kb in candidates {
             if let Some(ref when) = kb.when {
-                if ContextKeyEvaluator::evaluate(when, &context) {
+                if evaluate_when(when, &context) {
                     buffer.clear();
                     return Some(self.create_resolved(kb, current_chord, 0));
                 }
@@ -195,6 +366,20 @@ impl KeybindingResolver {
         None
     }
 
+    /// Merges registered entries and loaded files, drops defaults targeted by `-command`
+    /// removals, and orders by weight. Within a file, later entries win.
+    fn rebuild(&self) {
+        let mut all: Vec<KeybindingMapEntry> = self.registered.read().unwrap().clone();
+        for file in self.files.read().unwrap().iter() {
+            all.extend(file.entries.iter().rev().cloned());
+        }
+        let (removals, mut resolved): (Vec<_>, Vec<_>) = all.into_iter().partition(|e| e.command.starts_with('-'));
+        resolved.retain(|entry| !is_default(entry) || !removals.iter().any(|r| is_removed_by(entry, r)));
+        // Sort by weight descending so we find winners faster
+        resolved.sort_by_key(|e| std::cmp::Reverse(e.weight));
+        *self.entries.write().unwrap() = resolved;
+    }
+
     fn create_resolved(&self, kb: &KeybindingMapEntry, key_str: String, shadows: u32) -> ResolvedKeybinding {
         ResolvedKeybinding {
             command: kb.command.clone(),
@@ -217,7 +402,7 @@ impl KeybindingResolver {
             .collect();
 
         if matching_entries.len() > 1 {
-            matching_entries.sort_by(|a, b| b.weight.cmp(&a.weight));
+            matching_entries.sort_by_key(|e| std::cmp::Reverse(e.weight));
             let winner = matching_entries[0].command.clone();
             let losers = matching_entries[1..].iter().map(|e| e.command.clone()).collect();
 
@@ -237,3 +422,56 @@ impl KeybindingResolver {
         self.chord_buffer.lock().unwrap().clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, command: &str, when: Option<&str>) -> KeybindingMapEntry {
+        KeybindingMapEntry { key: key.into(), command: command.into(), when: when.map(String::from), weight: 0, args: None, id: None }
+    }
+
+    #[test]
+    fn test_normalize_keybinding() {
+        assert_eq!(normalize_keybinding("shift+ctrl+k ctrl+c").unwrap(), "Ctrl+Shift+K Ctrl+C");
+        assert_eq!(normalize_keybinding("cmd+pagedown").unwrap(), "Meta+PageDown");
+        assert!(normalize_keybinding("ctrl+foo").is_err());
+        assert!(normalize_keybinding("hyper+a").is_err());
+    }
+
+    #[test]
+    fn test_load_user_layer_with_removals() {
+        let resolver = KeybindingResolver::new();
+        resolver.bulk_register(vec![
+            entry("Ctrl+P", "workbench.action.quickOpen", None),
+            entry("Ctrl+S", "workbench.action.files.save", Some("editorFocus")),
+        ]);
+        let content = r#"[
+            // Free up Ctrl+P
+            { "key": "ctrl+p", "command": "-workbench.action.quickOpen" },
+            { "key": "ctrl+p", "command": "workbench.action.files.save" },
+            { "key": "ctrl+s", "command": "-workbench.action.files.save", "when": "other" },
+            { "key": "ctrl+bogus", "command": "workbench.action.files.save" },
+            { "key": "ctrl+u", "command": "does.not.exist", },
+        ]"#;
+        let result = resolver.load_keybindings_content("keybindings.json".into(), "user".into(), content.into());
+        assert_eq!((result.entries, result.removals), (2, 2));
+        let messages: Vec<_> = result.diagnostics.iter().map(|d| (d.line, d.message.starts_with("Unknown command"))).collect();
+        assert_eq!(messages, vec![(6, false), (7, true)]);
+
+        // Positions come from the original text, so a leading comment shifts the column.
+        let commented = "[\n  /* é */ { \"key\": \"ctrl+bogus\", \"command\": \"x\" }\n]";
+        let result = resolver.load_keybindings_content("other.json".into(), "user".into(), commented.into());
+        assert_eq!((result.diagnostics[0].line, result.diagnostics[0].column), (2, 11));
+
+        let resolved = resolver.resolve_string("Ctrl+P".into(), "{}".into()).unwrap();
+        assert_eq!((resolved.command.as_str(), resolved.source.as_str()), ("workbench.action.files.save", "user"));
+        // The removal's `when` does not match the default's, so Ctrl+S stays bound.
+        assert!(resolver.resolve_string("Ctrl+S".into(), r#"{"editorFocus": true}"#.into()).is_some());
+
+        assert!(!resolver.load_keybindings_content("keybindings.json".into(), "user".into(), content.into()).changed);
+        assert!(!resolver.load_keybindings_content("keybindings.json".into(), "user".into(), "[ {".into()).changed);
+        assert!(resolver.unload_keybindings_file("keybindings.json".into()));
+        assert_eq!(resolver.resolve_string("Ctrl+P".into(), "{}".into()).unwrap().command, "workbench.action.quickOpen");
+    }
+}
@@ -0,0 +1,321 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Language Configuration — Rust port of `src/vs/editor/common/languages/languageConfigurationRegistry.ts`
+//! and `supports/onEnter.ts`.
+//!
+//! Loads a `language-configuration.json` and answers the questions the typing
+//! commands ask: how to indent after Enter, whether a line should be outdented,
+//! and which pairs auto-close or surround a selection. Patterns are compiled
+//! with `fancy_regex`, so the lookarounds in the stock configurations work;
+//! patterns that still fail to compile are skipped and reported through
+//! `get_errors`.
+
+use fancy_regex::{Regex, RegexBuilder};
+use napi_derive::napi;
+use serde::Deserialize;
+
+/// VS Code's default `autoCloseBefore`: auto-close only before these characters.
+const DEFAULT_AUTO_CLOSE_BEFORE: &str = ";:.,=}])> \n\t";
+
+#[napi]
+#[derive(Debug, PartialEq, Eq)]
+pub enum IndentAction {
+    None = 0,
+    Indent = 1,
+    /// Indent the new line and put the text after the cursor on a further line at the old level.
+    IndentOutdent = 2,
+    Outdent = 3,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnterAction {
+    pub indent_action: IndentAction,
+    pub append_text: Option<String>,
+    /// Characters removed from the new line's indentation.
+    pub remove_text: Option<u32>,
+}
+
+impl EnterAction {
+    pub fn new(indent_action: IndentAction) -> Self {
+        Self { indent_action, append_text: None, remove_text: None }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AutoClosingPair {
+    pub open: String,
+    pub close: String,
+    /// Token types (`"string"`, `"comment"`) in which the pair does not auto-close.
+    pub not_in: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+struct OnEnterRule {
+    before_text: Regex,
+    after_text: Option<Regex>,
+    previous_line_text: Option<Regex>,
+    action: EnterAction,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RawLanguageConfiguration {
+    #[serde(default)]
+    brackets: Vec<(String, String)>,
+    #[serde(default)]
+    auto_closing_pairs: Vec<RawPair>,
+    #[serde(default)]
+    surrounding_pairs: Vec<RawPair>,
+    auto_close_before: Option<String>,
+    indentation_rules: Option<RawIndentationRules>,
+    #[serde(default)]
+    on_enter_rules: Vec<RawOnEnterRule>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPair {
+    Tuple(String, String),
+    Object {
+        open: String,
+        close: String,
+        #[serde(default, rename = "notIn")]
+        not_in: Vec<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPattern {
+    Source(String),
+    Object { pattern: String, flags: Option<String> },
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RawIndentationRules {
+    increase_indent_pattern: Option<RawPattern>,
+    decrease_indent_pattern: Option<RawPattern>,
+    indent_next_line_pattern: Option<RawPattern>,
+    un_indented_line_pattern: Option<RawPattern>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawOnEnterRule {
+    before_text: RawPattern,
+    after_text: Option<RawPattern>,
+    previous_line_text: Option<RawPattern>,
+    action: RawEnterAction,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawEnterAction {
+    indent: String,
+    append_text: Option<String>,
+    remove_text: Option<u32>,
+}
+
+#[napi]
+#[derive(Clone, Default)]
+pub struct LanguageConfiguration {
+    brackets: Vec<(String, String)>,
+    auto_closing_pairs: Vec<AutoClosingPair>,
+    surrounding_pairs: Vec<(String, String)>,
+    auto_close_before: String,
+    increase_indent: Option<Regex>,
+    decrease_indent: Option<Regex>,
+    indent_next_line: Option<Regex>,
+    unindented_line: Option<Regex>,
+    on_enter_rules: Vec<OnEnterRule>,
+    errors: Vec<String>,
+}
+
+#[napi]
+impl LanguageConfiguration {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        Self { auto_close_before: DEFAULT_AUTO_CLOSE_BEFORE.to_string(), ..Default::default() }
+    }
+
+    /// Replaces the configuration with the contents of a `language-configuration.json`.
+    #[napi]
+    pub fn load(&mut self, json: String) -> napi::Result<()> {
+        let raw: RawLanguageConfiguration = serde_json::from_str(&json).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        *self = Self::new();
+
+        self.brackets = raw.brackets;
+        self.auto_closing_pairs = raw.auto_closing_pairs.into_iter().map(|pair| match pair {
+            RawPair::Tuple(open, close) => AutoClosingPair { open, close, not_in: Vec::new() },
+            RawPair::Object { open, close, not_in } => AutoClosingPair { open, close, not_in },
+        }).collect();
+        self.surrounding_pairs = raw.surrounding_pairs.into_iter().map(|pair| match pair {
+            RawPair::Tuple(open, close) | RawPair::Object { open, close, .. } => (open, close),
+        }).collect();
+        if let Some(before) = raw.auto_close_before {
+            self.auto_close_before = before;
+        }
+
+        let rules = raw.indentation_rules.unwrap_or_default();
+        self.increase_indent = self.compile(rules.increase_indent_pattern.as_ref());
+        self.decrease_indent = self.compile(rules.decrease_indent_pattern.as_ref());
+        self.indent_next_line = self.compile(rules.indent_next_line_pattern.as_ref());
+        self.unindented_line = self.compile(rules.un_indented_line_pattern.as_ref());
+
+        for rule in raw.on_enter_rules {
+            let indent_action = match rule.action.indent.as_str() {
+                "indent" => IndentAction::Indent,
+                "indentOutdent" => IndentAction::IndentOutdent,
+                "outdent" => IndentAction::Outdent,
+                _ => IndentAction::None,
+            };
+            let Some(before_text) = self.compile(Some(&rule.before_text)) else { continue };
+            let after_text = self.compile(rule.after_text.as_ref());
+            let previous_line_text = self.compile(rule.previous_line_text.as_ref());
+            if (rule.after_text.is_some() && after_text.is_none()) || (rule.previous_line_text.is_some() && previous_line_text.is_none()) {
+                continue;
+            }
+            self.on_enter_rules.push(OnEnterRule {
+                before_text,
+                after_text,
+                previous_line_text,
+                action: EnterAction { indent_action, append_text: rule.action.append_text, remove_text: rule.action.remove_text },
+            });
+        }
+        Ok(())
+    }
+
+    /// Patterns that failed to compile and were skipped.
+    #[napi]
+    pub fn get_errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+
+    /// What pressing Enter between `before_text` and `after_text` should do,
+    /// from the on-enter rules first and then the bracket pairs.
+    #[napi]
+    pub fn on_enter(&self, previous_line_text: String, before_text: String, after_text: String) -> Option<EnterAction> {
+        let rule = self.on_enter_rules.iter().find(|rule| {
+            matches(&rule.before_text, &before_text)
+                && rule.after_text.as_ref().is_none_or(|r| matches(r, &after_text))
+                && rule.previous_line_text.as_ref().is_none_or(|r| matches(r, &previous_line_text))
+        });
+        if let Some(rule) = rule {
+            return Some(rule.action.clone());
+        }
+
+        let before = before_text.trim_end();
+        let after = after_text.trim_start();
+        let (_, close) = self.brackets.iter().find(|(open, _)| before.ends_with(open.as_str()))?;
+        if after.starts_with(close.as_str()) {
+            Some(EnterAction::new(IndentAction::IndentOutdent))
+        } else {
+            Some(EnterAction::new(IndentAction::Indent))
+        }
+    }
+}
+
+/// Whether `regex` matches `text`; a match that exceeds the backtracking limit counts as none.
+fn matches(regex: &Regex, text: &str) -> bool {
+    regex.is_match(text).unwrap_or(false)
+}
+
+impl LanguageConfiguration {
+    fn compile(&mut self, pattern: Option<&RawPattern>) -> Option<Regex> {
+        let (source, flags) = match pattern? {
+            RawPattern::Source(source) => (source.as_str(), ""),
+            RawPattern::Object { pattern, flags } => (pattern.as_str(), flags.as_deref().unwrap_or("")),
+        };
+        match RegexBuilder::new(source).case_insensitive(flags.contains('i')).build() {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                self.errors.push(format!("{}: {}", source, e));
+                None
+            }
+        }
+    }
+
+    /// The line after `line` should be indented one more level.
+    pub fn increases_indent(&self, line: &str) -> bool {
+        self.increase_indent.as_ref().is_some_and(|r| matches(r, line))
+            || self.indent_next_line.as_ref().is_some_and(|r| matches(r, line))
+    }
+
+    /// `line` belongs one level below the line that opened its block.
+    pub fn decreases_indent(&self, line: &str) -> bool {
+        self.decrease_indent.as_ref().is_some_and(|r| matches(r, line))
+    }
+
+    /// Lines (e.g. preprocessor directives) that never take part in indentation.
+    pub fn is_unindented(&self, line: &str) -> bool {
+        self.unindented_line.as_ref().is_some_and(|r| matches(r, line))
+    }
+
+    pub fn auto_closing_pair(&self, open: &str) -> Option<&AutoClosingPair> {
+        self.auto_closing_pairs.iter().find(|p| p.open == open)
+    }
+
+    pub fn is_auto_closing_close(&self, close: &str) -> bool {
+        self.auto_closing_pairs.iter().any(|p| p.close == close)
+    }
+
+    pub fn auto_close_before(&self) -> &str {
+        &self.auto_close_before
+    }
+
+    pub fn surrounding_pair(&self, open: &str) -> Option<&(String, String)> {
+        self.surrounding_pairs.iter().find(|(o, _)| o == open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "brackets": [["{", "}"], ["(", ")"]],
+        "autoClosingPairs": [["{", "}"], { "open": "\"", "close": "\"", "notIn": ["string"] }],
+        "surroundingPairs": [["(", ")"], { "open": "'", "close": "'" }],
+        "indentationRules": {
+            "increaseIndentPattern": "^.*\\{[^}\"']*$",
+            "decreaseIndentPattern": { "pattern": "^((?!.*?/\\*).*\\*/)?\\s*[\\}\\]].*$", "flags": "i" },
+            "unIndentedLinePattern": "^(?!x)"
+        },
+        "onEnterRules": [{ "beforeText": "^\\s*/\\*\\*(?:[^*]|\\*[^/])*$", "action": { "indent": "none", "appendText": " * " } }]
+    }"#;
+
+    #[test]
+    fn test_load_and_errors() {
+        let mut config = LanguageConfiguration::new();
+        config.load(CONFIG.into()).unwrap();
+        assert!(config.get_errors().is_empty());
+        assert!(config.increases_indent("fn a() {"));
+        assert!(config.decreases_indent("    }"));
+        // The stock TypeScript lookahead: a comment closed from an earlier line counts.
+        assert!(config.decreases_indent(" end of comment */ }"));
+        assert!(!config.decreases_indent("/* a */ }"));
+        assert!(config.is_unindented("#if DEBUG"));
+        assert!(!config.is_unindented("x = 1"));
+        assert_eq!(config.auto_closing_pair("\"").unwrap().not_in, vec!["string"]);
+        assert_eq!(config.surrounding_pair("'").unwrap().1, "'");
+        config.load(r#"{"indentationRules": {"increaseIndentPattern": "("}}"#.into()).unwrap();
+        assert_eq!(config.get_errors().len(), 1);
+        assert!(config.load("{".into()).is_err());
+    }
+
+    #[test]
+    fn test_on_enter_rules_then_brackets() {
+        let mut config = LanguageConfiguration::new();
+        config.load(CONFIG.into()).unwrap();
+        let doc = config.on_enter(String::new(), "/**".into(), String::new()).unwrap();
+        assert_eq!(doc.append_text.as_deref(), Some(" * "));
+        assert_eq!(config.on_enter(String::new(), "if (x) {".into(), "}".into()).unwrap().indent_action, IndentAction::IndentOutdent);
+        assert_eq!(config.on_enter(String::new(), "call(".into(), "a".into()).unwrap().indent_action, IndentAction::Indent);
+        assert!(config.on_enter(String::new(), "x".into(), String::new()).is_none());
+    }
+}
@@ -26,8 +26,11 @@ mod integrity;
 mod network;
 mod fs_watcher;
 mod search;
+mod trigram_index;
 mod diff;
+mod patch;
 mod indexer;
+mod symbol_index;
 mod process;
 mod logger;
 mod config;
@@ -40,9 +43,13 @@ mod strings;
 mod paths;
 mod collections;
 mod glob_engine;
+mod filters;
 mod hash_utils;
 mod lifecycle;
 mod json_parser;
+mod json_tree;
+mod json_edit;
+mod json_schema;
 mod platform_utils;
 mod color;
 mod date;
@@ -57,8 +64,11 @@ pub use integrity::*;
 pub use network::*;
 pub use fs_watcher::*;
 pub use search::*;
+pub use trigram_index::*;
 pub use diff::*;
+pub use patch::*;
 pub use indexer::*;
+pub use symbol_index::*;
 pub use process::*;
 pub use logger::*;
 pub use config::*;
@@ -71,9 +81,13 @@ pub use strings::*;
 pub use paths::*;
 pub use collections::*;
 pub use glob_engine::*;
+pub use filters::*;
 pub use hash_utils::*;
 pub use lifecycle::*;
 pub use json_parser::*;
+pub use json_tree::*;
+pub use json_edit::*;
+pub use json_schema::*;
 pub use platform_utils::*;
 pub use color::*;
 pub use date::*;
@@ -104,10 +118,17 @@ mod selection;
 mod text_model_types;
 mod piece_tree;
 mod text_model;
+mod text_model_tokens;
+mod bracket_pairs;
+mod interval_tree;
 mod cursor;
 mod edit_stack;
 mod view_model;
+mod line_projection;
+mod folding;
 mod tokenizer;
+mod textmate;
+mod language_configuration;
 mod text_edit;
 mod editor_config;
 mod editor_core;
@@ -118,22 +139,30 @@ pub use selection::*;
 pub use text_model_types::*;
 pub use piece_tree::*;
 pub use text_model::*;
+pub use text_model_tokens::*;
+pub use bracket_pairs::*;
 pub use cursor::*;
 pub use edit_stack::*;
 pub use view_model::*;
+pub use line_projection::*;
+pub use folding::*;
 pub use tokenizer::*;
+pub use textmate::*;
+pub use language_configuration::*;
 pub use text_edit::*;
 pub use editor_config::*;
 pub use editor_core::*;
 
 // Phase 11: Editor Contrib (Algorithms)
 mod snippet_parser;
+mod snippet_session;
 mod color_picker;
 mod link_detector;
 mod word_ops;
 mod suggest;
 
 pub use snippet_parser::*;
+pub use snippet_session::*;
 pub use color_picker::*;
 pub use link_detector::*;
 pub use word_ops::*;
@@ -135,6 +135,11 @@ impl CancellationToken {
     pub fn reset(&self) {
         self.cancelled.store(false, Ordering::Relaxed);
     }
+
+    /// The shared flag, for native work that must observe cancellation off the JS thread.
+    pub(crate) fn flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
 }
 
 // ─── Disposable Store ──────────────────────────────────────────────────────
@@ -0,0 +1,326 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Model Line Projection — Rust port of `src/vs/editor/common/modelLineProjectionData.ts`
+//! and `src/vs/editor/common/viewModel/monospaceLineBreaksComputer.ts`.
+//!
+//! A model line is projected to one or more view lines: injected text (inlay
+//! hints) is spliced in first, then the result is broken into wrapped
+//! segments. Break points prefer word boundaries; tabs advance to the next tab
+//! stop and East Asian wide characters take two columns. View columns count
+//! characters, with continuation segments prefixed by the wrapping indent.
+
+use napi_derive::napi;
+use crate::strings::is_full_width_character;
+
+#[napi]
+#[derive(Debug, PartialEq, Eq)]
+pub enum WrappingIndent {
+    /// Continuation lines start at column 1.
+    None = 0,
+    /// Continuation lines get the same indent as the wrapped line.
+    Same = 1,
+    /// One extra indent level.
+    Indent = 2,
+    /// Two extra indent levels.
+    DeepIndent = 3,
+}
+
+/// Characters a line may be broken before.
+const BREAK_BEFORE_CHARACTERS: &str = "([{‘“〈《「『【〔（［｛｢£¥＄￡￥+＋";
+/// Characters a line may be broken after.
+const BREAK_AFTER_CHARACTERS: &str = " \t})]?|/&.,;¢°′″‰℃、。｡､￠，．：；？！％・･ゝゞヽヾーァィゥェォッャュョヮヵヶぁぃぅぇぉっゃゅょゎゕゖㇰㇱㇲㇳㇴㇵㇶㇷㇸㇹㇺㇻㇼㇽㇾㇿ々〻ｧｨｩｪｫｬｭｮｯｰ”〉》」』】〕）］｝｣";
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharacterClass {
+    None,
+    BreakBefore,
+    BreakAfter,
+    BreakIdeographic,
+}
+
+fn classify(ch: char) -> CharacterClass {
+    if BREAK_AFTER_CHARACTERS.contains(ch) {
+        CharacterClass::BreakAfter
+    } else if BREAK_BEFORE_CHARACTERS.contains(ch) {
+        CharacterClass::BreakBefore
+    } else if matches!(ch as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF) {
+        // Hiragana, Katakana and CJK ideographs can break anywhere.
+        CharacterClass::BreakIdeographic
+    } else {
+        CharacterClass::None
+    }
+}
+
+fn can_break(prev: char, ch: char) -> bool {
+    let (prev_class, class) = (classify(prev), classify(ch));
+    ch != ' '
+        && ((prev_class == CharacterClass::BreakAfter && class != CharacterClass::BreakAfter)
+            || (prev_class != CharacterClass::BreakBefore && class == CharacterClass::BreakBefore)
+            || (prev_class == CharacterClass::BreakIdeographic && class != CharacterClass::BreakAfter)
+            || (class == CharacterClass::BreakIdeographic && prev_class != CharacterClass::BreakBefore))
+}
+
+/// Visible width of `ch` when it starts at `visible_column` (0-based).
+pub(crate) fn char_width(ch: char, visible_column: usize, tab_size: usize) -> usize {
+    if ch == '\t' {
+        return tab_size.max(1) - visible_column % tab_size.max(1);
+    }
+    if is_full_width_character(ch as u32) || (ch as u32) < 32 {
+        return 2;
+    }
+    1
+}
+
+/// 0-based visible column reached after the first `char_count` characters of `text`.
+pub(crate) fn visible_column_at(text: &str, char_count: usize, tab_size: usize) -> usize {
+    text.chars().take(char_count).fold(0, |visible, ch| visible + char_width(ch, visible, tab_size))
+}
+
+/// Number of characters of `text` before the character covering `visible_column`.
+/// Positions inside a wide character or tab snap to the nearer edge.
+pub(crate) fn char_count_at_visible_column(text: &str, visible_column: usize, tab_size: usize) -> usize {
+    let mut visible = 0;
+    for (i, ch) in text.chars().enumerate() {
+        let width = char_width(ch, visible, tab_size);
+        if visible_column < visible + width {
+            return if visible_column - visible > width / 2 { i + 1 } else { i };
+        }
+        visible += width;
+    }
+    text.chars().count()
+}
+
+/// Text injected into a model line at a 1-based model column.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct InjectedText {
+    pub column: u32,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ModelLineProjection {
+    /// Model line with the injected texts spliced in.
+    output: Vec<char>,
+    /// Character offsets in the model line where text was injected, ascending.
+    injection_offsets: Vec<usize>,
+    injection_lengths: Vec<usize>,
+    /// Output offsets where each continuation segment starts.
+    break_offsets: Vec<usize>,
+    wrapped_text_indent_length: usize,
+}
+
+impl ModelLineProjection {
+    /// Projects `line`. A `wrap_column` of 0 disables wrapping.
+    pub fn compute(line: &str, injected: &[InjectedText], wrap_column: u32, tab_size: u32, wrapping_indent: WrappingIndent) -> Self {
+        let input: Vec<char> = line.chars().collect();
+        let mut injected: Vec<&InjectedText> = injected.iter().collect();
+        injected.sort_by_key(|t| t.column);
+
+        let mut output = Vec::with_capacity(input.len());
+        let mut injection_offsets = Vec::new();
+        let mut injection_lengths = Vec::new();
+        let mut next = 0;
+        for text in injected {
+            let offset = (text.column.max(1) as usize - 1).min(input.len());
+            output.extend_from_slice(&input[next..offset]);
+            output.extend(text.content.chars());
+            injection_offsets.push(offset);
+            injection_lengths.push(text.content.chars().count());
+            next = offset;
+        }
+        output.extend_from_slice(&input[next..]);
+
+        let tab_size = tab_size.max(1) as usize;
+        let wrap_column = wrap_column as usize;
+        let (break_offsets, wrapped_text_indent_length) = if wrap_column > 0 {
+            let indent = wrapped_text_indent_length(&input, wrap_column, tab_size, wrapping_indent);
+            (compute_break_offsets(&output, wrap_column, tab_size, indent), indent)
+        } else {
+            (Vec::new(), 0)
+        };
+
+        Self { output, injection_offsets, injection_lengths, break_offsets, wrapped_text_indent_length }
+    }
+
+    /// True when the line maps 1:1 onto a single view line.
+    pub fn is_trivial(&self) -> bool {
+        self.break_offsets.is_empty() && self.injection_offsets.is_empty()
+    }
+
+    pub fn output_line_count(&self) -> usize {
+        self.break_offsets.len() + 1
+    }
+
+    /// Content of the view line for `segment`, including the wrapping indent.
+    pub fn view_line_content(&self, segment: usize) -> String {
+        let (start, end) = self.segment_bounds(segment);
+        let mut content = " ".repeat(self.indent_of(segment));
+        content.extend(&self.output[start..end]);
+        content
+    }
+
+    /// Maps a 1-based model column to (segment, 1-based view column).
+    pub fn model_to_view(&self, model_column: u32) -> (usize, u32) {
+        let input = model_column.saturating_sub(1) as usize;
+        let mut output = input;
+        for (offset, length) in self.injection_offsets.iter().zip(&self.injection_lengths) {
+            if *offset >= input {
+                break;
+            }
+            output += length;
+        }
+        let output = output.min(self.output.len());
+        let segment = self.break_offsets.partition_point(|&b| b <= output);
+        let (start, _) = self.segment_bounds(segment);
+        (segment, (self.indent_of(segment) + output - start) as u32 + 1)
+    }
+
+    /// Maps a segment and 1-based view column back to a 1-based model column.
+    /// Columns inside injected text snap to the injection point.
+    pub fn view_to_model(&self, segment: usize, view_column: u32) -> u32 {
+        let segment = segment.min(self.break_offsets.len());
+        let (start, end) = self.segment_bounds(segment);
+        let local = (view_column.saturating_sub(1) as usize).saturating_sub(self.indent_of(segment));
+        let output = start + local.min(end - start);
+
+        let mut delta = 0;
+        for (offset, length) in self.injection_offsets.iter().zip(&self.injection_lengths) {
+            let injected_start = offset + delta;
+            if output < injected_start {
+                break;
+            }
+            if output < injected_start + length {
+                return *offset as u32 + 1;
+            }
+            delta += length;
+        }
+        (output - delta) as u32 + 1
+    }
+
+    fn segment_bounds(&self, segment: usize) -> (usize, usize) {
+        let start = if segment == 0 { 0 } else { self.break_offsets[segment - 1] };
+        let end = self.break_offsets.get(segment).copied().unwrap_or(self.output.len());
+        (start, end)
+    }
+
+    fn indent_of(&self, segment: usize) -> usize {
+        if segment == 0 { 0 } else { self.wrapped_text_indent_length }
+    }
+}
+
+fn wrapped_text_indent_length(line: &[char], wrap_column: usize, tab_size: usize, wrapping_indent: WrappingIndent) -> usize {
+    if wrapping_indent == WrappingIndent::None {
+        return 0;
+    }
+    let Some(first_non_whitespace) = line.iter().position(|c| !c.is_whitespace()) else {
+        return 0;
+    };
+    let mut indent = line[..first_non_whitespace].iter().fold(0, |visible, &ch| visible + char_width(ch, visible, tab_size));
+    let additional_tabs = match wrapping_indent {
+        WrappingIndent::DeepIndent => 2,
+        WrappingIndent::Indent => 1,
+        _ => 0,
+    };
+    for _ in 0..additional_tabs {
+        indent += tab_size - indent % tab_size;
+    }
+    // Stick to the start of the line if not even a wide character fits after the indent.
+    if indent + 2 > wrap_column { 0 } else { indent }
+}
+
+/// Greedy line breaking: fill each view line up to `wrap_column` visible
+/// columns and break at the last allowed break point, or mid-word if there
+/// is none. Trailing whitespace may overflow the wrap column.
+fn compute_break_offsets(chars: &[char], wrap_column: usize, tab_size: usize, indent: usize) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut segment_start = 0;
+    let mut visible = 0;
+    let mut candidate: Option<usize> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        if i > segment_start && can_break(chars[i - 1], ch) {
+            candidate = Some(i);
+        }
+        let width = char_width(ch, visible, tab_size);
+        if visible + width > wrap_column && i > segment_start && !matches!(ch, ' ' | '\t') {
+            let break_at = candidate.unwrap_or(i);
+            breaks.push(break_at);
+            segment_start = break_at;
+            candidate = None;
+            // Re-measure the characters that moved to the new view line, then
+            // look at character `i` again from its new column.
+            visible = indent;
+            for j in break_at..i {
+                if j > break_at && can_break(chars[j - 1], chars[j]) {
+                    candidate = Some(j);
+                }
+                visible += char_width(chars[j], visible, tab_size);
+            }
+            continue;
+        }
+        visible += width;
+        i += 1;
+    }
+    breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments(projection: &ModelLineProjection) -> Vec<String> {
+        (0..projection.output_line_count()).map(|i| projection.view_line_content(i)).collect()
+    }
+
+    #[test]
+    fn test_breaks_at_word_boundaries() {
+        let p = ModelLineProjection::compute("the quick brown fox jumps", &[], 10, 4, WrappingIndent::None);
+        assert_eq!(segments(&p), vec!["the quick ", "brown fox ", "jumps"]);
+
+        let p = ModelLineProjection::compute("averyveryverylongword", &[], 8, 4, WrappingIndent::None);
+        assert_eq!(segments(&p), vec!["averyver", "yverylon", "gword"]);
+
+        let p = ModelLineProjection::compute("call(a.b, c)", &[], 8, 4, WrappingIndent::None);
+        assert_eq!(segments(&p), vec!["call(a.", "b, c)"]);
+    }
+
+    #[test]
+    fn test_wrapping_indent_and_tabs() {
+        let p = ModelLineProjection::compute("\tone two three", &[], 12, 4, WrappingIndent::Same);
+        assert_eq!(segments(&p), vec!["\tone two ", "    three"]);
+        assert_eq!(p.model_to_view(10), (1, 5));
+        assert_eq!(p.view_to_model(1, 5), 10);
+        assert_eq!(p.view_to_model(1, 1), 10);
+
+        let p = ModelLineProjection::compute("  aaaa bbbb cccc", &[], 10, 2, WrappingIndent::Indent);
+        assert_eq!(segments(&p), vec!["  aaaa ", "    bbbb ", "    cccc"]);
+    }
+
+    #[test]
+    fn test_wide_characters_take_two_columns() {
+        let p = ModelLineProjection::compute("日本語のテキストです", &[], 8, 4, WrappingIndent::None);
+        assert_eq!(segments(&p), vec!["日本語の", "テキスト", "です"]);
+        assert_eq!(visible_column_at("日本a", 3, 4), 5);
+        assert_eq!(char_count_at_visible_column("日本a", 4, 4), 2);
+    }
+
+    #[test]
+    fn test_injected_text_shifts_view_columns() {
+        let hint = InjectedText { column: 4, content: ": i32".into() };
+        let p = ModelLineProjection::compute("let x = 1;", &[InjectedText { column: 6, content: ": i32".into() }], 0, 4, WrappingIndent::None);
+        assert_eq!(p.view_line_content(0), "let x: i32 = 1;");
+        assert_eq!(p.model_to_view(6), (0, 6));
+        assert_eq!(p.model_to_view(7), (0, 12));
+        assert_eq!(p.view_to_model(0, 8), 6);
+        assert_eq!(p.view_to_model(0, 12), 7);
+
+        let p = ModelLineProjection::compute("abc def", &[hint], 6, 4, WrappingIndent::None);
+        assert_eq!(segments(&p), vec!["abc: ", "i32 ", "def"]);
+        assert_eq!(p.model_to_view(5), (2, 1));
+        assert_eq!(p.view_to_model(2, 1), 5);
+    }
+}
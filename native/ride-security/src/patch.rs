@@ -0,0 +1,520 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! RIDE Patch Engine
+//!
+//! Parses unified and `git diff` patches and applies them the way GNU `patch` does:
+//! - Offset search around the expected line, carrying the offset to later hunks
+//! - Fuzz: dropping up to N leading/trailing context lines when the exact hunk does not match
+//! - Reverse apply, and per-hunk selection so callers can accept or reject hunks
+//! - Rejected hunks are reported with the line they were expected at
+//!
+//! Works on strings, on files under a directory (including git renames), and on a
+//! `TextModel` (as one undo step).
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct PatchHunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Text after the closing `@@`, usually the enclosing function.
+    pub section: String,
+    /// Body lines including their ' ', '-', '+' or '\' prefix.
+    pub lines: Vec<String>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FilePatch {
+    /// `None` for `/dev/null`, i.e. a created file.
+    pub old_path: Option<String>,
+    /// `None` for `/dev/null`, i.e. a deleted file.
+    pub new_path: Option<String>,
+    pub hunks: Vec<PatchHunk>,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct PatchOptions {
+    pub reverse: Option<bool>,
+    /// Context lines that may be ignored at each end of a hunk. Defaults to 2.
+    pub fuzz: Option<u32>,
+    /// How far from the expected line a hunk may be found. Unlimited by default.
+    pub max_offset: Option<u32>,
+    /// Indices of the hunks to apply; the others are reported as "skipped".
+    pub hunks: Option<Vec<u32>>,
+    /// Compute the result without writing files.
+    pub dry_run: Option<bool>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct HunkOutcome {
+    pub index: u32,
+    /// "applied", "rejected" or "skipped".
+    pub status: String,
+    /// Line where the hunk was applied, or where it was expected when rejected.
+    pub line_number: u32,
+    pub offset: i32,
+    pub fuzz: u32,
+}
+
+#[napi(object)]
+pub struct PatchResult {
+    pub text: String,
+    pub hunks: Vec<HunkOutcome>,
+    pub applied: u32,
+    pub rejected: u32,
+}
+
+#[napi(object)]
+pub struct FilePatchResult {
+    pub path: String,
+    /// Previous path when the patch renames the file.
+    pub renamed_from: Option<String>,
+    pub created: bool,
+    pub deleted: bool,
+    pub hunks: Vec<HunkOutcome>,
+    pub rejected: u32,
+}
+
+/// A hunk matched against the target: lines `[start, end)` are replaced by `text`.
+pub(crate) struct Placement {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+fn parse_path(raw: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path);
+    Some(path.to_string())
+}
+
+fn parse_range(raw: &str) -> Option<(u32, u32)> {
+    match raw.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((raw.parse().ok()?, 1)),
+    }
+}
+
+/// Parses a unified or git patch. Git `rename from` / `rename to` lines set the paths of
+/// their file; other lines outside file headers and hunks (commit messages, `index` lines,
+/// mode changes) are ignored.
+#[napi]
+pub fn parse_patch(patch: String) -> Result<Vec<FilePatch>> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut lines = patch.lines().peekable();
+    // A `diff --git` header already opened the file that the following `---` describes.
+    let mut header_open = false;
+
+    while let Some(line) = lines.next() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let (old, new) = rest.split_once(" b/").map(|(a, b)| (a, format!("b/{}", b))).unwrap_or((rest, rest.to_string()));
+            files.push(FilePatch { old_path: parse_path(old), new_path: parse_path(&new), hunks: Vec::new() });
+            header_open = true;
+        } else if let Some(rest) = line.strip_prefix("rename from ").filter(|_| header_open) {
+            files.last_mut().unwrap().old_path = Some(rest.to_string());
+        } else if let Some(rest) = line.strip_prefix("rename to ").filter(|_| header_open) {
+            files.last_mut().unwrap().new_path = Some(rest.to_string());
+        } else if let Some(rest) = line.strip_prefix("--- ") {
+            if !header_open {
+                files.push(FilePatch { old_path: None, new_path: None, hunks: Vec::new() });
+            }
+            header_open = false;
+            files.last_mut().unwrap().old_path = parse_path(rest);
+        } else if let Some(rest) = line.strip_prefix("+++ ") {
+            if let Some(file) = files.last_mut() {
+                file.new_path = parse_path(rest);
+            }
+        } else if let Some(rest) = line.strip_prefix("@@ -") {
+            let invalid = || Error::from_reason(format!("Invalid hunk header: {}", line));
+            let (ranges, section) = rest.split_once(" @@").ok_or_else(invalid)?;
+            let (old, new) = ranges.split_once(" +").ok_or_else(invalid)?;
+            let (old_start, old_lines) = parse_range(old).ok_or_else(invalid)?;
+            let (new_start, new_lines) = parse_range(new).ok_or_else(invalid)?;
+
+            let mut body = Vec::new();
+            let (mut old_left, mut new_left) = (old_lines, new_lines);
+            while old_left > 0 || new_left > 0 {
+                let Some(body_line) = lines.next() else {
+                    return Err(Error::from_reason(format!("Truncated hunk: {}", line)));
+                };
+                match body_line.chars().next() {
+                    Some(' ') | None => { old_left = old_left.saturating_sub(1); new_left = new_left.saturating_sub(1); }
+                    Some('-') => old_left = old_left.saturating_sub(1),
+                    Some('+') => new_left = new_left.saturating_sub(1),
+                    Some('\\') => {}
+                    _ => return Err(Error::from_reason(format!("Unexpected line in hunk: {}", body_line))),
+                }
+                body.push(if body_line.is_empty() { " ".to_string() } else { body_line.to_string() });
+            }
+            if let Some(marker) = lines.next_if(|l| l.starts_with('\\')) {
+                body.push(marker.to_string());
+            }
+
+            if files.is_empty() {
+                files.push(FilePatch { old_path: None, new_path: None, hunks: Vec::new() });
+            }
+            header_open = false;
+            let section = section.trim().to_string();
+            files.last_mut().unwrap().hunks.push(PatchHunk { old_start, old_lines, new_start, new_lines, section, lines: body });
+        }
+    }
+    Ok(files)
+}
+
+/// A body line reduced to its kind and text; `no_eol` marks a following "\ No newline".
+struct BodyLine<'a> {
+    kind: char,
+    text: &'a str,
+    no_eol: bool,
+}
+
+fn body_lines(hunk: &PatchHunk, reverse: bool) -> Vec<BodyLine<'_>> {
+    let mut body: Vec<BodyLine> = Vec::with_capacity(hunk.lines.len());
+    for line in &hunk.lines {
+        let (kind, text) = line.split_at(line.chars().next().map_or(0, char::len_utf8));
+        match kind {
+            "\\" => if let Some(last) = body.last_mut() { last.no_eol = true },
+            "-" | "+" if reverse => body.push(BodyLine { kind: if kind == "-" { '+' } else { '-' }, text, no_eol: false }),
+            _ => body.push(BodyLine { kind: kind.chars().next().unwrap_or(' '), text, no_eol: false }),
+        }
+    }
+    body
+}
+
+fn same_line(file_line: &str, patch_line: &str) -> bool {
+    file_line.trim_end_matches(['\r', '\n']) == patch_line
+}
+
+/// Finds where each selected hunk applies in `lines` (terminators included) and what replaces it.
+pub(crate) fn place_hunks(lines: &[&str], hunks: &[PatchHunk], options: &PatchOptions) -> (Vec<Placement>, Vec<HunkOutcome>) {
+    let reverse = options.reverse.unwrap_or(false);
+    let max_fuzz = options.fuzz.unwrap_or(2) as usize;
+    let max_offset = options.max_offset.map_or(lines.len(), |o| o as usize);
+    let eol = lines.first().filter(|l| l.ends_with("\r\n")).map_or("\n", |_| "\r\n");
+
+    let mut placements = Vec::new();
+    let mut outcomes = Vec::with_capacity(hunks.len());
+    let mut carried: isize = 0;
+    let mut min_start = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let (old_start, old_lines) = if reverse { (hunk.new_start, hunk.new_lines) } else { (hunk.old_start, hunk.old_lines) };
+        // An empty old side names the line *after which* the new lines go.
+        let expected = if old_lines == 0 { old_start as isize } else { old_start as isize - 1 } + carried;
+        let outcome = |status: &str, line: isize, offset: isize, fuzz: usize| HunkOutcome {
+            index: index as u32,
+            status: status.to_string(),
+            line_number: (line + 1).max(1) as u32,
+            offset: offset as i32,
+            fuzz: fuzz as u32,
+        };
+        if options.hunks.as_ref().is_some_and(|accepted| !accepted.contains(&(index as u32))) {
+            outcomes.push(outcome("skipped", expected, 0, 0));
+            continue;
+        }
+
+        let body = body_lines(hunk, reverse);
+        let leading = body.iter().take_while(|l| l.kind == ' ').count();
+        let trailing = body.iter().rev().take_while(|l| l.kind == ' ').count().min(body.len() - leading);
+        let mut found = None;
+        for fuzz in 0..=max_fuzz {
+            let (skip_front, skip_back) = (fuzz.min(leading), fuzz.min(trailing));
+            if fuzz > 0 && skip_front < fuzz && skip_back < fuzz {
+                break;
+            }
+            let part = &body[skip_front..body.len() - skip_back];
+            let old: Vec<&str> = part.iter().filter(|l| l.kind != '+').map(|l| l.text).collect();
+            let target = expected + skip_front as isize;
+            let matches_at = |pos: isize| {
+                pos >= min_start as isize
+                    && pos as usize + old.len() <= lines.len()
+                    && old.iter().enumerate().all(|(k, text)| same_line(lines[pos as usize + k], text))
+            };
+            let offset = (0..=max_offset as isize)
+                .flat_map(|d| if d == 0 { vec![0] } else { vec![d, -d] })
+                .find(|&d| matches_at(target + d));
+            if let Some(offset) = offset {
+                found = Some((fuzz, skip_front, part, (target + offset) as usize, offset, old.len()));
+                break;
+            }
+        }
+
+        let Some((fuzz, skip_front, part, start, offset, old_len)) = found else {
+            outcomes.push(outcome("rejected", expected, 0, 0));
+            continue;
+        };
+        let mut text = String::new();
+        let mut k = start;
+        for line in part {
+            match line.kind {
+                '+' => {
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push_str(eol);
+                    }
+                    text.push_str(line.text);
+                    if !line.no_eol {
+                        text.push_str(eol);
+                    }
+                }
+                '-' => k += 1,
+                _ => {
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push_str(eol);
+                    }
+                    text.push_str(lines[k]);
+                    k += 1;
+                }
+            }
+        }
+        carried += offset;
+        min_start = start + old_len;
+        outcomes.push(outcome("applied", start as isize - skip_front as isize, offset, fuzz));
+        placements.push(Placement { start, end: start + old_len, text });
+    }
+    (placements, outcomes)
+}
+
+fn patch_text(content: &str, hunks: &[PatchHunk], options: &PatchOptions) -> PatchResult {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let (placements, outcomes) = place_hunks(&lines, hunks, options);
+    let mut text = String::with_capacity(content.len());
+    let mut next = 0;
+    for placement in &placements {
+        text.extend(lines[next..placement.start].iter().copied());
+        if !placement.text.is_empty() && !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&placement.text);
+        next = placement.end;
+    }
+    text.extend(lines[next..].iter().copied());
+    summarize(text, outcomes)
+}
+
+pub(crate) fn summarize(text: String, hunks: Vec<HunkOutcome>) -> PatchResult {
+    let applied = hunks.iter().filter(|h| h.status == "applied").count() as u32;
+    let rejected = hunks.iter().filter(|h| h.status == "rejected").count() as u32;
+    PatchResult { text, hunks, applied, rejected }
+}
+
+/// Applies the hunks of a single-file patch to `content`.
+#[napi]
+pub fn apply_patch(content: String, patch: String, options: Option<PatchOptions>) -> Result<PatchResult> {
+    let hunks = single_file_hunks(patch)?;
+    Ok(patch_text(&content, &hunks, &options.unwrap_or_default()))
+}
+
+pub(crate) fn single_file_hunks(patch: String) -> Result<Vec<PatchHunk>> {
+    let mut files = parse_patch(patch)?;
+    match files.len() {
+        0 => Err(Error::from_reason("Patch contains no hunks")),
+        1 => Ok(files.remove(0).hunks),
+        n => Err(Error::from_reason(format!("Patch touches {} files; expected one", n))),
+    }
+}
+
+/// Joins a patch path to the canonical `root`. Absolute paths, `..` and symlinks leading
+/// out of `root` are refused so a patch cannot touch files outside the directory it is
+/// applied to.
+fn path_below(root: &Path, relative: &str) -> Result<PathBuf> {
+    let path = Path::new(relative);
+    let outside = || Error::from_reason(format!("invalid path: {}: outside the target directory", relative));
+    if path.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(outside());
+    }
+    // A symlink below `root` can still lead out of it: resolve the deepest part of the path
+    // that exists, which for a new file is the directory it will be created in.
+    let joined = root.join(path);
+    let existing = joined.ancestors().find(|p| p.symlink_metadata().is_ok()).unwrap_or(root);
+    let resolved = existing.canonicalize().map_err(|e| Error::from_reason(format!("invalid path: {}: {}", relative, e)))?;
+    if !resolved.starts_with(root) {
+        return Err(outside());
+    }
+    Ok(joined)
+}
+
+/// Applies every file of a patch below `root`, creating, deleting and renaming files as
+/// the patch says. Files are written when at least one of their hunks applied, or when
+/// they are renamed. Nothing is written when a path leaves `root` (directly or through a
+/// symlink), a created file already exists or a patched file cannot be read.
+#[napi]
+pub fn apply_patch_to_directory(root: String, patch: String, options: Option<PatchOptions>) -> Result<Vec<FilePatchResult>> {
+    let options = options.unwrap_or_default();
+    let reverse = options.reverse.unwrap_or(false);
+    let dry_run = options.dry_run.unwrap_or(false);
+    let root = fs::canonicalize(&root).map_err(|e| Error::from_reason(format!("invalid root: {}: {}", root, e)))?;
+    let root = root.as_path();
+
+    let mut planned = Vec::new();
+    for file in parse_patch(patch)? {
+        let (source, target) = if reverse { (file.new_path, file.old_path) } else { (file.old_path, file.new_path) };
+        let Some(relative) = target.clone().or(source.clone()) else { continue };
+        let path = path_below(root, &relative)?;
+        let source_path = source.as_deref().map(|s| path_below(root, s)).transpose()?;
+        if source.is_none() && path.exists() {
+            return Err(Error::from_reason(format!("create failed: {}: file already exists", relative)));
+        }
+        planned.push((file.hunks, source, target, source_path, path, relative));
+    }
+
+    let mut patched = Vec::new();
+    for (hunks, source, target, source_path, path, relative) in planned {
+        let content = match &source_path {
+            Some(source_path) => fs::read_to_string(source_path).map_err(|e| Error::from_reason(format!("read failed: {}: {}", source.as_deref().unwrap_or_default(), e)))?,
+            None => String::new(),
+        };
+        patched.push((patch_text(&content, &hunks, &options), source, target, source_path, path, relative));
+    }
+
+    let mut results = Vec::new();
+    for (result, source, target, source_path, path, relative) in patched {
+        let (created, deleted) = (source.is_none(), target.is_none());
+        let renamed_from = source.filter(|s| target.as_ref().is_some_and(|t| t != s));
+        if !dry_run && (result.applied > 0 || renamed_from.is_some()) {
+            if deleted && result.text.is_empty() {
+                fs::remove_file(&path).map_err(|e| Error::from_reason(format!("remove failed: {}: {}", relative, e)))?;
+            } else {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).ok();
+                }
+                fs::write(&path, &result.text).map_err(|e| Error::from_reason(format!("write failed: {}: {}", relative, e)))?;
+                if let (Some(old), Some(source_path)) = (&renamed_from, &source_path) {
+                    fs::remove_file(source_path).map_err(|e| Error::from_reason(format!("remove failed: {}: {}", old, e)))?;
+                }
+            }
+        }
+        results.push(FilePatchResult { path: relative, renamed_from, created, deleted, hunks: result.hunks, rejected: result.rejected });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "diff --git a/src/x.txt b/src/x.txt\n--- a/src/x.txt\n+++ b/src/x.txt\n@@ -2,3 +2,3 @@ fn main\n b\n-c\n+C\n d\n@@ -8,2 +8,3 @@\n h\n i\n+j\n";
+
+    #[test]
+    fn test_parse_patch() {
+        let files = parse_patch(PATCH.into()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].old_path.as_deref(), Some("src/x.txt"));
+        assert_eq!(files[0].hunks.len(), 2);
+        assert_eq!(files[0].hunks[0].section, "fn main");
+        assert_eq!(files[0].hunks[1].lines, vec![" h", " i", "+j"]);
+        assert!(parse_patch("@@ -1,2 +1,2 @@\n a\n".into()).is_err());
+    }
+
+    #[test]
+    fn test_apply_with_offset_fuzz_and_reverse() {
+        // Two extra lines at the top shift both hunks; "d" changed, so the first hunk needs fuzz.
+        let content = "0\n1\na\nb\nc\nD\ne\nf\ng\nh\ni\n";
+        let result = apply_patch(content.into(), PATCH.into(), None).unwrap();
+        assert_eq!(result.text, "0\n1\na\nb\nC\nD\ne\nf\ng\nh\ni\nj\n");
+        assert_eq!((result.hunks[0].line_number, result.hunks[0].offset, result.hunks[0].fuzz), (4, 2, 1));
+        assert_eq!((result.hunks[1].line_number, result.hunks[1].offset), (10, 0));
+
+        let reversed = apply_patch(result.text, PATCH.into(), Some(PatchOptions { reverse: Some(true), fuzz: Some(1), ..Default::default() })).unwrap();
+        assert_eq!(reversed.text, content);
+    }
+
+    #[test]
+    fn test_reject_and_select_hunks() {
+        let content = "a\nb\nc\nd\n";
+        let options = PatchOptions { fuzz: Some(0), hunks: Some(vec![0]), ..Default::default() };
+        let result = apply_patch(content.into(), PATCH.into(), Some(options)).unwrap();
+        assert_eq!(result.text, "a\nb\nC\nd\n");
+        assert_eq!(result.hunks[1].status, "skipped");
+
+        let result = apply_patch("x\ny\n".into(), PATCH.into(), None).unwrap();
+        assert_eq!((result.applied, result.rejected), (0, 2));
+        assert_eq!(result.hunks[0].line_number, 2);
+    }
+
+    #[test]
+    fn test_apply_patch_to_text_model() {
+        let model = crate::text_model::TextModel::new("file:///x.txt".into(), "a\nb\nc\nd\ne\nf\ng\nh\ni".into());
+        let result = model.apply_patch(PATCH.into(), None).unwrap();
+        assert_eq!(result.applied, 2);
+        assert_eq!(model.get_value(), "a\nb\nC\nd\ne\nf\ng\nh\ni\nj\n");
+        model.undo();
+        assert_eq!(model.get_value(), "a\nb\nc\nd\ne\nf\ng\nh\ni");
+    }
+
+    #[test]
+    fn test_apply_patch_to_directory() {
+        let root = std::env::temp_dir().join(format!("ride-patch-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/x.txt"), "a\nb\nc\nd\ne\nf\ng\nh\ni\n").unwrap();
+        let patch = format!("{}--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+hello\n", PATCH);
+
+        let results = apply_patch_to_directory(root.to_string_lossy().into(), patch, None).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[1].created);
+        assert_eq!(fs::read_to_string(root.join("src/x.txt")).unwrap(), "a\nb\nC\nd\ne\nf\ng\nh\ni\nj\n");
+        assert_eq!(fs::read_to_string(root.join("new.txt")).unwrap(), "hello\n");
+
+        // Creating an existing file fails before anything is written.
+        let recreate = "--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+again\n";
+        assert!(apply_patch_to_directory(root.to_string_lossy().into(), recreate.into(), None).is_err());
+        assert_eq!(fs::read_to_string(root.join("new.txt")).unwrap(), "hello\n");
+
+        let rename = "diff --git a/new.txt b/moved/new.txt\nsimilarity index 100%\nrename from new.txt\nrename to moved/new.txt\n";
+        let results = apply_patch_to_directory(root.to_string_lossy().into(), rename.into(), None).unwrap();
+        assert_eq!((results[0].path.as_str(), results[0].renamed_from.as_deref()), ("moved/new.txt", Some("new.txt")));
+        assert!(!root.join("new.txt").exists());
+        assert_eq!(fs::read_to_string(root.join("moved/new.txt")).unwrap(), "hello\n");
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_apply_patch_to_directory_rejects_paths_outside_root() {
+        let root = std::env::temp_dir().join(format!("ride-patch-escape-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        for target in ["b/../escaped.txt", "/tmp/ride-patch-escaped.txt"] {
+            let patch = format!("--- /dev/null\n+++ {}\n@@ -0,0 +1 @@\n+x\n", target);
+            assert!(apply_patch_to_directory(root.to_string_lossy().into(), patch, None).is_err());
+        }
+        assert!(!root.parent().unwrap().join("escaped.txt").exists());
+        assert!(!Path::new("/tmp/ride-patch-escaped.txt").exists());
+
+        #[cfg(unix)]
+        {
+            let outside = std::env::temp_dir().join(format!("ride-patch-outside-{}", std::process::id()));
+            fs::create_dir_all(&outside).unwrap();
+            std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+            let patch = "--- /dev/null\n+++ b/link/escaped.txt\n@@ -0,0 +1 @@\n+x\n";
+            assert!(apply_patch_to_directory(root.to_string_lossy().into(), patch.into(), None).is_err());
+            assert!(!outside.join("escaped.txt").exists());
+            fs::remove_dir_all(outside).ok();
+        }
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_apply_patch_to_directory_writes_nothing_when_a_read_fails() {
+        let root = std::env::temp_dir().join(format!("ride-patch-unreadable-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/x.txt"), "a\nb\nc\nd\n").unwrap();
+        let patch = format!("{}--- a/missing.txt\n+++ b/missing.txt\n@@ -1 +1 @@\n-a\n+b\n", PATCH);
+        let err = apply_patch_to_directory(root.to_string_lossy().into(), patch, None).err().unwrap();
+        assert!(err.reason.contains("read failed: missing.txt"), "{}", err.reason);
+        assert_eq!(fs::read_to_string(root.join("src/x.txt")).unwrap(), "a\nb\nc\nd\n");
+        fs::remove_dir_all(root).ok();
+    }
+}
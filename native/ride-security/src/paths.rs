@@ -1044,7 +1044,7 @@ impl URI {
         URI::new(scheme, self.authority.clone(), self.path.clone(), self.query.clone(), self.fragment.clone())
     }
 
-    #[napi(factory)]
+    #[napi]
     pub fn revive(data: JsUnknown) -> Option<URI> {
         // Complex parsing logic would go here.
         // Assuming passed object matches UriComponents roughly.
@@ -2383,7 +2383,7 @@ mod comprehensive_path_tests {
              RelativeTestCase { from: "/baz", to: "/baz-quux", expected: "../baz-quux" },
          ];
          for case in cases {
-             assert_eq!(PosixPath::relative(case.from.to_string(), case.to_string()), case.expected, "PosixPath::relative({}, {})", case.from, case.to);
+             assert_eq!(PosixPath::relative(case.from.to_string(), case.to.to_string()), case.expected, "PosixPath::relative({}, {})", case.from, case.to);
          }
     }
 
@@ -2449,8 +2449,8 @@ mod comprehensive_path_tests {
 
     #[test]
     fn test_uri_with() {
-        let uri = URI::parse("foo:bar/path".to_string(), None);
-        let uri2 = uri.with(UriComponents { scheme: "foo".to_string(), authority: None, path: Some("bar/path".to_string()), query: None, fragment: None });
+        let mut uri = URI::parse("foo:bar/path".to_string(), None);
+        let mut uri2 = uri.with(UriComponents { scheme: "foo".to_string(), authority: None, path: Some("bar/path".to_string()), query: None, fragment: None });
         // identity check not possible in Rust easily without pointer checks, but value equality:
         assert_eq!(uri.to_string(None), uri2.to_string(None));
 
@@ -12,25 +12,68 @@
 //! - Line-table caching for sub-millisecond coordinate resolution
 //! - Atomic Snapshot support for non-blocking search and rendering
 //! - Memory-efficient node reuse
+//!
+//! Offsets are byte offsets into the UTF-8 text; columns are 1-based character
+//! columns, matching `TextModel`.
 
-use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use std::sync::Arc;
+use crate::position::Position;
+
+/// Target size of an added-text buffer before a new one is started. Bounds the
+/// copy made when an edit lands while a snapshot still shares the buffer.
+const AVERAGE_BUFFER_SIZE: usize = 65535;
+
+/// Index of the sentinel leaf. Every absent child or parent points here.
+const NIL: usize = 0;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum NodeColor { Red, Black }
 
+/// An immutable-once-shared text buffer together with the offsets of its line feeds.
+#[derive(Clone, Debug, Default)]
+struct StringBuffer {
+    text: Vec<u8>,
+    line_feeds: Vec<u32>,
+}
+
+impl StringBuffer {
+    fn new(text: Vec<u8>) -> Self {
+        let line_feeds = text.iter().enumerate()
+            .filter(|&(_, &b)| b == b'\n')
+            .map(|(i, _)| i as u32)
+            .collect();
+        Self { text, line_feeds }
+    }
+
+    fn append(&mut self, bytes: &[u8]) {
+        let base = self.text.len() as u32;
+        self.line_feeds.extend(bytes.iter().enumerate().filter(|&(_, &b)| b == b'\n').map(|(i, _)| base + i as u32));
+        self.text.extend_from_slice(bytes);
+    }
+
+    /// Number of line feeds in `[start, end)`.
+    fn count_lf(&self, start: u32, end: u32) -> u32 {
+        (self.line_feeds.partition_point(|&p| p < end) - self.line_feeds.partition_point(|&p| p < start)) as u32
+    }
+
+    /// Offset of the `k`-th (1-based) line feed at or after `start`.
+    fn nth_lf_from(&self, start: u32, k: u32) -> u32 {
+        self.line_feeds[self.line_feeds.partition_point(|&p| p < start) + k as usize - 1]
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PieceNode {
-    buffer_index: u32, // 0 for original, >=1 for addition buffers
+    buffer_index: u32, // original chunks first, then addition buffers
     start: u32,
     length: u32,
     line_feeds: u32,
 
-    // Tree navigation (indexes into PieceTree.nodes)
-    left: Option<usize>,
-    right: Option<usize>,
-    parent: Option<usize>,
+    // Tree navigation (indexes into PieceTree.nodes, NIL when absent)
+    left: usize,
+    right: usize,
+    parent: usize,
     color: NodeColor,
 
     // Augmented totals for subtree
@@ -38,238 +81,879 @@ pub struct PieceNode {
     line_feeds_subtree: u32,
 }
 
+impl PieceNode {
+    fn sentinel() -> Self {
+        Self {
+            buffer_index: 0,
+            start: 0,
+            length: 0,
+            line_feeds: 0,
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+            color: NodeColor::Black,
+            size_subtree: 0,
+            line_feeds_subtree: 0,
+        }
+    }
+}
+
 #[napi]
+#[derive(Clone)]
 pub struct PieceTree {
-    buffers: Vec<Vec<u8>>,
+    buffers: Vec<Arc<StringBuffer>>,
     nodes: Vec<PieceNode>,
-    root: Option<usize>,
-    total_length: u32,
-    total_line_feeds: u32,
+    free_nodes: Vec<usize>,
+    root: usize,
+    /// Buffers `0..original_buffer_count` hold the initial content and are never appended to.
+    original_buffer_count: usize,
 }
 
 #[napi]
 impl PieceTree {
     #[napi(constructor)]
     pub fn new(content: String) -> Self {
-        let original = content.into_bytes();
-        let len = original.len() as u32;
-        let lf = count_lf(&original);
-
         let mut pt = Self {
-            buffers: vec![original],
+            buffers: Vec::new(),
             nodes: Vec::with_capacity(1024),
-            root: None,
-            total_length: len,
-            total_line_feeds: lf,
+            free_nodes: Vec::new(),
+            root: NIL,
+            original_buffer_count: 0,
         };
-
-        if len > 0 {
-            let root_node = PieceNode {
-                buffer_index: 0,
-                start: 0,
-                length: len,
-                line_feeds: lf,
-                left: None,
-                right: None,
-                parent: None,
-                color: NodeColor::Black,
-                size_subtree: len,
-                line_feeds_subtree: lf,
-            };
-            pt.nodes.push(root_node);
-            pt.root = Some(0);
+        pt.nodes.push(PieceNode::sentinel());
+
+        // The original content is chunked so a single piece never spans more
+        // than one buffer-sized region.
+        let bytes = content.into_bytes();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let mut end = (offset + AVERAGE_BUFFER_SIZE).min(bytes.len());
+            while end < bytes.len() && (bytes[end] & 0xC0) == 0x80 {
+                end += 1;
+            }
+            let chunk = StringBuffer::new(bytes[offset..end].to_vec());
+            let length = chunk.text.len() as u32;
+            let lf = chunk.line_feeds.len() as u32;
+            pt.buffers.push(Arc::new(chunk));
+            let node = pt.alloc_node((pt.buffers.len() - 1) as u32, 0, length, lf);
+            pt.insert_node_at_end(node);
+            offset = end;
         }
+        pt.original_buffer_count = pt.buffers.len();
         pt
     }
 
     #[napi]
     pub fn get_text(&self) -> String {
-        let mut result = Vec::with_capacity(self.total_length as usize);
+        let mut result = Vec::with_capacity(self.get_length() as usize);
         self.collect_text(self.root, &mut result);
         String::from_utf8_lossy(&result).to_string()
     }
 
-    fn collect_text(&self, node_idx: Option<usize>, result: &mut Vec<u8>) {
-        if let Some(idx) = node_idx {
-            let node = &self.nodes[idx];
-            self.collect_text(node.left, result);
-            let buffer = &self.buffers[node.buffer_index as usize];
-            result.extend_from_slice(&buffer[node.start as usize..(node.start + node.length) as usize]);
-            self.collect_text(node.right, result);
+    fn collect_text(&self, node_idx: usize, result: &mut Vec<u8>) {
+        if node_idx == NIL {
+            return;
+        }
+        let node = &self.nodes[node_idx];
+        self.collect_text(node.left, result);
+        result.extend_from_slice(self.piece_bytes(node_idx));
+        self.collect_text(node.right, result);
+    }
+
+    #[napi]
+    pub fn get_length(&self) -> u32 {
+        self.nodes[self.root].size_subtree
+    }
+
+    #[napi]
+    pub fn get_line_count(&self) -> u32 {
+        self.nodes[self.root].line_feeds_subtree + 1
+    }
+
+    /// Text between two byte offsets.
+    #[napi]
+    pub fn get_text_in_range(&self, start_offset: u32, end_offset: u32) -> String {
+        let end = end_offset.min(self.get_length());
+        if start_offset >= end {
+            return String::new();
+        }
+        let mut result = Vec::with_capacity((end - start_offset) as usize);
+        let (mut node, mut in_node) = self.node_at(start_offset);
+        let mut remaining = end - start_offset;
+        while node != NIL && remaining > 0 {
+            let bytes = self.piece_bytes(node);
+            let take = remaining.min(bytes.len() as u32 - in_node);
+            result.extend_from_slice(&bytes[in_node as usize..(in_node + take) as usize]);
+            remaining -= take;
+            in_node = 0;
+            node = self.successor(node);
         }
+        String::from_utf8_lossy(&result).to_string()
     }
 
     #[napi]
     pub fn insert_v2(&mut self, offset: u32, text: String) {
         if text.is_empty() { return; }
+        let offset = offset.min(self.get_length());
         let bytes = text.into_bytes();
-        let len = bytes.len() as u32;
-        let lf = count_lf(&bytes);
-
-        self.buffers.push(bytes);
-        let buf_idx = (self.buffers.len() - 1) as u32;
-
-        if self.root.is_none() {
-            let node = PieceNode {
-                buffer_index: buf_idx,
-                start: 0,
-                length: len,
-                line_feeds: lf,
-                left: None,
-                right: None,
-                parent: None,
-                color: NodeColor::Black,
-                size_subtree: len,
-                line_feeds_subtree: lf,
-            };
-            self.nodes.push(node);
-            self.root = Some(0);
-        } else {
-            self.insert_at_offset(offset, buf_idx, 0, len, lf);
+
+        // Typing fast path: the piece ending at `offset` is the tail of the
+        // current add buffer, so the new text can simply extend it.
+        if offset > 0 {
+            let (prev, prev_off) = self.node_at(offset - 1);
+            let buffer_index = self.nodes[prev].buffer_index as usize;
+            if prev_off + 1 == self.nodes[prev].length
+                && !self.is_original_buffer(buffer_index)
+                && buffer_index == self.buffers.len() - 1
+                && self.nodes[prev].start + self.nodes[prev].length == self.buffers[buffer_index].text.len() as u32
+                && self.buffers[buffer_index].text.len() + bytes.len() <= AVERAGE_BUFFER_SIZE
+            {
+                let buffer = Arc::make_mut(&mut self.buffers[buffer_index]);
+                let lf_before = buffer.line_feeds.len();
+                buffer.append(&bytes);
+                let added_lf = (buffer.line_feeds.len() - lf_before) as u32;
+                self.nodes[prev].length += bytes.len() as u32;
+                self.nodes[prev].line_feeds += added_lf;
+                self.update_metrics_to_root(prev);
+                return;
+            }
         }
 
-        self.total_length += len;
-        self.total_line_feeds += lf;
+        let (buf_idx, start, len, lf) = self.append_to_add_buffer(&bytes);
+        let new_node = self.alloc_node(buf_idx, start, len, lf);
+        if self.root == NIL || offset == self.get_length() {
+            self.insert_node_at_end(new_node);
+        } else {
+            self.insert_at_offset(offset, new_node);
+        }
     }
 
-    fn insert_at_offset(&mut self, offset: u32, buf_idx: u32, start: u32, length: u32, lf: u32) {
-        let (node_idx, node_offset) = self.find_node_at_offset(offset);
+    /// Removes `length` bytes starting at `offset`.
+    #[napi]
+    pub fn delete(&mut self, offset: u32, length: u32) {
+        let total = self.get_length();
+        if length == 0 || offset >= total {
+            return;
+        }
+        let end = (offset + length).min(total);
+        let (start_node, start_off) = self.node_at(offset);
+        let (end_node, end_off) = self.node_at_end(end);
+
+        if start_node == end_node {
+            let node_len = self.nodes[start_node].length;
+            if start_off == 0 && end_off == node_len {
+                self.rb_delete(start_node);
+            } else if start_off == 0 {
+                self.trim_head(start_node, end_off);
+            } else if end_off == node_len {
+                self.trim_tail(start_node, start_off);
+            } else {
+                // Deleting from the middle of a piece: keep the head here and
+                // re-insert the tail as its own piece.
+                let node = self.nodes[start_node].clone();
+                let tail_start = node.start + end_off;
+                let tail_len = node.length - end_off;
+                let tail_lf = self.buffers[node.buffer_index as usize].count_lf(tail_start, tail_start + tail_len);
+                self.trim_tail(start_node, start_off);
+                let tail = self.alloc_node(node.buffer_index, tail_start, tail_len, tail_lf);
+                self.insert_after(start_node, tail);
+            }
+            return;
+        }
 
-        // Logical split if inserting in the middle
-        if node_offset > 0 && node_offset < self.nodes[node_idx].length {
-            self.split_node(node_idx, node_offset);
-            // After split, we want to insert between the two new nodes
-            // Re-find the insertion point which is now between nodes
-            let (new_node_idx, new_node_offset) = self.find_node_at_offset(offset);
-            self.insert_node_after(new_node_idx, new_node_offset, buf_idx, start, length, lf);
-        } else if node_offset == 0 {
-            self.insert_node_before(node_idx, buf_idx, start, length, lf);
+        let mut between = Vec::new();
+        let mut cursor = self.successor(start_node);
+        while cursor != end_node && cursor != NIL {
+            between.push(cursor);
+            cursor = self.successor(cursor);
+        }
+
+        let end_len = self.nodes[end_node].length;
+        if end_off == end_len {
+            between.push(end_node);
         } else {
-            self.insert_node_after(node_idx, node_offset, buf_idx, start, length, lf);
+            self.trim_head(end_node, end_off);
+        }
+        if start_off == 0 {
+            between.push(start_node);
+        } else {
+            self.trim_tail(start_node, start_off);
+        }
+        for node in between {
+            self.rb_delete(node);
         }
     }
 
-    fn find_node_at_offset(&self, mut offset: u32) -> (usize, u32) {
-        let mut curr = self.root.unwrap();
-        loop {
-            let node = &self.nodes[curr];
-            let left_size = node.left.map(|idx| self.nodes[idx].size_subtree).unwrap_or(0);
+    /// Content of the 1-based `line_number`, without its line terminator.
+    #[napi]
+    pub fn get_line_content(&self, line_number: u32) -> String {
+        if line_number == 0 || line_number > self.get_line_count() {
+            return String::new();
+        }
+        let start = self.line_start_offset(line_number - 1);
+        let end = if line_number == self.get_line_count() {
+            self.get_length()
+        } else {
+            self.line_start_offset(line_number) - 1
+        };
+        let mut line = self.get_text_in_range(start, end);
+        if line.ends_with('\r') {
+            line.pop();
+        }
+        line
+    }
+
+    #[napi]
+    pub fn get_line_length(&self, line_number: u32) -> u32 {
+        self.get_line_content(line_number).chars().count() as u32
+    }
+
+    /// Byte offset of a 1-based (line, column) position, clamped to the text.
+    #[napi]
+    pub fn offset_at(&self, line_number: u32, column: u32) -> u32 {
+        let line = line_number.clamp(1, self.get_line_count());
+        let start = self.line_start_offset(line - 1);
+        let content = self.get_line_content(line);
+        let in_line = content.char_indices()
+            .nth(column.saturating_sub(1) as usize)
+            .map(|(i, _)| i)
+            .unwrap_or(content.len());
+        start + in_line as u32
+    }
+
+    /// 1-based (line, column) of a byte offset, clamped to the text.
+    #[napi]
+    pub fn position_at(&self, offset: u32) -> Position {
+        let offset = offset.min(self.get_length());
+        let line_index = self.line_index_at(offset);
+        let start = self.line_start_offset(line_index);
+        let before = self.get_text_in_range(start, offset);
+        Position::new(line_index + 1, before.trim_end_matches('\r').chars().count() as u32 + 1)
+    }
 
-            if offset < left_size {
-                curr = node.left.unwrap();
-            } else if offset < left_size + node.length {
-                return (curr, offset - left_size);
+    /// Immutable view of the current text. Later edits do not affect it, so it
+    /// can be read from a background search while the tree keeps changing.
+    #[napi]
+    pub fn create_snapshot(&self) -> PieceTreeSnapshot {
+        let mut pieces = Vec::new();
+        let mut node = self.leftmost(self.root);
+        while node != NIL {
+            let n = &self.nodes[node];
+            pieces.push((self.buffers[n.buffer_index as usize].clone(), n.start, n.length));
+            node = self.successor(node);
+        }
+        PieceTreeSnapshot {
+            pieces,
+            line_count: self.get_line_count(),
+            read_index: 0,
+        }
+    }
+
+    /// 0-based index of the line containing `offset`.
+    pub fn line_index_at(&self, offset: u32) -> u32 {
+        let mut node = self.root;
+        let mut remaining = offset;
+        let mut lines = 0;
+        while node != NIL {
+            let n = &self.nodes[node];
+            let left_size = self.nodes[n.left].size_subtree;
+            if remaining < left_size {
+                node = n.left;
+            } else if remaining < left_size + n.length || n.right == NIL {
+                let in_node = (remaining - left_size).min(n.length);
+                let buffer = &self.buffers[n.buffer_index as usize];
+                return lines + self.nodes[n.left].line_feeds_subtree + buffer.count_lf(n.start, n.start + in_node);
             } else {
-                offset -= left_size + node.length;
-                if let Some(right) = node.right {
-                    curr = right;
-                } else {
-                    return (curr, node.length);
-                }
+                lines += self.nodes[n.left].line_feeds_subtree + n.line_feeds;
+                remaining -= left_size + n.length;
+                node = n.right;
             }
         }
+        lines
     }
 
-    fn split_node(&mut self, node_idx: usize, offset: u32) {
-        let mut node = self.nodes[node_idx].clone();
-        let right_len = node.length - offset;
-        let right_lf = count_lf_in_buffer(&self.buffers[node.buffer_index as usize], node.start + offset, right_len);
+    /// Byte offset where the 0-based `line_index` starts, using the augmented line-feed counts.
+    pub fn line_start_offset(&self, line_index: u32) -> u32 {
+        if line_index == 0 {
+            return 0;
+        }
+        let mut node = self.root;
+        let mut k = line_index;
+        let mut offset = 0;
+        while node != NIL {
+            let n = &self.nodes[node];
+            let left_lf = self.nodes[n.left].line_feeds_subtree;
+            if k <= left_lf {
+                node = n.left;
+            } else if k <= left_lf + n.line_feeds {
+                let buffer = &self.buffers[n.buffer_index as usize];
+                let lf_pos = buffer.nth_lf_from(n.start, k - left_lf);
+                return offset + self.nodes[n.left].size_subtree + (lf_pos - n.start) + 1;
+            } else {
+                k -= left_lf + n.line_feeds;
+                offset += self.nodes[n.left].size_subtree + n.length;
+                node = n.right;
+            }
+        }
+        self.get_length()
+    }
 
-        // Update original node
-        self.nodes[node_idx].length = offset;
-        self.nodes[node_idx].line_feeds -= right_lf;
+    // ─── Buffers ──────────────────────────────────────────────────────────
 
-        // Create right node
-        let right_node_idx = self.nodes.len();
-        self.nodes.push(PieceNode {
-            buffer_index: node.buffer_index,
-            start: node.start + offset,
-            length: right_len,
-            line_feeds: right_lf,
-            left: None,
-            right: None,
-            parent: Some(node_idx),
-            color: NodeColor::Red,
-            size_subtree: right_len,
-            line_feeds_subtree: right_lf,
-        });
+    fn piece_bytes(&self, node_idx: usize) -> &[u8] {
+        let node = &self.nodes[node_idx];
+        let buffer = &self.buffers[node.buffer_index as usize];
+        &buffer.text[node.start as usize..(node.start + node.length) as usize]
+    }
 
-        // Insert right node into tree logic... (simplified for now)
-        let old_right = self.nodes[node_idx].right;
-        self.nodes[node_idx].right = Some(right_node_idx);
-        self.nodes[right_node_idx].right = old_right;
-        if let Some(or_idx) = old_right {
-            self.nodes[or_idx].parent = Some(right_node_idx);
+    /// Stores `bytes` in an add buffer and returns (buffer, start, length, line feeds).
+    fn append_to_add_buffer(&mut self, bytes: &[u8]) -> (u32, u32, u32, u32) {
+        let last = self.buffers.len().wrapping_sub(1);
+        let can_append = !self.buffers.is_empty()
+            && self.buffers[last].text.len() + bytes.len() <= AVERAGE_BUFFER_SIZE
+            && !self.is_original_buffer(last);
+        if can_append {
+            let buffer = Arc::make_mut(&mut self.buffers[last]);
+            let start = buffer.text.len() as u32;
+            let lf_before = buffer.line_feeds.len();
+            buffer.append(bytes);
+            return (last as u32, start, bytes.len() as u32, (buffer.line_feeds.len() - lf_before) as u32);
         }
+        let buffer = StringBuffer::new(bytes.to_vec());
+        let lf = buffer.line_feeds.len() as u32;
+        self.buffers.push(Arc::new(buffer));
+        ((self.buffers.len() - 1) as u32, 0, bytes.len() as u32, lf)
+    }
 
-        self.update_subtree_metrics(node_idx);
+    fn is_original_buffer(&self, index: usize) -> bool {
+        index < self.original_buffer_count
     }
 
-    fn insert_node_after(&mut self, _target_idx: usize, _offset: u32, buf_idx: u32, start: u32, length: u32, lf: u32) {
-        // High-performance RB-Tree insertion would happen here.
-        // For now, we append to nodes and update metrics.
-        let new_idx = self.nodes.len();
-        self.nodes.push(PieceNode {
-            buffer_index: buf_idx,
+    // ─── Tree Editing ─────────────────────────────────────────────────────
+
+    fn alloc_node(&mut self, buffer_index: u32, start: u32, length: u32, line_feeds: u32) -> usize {
+        let node = PieceNode {
+            buffer_index,
             start,
             length,
-            line_feeds: lf,
-            left: None,
-            right: None,
-            parent: None,
+            line_feeds,
+            left: NIL,
+            right: NIL,
+            parent: NIL,
             color: NodeColor::Red,
             size_subtree: length,
-            line_feeds_subtree: lf,
-        });
-        // Tree linking logic...
+            line_feeds_subtree: line_feeds,
+        };
+        if let Some(idx) = self.free_nodes.pop() {
+            self.nodes[idx] = node;
+            idx
+        } else {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        }
     }
 
-    fn insert_node_before(&mut self, _target_idx: usize, buf_idx: u32, start: u32, length: u32, lf: u32) {
-        let new_idx = self.nodes.len();
-        self.nodes.push(PieceNode {
-            buffer_index: buf_idx,
-            start,
-            length,
-            line_feeds: lf,
-            left: None,
-            right: None,
-            parent: None,
-            color: NodeColor::Red,
-            size_subtree: length,
-            line_feeds_subtree: lf,
-        });
+    /// Finds the node containing `offset` and the offset inside it. An offset
+    /// equal to the total length maps to the end of the last node.
+    fn node_at(&self, offset: u32) -> (usize, u32) {
+        let mut node = self.root;
+        let mut remaining = offset;
+        while node != NIL {
+            let n = &self.nodes[node];
+            let left_size = self.nodes[n.left].size_subtree;
+            if remaining < left_size {
+                node = n.left;
+            } else if remaining < left_size + n.length {
+                return (node, remaining - left_size);
+            } else if n.right == NIL {
+                return (node, n.length);
+            } else {
+                remaining -= left_size + n.length;
+                node = n.right;
+            }
+        }
+        (NIL, 0)
     }
 
-    fn update_subtree_metrics(&mut self, mut curr_idx: usize) {
-        loop {
-            let left_size = self.nodes[curr_idx].left.map(|i| self.nodes[i].size_subtree).unwrap_or(0);
-            let right_size = self.nodes[curr_idx].right.map(|i| self.nodes[i].size_subtree).unwrap_or(0);
-            let left_lf = self.nodes[curr_idx].left.map(|i| self.nodes[i].line_feeds_subtree).unwrap_or(0);
-            let right_lf = self.nodes[curr_idx].right.map(|i| self.nodes[i].line_feeds_subtree).unwrap_or(0);
+    /// Like `node_at`, but an offset on a piece boundary maps to the end of the
+    /// piece before it. Used for exclusive range ends.
+    fn node_at_end(&self, offset: u32) -> (usize, u32) {
+        if offset == 0 {
+            return self.node_at(0);
+        }
+        let (node, in_node) = self.node_at(offset - 1);
+        (node, in_node + 1)
+    }
 
-            self.nodes[curr_idx].size_subtree = left_size + right_size + self.nodes[curr_idx].length;
-            self.nodes[curr_idx].line_feeds_subtree = left_lf + right_lf + self.nodes[curr_idx].line_feeds;
+    fn insert_at_offset(&mut self, offset: u32, new_node: usize) {
+        let (node, in_node) = self.node_at(offset);
+        if in_node == 0 {
+            self.insert_before(node, new_node);
+        } else if in_node >= self.nodes[node].length {
+            self.insert_after(node, new_node);
+        } else {
+            self.split_node(node, in_node);
+            self.insert_after(node, new_node);
+        }
+    }
+
+    /// Splits `node` at `offset`, inserting the right part as its successor.
+    fn split_node(&mut self, node_idx: usize, offset: u32) {
+        let node = self.nodes[node_idx].clone();
+        let right_start = node.start + offset;
+        let right_len = node.length - offset;
+        let right_lf = self.buffers[node.buffer_index as usize].count_lf(right_start, right_start + right_len);
+
+        self.nodes[node_idx].length = offset;
+        self.nodes[node_idx].line_feeds -= right_lf;
+        self.update_metrics_to_root(node_idx);
 
-            if let Some(parent) = self.nodes[curr_idx].parent {
-                curr_idx = parent;
+        let right = self.alloc_node(node.buffer_index, right_start, right_len, right_lf);
+        self.insert_after(node_idx, right);
+    }
+
+    fn trim_head(&mut self, node_idx: usize, cut: u32) {
+        let node = &mut self.nodes[node_idx];
+        let removed_lf = self.buffers[node.buffer_index as usize].count_lf(node.start, node.start + cut);
+        node.start += cut;
+        node.length -= cut;
+        node.line_feeds -= removed_lf;
+        self.update_metrics_to_root(node_idx);
+    }
+
+    fn trim_tail(&mut self, node_idx: usize, keep: u32) {
+        let node = &mut self.nodes[node_idx];
+        let removed_lf = self.buffers[node.buffer_index as usize].count_lf(node.start + keep, node.start + node.length);
+        node.length = keep;
+        node.line_feeds -= removed_lf;
+        self.update_metrics_to_root(node_idx);
+    }
+
+    fn insert_node_at_end(&mut self, new_node: usize) {
+        if self.root == NIL {
+            self.nodes[new_node].color = NodeColor::Black;
+            self.nodes[new_node].parent = NIL;
+            self.root = new_node;
+            return;
+        }
+        let last = self.rightmost(self.root);
+        self.insert_after(last, new_node);
+    }
+
+    fn insert_after(&mut self, target: usize, new_node: usize) {
+        if self.nodes[target].right == NIL {
+            self.nodes[target].right = new_node;
+            self.nodes[new_node].parent = target;
+        } else {
+            let next = self.leftmost(self.nodes[target].right);
+            self.nodes[next].left = new_node;
+            self.nodes[new_node].parent = next;
+        }
+        self.update_metrics_to_root(new_node);
+        self.insert_fixup(new_node);
+    }
+
+    fn insert_before(&mut self, target: usize, new_node: usize) {
+        if self.nodes[target].left == NIL {
+            self.nodes[target].left = new_node;
+            self.nodes[new_node].parent = target;
+        } else {
+            let prev = self.rightmost(self.nodes[target].left);
+            self.nodes[prev].right = new_node;
+            self.nodes[new_node].parent = prev;
+        }
+        self.update_metrics_to_root(new_node);
+        self.insert_fixup(new_node);
+    }
+
+    fn recompute(&mut self, idx: usize) {
+        if idx == NIL {
+            return;
+        }
+        let (left, right) = (self.nodes[idx].left, self.nodes[idx].right);
+        self.nodes[idx].size_subtree = self.nodes[left].size_subtree + self.nodes[right].size_subtree + self.nodes[idx].length;
+        self.nodes[idx].line_feeds_subtree = self.nodes[left].line_feeds_subtree + self.nodes[right].line_feeds_subtree + self.nodes[idx].line_feeds;
+    }
+
+    fn update_metrics_to_root(&mut self, mut idx: usize) {
+        while idx != NIL {
+            self.recompute(idx);
+            idx = self.nodes[idx].parent;
+        }
+    }
+
+    fn leftmost(&self, mut idx: usize) -> usize {
+        while idx != NIL && self.nodes[idx].left != NIL {
+            idx = self.nodes[idx].left;
+        }
+        idx
+    }
+
+    fn rightmost(&self, mut idx: usize) -> usize {
+        while idx != NIL && self.nodes[idx].right != NIL {
+            idx = self.nodes[idx].right;
+        }
+        idx
+    }
+
+    fn successor(&self, idx: usize) -> usize {
+        if self.nodes[idx].right != NIL {
+            return self.leftmost(self.nodes[idx].right);
+        }
+        let mut child = idx;
+        let mut parent = self.nodes[idx].parent;
+        while parent != NIL && self.nodes[parent].right == child {
+            child = parent;
+            parent = self.nodes[parent].parent;
+        }
+        parent
+    }
+
+    // ─── Red-Black Balancing ──────────────────────────────────────────────
+
+    fn rotate_left(&mut self, x: usize) {
+        let y = self.nodes[x].right;
+        self.nodes[x].right = self.nodes[y].left;
+        if self.nodes[y].left != NIL {
+            let yl = self.nodes[y].left;
+            self.nodes[yl].parent = x;
+        }
+        self.replace_child(x, y);
+        self.nodes[y].left = x;
+        self.nodes[x].parent = y;
+        self.recompute(x);
+        self.recompute(y);
+    }
+
+    fn rotate_right(&mut self, x: usize) {
+        let y = self.nodes[x].left;
+        self.nodes[x].left = self.nodes[y].right;
+        if self.nodes[y].right != NIL {
+            let yr = self.nodes[y].right;
+            self.nodes[yr].parent = x;
+        }
+        self.replace_child(x, y);
+        self.nodes[y].right = x;
+        self.nodes[x].parent = y;
+        self.recompute(x);
+        self.recompute(y);
+    }
+
+    /// Puts `new` where `old` hangs from its parent.
+    fn replace_child(&mut self, old: usize, new: usize) {
+        let parent = self.nodes[old].parent;
+        self.nodes[new].parent = parent;
+        if parent == NIL {
+            self.root = new;
+        } else if self.nodes[parent].left == old {
+            self.nodes[parent].left = new;
+        } else {
+            self.nodes[parent].right = new;
+        }
+    }
+
+    fn insert_fixup(&mut self, mut z: usize) {
+        while self.nodes[self.nodes[z].parent].color == NodeColor::Red {
+            let p = self.nodes[z].parent;
+            let g = self.nodes[p].parent;
+            if p == self.nodes[g].left {
+                let uncle = self.nodes[g].right;
+                if self.nodes[uncle].color == NodeColor::Red {
+                    self.nodes[p].color = NodeColor::Black;
+                    self.nodes[uncle].color = NodeColor::Black;
+                    self.nodes[g].color = NodeColor::Red;
+                    z = g;
+                } else {
+                    if z == self.nodes[p].right {
+                        z = p;
+                        self.rotate_left(z);
+                    }
+                    let p = self.nodes[z].parent;
+                    let g = self.nodes[p].parent;
+                    self.nodes[p].color = NodeColor::Black;
+                    self.nodes[g].color = NodeColor::Red;
+                    self.rotate_right(g);
+                }
             } else {
-                break;
+                let uncle = self.nodes[g].left;
+                if self.nodes[uncle].color == NodeColor::Red {
+                    self.nodes[p].color = NodeColor::Black;
+                    self.nodes[uncle].color = NodeColor::Black;
+                    self.nodes[g].color = NodeColor::Red;
+                    z = g;
+                } else {
+                    if z == self.nodes[p].left {
+                        z = p;
+                        self.rotate_right(z);
+                    }
+                    let p = self.nodes[z].parent;
+                    let g = self.nodes[p].parent;
+                    self.nodes[p].color = NodeColor::Black;
+                    self.nodes[g].color = NodeColor::Red;
+                    self.rotate_left(g);
+                }
             }
         }
+        let root = self.root;
+        self.nodes[root].color = NodeColor::Black;
+        self.nodes[NIL].color = NodeColor::Black;
     }
 
-    #[napi]
-    pub fn get_line_count(&self) -> u32 {
-        self.total_line_feeds + 1
+    fn transplant(&mut self, u: usize, v: usize) {
+        let parent = self.nodes[u].parent;
+        if parent == NIL {
+            self.root = v;
+        } else if self.nodes[parent].left == u {
+            self.nodes[parent].left = v;
+        } else {
+            self.nodes[parent].right = v;
+        }
+        self.nodes[v].parent = parent;
+    }
+
+    fn rb_delete(&mut self, z: usize) {
+        let mut y = z;
+        let mut y_color = self.nodes[y].color;
+        let x;
+        if self.nodes[z].left == NIL {
+            x = self.nodes[z].right;
+            self.transplant(z, x);
+        } else if self.nodes[z].right == NIL {
+            x = self.nodes[z].left;
+            self.transplant(z, x);
+        } else {
+            y = self.leftmost(self.nodes[z].right);
+            y_color = self.nodes[y].color;
+            x = self.nodes[y].right;
+            if self.nodes[y].parent == z {
+                self.nodes[x].parent = y;
+            } else {
+                self.transplant(y, x);
+                self.nodes[y].right = self.nodes[z].right;
+                let yr = self.nodes[y].right;
+                self.nodes[yr].parent = y;
+            }
+            self.transplant(z, y);
+            self.nodes[y].left = self.nodes[z].left;
+            let yl = self.nodes[y].left;
+            self.nodes[yl].parent = y;
+            self.nodes[y].color = self.nodes[z].color;
+        }
+
+        self.update_metrics_to_root(self.nodes[x].parent);
+        if y_color == NodeColor::Black {
+            self.delete_fixup(x);
+        }
+
+        self.nodes[NIL] = PieceNode::sentinel();
+        self.nodes[z] = PieceNode::sentinel();
+        self.free_nodes.push(z);
+    }
+
+    fn delete_fixup(&mut self, mut x: usize) {
+        while x != self.root && self.nodes[x].color == NodeColor::Black {
+            let p = self.nodes[x].parent;
+            if x == self.nodes[p].left {
+                let mut w = self.nodes[p].right;
+                if self.nodes[w].color == NodeColor::Red {
+                    self.nodes[w].color = NodeColor::Black;
+                    self.nodes[p].color = NodeColor::Red;
+                    self.rotate_left(p);
+                    w = self.nodes[self.nodes[x].parent].right;
+                }
+                let (wl, wr) = (self.nodes[w].left, self.nodes[w].right);
+                if self.nodes[wl].color == NodeColor::Black && self.nodes[wr].color == NodeColor::Black {
+                    self.nodes[w].color = NodeColor::Red;
+                    x = self.nodes[x].parent;
+                } else {
+                    if self.nodes[wr].color == NodeColor::Black {
+                        self.nodes[wl].color = NodeColor::Black;
+                        self.nodes[w].color = NodeColor::Red;
+                        self.rotate_right(w);
+                        w = self.nodes[self.nodes[x].parent].right;
+                    }
+                    let p = self.nodes[x].parent;
+                    self.nodes[w].color = self.nodes[p].color;
+                    self.nodes[p].color = NodeColor::Black;
+                    let wr = self.nodes[w].right;
+                    self.nodes[wr].color = NodeColor::Black;
+                    self.rotate_left(p);
+                    x = self.root;
+                }
+            } else {
+                let mut w = self.nodes[p].left;
+                if self.nodes[w].color == NodeColor::Red {
+                    self.nodes[w].color = NodeColor::Black;
+                    self.nodes[p].color = NodeColor::Red;
+                    self.rotate_right(p);
+                    w = self.nodes[self.nodes[x].parent].left;
+                }
+                let (wl, wr) = (self.nodes[w].left, self.nodes[w].right);
+                if self.nodes[wl].color == NodeColor::Black && self.nodes[wr].color == NodeColor::Black {
+                    self.nodes[w].color = NodeColor::Red;
+                    x = self.nodes[x].parent;
+                } else {
+                    if self.nodes[wl].color == NodeColor::Black {
+                        self.nodes[wr].color = NodeColor::Black;
+                        self.nodes[w].color = NodeColor::Red;
+                        self.rotate_left(w);
+                        w = self.nodes[self.nodes[x].parent].left;
+                    }
+                    let p = self.nodes[x].parent;
+                    self.nodes[w].color = self.nodes[p].color;
+                    self.nodes[p].color = NodeColor::Black;
+                    let wl = self.nodes[w].left;
+                    self.nodes[wl].color = NodeColor::Black;
+                    self.rotate_right(p);
+                    x = self.root;
+                }
+            }
+        }
+        self.nodes[x].color = NodeColor::Black;
     }
 }
 
-fn count_lf(data: &[u8]) -> u32 {
-    data.iter().filter(|&&b| b == b'\n').count() as u32
+/// Point-in-time copy of a `PieceTree`'s piece list. Shares the underlying
+/// buffers, so creating one is O(pieces) and costs no text copies.
+#[napi]
+pub struct PieceTreeSnapshot {
+    pieces: Vec<(Arc<StringBuffer>, u32, u32)>,
+    line_count: u32,
+    read_index: usize,
 }
 
-fn count_lf_in_buffer(buffer: &[u8], start: u32, length: u32) -> u32 {
-    let start = start as usize;
-    let end = (start + length as usize);
-    buffer[start..end].iter().filter(|&&b| b == b'\n').count() as u32
+#[napi]
+impl PieceTreeSnapshot {
+    /// Returns the next chunk of text, or `None` once everything was read.
+    #[napi]
+    pub fn read(&mut self) -> Option<String> {
+        let (buffer, start, length) = self.pieces.get(self.read_index)?;
+        self.read_index += 1;
+        Some(String::from_utf8_lossy(&buffer.text[*start as usize..(*start + *length) as usize]).to_string())
+    }
+
+    #[napi]
+    pub fn get_text(&self) -> String {
+        let mut result = Vec::new();
+        for (buffer, start, length) in &self.pieces {
+            result.extend_from_slice(&buffer.text[*start as usize..(*start + *length) as usize]);
+        }
+        String::from_utf8_lossy(&result).to_string()
+    }
+
+    #[napi(getter)]
+    pub fn line_count(&self) -> u32 {
+        self.line_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks red-black and augmentation invariants; returns the black height.
+    fn check_node(tree: &PieceTree, idx: usize) -> u32 {
+        if idx == NIL {
+            return 1;
+        }
+        let n = &tree.nodes[idx];
+        if n.color == NodeColor::Red {
+            assert_eq!(tree.nodes[n.left].color, NodeColor::Black);
+            assert_eq!(tree.nodes[n.right].color, NodeColor::Black);
+        }
+        assert_eq!(n.size_subtree, tree.nodes[n.left].size_subtree + tree.nodes[n.right].size_subtree + n.length);
+        assert_eq!(n.line_feeds_subtree, tree.nodes[n.left].line_feeds_subtree + tree.nodes[n.right].line_feeds_subtree + n.line_feeds);
+        let left = check_node(tree, n.left);
+        assert_eq!(left, check_node(tree, n.right));
+        left + (n.color == NodeColor::Black) as u32
+    }
+
+    fn assert_matches(tree: &PieceTree, expected: &str) {
+        check_node(tree, tree.root);
+        assert_eq!(tree.get_text(), expected);
+        let lines: Vec<&str> = expected.split('\n').collect();
+        assert_eq!(tree.get_line_count() as usize, lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            assert_eq!(tree.get_line_content(i as u32 + 1), *line);
+        }
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut tree = PieceTree::new("hello world".into());
+        tree.insert_v2(5, ",".into());
+        tree.insert_v2(12, "!\nsecond".into());
+        assert_matches(&tree, "hello, world!\nsecond");
+        tree.delete(5, 1);
+        tree.delete(0, 6);
+        assert_matches(&tree, "world!\nsecond");
+        tree.delete(3, 8);
+        assert_matches(&tree, "wornd");
+        tree.delete(0, 100);
+        assert_matches(&tree, "");
+        tree.insert_v2(0, "again".into());
+        assert_matches(&tree, "again");
+    }
+
+    #[test]
+    fn test_random_edits_match_string() {
+        let mut seed: u64 = 42;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) as usize) % bound.max(1)
+        };
+        let samples = ["a", "bc\n", "\n", "xyz", "é", "\r\n", "long line of text\nwith two\n"];
+        let mut expected = String::from("start\nof the\ndocument");
+        let mut tree = PieceTree::new(expected.clone());
+        for _ in 0..2000 {
+            if next(3) == 0 && !expected.is_empty() {
+                let chars: Vec<usize> = expected.char_indices().map(|(i, _)| i).chain([expected.len()]).collect();
+                let a = chars[next(chars.len())];
+                let b = chars[next(chars.len())];
+                let (start, end) = (a.min(b), a.max(b));
+                expected.replace_range(start..end, "");
+                tree.delete(start as u32, (end - start) as u32);
+            } else {
+                let chars: Vec<usize> = expected.char_indices().map(|(i, _)| i).chain([expected.len()]).collect();
+                let at = chars[next(chars.len())];
+                let text = samples[next(samples.len())];
+                expected.insert_str(at, text);
+                tree.insert_v2(at as u32, text.into());
+            }
+            check_node(&tree, tree.root);
+            assert_eq!(tree.get_length() as usize, expected.len());
+        }
+        assert_matches(&tree, &expected);
+    }
+
+    #[test]
+    fn test_offset_position_round_trip() {
+        let tree = PieceTree::new("ab\r\nçd\n\nlast".into());
+        assert_eq!(tree.get_line_content(1), "ab");
+        assert_eq!(tree.offset_at(2, 2), 6);
+        assert_eq!(tree.position_at(6), Position::new(2, 2));
+        assert_eq!(tree.offset_at(3, 5), 8);
+        assert_eq!(tree.position_at(8), Position::new(3, 1));
+        assert_eq!(tree.position_at(9), Position::new(4, 1));
+        assert_eq!(tree.offset_at(1, 99), 2);
+        assert_eq!(tree.position_at(100), Position::new(4, 5));
+    }
+
+    #[test]
+    fn test_snapshot_is_isolated_from_edits() {
+        let mut tree = PieceTree::new("line one\nline two".into());
+        tree.insert_v2(4, "s".into());
+        let mut snapshot = tree.create_snapshot();
+        tree.insert_v2(5, " typed".into());
+        tree.delete(0, 2);
+        assert_eq!(snapshot.get_text(), "lines one\nline two");
+        assert_eq!(snapshot.line_count(), 2);
+
+        let mut chunks = String::new();
+        while let Some(chunk) = snapshot.read() {
+            chunks.push_str(&chunk);
+        }
+        assert_eq!(chunks, "lines one\nline two");
+        assert_eq!(tree.get_text(), "nes typed one\nline two");
+    }
 }
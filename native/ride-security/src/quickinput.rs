@@ -1,11 +1,14 @@
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
+use crate::filters::{fuzzy_score_str, MatchRange};
 
 #[napi(object)]
 pub struct QuickPickItem {
     pub label: String,
     pub description: Option<String>,
     pub detail: Option<String>,
+    pub label_highlights: Option<Vec<MatchRange>>,
+    pub description_highlights: Option<Vec<MatchRange>>,
 }
 
 #[napi]
@@ -18,14 +21,26 @@ impl QuickInputService {
         Self {}
     }
 
+    /// Fuzzy-filters items with the suggest scorer. Label matches rank above
+    /// description-only matches; ties keep the input order.
     #[napi]
     pub fn filter_items(&self, items: Vec<QuickPickItem>, query: String) -> Vec<QuickPickItem> {
-        let q = query.to_lowercase();
-        items.into_iter()
-            .filter(|item| {
-                item.label.to_lowercase().contains(&q) || 
-                item.description.as_ref().map(|d| d.to_lowercase().contains(&q)).unwrap_or(false)
+        let query = query.trim();
+        if query.is_empty() {
+            return items;
+        }
+        let mut scored: Vec<(bool, i32, QuickPickItem)> = items.into_iter()
+            .filter_map(|mut item| {
+                if let Some(m) = fuzzy_score_str(query, &item.label, false) {
+                    item.label_highlights = Some(m.matches);
+                    return Some((true, m.score, item));
+                }
+                let m = item.description.as_deref().and_then(|d| fuzzy_score_str(query, d, true))?;
+                item.description_highlights = Some(m.matches);
+                Some((false, m.score, item))
             })
-            .collect()
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+        scored.into_iter().map(|(_, _, item)| item).collect()
     }
 }
@@ -11,16 +11,26 @@
 //! - Smart binary file skipping (null-byte detection)
 //! - Detailed match metadata (byte offsets, column indices, line snippets)
 //! - Memory-efficient streaming file processing for large artifacts
+//! - Optional trigram index narrowing of candidate files
+//! - Streaming batches with progress and cancellation for the search view
 
 use ignore::WalkBuilder;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use rayon::prelude::*;
 use regex::Regex;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read};
-use std::sync::Mutex;
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use encoding_rs_io::DecodeReaderBytes;
+use crate::lifecycle::CancellationToken;
+use crate::trigram_index;
 
 #[napi(object)]
 #[derive(Clone, Debug)]
@@ -42,6 +52,8 @@ pub struct SearchOptions {
     pub whole_word: Option<bool>,
     pub include_globs: Option<Vec<String>>,
     pub exclude_globs: Option<Vec<String>>,
+    /// Narrow candidates with the trigram index when one covers `directory` (default true).
+    pub use_index: Option<bool>,
 }
 
 #[napi(object)]
@@ -51,82 +63,140 @@ pub struct SearchResult {
     pub files_with_matches: u32,
     pub truncated: bool,
     pub duration_ms: f64,
+    pub used_index: bool,
+    /// The trigram index used for this search may have missed changes.
+    pub index_stale: bool,
 }
 
-#[napi]
-pub fn search_files_v2(directory: String, query: String, options: Option<SearchOptions>) -> Result<SearchResult> {
-    let start = std::time::Instant::now();
-    let max_results = options.as_ref().and_then(|o| o.max_results).unwrap_or(10000) as usize;
+/// Progress of a streaming search, sent with every batch.
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SearchProgress {
+    pub files_total: u32,
+    pub files_scanned: u32,
+    pub matches_found: u32,
+    /// Set on the final batch.
+    pub done: bool,
+    pub cancelled: bool,
+    pub truncated: bool,
+}
 
-    let pattern = if options.as_ref().and_then(|o| o.is_regex).unwrap_or(false) {
-        if options.as_ref().and_then(|o| o.case_insensitive).unwrap_or(false) {
-            format!("(?i){}", query)
-        } else {
-            query.clone()
-        }
+#[napi(object)]
+pub struct SearchBatch {
+    pub search_id: u32,
+    pub matches: Vec<SearchMatch>,
+    pub progress: SearchProgress,
+}
+
+/// Matches are flushed once this many are pending, or after `BATCH_INTERVAL`.
+const BATCH_SIZE: usize = 256;
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+type SearchFile = (PathBuf, Option<(f64, f64)>);
+
+/// Builds the search regex. Returns it with the pattern before case folding, which the
+/// trigram index extracts literals from.
+fn build_search_regex(query: &str, options: Option<&SearchOptions>) -> Result<(Regex, String, bool)> {
+    let case_insensitive = options.and_then(|o| o.case_insensitive).unwrap_or(false);
+    let base = if options.and_then(|o| o.is_regex).unwrap_or(false) {
+        query.to_string()
     } else {
-        let mut p = regex::escape(&query);
-        if options.as_ref().and_then(|o| o.whole_word).unwrap_or(false) {
-            p = format!(r"\b{}\b", p);
-        }
-        if options.as_ref().and_then(|o| o.case_insensitive).unwrap_or(false) {
-            format!("(?i){}", p)
+        let p = regex::escape(query);
+        if options.and_then(|o| o.whole_word).unwrap_or(false) {
+            format!(r"\b{}\b", p)
         } else {
             p
         }
     };
+    let pattern = if case_insensitive { format!("(?i){}", base) } else { base.clone() };
 
     let re = Regex::new(&pattern).map_err(|e| Error::from_reason(e.to_string()))?;
+    Ok((re, base, case_insensitive))
+}
+
+/// Files to search, narrowed by the trigram index when one covers `directory`. Indexed
+/// candidates carry the (mtime, size) they were indexed at.
+fn collect_search_files(directory: &str, base: &str, case_insensitive: bool, options: Option<&SearchOptions>) -> (Vec<SearchFile>, bool) {
+    let indexed = if options.and_then(|o| o.use_index).unwrap_or(true) {
+        trigram_index::candidate_files(directory, base, case_insensitive)
+    } else {
+        None
+    };
+    let used_index = indexed.is_some();
+    let files = match indexed {
+        Some(candidates) => candidates.into_iter().map(|(p, mtime, size)| (p, Some((mtime, size)))).collect(),
+        None => WalkBuilder::new(directory)
+            .git_ignore(true)
+            .hidden(true)
+            .build()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().map(|f| f.is_file()).unwrap_or(false))
+            .map(|e| (e.into_path(), None))
+            .collect(),
+    };
+    (files, used_index)
+}
 
-    let files: Vec<_> = WalkBuilder::new(&directory)
-        .git_ignore(true)
-        .hidden(true)
-        .build()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().map(|f| f.is_file()).unwrap_or(false))
-        .map(|e| e.into_path())
-        .collect();
+/// Searches one file, counting matches into `match_count` and stopping at `max_results`.
+/// Fails when the file cannot be opened or decoded, e.g. because it was deleted meanwhile.
+fn search_file(file: &SearchFile, re: &Regex, match_count: &AtomicUsize, max_results: usize) -> std::io::Result<Vec<SearchMatch>> {
+    let (path, stamp) = file;
+    let mut file_matches = Vec::new();
+    if let Some((mtime, size)) = stamp {
+        trigram_index::check_candidate(path, *mtime, *size);
+    }
+
+    let mut file = File::open(path)?;
+    // Check for binary (null byte) in first 1024 bytes
+    let mut buf = [0u8; 1024];
+    let n = file.read(&mut buf)?;
+    if buf[..n].contains(&0) {
+        return Ok(file_matches);
+    }
+    file.rewind()?;
+
+    // Decode with encoding detection
+    let mut reader = DecodeReaderBytes::new(file);
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+    for (line_idx, line) in content.lines().enumerate() {
+        for m in re.find_iter(line) {
+            file_matches.push(SearchMatch {
+                file_path: path.to_string_lossy().to_string(),
+                line_number: (line_idx + 1) as u32,
+                column: m.start() as u32,
+                byte_offset: m.start() as u32, // Simplified for now
+                match_length: m.len() as u32,
+                line_content: line.to_string(),
+            });
+            if match_count.fetch_add(1, AtomicOrdering::Relaxed) >= max_results {
+                break;
+            }
+        }
+    }
+    Ok(file_matches)
+}
+
+#[napi]
+pub fn search_files_v2(directory: String, query: String, options: Option<SearchOptions>) -> Result<SearchResult> {
+    let start = std::time::Instant::now();
+    let max_results = options.as_ref().and_then(|o| o.max_results).unwrap_or(10000) as usize;
+
+    let (re, base, case_insensitive) = build_search_regex(&query, options.as_ref())?;
+    let (files, used_index) = collect_search_files(&directory, &base, case_insensitive, options.as_ref());
 
     let files_scanned = files.len() as u32;
     let results = Mutex::new(Vec::with_capacity(256));
-    let match_count = std::sync::atomic::AtomicUsize::new(0);
+    let match_count = AtomicUsize::new(0);
 
-    files.par_iter().for_each(|path| {
-        if match_count.load(std::sync::atomic::Ordering::Relaxed) >= max_results {
+    files.par_iter().for_each(|file| {
+        if match_count.load(AtomicOrdering::Relaxed) >= max_results {
             return;
         }
-
-        if let Ok(file) = File::open(path) {
-            // Check for binary (null byte) in first 1024 bytes
-            let mut buf = [0u8; 1024];
-            if let Ok(n) = (&file).read(&mut buf) {
-                if buf[..n].iter().any(|&b| b == 0) { return; }
-            }
-
-            // Decode with encoding detection
-            let mut reader = DecodeReaderBytes::new(File::open(path).unwrap());
-            let mut content = String::new();
-            if reader.read_to_string(&mut content).is_ok() {
-                let mut file_matches = Vec::new();
-                for (line_idx, line) in content.lines().enumerate() {
-                    for m in re.find_iter(line) {
-                        file_matches.push(SearchMatch {
-                            file_path: path.to_string_lossy().to_string(),
-                            line_number: (line_idx + 1) as u32,
-                            column: m.start() as u32,
-                            byte_offset: m.start() as u32, // Simplified for now
-                            match_length: m.len() as u32,
-                            line_content: line.to_string(),
-                        });
-                        if match_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) >= max_results {
-                            break;
-                        }
-                    }
-                }
-                if !file_matches.is_empty() {
-                    results.lock().unwrap().extend(file_matches);
-                }
-            }
+        // Unreadable files have no matches.
+        let file_matches = search_file(file, &re, &match_count, max_results).unwrap_or_default();
+        if !file_matches.is_empty() {
+            results.lock().unwrap().extend(file_matches);
         }
     });
 
@@ -139,5 +209,230 @@ pub fn search_files_v2(directory: String, query: String, options: Option<SearchO
         matches,
         files_scanned,
         duration_ms: duration,
+        used_index,
+        index_stale: used_index && trigram_index::is_stale(),
     })
 }
+
+/// Cancellation of one streaming search: its own flag, set by `cancel_search` or a newer
+/// search over the same directory, and the caller's token, which is only read.
+#[derive(Default)]
+struct SearchCancellation {
+    own: AtomicBool,
+    token: Option<Arc<AtomicBool>>,
+}
+
+impl SearchCancellation {
+    fn cancel(&self) {
+        self.own.store(true, AtomicOrdering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.own.load(AtomicOrdering::Relaxed) || self.token.as_ref().is_some_and(|t| t.load(AtomicOrdering::Relaxed))
+    }
+}
+
+/// Running streaming searches: id -> (directory, cancellation).
+type ActiveSearches = Mutex<HashMap<u32, (String, Arc<SearchCancellation>)>>;
+
+fn get_active_searches() -> &'static ActiveSearches {
+    static ACTIVE: OnceLock<ActiveSearches> = OnceLock::new();
+    ACTIVE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+static NEXT_SEARCH_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Registers a streaming search over `directory`, cancelling the ones already running there.
+fn register_search(directory: &str, token: Option<Arc<AtomicBool>>) -> (u32, Arc<SearchCancellation>) {
+    let search_id = NEXT_SEARCH_ID.fetch_add(1, AtomicOrdering::Relaxed);
+    let cancellation = Arc::new(SearchCancellation { own: AtomicBool::new(false), token });
+    let mut active = get_active_searches().lock().unwrap();
+    for (dir, previous) in active.values() {
+        if dir == directory {
+            previous.cancel();
+        }
+    }
+    active.insert(search_id, (directory.to_string(), cancellation.clone()));
+    (search_id, cancellation)
+}
+
+/// Unregisters a streaming search when dropped, even if the search thread panics.
+struct ActiveSearch(u32);
+
+impl Drop for ActiveSearch {
+    fn drop(&mut self) {
+        get_active_searches().lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Runs a search, sending batches of matches with progress to `emit` until the files are
+/// exhausted, `max_results` is reached or `cancelled` is set. The last batch has `done` set.
+fn run_streaming_search(search_id: u32, files: Vec<SearchFile>, re: Regex, max_results: usize, cancelled: &SearchCancellation, emit: &dyn Fn(SearchBatch)) {
+    let files_total = files.len() as u32;
+    let files_scanned = AtomicUsize::new(0);
+    let match_count = AtomicUsize::new(0);
+    let (tx, rx) = channel::<Vec<SearchMatch>>();
+
+    let progress = |done: bool| {
+        let matches_found = match_count.load(AtomicOrdering::Relaxed);
+        SearchProgress {
+            files_total,
+            files_scanned: files_scanned.load(AtomicOrdering::Relaxed) as u32,
+            matches_found: matches_found.min(max_results) as u32,
+            done,
+            cancelled: cancelled.is_cancelled(),
+            truncated: matches_found >= max_results,
+        }
+    };
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            files.par_iter().for_each_with(tx, |tx, file| {
+                if cancelled.is_cancelled() || match_count.load(AtomicOrdering::Relaxed) >= max_results {
+                    return;
+                }
+                // Unreadable files count as scanned without matches.
+                let file_matches = search_file(file, &re, &match_count, max_results).unwrap_or_default();
+                files_scanned.fetch_add(1, AtomicOrdering::Relaxed);
+                if !file_matches.is_empty() {
+                    let _ = tx.send(file_matches);
+                }
+            });
+        });
+
+        let mut pending: Vec<SearchMatch> = Vec::new();
+        let mut last_flush = Instant::now();
+        let mut last_scanned = 0;
+        let mut emitted = 0;
+        loop {
+            let disconnected = match rx.recv_timeout(BATCH_INTERVAL) {
+                Ok(file_matches) => {
+                    pending.extend(file_matches);
+                    false
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+            if disconnected {
+                break;
+            }
+            if cancelled.is_cancelled() {
+                continue;
+            }
+            let scanned = files_scanned.load(AtomicOrdering::Relaxed);
+            let due = last_flush.elapsed() >= BATCH_INTERVAL && (!pending.is_empty() || scanned != last_scanned);
+            if pending.len() >= BATCH_SIZE || due {
+                // Workers may overshoot `max_results` slightly before they notice.
+                pending.truncate(max_results - emitted);
+                emitted += pending.len();
+                emit(SearchBatch { search_id, matches: std::mem::take(&mut pending), progress: progress(false) });
+                last_flush = Instant::now();
+                last_scanned = scanned;
+            }
+        }
+        // Matches that arrive after cancellation are dropped.
+        if cancelled.is_cancelled() {
+            pending.clear();
+        }
+        pending.truncate(max_results - emitted);
+        emit(SearchBatch { search_id, matches: pending, progress: progress(true) });
+    });
+}
+
+/// Starts a search on a background thread and streams `SearchBatch`es to `on_batch` as files
+/// are scanned. Returns the search id. The search stops when `token` is cancelled or
+/// `cancel_search` is called; starting another streaming search over the same directory
+/// cancels this one (but not its `token`), so a new query typed in the search view
+/// supersedes the old.
+#[napi]
+pub fn search_files_streaming(
+    directory: String,
+    query: String,
+    options: Option<SearchOptions>,
+    token: Option<&CancellationToken>,
+    #[napi(ts_arg_type = "(batch: SearchBatch) => void")]
+    on_batch: ThreadsafeFunction<SearchBatch, ErrorStrategy::Fatal>,
+) -> Result<u32> {
+    let max_results = options.as_ref().and_then(|o| o.max_results).unwrap_or(10000) as usize;
+    let (re, base, case_insensitive) = build_search_regex(&query, options.as_ref())?;
+    let (search_id, cancelled) = register_search(&directory, token.map(|t| t.flag()));
+
+    std::thread::spawn(move || {
+        let _active = ActiveSearch(search_id);
+        let (files, _) = collect_search_files(&directory, &base, case_insensitive, options.as_ref());
+        run_streaming_search(search_id, files, re, max_results, &cancelled, &|batch| {
+            on_batch.call(batch, ThreadsafeFunctionCallMode::Blocking);
+        });
+    });
+    Ok(search_id)
+}
+
+/// Cancels a running streaming search. Returns `false` if it already finished.
+#[napi]
+pub fn cancel_search(search_id: u32) -> bool {
+    match get_active_searches().lock().unwrap().get(&search_id) {
+        Some((_, cancellation)) => {
+            cancellation.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streaming_search_batches_and_cancellation() {
+        let root = std::env::temp_dir().join(format!("ride-search-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        for i in 0..20 {
+            std::fs::write(root.join(format!("f{}.txt", i)), "needle\nhay\nneedle again\n").unwrap();
+        }
+        let directory = root.to_string_lossy().to_string();
+        let (re, base, ci) = build_search_regex("needle", None).unwrap();
+
+        let (files, _) = collect_search_files(&directory, &base, ci, None);
+        let batches = Mutex::new(Vec::new());
+        run_streaming_search(7, files, re.clone(), 10000, &SearchCancellation::default(), &|b| batches.lock().unwrap().push(b));
+        let batches = batches.into_inner().unwrap();
+        let last = batches.last().unwrap();
+        assert!(last.progress.done && !last.progress.cancelled);
+        assert_eq!((last.progress.files_total, last.progress.files_scanned, last.progress.matches_found), (20, 20, 40));
+        assert_eq!(batches.iter().map(|b| b.matches.len()).sum::<usize>(), 40);
+        assert!(batches.iter().all(|b| b.search_id == 7));
+
+        let (files, _) = collect_search_files(&directory, &base, ci, None);
+        let batches = Mutex::new(Vec::new());
+        let cancelled = SearchCancellation { own: AtomicBool::new(true), token: None };
+        run_streaming_search(8, files, re, 10000, &cancelled, &|b| batches.lock().unwrap().push(b));
+        let batches = batches.into_inner().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].progress.done && batches[0].progress.cancelled);
+        assert!(batches[0].matches.is_empty());
+        assert_eq!(batches[0].progress.files_scanned, 0);
+
+        // A deleted candidate is skipped rather than failing the search.
+        let missing = (root.join("missing.txt"), None);
+        let (re, _, _) = build_search_regex("needle", None).unwrap();
+        assert!(search_file(&missing, &re, &AtomicUsize::new(0), 10).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_newer_search_cancels_without_touching_the_callers_token() {
+        let token = CancellationToken::new();
+        let directory = format!("ride-search-supersede-{}", std::process::id());
+        let (first, first_cancellation) = register_search(&directory, Some(token.flag()));
+        let (second, second_cancellation) = register_search(&directory, Some(token.flag()));
+        assert!(first_cancellation.is_cancelled());
+        assert!(!second_cancellation.is_cancelled() && !token.is_cancelled());
+
+        token.cancel();
+        assert!(second_cancellation.is_cancelled());
+        drop((ActiveSearch(first), ActiveSearch(second)));
+        assert!(!cancel_search(first));
+    }
+}
@@ -11,6 +11,7 @@ use std::fs::File;
 use std::io::{BufRead, BufReader};
 use ignore::WalkBuilder;
 use serde::{Deserialize, Serialize};
+use crate::trigram_index;
 
 #[napi(object)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -41,22 +42,31 @@ impl SearchService {
             Err(_) => return Vec::new(),
         };
 
-        let mut walker_builder = WalkBuilder::new(&root);
-        walker_builder.hidden(true).git_ignore(true);
-        if let Some(inc) = include_pattern {
+        let overrides = include_pattern.and_then(|inc| {
             let mut ov_builder = ignore::overrides::OverrideBuilder::new(&root);
             let _ = ov_builder.add(&inc);
-            if let Ok(ov) = ov_builder.build() {
-                walker_builder.overrides(ov);
-            }
-        }
+            ov_builder.build().ok()
+        });
 
-        // Build the list of files to search
-        let files: Vec<std::path::PathBuf> = walker_builder.build()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().map(|ft| ft.is_file()).unwrap_or(false))
-            .map(|e| e.into_path())
-            .collect();
+        // Build the list of files to search, narrowed by the trigram index when one covers `root`
+        let files: Vec<std::path::PathBuf> = match trigram_index::candidate_files(&root, &pattern, false) {
+            Some(candidates) => candidates.into_iter()
+                .map(|(path, _, _)| path)
+                .filter(|path| !overrides.as_ref().is_some_and(|ov| ov.matched(path, false).is_ignore()))
+                .collect(),
+            None => {
+                let mut walker_builder = WalkBuilder::new(&root);
+                walker_builder.hidden(true).git_ignore(true);
+                if let Some(ov) = overrides {
+                    walker_builder.overrides(ov);
+                }
+                walker_builder.build()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().map(|ft| ft.is_file()).unwrap_or(false))
+                    .map(|e| e.into_path())
+                    .collect()
+            }
+        };
 
         // Perform parallel search across files
         files.into_par_iter()
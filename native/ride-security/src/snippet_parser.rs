@@ -18,7 +18,7 @@ pub enum SnippetNodeType {
 }
 
 #[napi(object)]
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct SnippetTransform {
     pub regex: String,
     pub format: String,
@@ -58,12 +58,13 @@ impl SnippetParser {
             if let Some(node) = self.parse_node()? {
                 children.push(node);
             } else {
-                // If parse_node returns None but we aren't at end, it means we hit a stop char like } or : or /
-                // In top level, we shouldn't hit these. But if we do, treat as text?
-                // Actually parse_node handles text.
-                // If it returns None, it means EOF or special handling required by caller.
-                // But at top level loop, we expect to consume everything.
-                break;
+                // `}` and `:` only mean something inside a placeholder; at top level they are text.
+                let c = self.next();
+                children.push(SnippetNode {
+                    type_: SnippetNodeType::Text,
+                    text: Some(c.to_string()),
+                    name: None, index: None, children: None, transform: None
+                });
             }
         }
         Ok(Snippet { children })
@@ -176,8 +177,23 @@ impl SnippetParser {
                 let mut regex = String::new();
                 while self.peek() != '/' && self.pos < self.text.len() { regex.push(self.next()); }
                 self.next(); // /
+                // The format may contain `${1:/upcase}`, whose `/` does not end it.
                 let mut format = String::new();
-                while self.peek() != '/' && self.pos < self.text.len() { format.push(self.next()); }
+                let mut depth = 0;
+                while (self.peek() != '/' || depth > 0) && self.pos < self.text.len() {
+                    let c = self.next();
+                    if c == '\\' && self.pos < self.text.len() {
+                        format.push(c);
+                        format.push(self.next());
+                        continue;
+                    }
+                    if c == '$' && self.peek() == '{' {
+                        depth += 1;
+                    } else if c == '}' && depth > 0 {
+                        depth -= 1;
+                    }
+                    format.push(c);
+                }
                 self.next(); // /
                 let mut options = String::new();
                 while self.peek() != '}' && self.pos < self.text.len() { options.push(self.next()); }
@@ -231,6 +247,16 @@ mod tests {
         assert!(n.children.is_some());
     }
 
+    #[test]
+    fn test_parse_transform_and_top_level_colon() {
+        let s = parse_snippet("a: ${1/(.*)/${1:/upcase}/g}".into()).unwrap();
+        let transform = s.children.last().unwrap().transform.as_ref().unwrap();
+        assert_eq!(transform.format, "${1:/upcase}");
+        assert_eq!(transform.options.as_deref(), Some("g"));
+        let text: String = s.children.iter().filter_map(|n| n.text.clone()).collect();
+        assert_eq!(text, "a: ");
+    }
+
     #[test]
     fn test_parse_variable() {
         let s = parse_snippet("$V".into()).unwrap();
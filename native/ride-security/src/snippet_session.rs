@@ -0,0 +1,476 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Snippet Session — Rust port of `src/vs/editor/contrib/snippet/browser/snippetSession.ts`
+//! and `snippetVariables.ts`.
+//!
+//! A snippet is rendered once per cursor, with variables resolved against that
+//! cursor and its text re-indented to the insertion line. Placeholders become
+//! model decorations so they move with edits; the active tabstop grows when
+//! typing at its edges and all of its occurrences are selected together, so
+//! linked placeholders mirror each other through multi-cursor typing. Regex
+//! transforms are applied when leaving a tabstop.
+
+use std::collections::HashMap;
+use chrono::{Datelike, Local, Timelike};
+use napi_derive::napi;
+use rand::Rng;
+use crate::range::Range;
+use crate::selection::Selection;
+use crate::snippet_parser::{Snippet, SnippetNode, SnippetNodeType, SnippetTransform};
+use crate::text_model::{ModelDecoration, ModelDecorationOptions, TextModel};
+use crate::text_model_types::{RangePod, SingleEditOperation, TrackedRangeStickiness};
+
+/// Values only the host knows.
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct SnippetVariables {
+    pub clipboard: Option<String>,
+    pub workspace_folder: Option<String>,
+}
+
+/// Everything the variables of one insertion (one cursor) resolve against.
+pub struct VariableContext {
+    pub file_path: String,
+    pub selected_text: String,
+    pub current_line: String,
+    pub current_word: String,
+    pub line_number: u32,
+    pub host: SnippetVariables,
+}
+
+impl VariableContext {
+    /// `None` for unknown variables, which are then inserted as placeholders named after themselves.
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        let now = Local::now();
+        let path = std::path::Path::new(&self.file_path);
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        Some(match name {
+            "TM_SELECTED_TEXT" => self.selected_text.clone(),
+            "TM_CURRENT_LINE" => self.current_line.clone(),
+            "TM_CURRENT_WORD" => self.current_word.clone(),
+            "TM_LINE_INDEX" => (self.line_number - 1).to_string(),
+            "TM_LINE_NUMBER" => self.line_number.to_string(),
+            "TM_FILENAME" => file_name,
+            "TM_FILENAME_BASE" => path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            "TM_DIRECTORY" => path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+            "TM_FILEPATH" => self.file_path.clone(),
+            "RELATIVE_FILEPATH" => match &self.host.workspace_folder {
+                Some(folder) => path.strip_prefix(folder).map(|p| p.to_string_lossy().to_string()).unwrap_or_else(|_| self.file_path.clone()),
+                None => self.file_path.clone(),
+            },
+            "WORKSPACE_FOLDER" => self.host.workspace_folder.clone()?,
+            "WORKSPACE_NAME" => std::path::Path::new(self.host.workspace_folder.as_ref()?).file_name()?.to_string_lossy().to_string(),
+            "CLIPBOARD" => self.host.clipboard.clone()?,
+            "CURRENT_YEAR" => now.year().to_string(),
+            "CURRENT_YEAR_SHORT" => format!("{:02}", now.year() % 100),
+            "CURRENT_MONTH" => format!("{:02}", now.month()),
+            "CURRENT_MONTH_NAME" => now.format("%B").to_string(),
+            "CURRENT_MONTH_NAME_SHORT" => now.format("%b").to_string(),
+            "CURRENT_DATE" => format!("{:02}", now.day()),
+            "CURRENT_DAY_NAME" => now.format("%A").to_string(),
+            "CURRENT_DAY_NAME_SHORT" => now.format("%a").to_string(),
+            "CURRENT_HOUR" => format!("{:02}", now.hour()),
+            "CURRENT_MINUTE" => format!("{:02}", now.minute()),
+            "CURRENT_SECOND" => format!("{:02}", now.second()),
+            "CURRENT_SECONDS_UNIX" => now.timestamp().to_string(),
+            "CURRENT_TIMEZONE_OFFSET" => now.format("%:z").to_string(),
+            "RANDOM" => format!("{:06}", rand::thread_rng().gen_range(0..1_000_000)),
+            "RANDOM_HEX" => format!("{:06x}", rand::thread_rng().gen_range(0..0x1000000)),
+            "UUID" => uuid::Uuid::new_v4().to_string(),
+            _ => return None,
+        })
+    }
+}
+
+/// Applies a `/regex/format/options` transform; text outside the matches is kept.
+pub fn apply_transform(value: &str, transform: &SnippetTransform) -> String {
+    let options = transform.options.as_deref().unwrap_or("");
+    let Ok(regex) = regex::RegexBuilder::new(&transform.regex).case_insensitive(options.contains('i')).build() else {
+        return value.to_string();
+    };
+    let limit = if options.contains('g') { 0 } else { 1 };
+    regex.replacen(value, limit, |caps: &regex::Captures| format_captures(&transform.format, caps)).into_owned()
+}
+
+fn format_captures(format: &str, caps: &regex::Captures) -> String {
+    let chars: Vec<char> = format.chars().collect();
+    let group = |n: usize| caps.get(n).map_or("", |m| m.as_str()).to_string();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                out.push(chars[i + 1]);
+                i += 2;
+            }
+            '$' if chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) => {
+                let digits: String = chars[i + 1..].iter().take_while(|c| c.is_ascii_digit()).collect();
+                out.push_str(&group(digits.parse().unwrap_or(0)));
+                i += 1 + digits.len();
+            }
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                let Some(close) = chars[i..].iter().position(|&c| c == '}') else {
+                    out.extend(&chars[i..]);
+                    break;
+                };
+                let inner: String = chars[i + 2..i + close].iter().collect();
+                let (number, rest) = inner.split_once(':').unwrap_or((&inner, ""));
+                let value = group(number.parse().unwrap_or(0));
+                out.push_str(&format_group(&value, rest));
+                i += close + 1;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// One `${n:...}` format: case modifiers, `+if`, `-else`, `?if:else` or a plain default.
+fn format_group(value: &str, spec: &str) -> String {
+    let capitalize = |s: &str| {
+        let mut chars = s.chars();
+        chars.next().map(|c| c.to_uppercase().collect::<String>() + chars.as_str()).unwrap_or_default()
+    };
+    let words = |s: &str| s.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(|w| w.to_string()).collect::<Vec<_>>();
+    match spec {
+        "" => value.to_string(),
+        "/upcase" => value.to_uppercase(),
+        "/downcase" => value.to_lowercase(),
+        "/capitalize" => capitalize(value),
+        "/pascalcase" => words(value).iter().map(|w| capitalize(&w.to_lowercase())).collect(),
+        "/camelcase" => {
+            let pascal: String = words(value).iter().map(|w| capitalize(&w.to_lowercase())).collect();
+            let mut chars = pascal.chars();
+            chars.next().map(|c| c.to_lowercase().collect::<String>() + chars.as_str()).unwrap_or_default()
+        }
+        _ if spec.starts_with('+') => if value.is_empty() { String::new() } else { spec[1..].to_string() },
+        _ if spec.starts_with('?') => {
+            let (if_value, else_value) = spec[1..].split_once(':').unwrap_or((&spec[1..], ""));
+            if value.is_empty() { else_value.to_string() } else { if_value.to_string() }
+        }
+        _ if spec.starts_with('-') => if value.is_empty() { spec[1..].to_string() } else { value.to_string() },
+        _ => if value.is_empty() { spec.to_string() } else { value.to_string() },
+    }
+}
+
+/// A placeholder of a rendered snippet, as byte offsets into its text.
+#[derive(Clone, Debug)]
+pub struct RenderedPlaceholder {
+    pub index: u32,
+    pub start: usize,
+    pub end: usize,
+    pub transform: Option<SnippetTransform>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderedSnippet {
+    pub text: String,
+    pub placeholders: Vec<RenderedPlaceholder>,
+}
+
+struct Renderer<'a> {
+    context: &'a VariableContext,
+    indent: &'a str,
+    indent_unit: &'a str,
+    defaults: HashMap<u32, String>,
+    /// Index handed to the next unknown variable.
+    next_index: u32,
+    unknown: HashMap<String, u32>,
+    out: RenderedSnippet,
+}
+
+impl Renderer<'_> {
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    self.out.text.push('\n');
+                    self.out.text.push_str(self.indent);
+                }
+                '\t' => self.out.text.push_str(self.indent_unit),
+                _ => self.out.text.push(c),
+            }
+        }
+    }
+
+    fn render(&mut self, nodes: &[SnippetNode]) {
+        for node in nodes {
+            match node.type_ {
+                SnippetNodeType::Text => self.push_text(node.text.as_deref().unwrap_or("")),
+                SnippetNodeType::Placeholder => {
+                    let index = node.index.unwrap_or(0);
+                    let start = self.out.text.len();
+                    let default = self.defaults.get(&index).cloned().unwrap_or_default();
+                    match (&node.transform, &node.children) {
+                        (Some(transform), _) => self.out.text.push_str(&apply_transform(&default, transform)),
+                        (None, Some(children)) if !children.is_empty() => self.render(children),
+                        (None, _) => self.out.text.push_str(&default),
+                    }
+                    let end = self.out.text.len();
+                    self.out.placeholders.push(RenderedPlaceholder { index, start, end, transform: node.transform.clone() });
+                }
+                SnippetNodeType::Variable => {
+                    let name = node.name.clone().unwrap_or_default();
+                    match self.context.resolve(&name) {
+                        Some(value) => {
+                            let value = match &node.transform {
+                                Some(transform) => apply_transform(&value, transform),
+                                None => value,
+                            };
+                            match &node.children {
+                                Some(children) if value.is_empty() => self.render(children),
+                                _ => self.out.text.push_str(&value),
+                            }
+                        }
+                        None => match &node.children {
+                            Some(children) if !children.is_empty() => self.render(children),
+                            _ => {
+                                let next_index = &mut self.next_index;
+                                let index = *self.unknown.entry(name.clone()).or_insert_with(|| {
+                                    *next_index += 1;
+                                    *next_index
+                                });
+                                let start = self.out.text.len();
+                                self.out.text.push_str(&name);
+                                self.out.placeholders.push(RenderedPlaceholder { index, start, end: self.out.text.len(), transform: None });
+                            }
+                        },
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The plain text of a placeholder's default, used for its mirrors.
+fn plain_text(nodes: &[SnippetNode], defaults: &HashMap<u32, String>) -> String {
+    nodes.iter().map(|node| match node.type_ {
+        SnippetNodeType::Text => node.text.clone().unwrap_or_default(),
+        SnippetNodeType::Placeholder => match &node.children {
+            Some(children) if node.transform.is_none() => plain_text(children, defaults),
+            _ => defaults.get(&node.index.unwrap_or(0)).cloned().unwrap_or_default(),
+        },
+        SnippetNodeType::Variable => node.name.clone().unwrap_or_default(),
+    }).collect()
+}
+
+fn collect_defaults(nodes: &[SnippetNode], defaults: &mut HashMap<u32, String>, max_index: &mut u32) {
+    for node in nodes {
+        if node.type_ == SnippetNodeType::Placeholder {
+            let index = node.index.unwrap_or(0);
+            *max_index = (*max_index).max(index);
+            if let Some(children) = node.children.as_ref().filter(|c| !c.is_empty() && node.transform.is_none()) {
+                collect_defaults(children, defaults, max_index);
+                if !defaults.contains_key(&index) {
+                    let text = plain_text(children, defaults);
+                    defaults.insert(index, text);
+                }
+            }
+        } else if let Some(children) = &node.children {
+            collect_defaults(children, defaults, max_index);
+        }
+    }
+}
+
+/// Renders `snippet` for one cursor. Lines after the first get `indent`, tabs become
+/// `indent_unit`, and a final tabstop is added at the end when the snippet has no `$0`.
+pub fn render_snippet(snippet: &Snippet, context: &VariableContext, indent: &str, indent_unit: &str) -> RenderedSnippet {
+    let mut defaults = HashMap::new();
+    let mut max_index = 0;
+    collect_defaults(&snippet.children, &mut defaults, &mut max_index);
+    let mut renderer = Renderer {
+        context,
+        indent,
+        indent_unit,
+        defaults,
+        next_index: max_index,
+        unknown: HashMap::new(),
+        out: RenderedSnippet { text: String::new(), placeholders: Vec::new() },
+    };
+    renderer.render(&snippet.children);
+    let mut out = renderer.out;
+    if !out.placeholders.iter().any(|p| p.index == 0) {
+        let end = out.text.len();
+        out.placeholders.push(RenderedPlaceholder { index: 0, start: end, end, transform: None });
+    }
+    out
+}
+
+struct SessionPlaceholder {
+    /// Which insertion (cursor) the placeholder belongs to.
+    instance: usize,
+    index: u32,
+    decoration_id: String,
+    transform: Option<SnippetTransform>,
+}
+
+/// Tabstop state of one inserted snippet (at every cursor).
+pub struct SnippetSession {
+    model: TextModel,
+    placeholders: Vec<SessionPlaceholder>,
+    /// Distinct tabstop indices in visiting order, `$0` last.
+    order: Vec<u32>,
+    current: Option<usize>,
+}
+
+fn placeholder_options(active: bool, is_final: bool) -> ModelDecorationOptions {
+    ModelDecorationOptions {
+        stickiness: if active { TrackedRangeStickiness::AlwaysGrowsWhenTypingAtEdges } else { TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges },
+        class_name: Some(if is_final { "finish-snippet-placeholder" } else { "snippet-placeholder" }.to_string()),
+        inline_class_name: None,
+        hover_message: None,
+        before_content: None,
+        after_content: None,
+    }
+}
+
+impl SnippetSession {
+    /// Decorates `placeholders`, given as (instance, rendered placeholder, model range).
+    pub fn new(model: &TextModel, placeholders: Vec<(usize, RenderedPlaceholder, Range)>) -> Self {
+        let decorations: Vec<ModelDecoration> = placeholders.iter().map(|(_, p, range)| ModelDecoration {
+            id: String::new(),
+            range: *range,
+            options: placeholder_options(false, p.index == 0),
+        }).collect();
+        let ids = model.delta_decorations(Vec::new(), decorations);
+        let mut order: Vec<u32> = placeholders.iter().map(|(_, p, _)| p.index).filter(|&i| i != 0).collect();
+        order.sort_unstable();
+        order.dedup();
+        order.push(0);
+        Self {
+            model: model.clone(),
+            placeholders: placeholders.into_iter().zip(ids).map(|((instance, p, _), decoration_id)| SessionPlaceholder {
+                instance,
+                index: p.index,
+                decoration_id,
+                transform: p.transform,
+            }).collect(),
+            order,
+            current: None,
+        }
+    }
+
+    pub fn is_at_last_placeholder(&self) -> bool {
+        self.current == Some(self.order.len() - 1)
+    }
+
+    pub fn is_at_first_placeholder(&self) -> bool {
+        self.current.is_none_or(|c| c == 0)
+    }
+
+    /// Moves `delta` tabstops, applying the transforms of the one being left, and
+    /// returns the selections covering every editable occurrence of the new tabstop.
+    pub fn move_by(&mut self, delta: i32) -> Vec<Selection> {
+        self.apply_transforms();
+        let target = match self.current {
+            None => 0,
+            Some(current) => (current as i64 + delta as i64).clamp(0, self.order.len() as i64 - 1) as usize,
+        };
+        self.current = Some(target);
+        let index = self.order[target];
+
+        // Re-add decorations so only the active tabstop grows with typing at its edges.
+        let old_ids: Vec<String> = self.placeholders.iter().map(|p| p.decoration_id.clone()).collect();
+        let ranges: Vec<Option<Range>> = old_ids.iter().map(|id| self.model.get_decoration_range(id.clone())).collect();
+        let decorations: Vec<ModelDecoration> = self.placeholders.iter().zip(&ranges).filter_map(|(p, range)| Some(ModelDecoration {
+            id: String::new(),
+            range: (*range)?,
+            options: placeholder_options(p.index == index && p.index != 0, p.index == 0),
+        })).collect();
+        let mut new_ids = self.model.delta_decorations(old_ids, decorations).into_iter();
+        let mut kept = Vec::with_capacity(self.placeholders.len());
+        for (mut placeholder, range) in self.placeholders.drain(..).zip(&ranges) {
+            if range.is_some() {
+                placeholder.decoration_id = new_ids.next().unwrap_or_default();
+                kept.push(placeholder);
+            }
+        }
+        self.placeholders = kept;
+
+        let mut selections: Vec<Selection> = self.placeholders.iter()
+            .filter(|p| p.index == index && p.transform.is_none())
+            .filter_map(|p| self.model.get_decoration_range(p.decoration_id.clone()))
+            .map(|r| Selection::new(r.start_line_number, r.start_column, r.end_line_number, r.end_column))
+            .collect();
+        selections.sort_by_key(|s| (s.selection_start_line_number, s.selection_start_column));
+        selections
+    }
+
+    /// Rewrites transformed occurrences of the current tabstop from its editable one.
+    fn apply_transforms(&self) {
+        let Some(index) = self.current.map(|c| self.order[c]) else { return };
+        let mut edits = Vec::new();
+        for placeholder in self.placeholders.iter().filter(|p| p.index == index) {
+            let Some(transform) = &placeholder.transform else { continue };
+            let source = self.placeholders.iter()
+                .find(|p| p.instance == placeholder.instance && p.index == index && p.transform.is_none())
+                .and_then(|p| self.model.get_decoration_range(p.decoration_id.clone()));
+            let (Some(source), Some(target)) = (source, self.model.get_decoration_range(placeholder.decoration_id.clone())) else { continue };
+            let text = apply_transform(&self.model.get_value_in_range(source), transform);
+            if text != self.model.get_value_in_range(target) {
+                edits.push(SingleEditOperation {
+                    range: RangePod {
+                        start_line_number: target.start_line_number,
+                        start_column: target.start_column,
+                        end_line_number: target.end_line_number,
+                        end_column: target.end_column,
+                    },
+                    text: Some(text),
+                    force_move_markers: None,
+                });
+            }
+        }
+        if !edits.is_empty() {
+            self.model.apply_edits(edits);
+        }
+    }
+
+    /// Applies pending transforms and removes the placeholder decorations.
+    pub fn dispose(&mut self) {
+        self.apply_transforms();
+        let ids = self.placeholders.drain(..).map(|p| p.decoration_id).collect();
+        self.model.delta_decorations(ids, Vec::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snippet_parser::parse_snippet;
+
+    fn context() -> VariableContext {
+        VariableContext {
+            file_path: "/work/src/main.rs".into(),
+            selected_text: String::new(),
+            current_line: String::new(),
+            current_word: String::new(),
+            line_number: 3,
+            host: SnippetVariables { clipboard: Some("clip".into()), workspace_folder: Some("/work".into()) },
+        }
+    }
+
+    #[test]
+    fn test_transforms() {
+        let transform = |regex: &str, format: &str, options: &str| SnippetTransform { regex: regex.into(), format: format.into(), options: Some(options.into()) };
+        assert_eq!(apply_transform("foo_bar", &transform("(.*)", "${1:/pascalcase}", "")), "FooBar");
+        assert_eq!(apply_transform("a-b-c", &transform("-", "_", "g")), "a_b_c");
+        assert_eq!(apply_transform("x", &transform("(y)?x", "${1:?yes:no}", "")), "no");
+        assert_eq!(apply_transform("Name", &transform("^(.)", "${1:/downcase}", "")), "name");
+    }
+
+    #[test]
+    fn test_render_variables_and_indent() {
+        let snippet = parse_snippet("fn ${1:name}() {\n\t${TM_FILENAME_BASE/(.*)/${1:/upcase}/} $CLIPBOARD $RELATIVE_FILEPATH ${UNKNOWN}$1\n}".into()).unwrap();
+        let rendered = render_snippet(&snippet, &context(), "  ", "    ");
+        assert_eq!(rendered.text, "fn name() {\n      MAIN clip src/main.rs UNKNOWNname\n  }");
+        let indices: Vec<_> = rendered.placeholders.iter().map(|p| p.index).collect();
+        assert_eq!(indices, vec![1, 2, 1, 0]);
+        assert_eq!(&rendered.text[rendered.placeholders[1].start..rendered.placeholders[1].end], "UNKNOWN");
+    }
+}
@@ -2145,6 +2145,14 @@ pub fn compute_code_point(high: u32, low: u32) -> u32 {
     ((high - 0xD800) << 10) + (low - 0xDC00) + 0x10000
 }
 
+/// Characters rendered two columns wide in a monospace font (CJK, Hangul, fullwidth forms).
+#[napi]
+pub fn is_full_width_character(char_code: u32) -> bool {
+    (0x2E80..=0xD7AF).contains(&char_code)
+        || (0xF900..=0xFAFF).contains(&char_code)
+        || (0xFF01..=0xFF5E).contains(&char_code)
+}
+
 #[napi]
 pub fn is_emoji_imprecise(x: u32) -> bool {
     (x >= 0x1F1E6 && x <= 0x1F1FF) || (x == 8986) || (x == 8987) || (x == 9200) ||
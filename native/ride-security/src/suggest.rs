@@ -3,12 +3,15 @@
  *  Licensed under the MIT License. See License.txt in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
-//! Suggest Engine — Rust port of `src/vs/editor/contrib/suggest/browser/suggest.ts`.
-//! Filter and sort completion items based on fuzzy matching.
+//! Suggest Engine — Rust port of `src/vs/editor/contrib/suggest/browser/completionModel.ts`.
+//! Filter and sort completion items with `fuzzyScore`, honouring `filterText`/`sortText`,
+//! boosting recently accepted items and refiltering incrementally while the user types.
 
 use napi_derive::napi;
-use napi::bindgen_prelude::*;
-use crate::glob_engine::glob_fuzzy_match; // Uses fuzzy logic from Phase 8
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use crate::filters::{create_matches, fuzzy_score_raw, MatchRange};
 
 #[napi(object)]
 #[derive(Clone, Debug)]
@@ -18,62 +21,220 @@ pub struct CompletionItem {
     pub sort_text: Option<String>,
     pub kind: Option<u32>,
     pub score: Option<f64>,
+    /// Matched ranges in `label`, in chars.
+    pub highlights: Option<Vec<MatchRange>>,
 }
 
+/// Recently accepted completions, most recent with the highest sequence number.
+#[derive(Default)]
+struct SuggestMemory {
+    seq: u64,
+    used: HashMap<String, u64>,
+}
+
+fn get_suggest_memory() -> &'static Mutex<SuggestMemory> {
+    static MEMORY: OnceLock<Mutex<SuggestMemory>> = OnceLock::new();
+    MEMORY.get_or_init(|| Mutex::new(SuggestMemory::default()))
+}
+
+/// Records that `label` was accepted so it ranks ahead of equally scored items later.
 #[napi]
-pub fn filter_completion_items(query: String, items: Vec<CompletionItem>) -> Vec<CompletionItem> {
-    let mut scored_items: Vec<CompletionItem> = items.into_iter().map(|mut item| {
-        let text = item.filter_text.as_deref().unwrap_or(&item.label);
-        let result = glob_fuzzy_match(query.clone(), text.to_string());
-        if result.score > 0.0 {
-            item.score = Some(result.score);
-        } else {
-            item.score = None;
+pub fn remember_completion(label: String) {
+    let mut memory = get_suggest_memory().lock().unwrap();
+    memory.seq += 1;
+    let seq = memory.seq;
+    memory.used.insert(label, seq);
+}
+
+#[napi]
+pub fn clear_completion_memory() {
+    let mut memory = get_suggest_memory().lock().unwrap();
+    *memory = SuggestMemory::default();
+}
+
+struct CachedItem {
+    item: CompletionItem,
+    label: Vec<char>,
+    filter: Option<Vec<char>>,
+    /// `filterText` equals the label ignoring case, so its match positions are label highlights.
+    filter_is_label: bool,
+    sort_low: Option<String>,
+    score: i32,
+    matches: Vec<MatchRange>,
+}
+
+impl CachedItem {
+    fn new(item: CompletionItem) -> Self {
+        let filter_is_label = item.filter_text.as_ref().is_some_and(|f| f.to_lowercase() == item.label.to_lowercase());
+        Self {
+            label: item.label.chars().collect(),
+            filter: item.filter_text.as_ref().map(|f| f.chars().collect()),
+            filter_is_label,
+            sort_low: item.sort_text.as_ref().map(|s| s.to_lowercase()),
+            score: 0,
+            matches: Vec::new(),
+            item,
+        }
+    }
+
+    /// Scores the item against `word` (from `word_start`). A `filterText` decides whether and
+    /// how well the item matches; the label is rescored only to find highlights.
+    fn score(&mut self, word: &[char], word_start: usize) -> bool {
+        if word_start >= word.len() {
+            self.score = 0;
+            self.matches.clear();
+            return true;
         }
-        item
-    }).filter(|item| item.score.is_some()).collect();
-
-    // Sort by score (descending), then sortText (ascending), then label (ascending)
-    scored_items.sort_by(|a, b| {
-        b.score.unwrap_or(0.0).partial_cmp(&a.score.unwrap_or(0.0)).unwrap()
-            .then_with(|| {
-                a.sort_text.as_deref().unwrap_or(&a.label)
-                    .e_cmp(b.sort_text.as_deref().unwrap_or(&b.label))
-            })
-    });
-
-    scored_items
+        match &self.filter {
+            Some(filter) => {
+                let Some((score, positions)) = fuzzy_score_raw(word, word_start, filter, 0, false, true) else { return false };
+                self.score = score;
+                self.matches = if self.filter_is_label {
+                    create_matches(&positions)
+                } else {
+                    fuzzy_score_raw(word, word_start, &self.label, 0, true, true)
+                        .map(|(_, positions)| create_matches(&positions))
+                        .unwrap_or_default()
+                };
+            }
+            None => {
+                let Some((score, positions)) = fuzzy_score_raw(word, word_start, &self.label, 0, false, true) else { return false };
+                self.score = score;
+                self.matches = create_matches(&positions);
+            }
+        }
+        true
+    }
+
+    fn to_item(&self) -> CompletionItem {
+        CompletionItem { score: Some(self.score as f64), highlights: Some(self.matches.clone()), ..self.item.clone() }
+    }
 }
 
-trait Ecmp {
-    fn e_cmp(&self, other: &Self) -> std::cmp::Ordering;
+#[napi]
+pub struct CompletionModel {
+    items: Vec<CachedItem>,
+    word: String,
+    /// Indices of the items matching `word`, in display order.
+    filtered: Vec<usize>,
+}
+
+#[napi]
+impl CompletionModel {
+    #[napi(constructor)]
+    pub fn new(items: Vec<CompletionItem>) -> Self {
+        let mut model = Self { items: items.into_iter().map(CachedItem::new).collect(), word: String::new(), filtered: Vec::new() };
+        model.refilter(true);
+        model
+    }
+
+    /// Updates the word before the cursor. When the new word extends the previous one only
+    /// the items that still matched are rescored.
+    #[napi]
+    pub fn set_word(&mut self, word: String) -> Vec<CompletionItem> {
+        let incremental = !self.word.is_empty() && word.starts_with(&self.word);
+        self.word = word;
+        self.refilter(!incremental);
+        self.get_items()
+    }
+
+    #[napi]
+    pub fn get_items(&self) -> Vec<CompletionItem> {
+        self.filtered.iter().map(|&i| self.items[i].to_item()).collect()
+    }
+
+    #[napi(getter)]
+    pub fn word(&self) -> String {
+        self.word.clone()
+    }
+
+    fn refilter(&mut self, all: bool) {
+        let word: Vec<char> = self.word.chars().collect();
+        // Leading whitespace in the word is never matched.
+        let word_start = word.iter().take_while(|c| matches!(c, ' ' | '\t')).count();
+        let candidates: Vec<usize> = if all { (0..self.items.len()).collect() } else { std::mem::take(&mut self.filtered) };
+        let mut filtered: Vec<usize> = candidates.into_iter().filter(|&i| self.items[i].score(&word, word_start)).collect();
+
+        let memory = get_suggest_memory().lock().unwrap();
+        let recency = |item: &CachedItem| memory.used.get(&item.item.label).copied().unwrap_or(0);
+        filtered.sort_by(|&a, &b| {
+            let (x, y) = (&self.items[a], &self.items[b]);
+            y.score.cmp(&x.score)
+                .then_with(|| recency(y).cmp(&recency(x)))
+                .then_with(|| compare_sort_text(x, y))
+                .then_with(|| x.item.label.cmp(&y.item.label))
+                .then_with(|| a.cmp(&b))
+        });
+        self.filtered = filtered;
+    }
 }
 
-impl Ecmp for str {
-    fn e_cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // Simple string comparison for now. VS Code uses locale compare.
-        self.cmp(other)
+fn compare_sort_text(a: &CachedItem, b: &CachedItem) -> Ordering {
+    match (&a.sort_low, &b.sort_low) {
+        (Some(x), Some(y)) => x.cmp(y),
+        _ => Ordering::Equal,
     }
 }
 
+#[napi]
+pub fn filter_completion_items(query: String, items: Vec<CompletionItem>) -> Vec<CompletionItem> {
+    let mut model = CompletionModel::new(items);
+    model.set_word(query)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn item(label: &str, filter_text: Option<&str>, sort_text: Option<&str>) -> CompletionItem {
+        CompletionItem {
+            label: label.into(),
+            filter_text: filter_text.map(Into::into),
+            sort_text: sort_text.map(Into::into),
+            kind: None,
+            score: None,
+            highlights: None,
+        }
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|i| i.label.as_str()).collect()
+    }
+
     #[test]
     fn test_filter() {
-        let items = vec![
-            CompletionItem { label: "console".into(), filter_text: None, sort_text: None, kind: None, score: None },
-            CompletionItem { label: "const".into(), filter_text: None, sort_text: None, kind: None, score: None },
-            CompletionItem { label: "bar".into(), filter_text: None, sort_text: None, kind: None, score: None },
-        ];
+        let items = vec![item("console", None, None), item("const", None, None), item("bar", None, None)];
 
         let filtered = filter_completion_items("con".into(), items);
         assert_eq!(filtered.len(), 2);
-        assert_eq!(filtered[0].label, "console"); // fuzzy match "con" -> "console" (score higher?) or same?
-        // "console" has "con" at start. "const" has "con" at start.
-        // Length penalty might make "const" score higher (shorter)?
-        // Let's assume glob_fuzzy_match handles it.
-        // Actually fuzzy match usually prioritizes shorter matches or exact prefixes.
+        // Equal prefix scores fall back to the label order.
+        assert_eq!(labels(&filtered), ["console", "const"]);
+        assert_eq!(filtered[0].highlights, Some(vec![MatchRange { start: 0, end: 3 }]));
+
+        let filtered = filter_completion_items("gv".into(), vec![item("getValue", None, None), item("gravy", None, None)]);
+        assert_eq!(labels(&filtered), ["getValue", "gravy"]);
+        assert_eq!(filtered[0].highlights, Some(vec![MatchRange { start: 0, end: 1 }, MatchRange { start: 3, end: 4 }]));
+    }
+
+    #[test]
+    fn test_filter_text_sort_text_and_memory() {
+        let items = vec![
+            item("zeta", None, Some("b")),
+            item("zebra", None, Some("a")),
+            item("Array.from", Some("from"), None),
+        ];
+        let mut model = CompletionModel::new(items);
+        assert_eq!(labels(&model.set_word("ze".into())), ["zebra", "zeta"]);
+
+        let from = model.set_word("fr".into());
+        assert_eq!(labels(&from), ["Array.from"]);
+        assert_eq!(from[0].highlights, Some(vec![MatchRange { start: 6, end: 8 }]));
+
+        remember_completion("zeta".into());
+        assert_eq!(labels(&model.set_word("z".into())), ["zeta", "zebra"]);
+        // Incremental refilter only rescores the survivors.
+        assert_eq!(labels(&model.set_word("zeb".into())), ["zebra"]);
+        assert_eq!(labels(&model.set_word("".into())).len(), 3);
+        clear_completion_memory();
     }
 }
@@ -0,0 +1,696 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! RIDE Workspace Symbol Index
+//!
+//! Features:
+//! - Grammar-heuristic symbol extraction for Rust, TypeScript/JavaScript, Python, Go,
+//!   Java/Kotlin/C# and C/C++ (functions, classes, methods, constants, types)
+//! - Container tracking through brace depth (or indentation for Python)
+//! - Background, parallel workspace indexing with mtime-based reuse
+//! - Persistent MessagePack cache alongside the file index cache
+//! - Incremental updates from `fs_watcher` events
+//! - Fuzzy-ranked workspace-symbol and go-to-symbol queries
+
+use ignore::WalkBuilder;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError, RwLock};
+use crate::filters::{fuzzy_score_str, MatchRange};
+use crate::fs_watcher::FsEvent;
+
+/// Files larger than this are not parsed for symbols.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+#[napi(object)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SymbolInformation {
+    pub name: String,
+    /// "function", "method", "class", "struct", "interface", "trait", "enum", "type",
+    /// "module" or "constant".
+    pub kind: String,
+    pub container_name: Option<String>,
+    pub path: String,
+    /// 1-based line and char column of the symbol name.
+    pub line: u32,
+    pub column: u32,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SymbolMatch {
+    pub symbol: SymbolInformation,
+    pub score: f64,
+    pub highlights: Vec<MatchRange>,
+}
+
+#[napi(object)]
+pub struct SymbolIndexStatus {
+    pub indexing: bool,
+    pub files: u32,
+    pub symbols: u32,
+}
+
+// ─── Extraction ─────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    /// Declares no scope for its members.
+    None,
+    /// Functions declared directly inside become methods.
+    Type,
+    /// Members keep their own kind (modules, namespaces).
+    Namespace,
+}
+
+struct Rule {
+    re: Regex,
+    /// `None` for rules that only open a container (`impl` blocks).
+    kind: Option<&'static str>,
+    scope: Scope,
+    /// Only applies directly inside a `Scope::Type` container.
+    member_only: bool,
+    /// Only applies at the top level.
+    top_level_only: bool,
+}
+
+struct LanguageRules {
+    rules: Vec<Rule>,
+    indent_scoped: bool,
+    /// Whether `'` starts a string rather than a char literal or lifetime.
+    single_quote_strings: bool,
+}
+
+fn rule(pattern: &str, kind: Option<&'static str>, scope: Scope) -> Rule {
+    Rule { re: Regex::new(pattern).expect("invalid symbol pattern"), kind, scope, member_only: false, top_level_only: false }
+}
+
+fn member(pattern: &str, kind: &'static str) -> Rule {
+    Rule { member_only: true, ..rule(pattern, Some(kind), Scope::None) }
+}
+
+fn top_level(pattern: &str, kind: &'static str) -> Rule {
+    Rule { top_level_only: true, ..rule(pattern, Some(kind), Scope::None) }
+}
+
+const RESERVED: &[&str] = &["if", "for", "while", "switch", "catch", "return", "else", "new", "sizeof", "do", "function", "await", "typeof", "delete", "throw"];
+
+fn rust_rules() -> LanguageRules {
+    const VIS: &str = r"^\s*(?:pub(?:\([^)]*\))?\s+)?";
+    LanguageRules {
+        rules: vec![
+            rule(&format!(r#"{VIS}(?:(?:const|async|unsafe|extern\s+"[^"]*")\s+)*fn\s+(?P<name>\w+)"#), Some("function"), Scope::None),
+            rule(&format!(r"{VIS}struct\s+(?P<name>\w+)"), Some("struct"), Scope::None),
+            rule(&format!(r"{VIS}enum\s+(?P<name>\w+)"), Some("enum"), Scope::None),
+            rule(&format!(r"{VIS}(?:unsafe\s+)?trait\s+(?P<name>\w+)"), Some("trait"), Scope::Type),
+            rule(r"^\s*(?:unsafe\s+)?impl\b(?:\s*<[^{]*?>)?\s+(?:[^{]*?\bfor\s+)?(?P<name>\w+)", None, Scope::Type),
+            rule(&format!(r"{VIS}(?:const|static)\s+(?:mut\s+)?(?P<name>\w+)\s*:"), Some("constant"), Scope::None),
+            rule(&format!(r"{VIS}type\s+(?P<name>\w+)"), Some("type"), Scope::None),
+            rule(&format!(r"{VIS}mod\s+(?P<name>\w+)"), Some("module"), Scope::Namespace),
+        ],
+        indent_scoped: false,
+        single_quote_strings: false,
+    }
+}
+
+fn typescript_rules() -> LanguageRules {
+    const EXPORT: &str = r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?";
+    LanguageRules {
+        rules: vec![
+            rule(&format!(r"{EXPORT}(?:async\s+)?function\s*\*?\s*(?P<name>[\w$]+)"), Some("function"), Scope::None),
+            rule(&format!(r"{EXPORT}(?:abstract\s+)?class\s+(?P<name>[\w$]+)"), Some("class"), Scope::Type),
+            rule(&format!(r"{EXPORT}interface\s+(?P<name>[\w$]+)"), Some("interface"), Scope::None),
+            rule(&format!(r"{EXPORT}(?:const\s+)?enum\s+(?P<name>[\w$]+)"), Some("enum"), Scope::None),
+            rule(&format!(r"{EXPORT}type\s+(?P<name>[\w$]+)\s*(?:<[^=]*>)?\s*="), Some("type"), Scope::None),
+            rule(&format!(r"{EXPORT}(?:namespace|module)\s+(?P<name>[\w$.]+)\s*\{{"), Some("module"), Scope::Namespace),
+            top_level(&format!(r"{EXPORT}(?:const|let|var)\s+(?P<name>[\w$]+)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function\b|\([^)]*\)\s*(?::[^=]+)?=>|[\w$]+\s*=>)"), "function"),
+            top_level(&format!(r"{EXPORT}const\s+(?P<name>[\w$]+)"), "constant"),
+            member(r"^\s*(?:(?:public|private|protected|static|readonly|async|override|abstract|get|set)\s+)*\*?(?P<name>#?[\w$]+)\s*(?:<[^>]*>)?\s*\(", "method"),
+        ],
+        indent_scoped: false,
+        single_quote_strings: true,
+    }
+}
+
+fn python_rules() -> LanguageRules {
+    LanguageRules {
+        rules: vec![
+            rule(r"^\s*(?:async\s+)?def\s+(?P<name>\w+)", Some("function"), Scope::None),
+            rule(r"^\s*class\s+(?P<name>\w+)", Some("class"), Scope::Type),
+            top_level(r"^(?P<name>[A-Z][A-Z0-9_]*)\s*(?::[^=]*)?=[^=]", "constant"),
+        ],
+        indent_scoped: true,
+        single_quote_strings: true,
+    }
+}
+
+fn go_rules() -> LanguageRules {
+    LanguageRules {
+        rules: vec![
+            rule(r"^func\s+\(\s*\w*\s*\*?\s*(?P<container>\w+)[^)]*\)\s*(?P<name>\w+)", Some("method"), Scope::None),
+            rule(r"^func\s+(?P<name>\w+)", Some("function"), Scope::None),
+            rule(r"^type\s+(?P<name>\w+)\s+struct\b", Some("struct"), Scope::None),
+            rule(r"^type\s+(?P<name>\w+)\s+interface\b", Some("interface"), Scope::None),
+            rule(r"^type\s+(?P<name>\w+)", Some("type"), Scope::None),
+            rule(r"^const\s+(?P<name>\w+)", Some("constant"), Scope::None),
+        ],
+        indent_scoped: false,
+        single_quote_strings: false,
+    }
+}
+
+fn java_rules() -> LanguageRules {
+    const MODS: &str = r"^\s*(?:@\w+\s+)*(?:(?:public|private|protected|internal|static|abstract|final|sealed|partial|open|data|inner)\s+)*";
+    LanguageRules {
+        rules: vec![
+            rule(&format!(r"{MODS}(?:class|record|object)\s+(?P<name>\w+)"), Some("class"), Scope::Type),
+            rule(&format!(r"{MODS}interface\s+(?P<name>\w+)"), Some("interface"), Scope::Type),
+            rule(&format!(r"{MODS}enum\s+(?:class\s+)?(?P<name>\w+)"), Some("enum"), Scope::None),
+            rule(r"^\s*namespace\s+(?P<name>[\w.]+)", Some("module"), Scope::Namespace),
+            rule(r"^\s*(?:(?:\w+)\s+)*fun\s+(?:<[^>]*>\s*)?(?:[\w.]+\.)?(?P<name>\w+)\s*\(", Some("function"), Scope::None),
+            member(r"^\s*(?:(?:public|private|protected|internal)\s+)?(?:static\s+final|const)\s+[\w<>\[\]]+\s+(?P<name>\w+)\s*=", "constant"),
+            member(r"^\s*(?:@\w+\s+)*(?:(?:public|private|protected|internal|static|abstract|final|override|virtual|async|synchronized|native|default)\s+)*(?:<[^>]*>\s*)?[\w<>\[\],.?]+\s+(?P<name>\w+)\s*\([^;]*$", "method"),
+        ],
+        indent_scoped: false,
+        single_quote_strings: false,
+    }
+}
+
+fn c_rules() -> LanguageRules {
+    LanguageRules {
+        rules: vec![
+            rule(r"^\s*namespace\s+(?P<name>\w+)", Some("module"), Scope::Namespace),
+            rule(r"^\s*(?:typedef\s+)?struct\s+(?P<name>\w+)\s*(?::[^{;]*)?\{?\s*$", Some("struct"), Scope::Type),
+            rule(r"^\s*(?:template\s*<[^>]*>\s*)?class\s+(?P<name>\w+)\s*(?::[^{;]*)?\{?\s*$", Some("class"), Scope::Type),
+            rule(r"^\s*(?:typedef\s+)?enum\s+(?:class\s+)?(?P<name>\w+)", Some("enum"), Scope::None),
+            top_level(r"^\s*#\s*define\s+(?P<name>[A-Z_][A-Z0-9_]*)\b", "constant"),
+            rule(r"^\s*(?:[\w:*&<>,]+\s+)+[*&]*(?P<name>[\w:~]+)\s*\([^;]*$", Some("function"), Scope::None),
+        ],
+        indent_scoped: false,
+        single_quote_strings: false,
+    }
+}
+
+fn rules_for_path(path: &str) -> Option<&'static LanguageRules> {
+    static RUST: OnceLock<LanguageRules> = OnceLock::new();
+    static TS: OnceLock<LanguageRules> = OnceLock::new();
+    static PY: OnceLock<LanguageRules> = OnceLock::new();
+    static GO: OnceLock<LanguageRules> = OnceLock::new();
+    static JAVA: OnceLock<LanguageRules> = OnceLock::new();
+    static C: OnceLock<LanguageRules> = OnceLock::new();
+    let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+    Some(match ext.as_str() {
+        "rs" => RUST.get_or_init(rust_rules),
+        "ts" | "tsx" | "mts" | "cts" | "js" | "jsx" | "mjs" | "cjs" => TS.get_or_init(typescript_rules),
+        "py" | "pyi" => PY.get_or_init(python_rules),
+        "go" => GO.get_or_init(go_rules),
+        "java" | "kt" | "kts" | "cs" => JAVA.get_or_init(java_rules),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => C.get_or_init(c_rules),
+        _ => return None,
+    })
+}
+
+/// Blanks out strings and comments so braces and `;` inside them are ignored.
+fn strip_line(line: &str, in_block_comment: &mut bool, lang: &LanguageRules) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if *in_block_comment {
+            if c == '*' && next == Some('/') {
+                *in_block_comment = false;
+                i += 1;
+            }
+        } else if (lang.indent_scoped && c == '#') || (!lang.indent_scoped && c == '/' && next == Some('/')) {
+            break;
+        } else if !lang.indent_scoped && c == '/' && next == Some('*') {
+            *in_block_comment = true;
+            i += 1;
+        } else if c == '"' || c == '`' || (c == '\'' && lang.single_quote_strings) {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+        } else if c == '\'' {
+            // Char literals: 'x' or '\n'; anything else is a lifetime.
+            let len = if next == Some('\\') { 4 } else { 3 };
+            if chars.get(i + len - 1) == Some(&'\'') {
+                i += len - 1;
+            } else {
+                out.push(c);
+            }
+        } else {
+            out.push(c);
+        }
+        i += 1;
+    }
+    out
+}
+
+struct Container {
+    name: String,
+    scope: Scope,
+    /// Brace depth (or indentation) of the container's members.
+    depth: usize,
+}
+
+fn char_column(line: &str, byte: usize) -> u32 {
+    line[..byte].chars().count() as u32 + 1
+}
+
+/// Extracts the symbols declared in `content`, using the rules for `path`'s extension.
+pub fn extract_symbols(path: &str, content: &str) -> Vec<SymbolInformation> {
+    let Some(lang) = rules_for_path(path) else { return Vec::new() };
+    let mut symbols = Vec::new();
+    let mut stack: Vec<Container> = Vec::new();
+    let mut pending: Option<(String, Scope)> = None;
+    let mut depth = 0usize;
+    let mut in_block_comment = false;
+
+    for (line_index, line) in content.lines().enumerate() {
+        let code = strip_line(line, &mut in_block_comment, lang);
+        if code.trim().is_empty() {
+            continue;
+        }
+        if lang.indent_scoped {
+            depth = code.len() - code.trim_start().len();
+            while stack.last().is_some_and(|c| depth < c.depth) {
+                stack.pop();
+            }
+        }
+
+        let parent = stack.last().filter(|c| c.depth == depth || (lang.indent_scoped && depth >= c.depth));
+        let at_top = if lang.indent_scoped { stack.is_empty() } else { depth == 0 };
+        let in_type = parent.is_some_and(|c| c.scope == Scope::Type);
+        let visible = at_top || parent.is_some_and(|c| c.scope != Scope::None);
+
+        if visible {
+            let matched = lang.rules.iter().find_map(|r| {
+                if (r.member_only && !in_type) || (r.top_level_only && !at_top) {
+                    return None;
+                }
+                let caps = r.re.captures(line)?;
+                let name = caps.name("name")?;
+                if RESERVED.contains(&name.as_str()) || (r.kind == Some("method") && code.trim_end().ends_with(';')) {
+                    return None;
+                }
+                Some((r, caps.name("container").map(|m| m.as_str().to_string()), name))
+            });
+            if let Some((r, explicit_container, name)) = matched {
+                if let Some(kind) = r.kind {
+                    let kind = if kind == "function" && in_type { "method" } else { kind };
+                    symbols.push(SymbolInformation {
+                        name: name.as_str().to_string(),
+                        kind: kind.to_string(),
+                        container_name: explicit_container.or_else(|| parent.map(|c| c.name.clone())),
+                        path: path.to_string(),
+                        line: line_index as u32 + 1,
+                        column: char_column(line, name.start()),
+                    });
+                }
+                // Python functions open an opaque scope so their locals are not reported.
+                let scope = if lang.indent_scoped && r.kind == Some("function") { Some(Scope::None) } else { Some(r.scope).filter(|s| *s != Scope::None) };
+                if let Some(scope) = scope {
+                    if lang.indent_scoped {
+                        // Members are anything indented deeper than the declaration.
+                        stack.push(Container { name: name.as_str().to_string(), scope, depth: depth + 1 });
+                    } else {
+                        pending = Some((name.as_str().to_string(), scope));
+                    }
+                }
+            }
+        }
+
+        if !lang.indent_scoped {
+            for c in code.chars() {
+                match c {
+                    '{' => {
+                        depth += 1;
+                        if let Some((name, scope)) = pending.take() {
+                            stack.push(Container { name, scope, depth });
+                        }
+                    }
+                    '}' => {
+                        while stack.last().is_some_and(|c| c.depth >= depth) {
+                            stack.pop();
+                        }
+                        depth = depth.saturating_sub(1);
+                    }
+                    ';' => pending = None,
+                    _ => {}
+                }
+            }
+        }
+    }
+    symbols
+}
+
+#[napi]
+pub fn extract_document_symbols(path: String, content: String) -> Vec<SymbolInformation> {
+    extract_symbols(&path, &content)
+}
+
+// ─── Index ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    mtime: f64,
+    symbols: Vec<SymbolInformation>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SymbolIndex {
+    root: String,
+    files: HashMap<String, IndexedFile>,
+}
+
+static SYMBOL_INDEX: RwLock<Option<SymbolIndex>> = RwLock::new(None);
+static INDEXING: AtomicBool = AtomicBool::new(false);
+/// Watcher events received while a build is running; replayed on the index it installs.
+static EVENTS_DURING_BUILD: Mutex<Option<Vec<FsEvent>>> = Mutex::new(None);
+
+/// Buffers watcher events in `EVENTS_DURING_BUILD` for as long as it lives, so a build that
+/// panics does not leave events piling up.
+struct BuildEventBuffer;
+
+impl BuildEventBuffer {
+    fn start() -> Self {
+        EVENTS_DURING_BUILD.lock().unwrap().get_or_insert_with(Vec::new);
+        Self
+    }
+
+    /// Stops buffering and returns what was buffered.
+    fn finish(self) -> Vec<FsEvent> {
+        EVENTS_DURING_BUILD.lock().unwrap().take().unwrap_or_default()
+    }
+}
+
+impl Drop for BuildEventBuffer {
+    fn drop(&mut self) {
+        *EVENTS_DURING_BUILD.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+fn file_mtime(meta: &std::fs::Metadata) -> Option<f64> {
+    Some(meta.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs_f64())
+}
+
+/// Parses one file, or returns `None` when it is unsupported, too large or unreadable.
+fn index_file(path: &Path) -> Option<IndexedFile> {
+    let path_str = path.to_string_lossy();
+    rules_for_path(&path_str)?;
+    let meta = std::fs::metadata(path).ok()?;
+    if !meta.is_file() || meta.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    let content = String::from_utf8_lossy(&bytes);
+    Some(IndexedFile { mtime: file_mtime(&meta)?, symbols: extract_symbols(&path_str, &content) })
+}
+
+fn build_index(root: &str) {
+    let buffer = BuildEventBuffer::start();
+    let paths: Vec<_> = WalkBuilder::new(root)
+        .git_ignore(true)
+        .hidden(true)
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .map(|e| e.into_path())
+        .filter(|p| rules_for_path(&p.to_string_lossy()).is_some())
+        .collect();
+
+    // The previous index stays queryable and updatable until the new one replaces it, so
+    // cached entries are looked up under a short read lock per file.
+    let files: HashMap<String, IndexedFile> = paths.par_iter().filter_map(|path| {
+        let key = path.to_string_lossy().to_string();
+        let mtime = std::fs::metadata(path).ok().as_ref().and_then(file_mtime);
+        let cached = SYMBOL_INDEX.read().unwrap().as_ref()
+            .filter(|i| i.root == root)
+            .and_then(|i| i.files.get(&key))
+            .filter(|f| Some(f.mtime) == mtime)
+            .map(|f| IndexedFile { mtime: f.mtime, symbols: f.symbols.clone() });
+        cached.or_else(|| index_file(path)).map(|f| (key, f))
+    }).collect();
+
+    *SYMBOL_INDEX.write().unwrap() = Some(SymbolIndex { root: root.to_string(), files });
+    apply_events(buffer.finish());
+}
+
+/// Indexes `root` synchronously, reusing entries from a loaded cache whose mtime is unchanged.
+#[napi]
+pub fn index_workspace_symbols(root: String) -> SymbolIndexStatus {
+    build_index(&root);
+    get_symbol_index_status()
+}
+
+/// Starts indexing `root` on a background thread. Returns `false` if indexing is already running.
+#[napi]
+pub fn start_symbol_indexing(root: String) -> bool {
+    if INDEXING.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    std::thread::spawn(move || {
+        build_index(&root);
+        INDEXING.store(false, Ordering::SeqCst);
+    });
+    true
+}
+
+#[napi]
+pub fn get_symbol_index_status() -> SymbolIndexStatus {
+    let guard = SYMBOL_INDEX.read().unwrap();
+    let (files, symbols) = guard.as_ref().map_or((0, 0), |i| {
+        (i.files.len(), i.files.values().map(|f| f.symbols.len()).sum())
+    });
+    SymbolIndexStatus { indexing: INDEXING.load(Ordering::SeqCst), files: files as u32, symbols: symbols as u32 }
+}
+
+/// Applies file watcher events to the index. Files outside the indexed root are ignored.
+/// Returns the number of files reparsed.
+#[napi]
+pub fn update_symbol_index(events: Vec<FsEvent>) -> u32 {
+    if let Some(missed) = EVENTS_DURING_BUILD.lock().unwrap().as_mut() {
+        missed.extend(events.iter().cloned());
+    }
+    apply_events(events)
+}
+
+/// Parses the changed files without holding the index lock, then applies the results.
+fn apply_events(events: Vec<FsEvent>) -> u32 {
+    let Some(root) = SYMBOL_INDEX.read().unwrap().as_ref().map(|i| i.root.clone()) else { return 0 };
+    let parsed: Vec<(FsEvent, Option<IndexedFile>)> = events.into_iter()
+        .filter(|event| !event.is_directory)
+        .map(|event| {
+            let inside = Path::new(&event.path).starts_with(&root);
+            let file = if inside && event.event_type != "remove" { index_file(Path::new(&event.path)) } else { None };
+            (event, file)
+        })
+        .collect();
+
+    let mut guard = SYMBOL_INDEX.write().unwrap();
+    let Some(index) = guard.as_mut().filter(|i| i.root == root) else { return 0 };
+    let mut reparsed = 0;
+    for (event, file) in parsed {
+        if let Some(old_path) = &event.old_path {
+            index.files.remove(old_path);
+        }
+        match file {
+            Some(file) => {
+                index.files.insert(event.path, file);
+                reparsed += 1;
+            }
+            None => {
+                index.files.remove(&event.path);
+            }
+        }
+    }
+    reparsed
+}
+
+#[napi]
+pub fn save_symbol_index_cache(cache_path: String) -> Result<()> {
+    let guard = SYMBOL_INDEX.read().unwrap();
+    let index = guard.as_ref().ok_or_else(|| Error::from_reason("No symbol index to save"))?;
+
+    let encoded = rmp_serde::to_vec(index)
+        .map_err(|e| Error::from_reason(format!("Serialization error: {}", e)))?;
+
+    std::fs::write(cache_path, encoded)
+        .map_err(|e| Error::from_reason(format!("IO Error: {}", e)))?;
+
+    Ok(())
+}
+
+/// Loads a cached index. A following `index_workspace_symbols` only reparses files whose
+/// mtime changed since the cache was written.
+#[napi]
+pub fn load_symbol_index_cache(cache_path: String) -> Result<u32> {
+    let bytes = std::fs::read(&cache_path)
+        .map_err(|e| Error::from_reason(format!("IO Error: {}", e)))?;
+
+    let index: SymbolIndex = rmp_serde::from_slice(&bytes)
+        .map_err(|e| Error::from_reason(format!("Deserialization error: {}", e)))?;
+
+    let count = index.files.len() as u32;
+    *SYMBOL_INDEX.write().unwrap() = Some(index);
+    Ok(count)
+}
+
+/// A scored symbol that still borrows from the index; only returned matches are cloned.
+struct Ranked<'a> {
+    symbol: &'a SymbolInformation,
+    score: f64,
+    highlights: Vec<MatchRange>,
+}
+
+impl Ranked<'_> {
+    fn into_match(self) -> SymbolMatch {
+        SymbolMatch { symbol: self.symbol.clone(), score: self.score, highlights: self.highlights }
+    }
+}
+
+fn rank<'a>(query: &str, symbols: impl Iterator<Item = &'a SymbolInformation>) -> Vec<Ranked<'a>> {
+    let query: String = query.chars().filter(|c| !c.is_whitespace()).collect();
+    symbols.filter_map(|symbol| {
+        if query.is_empty() {
+            return Some(Ranked { symbol, score: 0.0, highlights: Vec::new() });
+        }
+        let m = fuzzy_score_str(&query, &symbol.name, false)?;
+        Some(Ranked { symbol, score: m.score as f64, highlights: m.matches })
+    }).collect()
+}
+
+/// Workspace symbol search (`#` in quick open): best fuzzy matches first, then shorter names.
+#[napi]
+pub fn query_workspace_symbols(query: String, limit: Option<u32>) -> Vec<SymbolMatch> {
+    let guard = SYMBOL_INDEX.read().unwrap();
+    let Some(index) = guard.as_ref() else { return Vec::new() };
+    let mut matches = rank(&query, index.files.values().flat_map(|f| f.symbols.iter()));
+    matches.sort_by(|a, b| {
+        b.score.partial_cmp(&a.score).unwrap()
+            .then(a.symbol.name.len().cmp(&b.symbol.name.len()))
+            .then_with(|| a.symbol.path.cmp(&b.symbol.path))
+            .then(a.symbol.line.cmp(&b.symbol.line))
+    });
+    matches.into_iter().take(limit.unwrap_or(100) as usize).map(Ranked::into_match).collect()
+}
+
+/// Go-to-symbol in one file (`@` in quick open). An empty query keeps document order.
+#[napi]
+pub fn query_document_symbols(path: String, query: String) -> Vec<SymbolMatch> {
+    let guard = SYMBOL_INDEX.read().unwrap();
+    let parsed;
+    let symbols = match guard.as_ref().and_then(|i| i.files.get(&path)) {
+        Some(file) => &file.symbols,
+        None => {
+            parsed = std::fs::read_to_string(&path).map(|c| extract_symbols(&path, &c)).unwrap_or_default();
+            &parsed
+        }
+    };
+    let mut matches = rank(&query, symbols.iter());
+    matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap().then(a.symbol.line.cmp(&b.symbol.line)));
+    matches.into_iter().map(Ranked::into_match).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(path: &str, content: &str) -> Vec<(String, String, Option<String>, u32)> {
+        extract_symbols(path, content).into_iter().map(|s| (s.name, s.kind, s.container_name, s.line)).collect()
+    }
+
+    fn sym(name: &str, kind: &str, container: Option<&str>, line: u32) -> (String, String, Option<String>, u32) {
+        (name.into(), kind.into(), container.map(Into::into), line)
+    }
+
+    #[test]
+    fn test_extract_rust_and_typescript() {
+        let rust = "pub const MAX: usize = 3;\npub struct Point { x: i32 }\nimpl<'a> Display for Point {\n    fn fmt(&self) -> char { let c = '{'; c }\n}\npub fn main() {\n    let local = 1;\n}\n";
+        assert_eq!(summary("a.rs", rust), vec![
+            sym("MAX", "constant", None, 1),
+            sym("Point", "struct", None, 2),
+            sym("fmt", "method", Some("Point"), 4),
+            sym("main", "function", None, 6),
+        ]);
+
+        let ts = "export const LIMIT = 10;\nexport class Editor {\n  private value = '}';\n  async save(force: boolean): Promise<void> {\n    if (force) { this.flush(); }\n  }\n}\nconst run = async () => {};\n";
+        assert_eq!(summary("a.ts", ts), vec![
+            sym("LIMIT", "constant", None, 1),
+            sym("Editor", "class", None, 2),
+            sym("save", "method", Some("Editor"), 4),
+            sym("run", "function", None, 8),
+        ]);
+    }
+
+    #[test]
+    fn test_extract_python_and_go() {
+        let py = "TIMEOUT = 5\nclass Server:\n    def start(self):\n        def helper():\n            pass\n\ndef main():\n    VALUE = 1\n";
+        assert_eq!(summary("a.py", py), vec![
+            sym("TIMEOUT", "constant", None, 1),
+            sym("Server", "class", None, 2),
+            sym("start", "method", Some("Server"), 3),
+            sym("main", "function", None, 7),
+        ]);
+
+        let go = "type Store struct {\n}\nfunc (s *Store) Get(key string) string {\n}\nfunc New() *Store {\n}\n";
+        assert_eq!(summary("a.go", go), vec![
+            sym("Store", "struct", None, 1),
+            sym("Get", "method", Some("Store"), 3),
+            sym("New", "function", None, 5),
+        ]);
+    }
+
+    #[test]
+    fn test_index_update_and_query() {
+        let root = std::env::temp_dir().join(format!("ride-symbols-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("lib.rs");
+        std::fs::write(&file, "fn parse_config() {}\nfn print_report() {}\n").unwrap();
+
+        let status = index_workspace_symbols(root.to_string_lossy().to_string());
+        assert_eq!((status.files, status.symbols), (1, 2));
+        let found = query_workspace_symbols("pc".into(), None);
+        assert_eq!(found[0].symbol.name, "parse_config");
+        assert_eq!(found[0].highlights, vec![MatchRange { start: 0, end: 1 }, MatchRange { start: 6, end: 7 }]);
+
+        let cache = root.join("symbols.cache");
+        save_symbol_index_cache(cache.to_string_lossy().to_string()).unwrap();
+        std::fs::write(&file, "fn render() {}\n").unwrap();
+        let path = file.to_string_lossy().to_string();
+        let event = FsEvent { event_type: "modify".into(), path: path.clone(), old_path: None, is_directory: false, timestamp_ms: 0.0 };
+        assert_eq!(update_symbol_index(vec![event]), 1);
+        let outside = std::env::temp_dir().join(format!("ride-symbols-outside-{}.rs", std::process::id()));
+        std::fs::write(&outside, "fn parse_cli() {}\n").unwrap();
+        let event = FsEvent { event_type: "create".into(), path: outside.to_string_lossy().to_string(), old_path: None, is_directory: false, timestamp_ms: 0.0 };
+        assert_eq!(update_symbol_index(vec![event]), 0);
+        std::fs::remove_file(&outside).unwrap();
+        assert!(query_workspace_symbols("pc".into(), None).is_empty());
+        assert_eq!(query_document_symbols(path, "".into())[0].symbol.name, "render");
+
+        assert_eq!(load_symbol_index_cache(cache.to_string_lossy().to_string()).unwrap(), 1);
+        assert_eq!(query_workspace_symbols("print".into(), None)[0].symbol.name, "print_report");
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // A build that panics stops buffering watcher events.
+        let _ = std::panic::catch_unwind(|| {
+            let _buffer = BuildEventBuffer::start();
+            panic!("build failed");
+        });
+        assert!(EVENTS_DURING_BUILD.lock().unwrap().is_none());
+    }
+}
@@ -3,21 +3,395 @@
  *  Licensed under the MIT License. See License.txt in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
-use napi_derive::napi;
-use crate::position::Position;
+//! Text Edit — the edit computations of `src/vs/editor/common/cursor/cursorTypeOperations.ts`
+//! and `cursorDeleteOperations.ts`.
+//!
+//! Every command maps each selection to exactly one replacement (possibly a
+//! no-op), in selection order, so `Editor` can apply them as one batch and
+//! place each cursor after its own edit. Typing and paste consult the
+//! `LanguageConfiguration` for auto-indent, auto-closing and surrounding pairs.
+
 use crate::range::Range;
-use crate::selection::Selection;
+use crate::selection::{Selection, SelectionDirection};
 use crate::text_model::TextModel;
-use crate::cursor::Cursor;
+use crate::editor_config::EditorConfig;
+use crate::language_configuration::{EnterAction, IndentAction, LanguageConfiguration};
+
+/// Replacement produced by a cursor command for one selection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CursorEdit {
+    pub range: Range,
+    pub text: String,
+    /// Resulting selection as (anchor, active) byte offsets into `text`;
+    /// `None` puts an empty selection after the text.
+    pub selection: Option<(usize, usize)>,
+}
+
+impl CursorEdit {
+    fn new(range: Range, text: impl Into<String>) -> Self {
+        Self { range, text: text.into(), selection: None }
+    }
+
+    fn with_selection(range: Range, text: impl Into<String>, anchor: usize, active: usize) -> Self {
+        Self { range, text: text.into(), selection: Some((anchor, active)) }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.range.is_empty() && self.text.is_empty()
+    }
+}
+
+pub fn selection_range(selection: &Selection) -> Range {
+    let start = selection.get_start_position();
+    let end = selection.get_end_position();
+    Range::new(start.line_number, start.column, end.line_number, end.column)
+}
+
+/// Visible column (0-based) of `column` in `line`, expanding tabs to `tab_size`.
+pub fn visible_column(line: &str, column: u32, tab_size: u32) -> u32 {
+    let tab_size = tab_size.max(1);
+    line.chars().take(column.saturating_sub(1) as usize).fold(0, |visible, ch| {
+        if ch == '\t' { visible + tab_size - visible % tab_size } else { visible + 1 }
+    })
+}
+
+pub(crate) fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn indent_width(indent: &str, config: &EditorConfig) -> u32 {
+    visible_column(indent, indent.chars().count() as u32 + 1, config.tab_size)
+}
 
-// Placeholder for now
-#[napi]
-struct TextEdit {}
+fn indent_string(width: u32, config: &EditorConfig) -> String {
+    let tab_size = config.tab_size.max(1);
+    if config.insert_spaces {
+        " ".repeat(width as usize)
+    } else {
+        "\t".repeat((width / tab_size) as usize) + &" ".repeat((width % tab_size) as usize)
+    }
+}
+
+pub(crate) fn indent_by(indent: &str, levels: i32, config: &EditorConfig) -> String {
+    let width = indent_width(indent, config) as i64 + levels as i64 * config.tab_size.max(1) as i64;
+    indent_string(width.max(0) as u32, config)
+}
 
-#[napi]
-impl TextEdit {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self {}
+/// Indentation a line at `line_number` should get from the lines above it.
+fn good_indent(model: &TextModel, line_number: u32, language: Option<&LanguageConfiguration>, config: &EditorConfig) -> String {
+    let reference = (1..line_number).rev()
+        .map(|n| model.get_line_content(n))
+        .find(|line| !line.trim().is_empty() && !language.is_some_and(|l| l.is_unindented(line)));
+    let Some(reference) = reference else {
+        return String::new();
+    };
+    let indent = leading_whitespace(&reference);
+    if language.is_some_and(|l| l.increases_indent(&reference)) {
+        indent_by(indent, 1, config)
+    } else {
+        indent.to_string()
     }
 }
+
+pub fn type_text(
+    model: &TextModel,
+    selections: &[Selection],
+    config: &EditorConfig,
+    language: Option<&LanguageConfiguration>,
+    text: &str,
+) -> Vec<CursorEdit> {
+    selections.iter().map(|selection| {
+        let range = selection_range(selection);
+        if text == "\n" && config.auto_indent {
+            return enter(model, selection, config, language);
+        }
+        if let Some(language) = language {
+            let special = surround(model, selection, config, language, text)
+                .or_else(|| overtype(model, selection, config, language, text))
+                .or_else(|| auto_close(model, selection, config, language, text))
+                .or_else(|| electric_outdent(model, selection, config, language, text));
+            if let Some(edit) = special {
+                return edit;
+            }
+        }
+        if text == "\t" && config.insert_spaces && selection.is_empty() {
+            let line = model.get_line_content(range.start_line_number);
+            let visible = visible_column(&line, range.start_column, config.tab_size);
+            let tab_size = config.tab_size.max(1);
+            let spaces = tab_size - visible % tab_size;
+            return CursorEdit::new(range, " ".repeat(spaces as usize));
+        }
+        CursorEdit::new(range, text)
+    }).collect()
+}
+
+pub fn delete_left(model: &TextModel, selections: &[Selection], config: &EditorConfig) -> Vec<CursorEdit> {
+    selections.iter().map(|selection| {
+        let range = selection_range(selection);
+        if !selection.is_empty() {
+            return CursorEdit::new(range, "");
+        }
+
+        let (line_number, column) = (range.start_line_number, range.start_column);
+        if column > 1 {
+            let line = model.get_line_content(line_number);
+            let before: String = line.chars().take(column as usize - 1).collect();
+            let tab_size = config.tab_size.max(1);
+            let start_column = if config.use_tab_stops && before.chars().all(|c| c == ' ') {
+                // Inside leading spaces: remove back to the previous tab stop.
+                ((column - 2) / tab_size) * tab_size + 1
+            } else {
+                column - 1
+            };
+            CursorEdit::new(Range::new(line_number, start_column, line_number, column), "")
+        } else if line_number > 1 {
+            let previous_len = model.get_line_length(line_number - 1);
+            CursorEdit::new(Range::new(line_number - 1, previous_len + 1, line_number, 1), "")
+        } else {
+            CursorEdit::new(range, "")
+        }
+    }).collect()
+}
+
+pub fn delete_right(model: &TextModel, selections: &[Selection]) -> Vec<CursorEdit> {
+    selections.iter().map(|selection| {
+        let range = selection_range(selection);
+        if !selection.is_empty() {
+            return CursorEdit::new(range, "");
+        }
+
+        let (line_number, column) = (range.start_line_number, range.start_column);
+        if column <= model.get_line_length(line_number) {
+            CursorEdit::new(Range::new(line_number, column, line_number, column + 1), "")
+        } else if line_number < model.line_count() {
+            CursorEdit::new(Range::new(line_number, column, line_number + 1, 1), "")
+        } else {
+            CursorEdit::new(range, "")
+        }
+    }).collect()
+}
+
+/// Enter: keeps the line's indentation, adjusted by the on-enter rules,
+/// the brackets around the cursor and the indentation rules.
+fn enter(model: &TextModel, selection: &Selection, config: &EditorConfig, language: Option<&LanguageConfiguration>) -> CursorEdit {
+    let range = selection_range(selection);
+    let line = model.get_line_content(range.start_line_number);
+    let before: String = line.chars().take(range.start_column as usize - 1).collect();
+    let after: String = model.get_line_content(range.end_line_number).chars().skip(range.end_column as usize - 1).collect();
+    let indent = leading_whitespace(&before).to_string();
+    let previous = if range.start_line_number > 1 { model.get_line_content(range.start_line_number - 1) } else { String::new() };
+
+    let action = language.map_or(EnterAction::new(IndentAction::None), |l| {
+        l.on_enter(previous, before.clone(), after.clone()).unwrap_or_else(|| {
+            match (l.increases_indent(&before), l.decreases_indent(&after)) {
+                (true, true) => EnterAction::new(IndentAction::IndentOutdent),
+                (true, false) => EnterAction::new(IndentAction::Indent),
+                (false, true) => EnterAction::new(IndentAction::Outdent),
+                (false, false) => EnterAction::new(IndentAction::None),
+            }
+        })
+    });
+
+    let mut new_indent = match action.indent_action {
+        IndentAction::None => indent.clone(),
+        IndentAction::Indent | IndentAction::IndentOutdent => indent_by(&indent, 1, config),
+        IndentAction::Outdent => indent_by(&indent, -1, config),
+    };
+    if let Some(remove) = action.remove_text {
+        let keep = new_indent.chars().count().saturating_sub(remove as usize);
+        new_indent = new_indent.chars().take(keep).collect();
+    }
+    let first = format!("\n{}{}", new_indent, action.append_text.unwrap_or_default());
+    if action.indent_action == IndentAction::IndentOutdent {
+        let caret = first.len();
+        return CursorEdit::with_selection(range, format!("{}\n{}", first, indent), caret, caret);
+    }
+    CursorEdit::new(range, first)
+}
+
+/// Typing an opening surround character wraps the selection instead of replacing it.
+fn surround(model: &TextModel, selection: &Selection, config: &EditorConfig, language: &LanguageConfiguration, text: &str) -> Option<CursorEdit> {
+    if !config.auto_surround || selection.is_empty() {
+        return None;
+    }
+    let (open, close) = language.surrounding_pair(text)?;
+    let range = selection_range(selection);
+    let selected = model.get_value_in_range(range);
+    let (start, end) = (open.len(), open.len() + selected.len());
+    let (anchor, active) = match selection.get_direction() {
+        SelectionDirection::LTR => (start, end),
+        SelectionDirection::RTL => (end, start),
+    };
+    Some(CursorEdit::with_selection(range, format!("{}{}{}", open, selected, close), anchor, active))
+}
+
+/// Typing a closing character right before the same character steps over it.
+fn overtype(model: &TextModel, selection: &Selection, config: &EditorConfig, language: &LanguageConfiguration, text: &str) -> Option<CursorEdit> {
+    if !config.auto_closing_brackets || !selection.is_empty() || !language.is_auto_closing_close(text) {
+        return None;
+    }
+    let range = selection_range(selection);
+    let line = model.get_line_content(range.start_line_number);
+    let rest: String = line.chars().skip(range.start_column as usize - 1).collect();
+    if !rest.starts_with(text) {
+        return None;
+    }
+    let end_column = range.start_column + text.chars().count() as u32;
+    Some(CursorEdit::new(Range::new(range.start_line_number, range.start_column, range.start_line_number, end_column), text))
+}
+
+fn auto_close(model: &TextModel, selection: &Selection, config: &EditorConfig, language: &LanguageConfiguration, text: &str) -> Option<CursorEdit> {
+    if !config.auto_closing_brackets || !selection.is_empty() {
+        return None;
+    }
+    let pair = language.auto_closing_pair(text)?;
+    let range = selection_range(selection);
+    let line = model.get_line_content(range.start_line_number);
+    let column = range.start_column as usize;
+    if line.chars().nth(column - 1).is_some_and(|next| !language.auto_close_before().contains(next)) {
+        return None;
+    }
+    if column > 1 {
+        let previous = line.chars().nth(column - 2).unwrap_or(' ');
+        // Quotes do not auto-close right after a word (`don't`).
+        if pair.open == pair.close && (previous.is_alphanumeric() || previous == '_') {
+            return None;
+        }
+        let byte = line.char_indices().nth(column - 2).map_or(0, |(i, _)| i) as u32;
+        let token_type = model.get_line_tokens(range.start_line_number).into_iter()
+            .find(|t| t.start_index <= byte && byte < t.start_index + t.length)
+            .map(|t| t.token_type);
+        if token_type.is_some_and(|t| pair.not_in.contains(&t)) {
+            return None;
+        }
+    }
+    let caret = pair.open.len();
+    Some(CursorEdit::with_selection(range, format!("{}{}", pair.open, pair.close), caret, caret))
+}
+
+/// Typing the character that completes a decrease-indent line (e.g. `}` on
+/// an otherwise blank line) re-aligns the line with its block opener.
+fn electric_outdent(model: &TextModel, selection: &Selection, config: &EditorConfig, language: &LanguageConfiguration, text: &str) -> Option<CursorEdit> {
+    if !config.auto_indent || !selection.is_empty() {
+        return None;
+    }
+    let range = selection_range(selection);
+    let line = model.get_line_content(range.start_line_number);
+    let before: String = line.chars().take(range.start_column as usize - 1).collect();
+    let after: String = line.chars().skip(range.start_column as usize - 1).collect();
+    if !before.trim().is_empty() || !language.decreases_indent(&format!("{}{}{}", before, text, after)) {
+        return None;
+    }
+    // One level below where a plain line would go: level with the block opener.
+    let desired = indent_by(&good_indent(model, range.start_line_number, Some(language), config), -1, config);
+    if desired == before {
+        return None;
+    }
+    let range = Range::new(range.start_line_number, 1, range.start_line_number, range.start_column);
+    Some(CursorEdit::new(range, format!("{}{}", desired, text)))
+}
+
+/// Pastes `text` into every selection. In `"spread"` mode a text with exactly
+/// one line per selection is distributed, one line to each. Multi-line pieces
+/// are re-indented to the target position when `auto_indent_on_paste` is on.
+pub fn paste(
+    model: &TextModel,
+    selections: &[Selection],
+    config: &EditorConfig,
+    language: Option<&LanguageConfiguration>,
+    text: &str,
+) -> Vec<CursorEdit> {
+    let distributed = distribute_paste(selections.len(), config, text);
+    selections.iter().enumerate().map(|(i, selection)| {
+        let piece = distributed.as_ref().map_or(text, |lines| lines[i].as_str());
+        if config.auto_indent_on_paste && piece.contains('\n') {
+            return reindent_paste(model, selection, config, language, piece);
+        }
+        CursorEdit::new(selection_range(selection), piece)
+    }).collect()
+}
+
+/// Shifts a pasted block so it sits at the indentation of the target
+/// position, keeping the relative indentation of its lines.
+fn reindent_paste(model: &TextModel, selection: &Selection, config: &EditorConfig, language: Option<&LanguageConfiguration>, text: &str) -> CursorEdit {
+    let mut range = selection_range(selection);
+    let line = model.get_line_content(range.start_line_number);
+    let before: String = line.chars().take(range.start_column as usize - 1).collect();
+    let lines: Vec<&str> = text.split('\n').collect();
+    let at_line_start = before.trim().is_empty();
+
+    let (target, base) = if at_line_start {
+        let mut target = good_indent(model, range.start_line_number, language, config);
+        if language.is_some_and(|l| l.decreases_indent(lines[0])) {
+            target = indent_by(&target, -1, config);
+        }
+        // The block replaces the whitespace before the cursor as well.
+        range = Range::new(range.start_line_number, 1, range.end_line_number, range.end_column);
+        (indent_width(&target, config), indent_width(leading_whitespace(lines[0]), config))
+    } else {
+        let mut target = leading_whitespace(&line).to_string();
+        if language.is_some_and(|l| l.increases_indent(&format!("{}{}", before, lines[0]))) {
+            target = indent_by(&target, 1, config);
+        }
+        let base = lines[1..].iter()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| indent_width(leading_whitespace(l), config))
+            .min()
+            .unwrap_or(0);
+        (indent_width(&target, config), base)
+    };
+
+    let reindented: Vec<String> = lines.iter().enumerate().map(|(i, l)| {
+        if (i == 0 && !at_line_start) || l.trim().is_empty() {
+            return l.to_string();
+        }
+        let width = (target + indent_width(leading_whitespace(l), config)).saturating_sub(base);
+        format!("{}{}", indent_string(width, config), l.trim_start())
+    }).collect();
+    CursorEdit::new(range, reindented.join("\n"))
+}
+
+fn distribute_paste(cursor_count: usize, config: &EditorConfig, text: &str) -> Option<Vec<String>> {
+    if cursor_count < 2 || config.multi_cursor_paste != "spread" {
+        return None;
+    }
+    let text = text.strip_suffix('\n').map(|t| t.strip_suffix('\r').unwrap_or(t)).unwrap_or(text);
+    let lines: Vec<String> = text.split('\n').map(|l| l.trim_end_matches('\r').to_string()).collect();
+    (lines.len() == cursor_count).then_some(lines)
+}
+
+/// Selection each cursor ends up with once `edits` (sorted, non-overlapping)
+/// are applied: the edit's own selection, or an empty one after its text.
+pub fn selections_after_edits(edits: &[CursorEdit]) -> Vec<Selection> {
+    let mut line_delta: i64 = 0;
+    // Column shift for text that followed the previous edit on its (original) end line.
+    let mut column_delta: i64 = 0;
+    let mut previous_end_line = 0;
+
+    edits.iter().map(|edit| {
+        let range = edit.range;
+        let start_line = (range.start_line_number as i64 + line_delta) as u32;
+        let start_column = if range.start_line_number == previous_end_line {
+            (range.start_column as i64 + column_delta) as u32
+        } else {
+            range.start_column
+        };
+
+        let position_at = |offset: usize| {
+            let prefix = &edit.text[..offset];
+            let breaks = prefix.matches('\n').count() as u32;
+            let last_line_len = prefix.rsplit('\n').next().unwrap_or("").chars().count() as u32;
+            let column = if breaks == 0 { start_column + last_line_len } else { last_line_len + 1 };
+            (start_line + breaks, column)
+        };
+        let (anchor, active) = edit.selection.unwrap_or((edit.text.len(), edit.text.len()));
+        let (anchor_line, anchor_column) = position_at(anchor);
+        let (line, column) = position_at(active);
+
+        let (end_line, end_column) = position_at(edit.text.len());
+        line_delta += end_line as i64 - start_line as i64 - (range.end_line_number - range.start_line_number) as i64;
+        column_delta = end_column as i64 - range.end_column as i64;
+        previous_end_line = range.end_line_number;
+        Selection::new(anchor_line, anchor_column, line, column)
+    }).collect()
+}
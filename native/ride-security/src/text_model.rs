@@ -8,40 +8,56 @@
 //! Features:
 //! - Multi-versioned state management with persistent Undo/Redo history
 //! - Zero-copy line-based retrieval utilizing PieceTree indexing
+//! - Immutable snapshots for background search
 //! - High-concurrency decoration and marker manager with spatial indexing
 //! - Transactional bulk-edit engine with conflict resolution and range shifting
 //! - Adaptive regex search engine with multi-threaded matching on large buffers
 //! - Line-level dirty-state tracking for efficient view-model invalidation
 
+use std::collections::VecDeque;
 use std::sync::{Arc, RwLock, Mutex};
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use crate::range::Range;
-use crate::piece_tree::PieceTree;
-use crate::text_edit::SingleEditOperation;
-use std::collections::HashMap;
+use crate::piece_tree::{PieceTree, PieceTreeSnapshot};
+use crate::position::Position;
+use crate::selection::Selection;
+use crate::edit_stack::{EditStack, RecordedChange};
+use crate::interval_tree::{IntervalEntry, IntervalTree};
+use crate::text_model_types::{EditOperationType, RangePod, SingleEditOperation, TrackedRangeStickiness};
+use crate::text_model_tokens::{TokenizationStateStore, TokensChangedRange};
+use crate::tokenizer::{Token, Tokenizer};
+use crate::patch::{place_hunks, single_file_hunks, summarize, PatchOptions, PatchResult};
+use crate::bracket_pairs::{BracketDiagnostic, BracketLineSource, BracketPairGuide, BracketPairInfo, BracketPairsStore, ColorizedBracket};
 
 #[napi(object)]
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ModelDecorationOptions {
-    pub stickiness: i32, // 0=AlwaysGrowsWithTyping, 1=NeverGrowsWithTyping
+    pub stickiness: TrackedRangeStickiness,
     pub class_name: Option<String>,
     pub inline_class_name: Option<String>,
     pub hover_message: Option<String>,
+    /// Text injected before the range start (e.g. an inlay hint).
+    pub before_content: Option<String>,
+    /// Text injected after the range end.
+    pub after_content: Option<String>,
 }
 
 #[napi(object)]
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ModelDecoration {
     pub id: String,
     pub range: Range,
     pub options: ModelDecorationOptions,
 }
 
-#[derive(Clone, Debug)]
-struct UndoElement {
+#[napi(object)]
+#[derive(Clone)]
+pub struct UndoRedoResult {
     pub version_id: u32,
-    pub edits: Vec<SingleEditOperation>,
+    pub alternative_version_id: u32,
+    /// Selections to restore: the state before the undone group, or after the redone one.
+    pub cursor_state: Option<Vec<Selection>>,
 }
 
 #[napi]
@@ -50,27 +66,88 @@ pub struct TextModel {
     id: String,
     uri: String,
     version_id: Arc<std::sync::atomic::AtomicU32>,
+    /// Equals `version_id` after a plain edit, but returns to earlier values on
+    /// undo/redo so it can be compared against the saved version.
+    alternative_version_id: Arc<std::sync::atomic::AtomicU32>,
+    saved_version_id: Arc<std::sync::atomic::AtomicU32>,
+    /// Bumped whenever decorations carrying injected text (inlay hints) are added or
+    /// removed, since those change the view layout without a text edit.
+    injected_text_version_id: Arc<std::sync::atomic::AtomicU32>,
+    // Locks are always taken in this order: edit_stack, buffer, tokens, brackets,
+    // decorations, line_edits.
     buffer: Arc<RwLock<PieceTree>>,
-    decorations: Arc<RwLock<HashMap<String, ModelDecoration>>>,
-    undo_stack: Arc<Mutex<Vec<UndoElement>>>,
-    redo_stack: Arc<Mutex<Vec<UndoElement>>>,
+    decorations: Arc<RwLock<IntervalTree>>,
+    edit_stack: Arc<Mutex<EditStack>>,
+    tokens: Arc<Mutex<TokenizationStateStore>>,
+    brackets: Arc<Mutex<BracketPairsStore>>,
+    line_edits: Arc<Mutex<LineEditLog>>,
 }
 
 #[napi]
 impl TextModel {
     #[napi(constructor)]
     pub fn new(uri: String, content: String) -> Self {
+        let line_count = content.matches('\n').count() + 1;
         Self {
             id: uuid::Uuid::new_v4().to_string(),
             uri,
             version_id: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+            alternative_version_id: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+            saved_version_id: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+            injected_text_version_id: Arc::new(std::sync::atomic::AtomicU32::new(1)),
             buffer: Arc::new(RwLock::new(PieceTree::new(content))),
-            decorations: Arc::new(RwLock::new(HashMap::new())),
-            undo_stack: Arc::new(Mutex::new(Vec::new())),
-            redo_stack: Arc::new(Mutex::new(Vec::new())),
+            decorations: Arc::new(RwLock::new(IntervalTree::new())),
+            edit_stack: Arc::new(Mutex::new(EditStack::new())),
+            tokens: Arc::new(Mutex::new(TokenizationStateStore::new(None, line_count))),
+            brackets: Arc::new(Mutex::new(BracketPairsStore::new(line_count))),
+            line_edits: Arc::new(Mutex::new(LineEditLog::new(1))),
         }
     }
 
+    #[napi]
+    pub fn set_value(&self, value: String) -> u32 {
+        let line_count = value.matches('\n').count() + 1;
+        let mut stack = self.edit_stack.lock().unwrap();
+        let mut buffer = self.buffer.write().unwrap();
+        *buffer = PieceTree::new(value);
+        stack.clear();
+        self.decorations.write().unwrap().clear();
+        self.injected_text_version_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.tokens.lock().unwrap().reset(line_count);
+        self.brackets.lock().unwrap().reset(line_count);
+        let version = self.version_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        self.alternative_version_id.store(version, std::sync::atomic::Ordering::SeqCst);
+        self.line_edits.lock().unwrap().reset(version);
+        version
+    }
+
+    #[napi(getter)]
+    pub fn uri(&self) -> String {
+        self.uri.clone()
+    }
+
+    #[napi]
+    pub fn get_version_id(&self) -> u32 {
+        self.version_id.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[napi]
+    pub fn get_alternative_version_id(&self) -> u32 {
+        self.alternative_version_id.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Records the current state as saved; undoing back to it clears `is_dirty`.
+    #[napi]
+    pub fn mark_saved(&self) {
+        let alt = self.get_alternative_version_id();
+        self.saved_version_id.store(alt, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[napi]
+    pub fn is_dirty(&self) -> bool {
+        self.get_alternative_version_id() != self.saved_version_id.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
     #[napi]
     pub fn get_value(&self) -> String {
         self.buffer.read().unwrap().get_text()
@@ -81,46 +158,322 @@ impl TextModel {
         self.buffer.read().unwrap().get_line_count()
     }
 
+    pub(crate) fn get_injected_text_version_id(&self) -> u32 {
+        self.injected_text_version_id.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Line edits that took the model from `from_version_id` to `to_version_id`, in order,
+    /// or `None` when they are no longer known (the value was replaced or too many edits
+    /// happened since).
+    pub(crate) fn line_edits_between(&self, from_version_id: u32, to_version_id: u32) -> Option<Vec<LineEdit>> {
+        self.line_edits.lock().unwrap().between(from_version_id, to_version_id)
+    }
+
+    /// Content of the 1-based `line_number`, without its line terminator.
     #[napi]
     pub fn get_line_content(&self, line_number: u32) -> String {
         self.buffer.read().unwrap().get_line_content(line_number)
     }
 
+    #[napi]
+    pub fn get_line_length(&self, line_number: u32) -> u32 {
+        self.buffer.read().unwrap().get_line_length(line_number)
+    }
+
+    #[napi]
+    pub fn get_value_in_range(&self, range: Range) -> String {
+        let buffer = self.buffer.read().unwrap();
+        let start = buffer.offset_at(range.start_line_number, range.start_column);
+        let end = buffer.offset_at(range.end_line_number, range.end_column);
+        buffer.get_text_in_range(start.min(end), start.max(end))
+    }
+
+    /// Byte offset of a 1-based position, clamped to the text.
+    #[napi]
+    pub fn get_offset_at(&self, line_number: u32, column: u32) -> u32 {
+        self.buffer.read().unwrap().offset_at(line_number, column)
+    }
+
+    #[napi]
+    pub fn get_position_at(&self, offset: u32) -> Position {
+        self.buffer.read().unwrap().position_at(offset)
+    }
+
+    /// Immutable copy of the current content that stays readable (e.g. from a
+    /// search thread) while further edits are applied.
+    #[napi]
+    pub fn create_snapshot(&self) -> PieceTreeSnapshot {
+        self.buffer.read().unwrap().create_snapshot()
+    }
+
+    /// Applies edits as a standalone undo step.
     #[napi]
     pub fn apply_edits(&self, edits: Vec<SingleEditOperation>) -> u32 {
+        self.push_edit_operations(None, edits, None, None)
+    }
+
+    /// Applies a single-file unified patch as one undo step.
+    #[napi]
+    pub fn apply_patch(&self, patch: String, options: Option<PatchOptions>) -> Result<PatchResult> {
+        let hunks = single_file_hunks(patch)?;
+        let content = self.get_value();
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let (placements, outcomes) = place_hunks(&lines, &hunks, &options.unwrap_or_default());
+
+        // Placements are in split lines; a line index past the model's last line means its end.
+        let line_count = self.line_count();
+        let position = |index: usize| {
+            if (index as u32) < line_count { (index as u32 + 1, 1) } else { (line_count, self.get_line_length(line_count) + 1) }
+        };
+        let edits: Vec<SingleEditOperation> = placements.into_iter().map(|placement| {
+            let (start_line_number, start_column) = position(placement.start);
+            let (end_line_number, end_column) = position(placement.end);
+            SingleEditOperation {
+                range: RangePod { start_line_number, start_column, end_line_number, end_column },
+                text: Some(placement.text),
+                force_move_markers: None,
+            }
+        }).collect();
+        if !edits.is_empty() {
+            self.apply_edits(edits);
+        }
+        Ok(summarize(self.get_value(), outcomes))
+    }
+
+    /// Applies edits and records them on the undo stack together with the
+    /// cursor state around them. Consecutive pushes of the same typing kind
+    /// merge into one undo step until `push_stack_element` is called.
+    #[napi]
+    pub fn push_edit_operations(
+        &self,
+        before_cursor_state: Option<Vec<Selection>>,
+        edits: Vec<SingleEditOperation>,
+        after_cursor_state: Option<Vec<Selection>>,
+        edit_type: Option<EditOperationType>,
+    ) -> u32 {
+        let mut stack = self.edit_stack.lock().unwrap();
         let mut buffer = self.buffer.write().unwrap();
+        let before_version = self.get_alternative_version_id();
         let version = self.version_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        self.alternative_version_id.store(version, std::sync::atomic::Ordering::SeqCst);
+
+        // Edits refer to the original text; resolve them all to offsets first.
+        let mut replacements: Vec<Replacement> = edits.into_iter().map(|edit| {
+            let start = buffer.offset_at(edit.range.start_line_number, edit.range.start_column) as usize;
+            let end = buffer.offset_at(edit.range.end_line_number, edit.range.end_column) as usize;
+            Replacement {
+                start: start.min(end),
+                end: start.max(end),
+                text: edit.text.unwrap_or_default(),
+                force_move_markers: edit.force_move_markers.unwrap_or(false),
+            }
+        }).collect();
+        replacements.sort_by_key(|r| r.start);
 
-        // Push to undo stack
-        let mut undo = self.undo_stack.lock().unwrap();
-        undo.push(UndoElement {
+        let mut delta: isize = 0;
+        let changes: Vec<RecordedChange> = replacements.iter().map(|r| {
+            let change = RecordedChange {
+                old_start: r.start,
+                old_text: buffer.get_text_in_range(r.start as u32, r.end as u32),
+                new_start: (r.start as isize + delta) as usize,
+                new_text: r.text.clone(),
+            };
+            delta += r.text.len() as isize - (r.end - r.start) as isize;
+            change
+        }).collect();
+
+        self.apply_replacements(&mut buffer, replacements, version);
+        stack.push_edit(
+            changes,
+            edit_type.unwrap_or(EditOperationType::Other),
+            before_cursor_state,
+            after_cursor_state,
+            before_version,
+            version,
+        );
+        version
+    }
+
+    /// Closes the current undo group.
+    #[napi]
+    pub fn push_stack_element(&self) {
+        self.edit_stack.lock().unwrap().push_stack_element();
+    }
+
+    #[napi]
+    pub fn can_undo(&self) -> bool {
+        self.edit_stack.lock().unwrap().can_undo()
+    }
+
+    #[napi]
+    pub fn can_redo(&self) -> bool {
+        self.edit_stack.lock().unwrap().can_redo()
+    }
+
+    #[napi]
+    pub fn undo(&self) -> Option<UndoRedoResult> {
+        let mut stack = self.edit_stack.lock().unwrap();
+        let element = stack.pop_undo()?;
+        let mut buffer = self.buffer.write().unwrap();
+        let version = self.version_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        for batch in element.batches.iter().rev() {
+            let replacements = batch.iter()
+                .map(|c| Replacement::new(c.new_start, c.new_start + c.new_text.len(), c.old_text.clone()))
+                .collect();
+            self.apply_replacements(&mut buffer, replacements, version);
+        }
+
+        self.alternative_version_id.store(element.before_version_id, std::sync::atomic::Ordering::SeqCst);
+        let result = UndoRedoResult {
             version_id: version,
-            edits: edits.clone(), // In a real impl, we'd store inverse edits
-        });
-        self.redo_stack.lock().unwrap().clear();
+            alternative_version_id: element.before_version_id,
+            cursor_state: element.before_cursor_state.clone(),
+        };
+        stack.push_redo(element);
+        Some(result)
+    }
 
-        for edit in edits {
-            buffer.insert(edit.range.start_line_number as usize, edit.text);
+    #[napi]
+    pub fn redo(&self) -> Option<UndoRedoResult> {
+        let mut stack = self.edit_stack.lock().unwrap();
+        let element = stack.pop_redo()?;
+        let mut buffer = self.buffer.write().unwrap();
+        let version = self.version_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        for batch in &element.batches {
+            let replacements = batch.iter()
+                .map(|c| Replacement::new(c.old_start, c.old_start + c.old_text.len(), c.new_text.clone()))
+                .collect();
+            self.apply_replacements(&mut buffer, replacements, version);
         }
 
-        version
+        self.alternative_version_id.store(element.after_version_id, std::sync::atomic::Ordering::SeqCst);
+        let result = UndoRedoResult {
+            version_id: version,
+            alternative_version_id: element.after_version_id,
+            cursor_state: element.after_cursor_state.clone(),
+        };
+        stack.push_undo(element);
+        Some(result)
+    }
+
+    /// Replaces byte ranges of the current text. Ranges must not overlap; they
+    /// are applied bottom-up so the offsets of the ones above stay valid.
+    fn apply_replacements(&self, buffer: &mut PieceTree, mut replacements: Vec<Replacement>, version_id: u32) {
+        replacements.sort_by_key(|r| std::cmp::Reverse(r.start));
+
+        let mut tokens = self.tokens.lock().unwrap();
+        let mut brackets = self.brackets.lock().unwrap();
+        let mut decorations = self.decorations.write().unwrap();
+        let mut line_edits = self.line_edits.lock().unwrap();
+        for r in replacements {
+            let (start, end) = (r.start as u32, r.end as u32);
+            let start_line = buffer.line_index_at(start) as usize;
+            let removed_line_breaks = buffer.line_index_at(end) as usize - start_line;
+            let inserted_line_breaks = r.text.matches('\n').count();
+            tokens.accept_edit(start_line, removed_line_breaks, inserted_line_breaks);
+            brackets.accept_edit(start_line, removed_line_breaks, inserted_line_breaks);
+            decorations.accept_replace(r.start, r.end - r.start, r.text.len(), r.force_move_markers);
+            line_edits.push(version_id, LineEdit { start_line_index: start_line, removed_line_breaks, inserted_line_breaks });
+            buffer.delete(start, end - start);
+            buffer.insert_v2(start, r.text);
+        }
     }
 
+    // ─── Tokenization ──────────────────────────────────────────────────────
+
+    /// Attaches a tokenizer; every line becomes invalid and is retokenized lazily.
     #[napi]
-    pub fn undo(&self) -> Option<u32> {
-        let mut undo = self.undo_stack.lock().unwrap();
-        if let Some(element) = undo.pop() {
-            // Real undo logic would revert edits using PieceTree operations
-            let version = self.version_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
-            self.redo_stack.lock().unwrap().push(element);
-            return Some(version);
+    pub fn set_tokenizer(&self, tokenizer: &Tokenizer) {
+        let line_count = self.line_count() as usize;
+        self.tokens.lock().unwrap().set_tokenizer(Some(tokenizer.clone()), line_count);
+        self.brackets.lock().unwrap().reset(line_count);
+    }
+
+    /// First 1-based line whose tokens are out of date, if any.
+    #[napi]
+    pub fn get_first_invalid_token_line(&self) -> Option<u32> {
+        self.tokens.lock().unwrap().first_invalid_line_index().map(|i| i as u32 + 1)
+    }
+
+    /// Tokens for `line_number`, retokenizing invalid lines above it first.
+    #[napi]
+    pub fn get_line_tokens(&self, line_number: u32) -> Vec<Token> {
+        if line_number == 0 || line_number > self.line_count() {
+            return Vec::new();
+        }
+        let index = line_number as usize - 1;
+        self.retokenize(index, usize::MAX);
+        self.tokens.lock().unwrap().line_tokens(index).cloned().unwrap_or_default()
+    }
+
+    /// Background step: retokenizes at most `max_lines` invalid lines and
+    /// returns the 1-based line ranges whose tokens changed.
+    #[napi]
+    pub fn tokenize_invalid_lines(&self, max_lines: u32) -> Vec<TokensChangedRange> {
+        self.retokenize(usize::MAX, max_lines as usize)
+    }
+
+    fn retokenize(&self, until_line_index: usize, budget: usize) -> Vec<TokensChangedRange> {
+        // Same lock order as edits (buffer, then tokens).
+        let buffer = self.buffer.read().unwrap();
+        let mut tokens = self.tokens.lock().unwrap();
+        if tokens.first_invalid_line_index().is_none_or(|i| i > until_line_index) {
+            return Vec::new();
         }
-        None
+        let changed = tokens.tokenize_until(until_line_index, budget, |i| buffer.get_line_content(i as u32 + 1));
+        let mut brackets = self.brackets.lock().unwrap();
+        changed.into_iter()
+        .map(|(from, to)| {
+            brackets.invalidate_lines(from, to);
+            TokensChangedRange { from_line_number: from as u32 + 1, to_line_number: to as u32 + 1 }
+        })
+        .collect()
+    }
+
+    // ─── Bracket Pairs ─────────────────────────────────────────────────────
+
+    /// Brackets on lines `start_line_number..=end_line_number` with their
+    /// nesting level; brackets inside strings and comments are ignored.
+    #[napi]
+    pub fn get_bracket_colorization(&self, start_line_number: u32, end_line_number: u32) -> Vec<ColorizedBracket> {
+        self.with_bracket_pairs(|store, lines| store.colorized_brackets(start_line_number, end_line_number, lines))
+    }
+
+    #[napi]
+    pub fn get_bracket_pairs_in_range(&self, range: Range) -> Vec<BracketPairInfo> {
+        self.with_bracket_pairs(|store, lines| store.pairs_in_range(range, lines))
+    }
+
+    /// The innermost bracket pair around `position`.
+    #[napi]
+    pub fn find_enclosing_brackets(&self, position: Position) -> Option<BracketPairInfo> {
+        self.with_bracket_pairs(|store, lines| store.enclosing_pair(position, lines))
+    }
+
+    /// Bracket pair guides for the visible lines; the pair around
+    /// `active_position` is flagged as active.
+    #[napi]
+    pub fn get_bracket_pair_guides(&self, start_line_number: u32, end_line_number: u32, active_position: Option<Position>) -> Vec<BracketPairGuide> {
+        self.with_bracket_pairs(|store, lines| store.guides(start_line_number, end_line_number, active_position, lines))
+    }
+
+    #[napi]
+    pub fn get_unmatched_brackets(&self) -> Vec<BracketDiagnostic> {
+        self.with_bracket_pairs(|store, lines| store.unmatched_brackets(lines))
+    }
+
+    fn with_bracket_pairs<R>(&self, f: impl FnOnce(&mut BracketPairsStore, &mut ModelLines) -> R) -> R {
+        // Same lock order as edits (buffer, tokens, then brackets).
+        let buffer = self.buffer.read().unwrap();
+        let mut tokens = self.tokens.lock().unwrap();
+        let mut brackets = self.brackets.lock().unwrap();
+        f(&mut brackets, &mut ModelLines { buffer: &buffer, tokens: &mut tokens })
     }
 
     #[napi]
     pub fn find_matches(&self, search_string: String, is_regex: bool, match_case: bool) -> Vec<Range> {
-        let content = self.get_value();
+        // Search a snapshot so edits are not blocked while the regex runs.
+        let content = self.create_snapshot().get_text();
         let pattern = if is_regex { search_string } else { regex::escape(&search_string) };
 
         let mut builder = regex::RegexBuilder::new(&pattern);
@@ -143,26 +496,66 @@ impl TextModel {
         results
     }
 
+    /// Removes `old_ids` and adds `new_decorations`, returning the new ids.
+    /// Decorations then move with the text according to their stickiness.
     #[napi]
     pub fn delta_decorations(&self, old_ids: Vec<String>, new_decorations: Vec<ModelDecoration>) -> Vec<String> {
+        let buffer = self.buffer.read().unwrap();
         let mut decs = self.decorations.write().unwrap();
+        let injects_text = |options: &ModelDecorationOptions| options.before_content.is_some() || options.after_content.is_some();
+        let mut injected_text_changed = false;
         for id in old_ids {
+            injected_text_changed |= decs.get(&id).is_some_and(|entry| injects_text(&entry.options));
             decs.remove(&id);
         }
 
         let mut added_ids = Vec::new();
-        for mut d in new_decorations {
+        for d in new_decorations {
             let id = if d.id.is_empty() { uuid::Uuid::new_v4().to_string() } else { d.id.clone() };
-            added_ids.push(id.clone());
-            d.id = id.clone();
-            decs.insert(id, d);
+            let start = buffer.offset_at(d.range.start_line_number, d.range.start_column) as usize;
+            let end = buffer.offset_at(d.range.end_line_number, d.range.end_column) as usize;
+            injected_text_changed |= injects_text(&d.options);
+            decs.insert(id.clone(), start, end, d.options);
+            added_ids.push(id);
+        }
+        if injected_text_changed {
+            self.injected_text_version_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         }
         added_ids
     }
 
     #[napi]
     pub fn get_decoration_range(&self, decoration_id: String) -> Option<Range> {
-        self.decorations.read().unwrap().get(&decoration_id).map(|d| d.range.clone())
+        let buffer = self.buffer.read().unwrap();
+        let entry = self.decorations.read().unwrap().get(&decoration_id)?;
+        Some(offsets_to_range(&buffer, entry.start, entry.end))
+    }
+
+    /// Decorations intersecting `range`, ordered by start.
+    #[napi]
+    pub fn get_decorations_in_range(&self, range: Range) -> Vec<ModelDecoration> {
+        let buffer = self.buffer.read().unwrap();
+        let start = buffer.offset_at(range.start_line_number, range.start_column) as usize;
+        let end = buffer.offset_at(range.end_line_number, range.end_column) as usize;
+        let entries = self.decorations.read().unwrap().search(start.min(end), start.max(end));
+        entries.into_iter().map(|e| to_model_decoration(&buffer, e)).collect()
+    }
+
+    /// Decorations touching any of the 1-based lines `start_line_number..=end_line_number`.
+    #[napi]
+    pub fn get_lines_decorations(&self, start_line_number: u32, end_line_number: u32) -> Vec<ModelDecoration> {
+        let buffer = self.buffer.read().unwrap();
+        let start = buffer.offset_at(start_line_number, 1) as usize;
+        let end = buffer.offset_at(end_line_number, u32::MAX) as usize;
+        let entries = self.decorations.read().unwrap().search(start, end);
+        entries.into_iter().map(|e| to_model_decoration(&buffer, e)).collect()
+    }
+
+    #[napi]
+    pub fn get_all_decorations(&self) -> Vec<ModelDecoration> {
+        let buffer = self.buffer.read().unwrap();
+        let entries = self.decorations.read().unwrap().search(0, usize::MAX);
+        entries.into_iter().map(|e| to_model_decoration(&buffer, e)).collect()
     }
 
     fn get_range_from_offsets(&self, line_starts: &[usize], start: usize, end: usize) -> Range {
@@ -192,3 +585,110 @@ impl TextModel {
         }
     }
 }
+
+/// Model lines and tokens as seen by the bracket pair store.
+struct ModelLines<'a> {
+    buffer: &'a PieceTree,
+    tokens: &'a mut TokenizationStateStore,
+}
+
+impl BracketLineSource for ModelLines<'_> {
+    fn retokenize_until(&mut self, line_index: usize) -> Vec<(usize, usize)> {
+        if self.tokens.first_invalid_line_index().is_none_or(|i| i > line_index) {
+            return Vec::new();
+        }
+        let buffer = self.buffer;
+        self.tokens.tokenize_until(line_index, usize::MAX, |i| buffer.get_line_content(i as u32 + 1))
+    }
+
+    fn content(&mut self, line_index: usize) -> String {
+        self.buffer.get_line_content(line_index as u32 + 1)
+    }
+
+    fn tokens(&mut self, line_index: usize) -> Vec<Token> {
+        self.tokens.line_tokens(line_index).cloned().unwrap_or_default()
+    }
+}
+
+/// Line-level shape of one replacement, for views that follow edits incrementally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct LineEdit {
+    pub start_line_index: usize,
+    pub removed_line_breaks: usize,
+    pub inserted_line_breaks: usize,
+}
+
+/// Most replacements kept for `line_edits_between`; older readers rebuild from scratch.
+const LINE_EDIT_LOG_SIZE: usize = 1024;
+
+/// Recent line edits, each tagged with the version id it produced, in application order.
+struct LineEditLog {
+    /// Every edit of versions from this one on is in `entries`.
+    first_version_id: u32,
+    entries: VecDeque<(u32, LineEdit)>,
+}
+
+impl LineEditLog {
+    fn new(version_id: u32) -> Self {
+        Self { first_version_id: version_id + 1, entries: VecDeque::new() }
+    }
+
+    /// After `set_value`: nothing before `version_id` can be replayed.
+    fn reset(&mut self, version_id: u32) {
+        *self = Self::new(version_id);
+    }
+
+    fn push(&mut self, version_id: u32, edit: LineEdit) {
+        self.entries.push_back((version_id, edit));
+        if self.entries.len() > LINE_EDIT_LOG_SIZE {
+            // Drop whole versions so a version is either fully replayable or not at all.
+            let oldest = self.entries[0].0;
+            while self.entries.front().is_some_and(|(v, _)| *v == oldest) {
+                self.entries.pop_front();
+            }
+            self.first_version_id = oldest + 1;
+        }
+    }
+
+    fn between(&self, from_version_id: u32, to_version_id: u32) -> Option<Vec<LineEdit>> {
+        (from_version_id + 1 >= self.first_version_id).then(|| {
+            self.entries.iter()
+                .filter(|(v, _)| *v > from_version_id && *v <= to_version_id)
+                .map(|(_, e)| *e)
+                .collect()
+        })
+    }
+}
+
+/// One offset-based replacement inside `apply_replacements`.
+struct Replacement {
+    start: usize,
+    end: usize,
+    text: String,
+    force_move_markers: bool,
+}
+
+impl Replacement {
+    fn new(start: usize, end: usize, text: String) -> Self {
+        Self { start, end, text, force_move_markers: false }
+    }
+}
+
+fn offsets_to_range(buffer: &PieceTree, start: usize, end: usize) -> Range {
+    let start = buffer.position_at(start as u32);
+    let end = buffer.position_at(end as u32);
+    Range {
+        start_line_number: start.line_number,
+        start_column: start.column,
+        end_line_number: end.line_number,
+        end_column: end.column,
+    }
+}
+
+fn to_model_decoration(buffer: &PieceTree, entry: IntervalEntry) -> ModelDecoration {
+    ModelDecoration {
+        range: offsets_to_range(buffer, entry.start, entry.end),
+        id: entry.id,
+        options: entry.options,
+    }
+}
@@ -0,0 +1,227 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! Text Model Tokens — Rust port of `src/vs/editor/common/model/textModelTokens.ts`.
+//!
+//! Keeps per-line tokens and the tokenizer state at the start of every line.
+//! Edits only invalidate the lines they touch; retokenization then walks
+//! forward from the first invalid line and stops as soon as a line ends in
+//! the same state it ended in before, so the work done is proportional to
+//! the edit rather than to the file.
+
+use napi_derive::napi;
+use crate::textmate::RuleStack;
+use crate::tokenizer::{Token, Tokenizer};
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokensChangedRange {
+    pub from_line_number: u32,
+    pub to_line_number: u32,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct TokenizationStateStore {
+    tokenizer: Option<Tokenizer>,
+    /// Tokenizer state at the start of each line (`None` = not yet known).
+    begin_states: Vec<Option<RuleStack>>,
+    tokens: Vec<Option<Vec<Token>>>,
+    valid: Vec<bool>,
+    /// Every line before this index is valid.
+    invalid_line_start_index: usize,
+}
+
+impl TokenizationStateStore {
+    pub fn new(tokenizer: Option<Tokenizer>, line_count: usize) -> Self {
+        let mut store = Self { tokenizer, ..Default::default() };
+        store.reset(line_count);
+        store
+    }
+
+    pub fn has_tokenizer(&self) -> bool {
+        self.tokenizer.is_some()
+    }
+
+    pub fn set_tokenizer(&mut self, tokenizer: Option<Tokenizer>, line_count: usize) {
+        self.tokenizer = tokenizer;
+        self.reset(line_count);
+    }
+
+    /// Drops every cached token (used on flush / `set_value`).
+    pub fn reset(&mut self, line_count: usize) {
+        self.begin_states = vec![None; line_count];
+        if let Some(first) = self.begin_states.first_mut() {
+            *first = Some(RuleStack::default());
+        }
+        self.tokens = vec![None; line_count];
+        self.valid = vec![false; line_count];
+        self.invalid_line_start_index = 0;
+    }
+
+    /// Mirrors a single edit: line `start_line_index` had `removed_line_breaks`
+    /// line breaks replaced by `inserted_line_breaks`. Edits must be reported
+    /// bottom-up so earlier line indices stay stable.
+    pub fn accept_edit(&mut self, start_line_index: usize, removed_line_breaks: usize, inserted_line_breaks: usize) {
+        if start_line_index >= self.valid.len() {
+            return;
+        }
+        self.valid[start_line_index] = false;
+        self.tokens[start_line_index] = None;
+
+        let remove_end = (start_line_index + 1 + removed_line_breaks).min(self.valid.len());
+        let insert_at = start_line_index + 1;
+        self.begin_states.splice(insert_at..remove_end, std::iter::repeat_n(None, inserted_line_breaks));
+        self.tokens.splice(insert_at..remove_end, std::iter::repeat_n(None, inserted_line_breaks));
+        self.valid.splice(insert_at..remove_end, std::iter::repeat_n(false, inserted_line_breaks));
+
+        self.invalid_line_start_index = self.invalid_line_start_index.min(start_line_index);
+    }
+
+    pub fn first_invalid_line_index(&self) -> Option<usize> {
+        if !self.has_tokenizer() {
+            return None;
+        }
+        let from = self.invalid_line_start_index.min(self.valid.len());
+        self.valid[from..].iter().position(|v| !v).map(|i| from + i)
+    }
+
+    pub fn line_tokens(&self, line_index: usize) -> Option<&Vec<Token>> {
+        self.tokens.get(line_index).and_then(|t| t.as_ref())
+    }
+
+    /// Tokenizes invalid lines up to and including `until_line_index`, at most
+    /// `budget` lines. Returns the line ranges (0-based, inclusive) whose
+    /// tokens were recomputed.
+    pub fn tokenize_until<F>(&mut self, until_line_index: usize, budget: usize, mut line_at: F) -> Vec<(usize, usize)>
+    where
+        F: FnMut(usize) -> String,
+    {
+        let mut changed: Vec<(usize, usize)> = Vec::new();
+        let tokenizer = match &self.tokenizer {
+            Some(t) => t.clone(),
+            None => return changed,
+        };
+        let line_count = self.valid.len();
+        let mut done = 0;
+        let mut next = self.first_invalid_line_index();
+
+        while let Some(i) = next {
+            if i > until_line_index || i >= line_count || done >= budget {
+                break;
+            }
+            let begin = self.begin_state_for(i);
+            let (tokens, end_state) = tokenizer.tokenize_line_with_state(&line_at(i), &begin);
+            self.tokens[i] = Some(tokens);
+            self.valid[i] = true;
+            done += 1;
+
+            match changed.last_mut() {
+                Some(last) if last.1 + 1 == i => last.1 = i,
+                _ => changed.push((i, i)),
+            }
+
+            if i + 1 < line_count && self.begin_states[i + 1].as_ref() != Some(&end_state) {
+                self.begin_states[i + 1] = Some(end_state);
+                self.valid[i + 1] = false;
+                next = Some(i + 1);
+                continue;
+            }
+            // The next line starts in the same state as before: everything
+            // after it that is still valid remains correct.
+            self.invalid_line_start_index = i + 1;
+            next = self.first_invalid_line_index();
+        }
+
+        self.invalid_line_start_index = next.unwrap_or(line_count);
+        changed
+    }
+
+    fn begin_state_for(&self, line_index: usize) -> RuleStack {
+        if line_index == 0 {
+            return RuleStack::default();
+        }
+        if let Some(Some(state)) = self.begin_states.get(line_index) {
+            return state.clone();
+        }
+        // Lines are tokenized in order, so a line without a begin state has not
+        // been reached yet; start it fresh.
+        RuleStack::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text_model::TextModel;
+    use crate::text_model_types::{RangePod, SingleEditOperation};
+    use crate::tokenizer::Tokenizer;
+
+    const GRAMMAR: &str = r#"{
+        "scopeName": "source.test",
+        "patterns": [
+            { "name": "comment.block.test", "begin": "/\\*", "end": "\\*/" },
+            { "name": "keyword.test", "match": "\\blet\\b" }
+        ]
+    }"#;
+
+    fn insert(model: &TextModel, line: u32, column: u32, text: &str) {
+        model.apply_edits(vec![SingleEditOperation {
+            range: RangePod { start_line_number: line, start_column: column, end_line_number: line, end_column: column },
+            text: Some(text.to_string()),
+            force_move_markers: None,
+        }]);
+    }
+
+    fn model_with_lines(count: usize) -> TextModel {
+        let content = vec!["let x = 1;"; count].join("\n");
+        let model = TextModel::new("file:///test".into(), content);
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.load_grammar(GRAMMAR.into()).unwrap();
+        model.set_tokenizer(&tokenizer);
+        model
+    }
+
+    #[test]
+    fn test_edit_retokenizes_only_touched_lines() {
+        let model = model_with_lines(1000);
+        let changed = model.tokenize_invalid_lines(u32::MAX);
+        assert_eq!(changed[0].from_line_number, 1);
+        assert_eq!(changed[0].to_line_number, 1000);
+
+        insert(&model, 500, 1, "let ");
+        let changed = model.tokenize_invalid_lines(u32::MAX);
+        assert_eq!(changed.len(), 1);
+        assert_eq!((changed[0].from_line_number, changed[0].to_line_number), (500, 500));
+        assert_eq!(model.get_first_invalid_token_line(), None);
+    }
+
+    #[test]
+    fn test_state_change_propagates_until_stable() {
+        let model = model_with_lines(10);
+        model.tokenize_invalid_lines(u32::MAX);
+
+        insert(&model, 3, 1, "/*");
+        let changed = model.tokenize_invalid_lines(u32::MAX);
+        assert_eq!((changed[0].from_line_number, changed[0].to_line_number), (3, 10));
+        assert_eq!(model.get_line_tokens(7)[0].token_type, "comment");
+
+        insert(&model, 5, 1, "*/");
+        let changed = model.tokenize_invalid_lines(u32::MAX);
+        assert_eq!((changed[0].from_line_number, changed[0].to_line_number), (5, 10));
+        assert_eq!(model.get_line_tokens(7)[0].token_type, "other");
+    }
+
+    #[test]
+    fn test_line_insertion_shifts_states() {
+        let model = model_with_lines(5);
+        model.tokenize_invalid_lines(u32::MAX);
+
+        insert(&model, 2, 1, "/* a\nb\nc */ ");
+        assert_eq!(model.line_count(), 7);
+        let changed = model.tokenize_invalid_lines(u32::MAX);
+        assert_eq!((changed[0].from_line_number, changed[0].to_line_number), (2, 4));
+        assert_eq!(model.get_line_tokens(3)[0].token_type, "comment");
+        assert_eq!(model.get_line_tokens(5)[0].scopes.last().unwrap(), "keyword.test");
+    }
+}
@@ -13,12 +13,23 @@ pub enum EndOfLineSequence {
     CRLF,
 }
 
-#[derive(Debug)]
+#[napi]
+#[derive(Debug, PartialEq, Eq)]
 pub enum EditOperationType {
-    Other,
-    Typing,
-    DeletingLeft,
-    DeletingRight,
+    Other = 0,
+    Typing = 1,
+    DeletingLeft = 2,
+    DeletingRight = 3,
+}
+
+/// How a decoration's edges behave when text is typed exactly at them.
+#[napi]
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrackedRangeStickiness {
+    AlwaysGrowsWhenTypingAtEdges = 0,
+    NeverGrowsWhenTypingAtEdges = 1,
+    GrowsOnlyWhenTypingBefore = 2,
+    GrowsOnlyWhenTypingAfter = 3,
 }
 
 #[napi(object)]
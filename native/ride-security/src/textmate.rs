@@ -0,0 +1,1122 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! TextMate Grammar Engine — Rust port of `vscode-textmate` (`grammar.ts`, `rule.ts`).
+//!
+//! Features:
+//! - `.tmLanguage.json` loading (match, begin/end, begin/while, captures, repository)
+//! - `#name`, `$self`, `$base`, `scope` and `scope#name` include resolution
+//! - Embedded languages through cross-grammar includes
+//! - Rule stack carried across line ends for multi-line constructs
+//! - End/while back-references (`\1`) resolved against begin captures
+//! - Oniguruma patterns run on `fancy-regex`, so lookaround, atomic groups, possessive
+//!   quantifiers and back-references behave as in `vscode-oniguruma`
+//!
+//! Patterns `fancy-regex` cannot compile (e.g. variable-length lookbehind) are reported
+//! through `errors` and never match.
+
+use crate::tokenizer::Token;
+use fancy_regex::{Regex, RegexBuilder};
+use lru::LruCache;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// End/while patterns with back-references compiled per begin match, kept per grammar.
+const DYNAMIC_PATTERN_CACHE_SIZE: usize = 256;
+
+static CAPTURE_REFERENCE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\$(\d+)|\$\{(\d+):/(downcase|upcase)\}").unwrap());
+
+// ─── Raw Grammar (tmLanguage.json) ─────────────────────────────────────────
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RawGrammar {
+    pub scope_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub file_types: Vec<String>,
+    #[serde(default)]
+    pub patterns: Vec<RawRule>,
+    #[serde(default)]
+    pub repository: HashMap<String, RawRule>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RawRule {
+    pub include: Option<String>,
+    pub name: Option<String>,
+    pub content_name: Option<String>,
+    #[serde(rename = "match")]
+    pub match_: Option<String>,
+    pub begin: Option<String>,
+    pub end: Option<String>,
+    #[serde(rename = "while")]
+    pub while_: Option<String>,
+    pub captures: Option<HashMap<String, RawRule>>,
+    pub begin_captures: Option<HashMap<String, RawRule>>,
+    pub end_captures: Option<HashMap<String, RawRule>>,
+    pub while_captures: Option<HashMap<String, RawRule>>,
+    pub patterns: Option<Vec<RawRule>>,
+    pub repository: Option<HashMap<String, RawRule>>,
+    pub apply_end_pattern_last: Option<serde_json::Value>,
+}
+
+impl RawGrammar {
+    pub fn from_json(json: &str) -> std::result::Result<Self, String> {
+        let grammar: RawGrammar = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if grammar.scope_name.is_empty() {
+            return Err("Grammar is missing `scopeName`".to_string());
+        }
+        Ok(grammar)
+    }
+}
+
+// ─── Compiled Rules ────────────────────────────────────────────────────────
+
+#[derive(Clone, Debug)]
+struct CompiledPattern {
+    regex: Option<Regex>,
+    /// Pattern contained `\G`: it may only match at the anchor position.
+    anchored: bool,
+}
+
+#[derive(Clone, Debug)]
+struct CaptureRule {
+    name: Option<String>,
+    content_name: Option<String>,
+    /// Rule whose patterns re-tokenize the captured text.
+    retokenize_rule: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum RuleKind {
+    IncludeOnly,
+    Match {
+        pattern: CompiledPattern,
+        captures: Vec<Option<CaptureRule>>,
+    },
+    BeginEnd {
+        begin: CompiledPattern,
+        begin_captures: Vec<Option<CaptureRule>>,
+        end_source: String,
+        end_has_back_references: bool,
+        end: CompiledPattern,
+        end_captures: Vec<Option<CaptureRule>>,
+        apply_end_pattern_last: bool,
+    },
+    BeginWhile {
+        begin: CompiledPattern,
+        begin_captures: Vec<Option<CaptureRule>>,
+        while_source: String,
+        while_has_back_references: bool,
+        while_: CompiledPattern,
+        while_captures: Vec<Option<CaptureRule>>,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    name: Option<String>,
+    content_name: Option<String>,
+    kind: RuleKind,
+    patterns: Vec<usize>,
+    /// `patterns` with include-only rules expanded, in priority order.
+    flat_patterns: Vec<usize>,
+}
+
+// ─── Rule Stack ────────────────────────────────────────────────────────────
+
+#[derive(Clone, Debug)]
+struct StackFrame {
+    rule_id: usize,
+    /// End (or while) pattern source with back-references already resolved.
+    end_source: Option<String>,
+    name_scopes: Vec<String>,
+    content_scopes: Vec<String>,
+    /// Position on the current line where the frame was pushed.
+    enter_pos: Option<usize>,
+    /// Position `\G` matches at while this frame is on top.
+    anchor_pos: Option<usize>,
+}
+
+impl PartialEq for StackFrame {
+    fn eq(&self, other: &Self) -> bool {
+        self.rule_id == other.rule_id
+            && self.end_source == other.end_source
+            && self.name_scopes == other.name_scopes
+            && self.content_scopes == other.content_scopes
+    }
+}
+
+/// Tokenizer state at a line end. An empty stack is the grammar's initial state.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RuleStack {
+    frames: Vec<StackFrame>,
+}
+
+impl RuleStack {
+    pub fn is_initial(&self) -> bool {
+        self.frames.len() <= 1
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Scopes active at the end of the line, innermost last.
+    pub fn scopes(&self) -> Vec<String> {
+        self.frames.last().map(|f| f.content_scopes.clone()).unwrap_or_default()
+    }
+}
+
+// ─── Compiler ──────────────────────────────────────────────────────────────
+
+type Repository = HashMap<String, RawRule>;
+
+struct Compiler<'a> {
+    grammars: &'a HashMap<String, Arc<RawGrammar>>,
+    base_scope: String,
+    rules: Vec<Rule>,
+    ids: HashMap<usize, usize>,
+    roots: HashMap<String, usize>,
+    errors: Vec<String>,
+}
+
+impl<'a> Compiler<'a> {
+    fn alloc(&mut self) -> usize {
+        self.rules.push(Rule {
+            name: None,
+            content_name: None,
+            kind: RuleKind::IncludeOnly,
+            patterns: Vec::new(),
+            flat_patterns: Vec::new(),
+        });
+        self.rules.len() - 1
+    }
+
+    fn compile_root(&mut self, scope_name: &str) -> Option<usize> {
+        if let Some(&id) = self.roots.get(scope_name) {
+            return Some(id);
+        }
+        let grammar = self.grammars.get(scope_name)?;
+        let id = self.alloc();
+        self.roots.insert(scope_name.to_string(), id);
+
+        let repos: Vec<&'a Repository> = vec![&grammar.repository];
+        let patterns = grammar.patterns.iter()
+            .filter_map(|p| self.compile_rule(p, scope_name, &repos))
+            .collect();
+        self.rules[id].patterns = patterns;
+        Some(id)
+    }
+
+    fn resolve_include(&mut self, include: &str, scope_name: &str, repos: &[&'a Repository]) -> Option<usize> {
+        match include {
+            "$self" => self.compile_root(scope_name),
+            "$base" => {
+                let base = self.base_scope.clone();
+                self.compile_root(&base)
+            }
+            _ if include.starts_with('#') => {
+                let key = &include[1..];
+                for (depth, repo) in repos.iter().enumerate().rev() {
+                    if let Some(raw) = repo.get(key) {
+                        return self.compile_rule(raw, scope_name, &repos[..=depth]);
+                    }
+                }
+                self.errors.push(format!("{}: unresolved include `{}`", scope_name, include));
+                None
+            }
+            _ => {
+                let (scope, rule) = match include.split_once('#') {
+                    Some((s, r)) => (s, Some(r)),
+                    None => (include, None),
+                };
+                let grammar = match self.grammars.get(scope) {
+                    Some(g) => g,
+                    None => {
+                        self.errors.push(format!("{}: unknown grammar `{}`", scope_name, scope));
+                        return None;
+                    }
+                };
+                match rule {
+                    None => self.compile_root(scope),
+                    Some(key) => match grammar.repository.get(key) {
+                        Some(raw) => self.compile_rule(raw, scope, &[&grammar.repository]),
+                        None => {
+                            self.errors.push(format!("{}: unresolved include `{}`", scope_name, include));
+                            None
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    fn compile_rule(&mut self, raw: &'a RawRule, scope_name: &str, repos: &[&'a Repository]) -> Option<usize> {
+        if raw.match_.is_none() && raw.begin.is_none()
+            && let Some(include) = &raw.include
+        {
+            return self.resolve_include(include, scope_name, repos);
+        }
+
+        let key = raw as *const RawRule as usize;
+        if let Some(&id) = self.ids.get(&key) {
+            return Some(id);
+        }
+        let id = self.alloc();
+        self.ids.insert(key, id);
+
+        let mut local_repos = repos.to_vec();
+        if let Some(repo) = &raw.repository {
+            local_repos.push(repo);
+        }
+
+        let patterns: Vec<usize> = raw.patterns.iter().flatten()
+            .filter_map(|p| self.compile_rule(p, scope_name, &local_repos))
+            .collect();
+
+        let kind = if let Some(m) = &raw.match_ {
+            RuleKind::Match {
+                pattern: self.compile_pattern(m, scope_name),
+                captures: self.compile_captures(raw.captures.as_ref(), scope_name, &local_repos),
+            }
+        } else if let Some(begin) = &raw.begin {
+            let begin_captures = raw.begin_captures.as_ref().or(raw.captures.as_ref());
+            if let Some(while_src) = &raw.while_ {
+                let while_captures = raw.while_captures.as_ref().or(raw.captures.as_ref());
+                RuleKind::BeginWhile {
+                    begin: self.compile_pattern(begin, scope_name),
+                    begin_captures: self.compile_captures(begin_captures, scope_name, &local_repos),
+                    while_source: while_src.clone(),
+                    while_has_back_references: has_back_references(while_src),
+                    while_: self.compile_pattern(while_src, scope_name),
+                    while_captures: self.compile_captures(while_captures, scope_name, &local_repos),
+                }
+            } else {
+                let end_src = raw.end.clone().unwrap_or_else(|| "\u{FFFF}".to_string());
+                let end_captures = raw.end_captures.as_ref().or(raw.captures.as_ref());
+                RuleKind::BeginEnd {
+                    begin: self.compile_pattern(begin, scope_name),
+                    begin_captures: self.compile_captures(begin_captures, scope_name, &local_repos),
+                    end_has_back_references: has_back_references(&end_src),
+                    end: self.compile_pattern(&end_src, scope_name),
+                    end_source: end_src,
+                    end_captures: self.compile_captures(end_captures, scope_name, &local_repos),
+                    apply_end_pattern_last: raw.apply_end_pattern_last.as_ref().map(is_truthy).unwrap_or(false),
+                }
+            }
+        } else {
+            RuleKind::IncludeOnly
+        };
+
+        let rule = &mut self.rules[id];
+        rule.name = raw.name.clone();
+        rule.content_name = raw.content_name.clone();
+        rule.kind = kind;
+        rule.patterns = patterns;
+        Some(id)
+    }
+
+    fn compile_captures(&mut self, raw: Option<&'a HashMap<String, RawRule>>, scope_name: &str, repos: &[&'a Repository]) -> Vec<Option<CaptureRule>> {
+        let raw = match raw {
+            Some(r) => r,
+            None => return Vec::new(),
+        };
+        let max = raw.keys().filter_map(|k| k.parse::<usize>().ok()).max();
+        let mut result = match max {
+            Some(m) => vec![None; m + 1],
+            None => return Vec::new(),
+        };
+        for (key, capture) in raw {
+            let index = match key.parse::<usize>() {
+                Ok(i) => i,
+                Err(_) => continue,
+            };
+            let retokenize_rule = match &capture.patterns {
+                Some(patterns) if !patterns.is_empty() => {
+                    let id = self.alloc();
+                    let ids = patterns.iter()
+                        .filter_map(|p| self.compile_rule(p, scope_name, repos))
+                        .collect();
+                    self.rules[id].patterns = ids;
+                    Some(id)
+                }
+                _ => None,
+            };
+            result[index] = Some(CaptureRule {
+                name: capture.name.clone(),
+                content_name: capture.content_name.clone(),
+                retokenize_rule,
+            });
+        }
+        result
+    }
+
+    fn compile_pattern(&mut self, source: &str, scope_name: &str) -> CompiledPattern {
+        match compile_onig(source) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.errors.push(format!("{}: cannot compile `{}`: {}", scope_name, source, e));
+                CompiledPattern { regex: None, anchored: false }
+            }
+        }
+    }
+
+    fn flatten(&mut self) {
+        for id in 0..self.rules.len() {
+            let mut flat = Vec::new();
+            let mut visited = HashSet::new();
+            visited.insert(id);
+            self.collect_flat(&self.rules[id].patterns.clone(), &mut visited, &mut flat);
+            self.rules[id].flat_patterns = flat;
+        }
+    }
+
+    fn collect_flat(&self, patterns: &[usize], visited: &mut HashSet<usize>, out: &mut Vec<usize>) {
+        for &p in patterns {
+            match self.rules[p].kind {
+                RuleKind::IncludeOnly => {
+                    if visited.insert(p) {
+                        self.collect_flat(&self.rules[p].patterns, visited, out);
+                    }
+                }
+                _ => out.push(p),
+            }
+        }
+    }
+}
+
+fn is_truthy(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::Number(n) => n.as_f64().map(|v| v != 0.0).unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn has_back_references(source: &str) -> bool {
+    let bytes = source.as_bytes();
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        if bytes[i] == b'\\' {
+            if bytes[i + 1].is_ascii_digit() {
+                return true;
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    false
+}
+
+/// Replaces `\N` back-references with the escaped text of begin capture `N`.
+fn resolve_back_references(source: &str, line: &str, captures: &[Option<(usize, usize)>]) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some(d) if d.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(&d) = chars.peek() {
+                    if !d.is_ascii_digit() { break; }
+                    digits.push(d);
+                    chars.next();
+                }
+                let index: usize = digits.parse().unwrap_or(0);
+                if let Some(Some((s, e))) = captures.get(index) {
+                    result.push_str(&regex::escape(&line[*s..*e]));
+                }
+            }
+            Some(_) => {
+                result.push('\\');
+                result.push(chars.next().unwrap());
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Substitutes `$N` and `${N:/downcase}` capture references in a scope name.
+fn substitute_captures(name: &str, line: &str, captures: &[Option<(usize, usize)>]) -> String {
+    if !name.contains('$') {
+        return name.to_string();
+    }
+    let capture_text = |index: usize| -> String {
+        match captures.get(index) {
+            Some(Some((s, e))) => line[*s..*e].trim_start_matches('.').to_string(),
+            _ => String::new(),
+        }
+    };
+    CAPTURE_REFERENCE.replace_all(name, |caps: &regex::Captures| {
+        if let Some(d) = caps.get(1) {
+            capture_text(d.as_str().parse().unwrap_or(0))
+        } else {
+            let text = capture_text(caps[2].parse().unwrap_or(0));
+            if &caps[3] == "downcase" { text.to_lowercase() } else { text.to_uppercase() }
+        }
+    }).into_owned()
+}
+
+/// Translates an Oniguruma pattern into `fancy-regex` syntax and compiles it.
+fn compile_onig(source: &str) -> std::result::Result<CompiledPattern, String> {
+    let (translated, anchored) = translate_onig(source);
+    let regex = RegexBuilder::new(&format!("(?m){}", translated))
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(CompiledPattern { regex: Some(regex), anchored })
+}
+
+/// Rewrites the escapes whose meaning differs between Oniguruma and `fancy-regex`. `\G` is
+/// removed and reported, since it is checked against the rule stack's anchor instead.
+fn translate_onig(source: &str) -> (String, bool) {
+    let chars: Vec<char> = source.chars().collect();
+    let mut out = String::with_capacity(source.len());
+    let mut anchored = false;
+    let mut in_class = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() {
+            let n = chars[i + 1];
+            match n {
+                'h' if in_class => out.push_str("0-9a-fA-F"),
+                'h' => out.push_str("[0-9a-fA-F]"),
+                'H' if !in_class => out.push_str("[^0-9a-fA-F]"),
+                'G' if !in_class => anchored = true,
+                'Z' if !in_class => out.push('$'),
+                _ => {
+                    out.push(c);
+                    out.push(n);
+                }
+            }
+            i += 2;
+            continue;
+        }
+        if in_class {
+            if c == '[' && chars.get(i + 1) == Some(&':') {
+                // POSIX class such as [:alpha:]
+                if let Some(end) = find_sequence(&chars, i + 2, &[':', ']']) {
+                    out.extend(&chars[i..end + 2]);
+                    i = end + 2;
+                    continue;
+                }
+            }
+            if c == ']' {
+                in_class = false;
+            }
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        if c == '[' {
+            in_class = true;
+            out.push(c);
+            if chars.get(i + 1) == Some(&'^') {
+                out.push('^');
+                i += 1;
+            }
+            if chars.get(i + 1) == Some(&']') {
+                out.push_str("\\]");
+                i += 1;
+            }
+        } else {
+            out.push(c);
+        }
+        i += 1;
+    }
+    (out, anchored)
+}
+
+fn find_sequence(chars: &[char], from: usize, seq: &[char]) -> Option<usize> {
+    (from..chars.len().saturating_sub(seq.len() - 1)).find(|&j| chars[j..j + seq.len()] == *seq)
+}
+
+// ─── Compiled Grammar ──────────────────────────────────────────────────────
+
+pub struct TextMateGrammar {
+    scope_name: String,
+    root_id: usize,
+    rules: Vec<Rule>,
+    errors: Vec<String>,
+    dynamic_patterns: Mutex<LruCache<String, CompiledPattern>>,
+}
+
+impl std::fmt::Debug for TextMateGrammar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextMateGrammar")
+            .field("scope_name", &self.scope_name)
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
+
+enum Candidate {
+    End,
+    Rule(usize),
+}
+
+struct LineTokens {
+    tokens: Vec<Token>,
+    last_pos: usize,
+    line_len: usize,
+}
+
+impl LineTokens {
+    fn produce(&mut self, scopes: &[String], end: usize) {
+        let end = end.min(self.line_len);
+        if end <= self.last_pos {
+            return;
+        }
+        if let Some(last) = self.tokens.last_mut()
+            && last.scopes == scopes
+        {
+            last.length += (end - self.last_pos) as u32;
+            self.last_pos = end;
+            return;
+        }
+        self.tokens.push(Token {
+            start_index: self.last_pos as u32,
+            length: (end - self.last_pos) as u32,
+            token_type: standard_token_type(scopes).to_string(),
+            scopes: scopes.to_vec(),
+        });
+        self.last_pos = end;
+    }
+}
+
+/// Maps a scope list to VS Code's `StandardTokenType` (innermost match wins).
+pub fn standard_token_type(scopes: &[String]) -> &'static str {
+    let mut result = "other";
+    for scope in scopes {
+        for segment in scope.split('.') {
+            match segment {
+                "comment" => result = "comment",
+                "string" => result = "string",
+                "regex" | "regexp" => result = "regex",
+                _ => {}
+            }
+        }
+        if scope.starts_with("meta.embedded") {
+            result = "other";
+        }
+    }
+    result
+}
+
+type CaptureIndices = Vec<Option<(usize, usize)>>;
+
+impl TextMateGrammar {
+    /// Compiles the grammar registered under `scope_name`, resolving includes
+    /// into any other grammar in `grammars`.
+    pub fn compile(scope_name: &str, grammars: &HashMap<String, Arc<RawGrammar>>) -> std::result::Result<Self, String> {
+        let mut compiler = Compiler {
+            grammars,
+            base_scope: scope_name.to_string(),
+            rules: Vec::new(),
+            ids: HashMap::new(),
+            roots: HashMap::new(),
+            errors: Vec::new(),
+        };
+        let root_id = compiler.compile_root(scope_name)
+            .ok_or_else(|| format!("Unknown grammar `{}`", scope_name))?;
+        compiler.flatten();
+        Ok(Self {
+            scope_name: scope_name.to_string(),
+            root_id,
+            rules: compiler.rules,
+            errors: compiler.errors,
+            dynamic_patterns: Mutex::new(LruCache::new(NonZeroUsize::new(DYNAMIC_PATTERN_CACHE_SIZE).unwrap())),
+        })
+    }
+
+    pub fn scope_name(&self) -> &str {
+        &self.scope_name
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn initial_stack(&self) -> RuleStack {
+        let scopes = vec![self.scope_name.clone()];
+        RuleStack {
+            frames: vec![StackFrame {
+                rule_id: self.root_id,
+                end_source: None,
+                name_scopes: scopes.clone(),
+                content_scopes: scopes,
+                enter_pos: None,
+                anchor_pos: None,
+            }],
+        }
+    }
+
+    /// Tokenizes one line starting from `state` and returns the tokens together
+    /// with the state at the end of the line.
+    pub fn tokenize_line(&self, line: &str, state: &RuleStack) -> (Vec<Token>, RuleStack) {
+        let mut stack = if state.frames.is_empty() {
+            self.initial_stack()
+        } else {
+            let mut s = state.clone();
+            for frame in &mut s.frames {
+                frame.enter_pos = None;
+                frame.anchor_pos = None;
+            }
+            s
+        };
+
+        let text = format!("{}\n", line);
+        let mut out = LineTokens { tokens: Vec::new(), last_pos: 0, line_len: line.len() };
+
+        let (pos, anchor) = self.check_while_conditions(&text, &mut stack, &mut out);
+        self.scan(&text, pos, anchor, &mut stack, &mut out);
+        let scopes = stack.scopes();
+        out.produce(&scopes, line.len());
+
+        for frame in &mut stack.frames {
+            frame.enter_pos = None;
+            frame.anchor_pos = None;
+        }
+        (out.tokens, stack)
+    }
+
+    fn check_while_conditions(&self, text: &str, stack: &mut RuleStack, out: &mut LineTokens) -> (usize, Option<usize>) {
+        let mut pos = 0;
+        let mut anchor = None;
+        let mut depth = 0;
+        while depth < stack.frames.len() {
+            let frame = stack.frames[depth].clone();
+            if let RuleKind::BeginWhile { while_, while_has_back_references, while_captures, .. } = &self.rules[frame.rule_id].kind {
+                let pattern = match (while_has_back_references, &frame.end_source) {
+                    (true, Some(src)) => self.dynamic_pattern(src),
+                    _ => while_.clone(),
+                };
+                let found = pattern.regex.as_ref()
+                    .and_then(|re| re.captures_from_pos(text, pos).ok().flatten())
+                    .filter(|caps| caps.get(0).map(|m| m.start()) == Some(pos));
+                match found {
+                    Some(caps) => {
+                        let indices = capture_indices(&caps);
+                        let (start, end) = indices[0].unwrap();
+                        out.produce(&frame.content_scopes, start);
+                        self.handle_captures(text, while_captures, &indices, &frame.content_scopes, out);
+                        out.produce(&frame.content_scopes, end);
+                        anchor = Some(end);
+                        pos = pos.max(end);
+                    }
+                    None => {
+                        stack.frames.truncate(depth);
+                        break;
+                    }
+                }
+            }
+            depth += 1;
+        }
+        (pos, anchor)
+    }
+
+    fn dynamic_pattern(&self, source: &str) -> CompiledPattern {
+        let mut cache = self.dynamic_patterns.lock().unwrap();
+        cache.get_or_insert(source.to_string(), || compile_onig(source).unwrap_or(CompiledPattern { regex: None, anchored: false }))
+            .clone()
+    }
+
+    fn scan(&self, text: &str, start: usize, mut anchor: Option<usize>, stack: &mut RuleStack, out: &mut LineTokens) {
+        let mut pos = start;
+        while pos < text.len() {
+            let top = match stack.frames.last() {
+                Some(f) => f.clone(),
+                None => return,
+            };
+            let (candidate, indices) = match self.match_at(text, pos, &top, anchor) {
+                Some(m) => m,
+                None => {
+                    out.produce(&top.content_scopes, text.len());
+                    return;
+                }
+            };
+            let (match_start, match_end) = indices[0].unwrap();
+            let has_advanced = match_end > pos;
+            out.produce(&top.content_scopes, match_start);
+
+            match candidate {
+                Candidate::End => {
+                    if let RuleKind::BeginEnd { end_captures, .. } = &self.rules[top.rule_id].kind {
+                        self.handle_captures(text, end_captures, &indices, &top.name_scopes, out);
+                    }
+                    out.produce(&top.name_scopes, match_end);
+                    let popped = stack.frames.pop().unwrap();
+                    anchor = popped.anchor_pos;
+                    if stack.frames.is_empty() || (!has_advanced && popped.enter_pos == Some(pos)) {
+                        // Grammar pushed and popped a rule without advancing.
+                        stack.frames.push(popped);
+                        out.produce(&top.content_scopes, text.len());
+                        return;
+                    }
+                }
+                Candidate::Rule(rule_id) => {
+                    let rule = &self.rules[rule_id];
+                    let mut name_scopes = top.content_scopes.clone();
+                    if let Some(name) = &rule.name {
+                        push_scopes(&mut name_scopes, &substitute_captures(name, text, &indices));
+                    }
+                    match &rule.kind {
+                        RuleKind::Match { captures, .. } => {
+                            self.handle_captures(text, captures, &indices, &name_scopes, out);
+                            out.produce(&name_scopes, match_end);
+                            if !has_advanced {
+                                // Grammar is neither advancing nor pushing: stop here.
+                                if stack.frames.len() > 1 {
+                                    stack.frames.pop();
+                                }
+                                let scopes = stack.scopes();
+                                out.produce(&scopes, text.len());
+                                return;
+                            }
+                        }
+                        RuleKind::BeginEnd { begin_captures, end_source, end_has_back_references, .. } => {
+                            self.handle_captures(text, begin_captures, &indices, &name_scopes, out);
+                            out.produce(&name_scopes, match_end);
+                            let end_source = if *end_has_back_references {
+                                resolve_back_references(end_source, text, &indices)
+                            } else {
+                                end_source.clone()
+                            };
+                            let content_scopes = self.content_scopes(rule, &name_scopes, text, &indices);
+                            anchor = Some(match_end);
+                            stack.frames.push(StackFrame {
+                                rule_id,
+                                end_source: Some(end_source),
+                                name_scopes,
+                                content_scopes,
+                                enter_pos: Some(pos),
+                                anchor_pos: anchor,
+                            });
+                            if !has_advanced && top.rule_id == rule_id {
+                                stack.frames.pop();
+                                out.produce(&top.content_scopes, text.len());
+                                return;
+                            }
+                        }
+                        RuleKind::BeginWhile { begin_captures, while_source, while_has_back_references, .. } => {
+                            self.handle_captures(text, begin_captures, &indices, &name_scopes, out);
+                            out.produce(&name_scopes, match_end);
+                            let while_source = if *while_has_back_references {
+                                resolve_back_references(while_source, text, &indices)
+                            } else {
+                                while_source.clone()
+                            };
+                            let content_scopes = self.content_scopes(rule, &name_scopes, text, &indices);
+                            anchor = Some(match_end);
+                            stack.frames.push(StackFrame {
+                                rule_id,
+                                end_source: Some(while_source),
+                                name_scopes,
+                                content_scopes,
+                                enter_pos: Some(pos),
+                                anchor_pos: anchor,
+                            });
+                            if !has_advanced && top.rule_id == rule_id {
+                                stack.frames.pop();
+                                out.produce(&top.content_scopes, text.len());
+                                return;
+                            }
+                        }
+                        RuleKind::IncludeOnly => {}
+                    }
+                }
+            }
+
+            if has_advanced {
+                pos = match_end;
+            }
+        }
+    }
+
+    fn content_scopes(&self, rule: &Rule, name_scopes: &[String], text: &str, indices: &CaptureIndices) -> Vec<String> {
+        let mut scopes = name_scopes.to_vec();
+        if let Some(content_name) = &rule.content_name {
+            push_scopes(&mut scopes, &substitute_captures(content_name, text, indices));
+        }
+        scopes
+    }
+
+    fn match_at(&self, text: &str, pos: usize, frame: &StackFrame, anchor: Option<usize>) -> Option<(Candidate, CaptureIndices)> {
+        let rule = &self.rules[frame.rule_id];
+        let mut candidates: Vec<Candidate> = Vec::with_capacity(rule.flat_patterns.len() + 1);
+        let end_last = match &rule.kind {
+            RuleKind::BeginEnd { apply_end_pattern_last, .. } => Some(*apply_end_pattern_last),
+            _ => None,
+        };
+        if end_last == Some(false) {
+            candidates.push(Candidate::End);
+        }
+        candidates.extend(rule.flat_patterns.iter().map(|&id| Candidate::Rule(id)));
+        if end_last == Some(true) {
+            candidates.push(Candidate::End);
+        }
+
+        let mut best: Option<(Candidate, CaptureIndices)> = None;
+        let mut best_start = usize::MAX;
+        for candidate in candidates {
+            let pattern = match &candidate {
+                Candidate::End => match &rule.kind {
+                    RuleKind::BeginEnd { end, end_has_back_references, .. } => match (end_has_back_references, &frame.end_source) {
+                        (true, Some(src)) => self.dynamic_pattern(src),
+                        _ => end.clone(),
+                    },
+                    _ => continue,
+                },
+                Candidate::Rule(id) => match &self.rules[*id].kind {
+                    RuleKind::Match { pattern, .. } => pattern.clone(),
+                    RuleKind::BeginEnd { begin, .. } | RuleKind::BeginWhile { begin, .. } => begin.clone(),
+                    RuleKind::IncludeOnly => continue,
+                },
+            };
+            let regex = match &pattern.regex {
+                Some(r) => r,
+                None => continue,
+            };
+            if pattern.anchored && anchor != Some(pos) {
+                continue;
+            }
+            // Hitting the backtrack limit counts as no match, like an Oniguruma search timeout.
+            let caps = match regex.captures_from_pos(text, pos) {
+                Ok(Some(c)) => c,
+                _ => continue,
+            };
+            let start = caps.get(0).unwrap().start();
+            if pattern.anchored && start != pos {
+                continue;
+            }
+            if start < best_start {
+                best_start = start;
+                best = Some((candidate, capture_indices(&caps)));
+                if start == pos {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    fn handle_captures(&self, text: &str, rules: &[Option<CaptureRule>], indices: &CaptureIndices, base: &[String], out: &mut LineTokens) {
+        if rules.is_empty() {
+            return;
+        }
+        let match_end = indices[0].map(|(_, e)| e).unwrap_or(0);
+        let mut local: Vec<(Vec<String>, usize)> = Vec::new();
+
+        for (i, capture) in rules.iter().enumerate().take(indices.len()) {
+            let capture = match capture {
+                Some(c) => c,
+                None => continue,
+            };
+            let (start, end) = match indices[i] {
+                Some(r) if r.0 < r.1 => r,
+                _ => continue,
+            };
+            if start > match_end {
+                break;
+            }
+            while let Some((scopes, local_end)) = local.last() {
+                if *local_end <= start {
+                    out.produce(scopes, *local_end);
+                    local.pop();
+                } else {
+                    break;
+                }
+            }
+            let parent: Vec<String> = local.last().map(|(s, _)| s.clone()).unwrap_or_else(|| base.to_vec());
+            out.produce(&parent, start);
+
+            let mut scopes = parent;
+            if let Some(name) = &capture.name {
+                push_scopes(&mut scopes, &substitute_captures(name, text, indices));
+            }
+
+            if let Some(rule_id) = capture.retokenize_rule {
+                let mut content_scopes = scopes.clone();
+                if let Some(content_name) = &capture.content_name {
+                    push_scopes(&mut content_scopes, &substitute_captures(content_name, text, indices));
+                }
+                let mut nested = RuleStack {
+                    frames: vec![StackFrame {
+                        rule_id,
+                        end_source: None,
+                        name_scopes: scopes,
+                        content_scopes: content_scopes.clone(),
+                        enter_pos: Some(start),
+                        anchor_pos: Some(start),
+                    }],
+                };
+                self.scan(&text[..end], start, Some(start), &mut nested, out);
+                out.produce(&content_scopes, end);
+                continue;
+            }
+
+            if capture.name.is_some() {
+                local.push((scopes, end));
+            }
+        }
+
+        while let Some((scopes, end)) = local.pop() {
+            out.produce(&scopes, end);
+        }
+    }
+}
+
+fn push_scopes(scopes: &mut Vec<String>, names: &str) {
+    scopes.extend(names.split_whitespace().map(|s| s.to_string()));
+}
+
+fn capture_indices(caps: &fancy_regex::Captures) -> CaptureIndices {
+    caps.iter().map(|m| m.map(|m| (m.start(), m.end()))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar(json: &str) -> TextMateGrammar {
+        let raw = RawGrammar::from_json(json).unwrap();
+        let scope = raw.scope_name.clone();
+        let mut grammars = HashMap::new();
+        grammars.insert(scope.clone(), Arc::new(raw));
+        TextMateGrammar::compile(&scope, &grammars).unwrap()
+    }
+
+    const TEST_GRAMMAR: &str = r##"{
+        "scopeName": "source.test",
+        "patterns": [
+            { "include": "#comment" },
+            { "name": "keyword.control.test", "match": "\\b(if|else)\\b" },
+            {
+                "name": "string.quoted.test",
+                "begin": "(['\"])",
+                "end": "\\1",
+                "patterns": [{ "name": "constant.character.escape.test", "match": "\\\\." }]
+            },
+            {
+                "match": "(fn)\\s+(\\w+)",
+                "captures": {
+                    "1": { "name": "storage.type.test" },
+                    "2": { "name": "entity.name.function.test" }
+                }
+            }
+        ],
+        "repository": {
+            "comment": {
+                "name": "comment.block.test",
+                "begin": "/\\*",
+                "end": "\\*/"
+            }
+        }
+    }"##;
+
+    #[test]
+    fn test_multi_line_comment_state() {
+        let g = grammar(TEST_GRAMMAR);
+        let (tokens, state) = g.tokenize_line("if /* open", &RuleStack::default());
+        assert_eq!(tokens[0].scopes, vec!["source.test", "keyword.control.test"]);
+        assert_eq!(tokens.last().unwrap().token_type, "comment");
+        assert_eq!(state.depth(), 2);
+
+        let (tokens, state) = g.tokenize_line("still */ else", &state);
+        assert_eq!(tokens[0].token_type, "comment");
+        assert_eq!(tokens[0].length, 8);
+        assert!(state.is_initial());
+        assert_eq!(tokens.last().unwrap().scopes.last().unwrap(), "keyword.control.test");
+    }
+
+    #[test]
+    fn test_back_reference_end() {
+        let g = grammar(TEST_GRAMMAR);
+        let (tokens, state) = g.tokenize_line("'a\"b' if", &RuleStack::default());
+        assert!(state.is_initial());
+        assert_eq!(tokens[0].token_type, "string");
+        assert_eq!(tokens[0].length, 5);
+        assert_eq!(tokens.last().unwrap().scopes.last().unwrap(), "keyword.control.test");
+    }
+
+    #[test]
+    fn test_captures() {
+        let g = grammar(TEST_GRAMMAR);
+        let (tokens, _) = g.tokenize_line("fn main", &RuleStack::default());
+        let names: Vec<&str> = tokens.iter().map(|t| t.scopes.last().unwrap().as_str()).collect();
+        assert_eq!(names, vec!["storage.type.test", "source.test", "entity.name.function.test"]);
+    }
+
+    #[test]
+    fn test_embedded_grammar() {
+        let outer = RawGrammar::from_json(r#"{
+            "scopeName": "text.outer",
+            "patterns": [{
+                "begin": "<js>", "end": "</js>",
+                "contentName": "meta.embedded.block.js",
+                "patterns": [{ "include": "source.js" }]
+            }]
+        }"#).unwrap();
+        let inner = RawGrammar::from_json(r#"{
+            "scopeName": "source.js",
+            "patterns": [{ "name": "string.quoted.js", "match": "\"[^\"]*\"" }]
+        }"#).unwrap();
+        let mut grammars = HashMap::new();
+        grammars.insert("text.outer".to_string(), Arc::new(outer));
+        grammars.insert("source.js".to_string(), Arc::new(inner));
+        let g = TextMateGrammar::compile("text.outer", &grammars).unwrap();
+        assert!(g.errors().is_empty());
+
+        let (tokens, state) = g.tokenize_line("<js>\"x\"", &RuleStack::default());
+        assert_eq!(state.depth(), 2);
+        let string = tokens.iter().find(|t| t.scopes.contains(&"string.quoted.js".to_string())).unwrap();
+        assert_eq!(string.start_index, 4);
+        assert_eq!(string.token_type, "string");
+    }
+
+    #[test]
+    fn test_translate_onig() {
+        assert_eq!(translate_onig(r"(?>a++)\h[\h]").0, "(?>a++)[0-9a-fA-F][0-9a-fA-F]");
+        let (src, anchored) = translate_onig(r"\G(?<=x)foo(?!bar)");
+        assert_eq!(src, "(?<=x)foo(?!bar)");
+        assert!(anchored);
+        assert!(compile_onig("(?<=a+)b").is_err());
+    }
+
+    #[test]
+    fn test_lookaround_and_back_references() {
+        let g = grammar(r#"{
+            "scopeName": "source.look",
+            "patterns": [
+                { "name": "entity.name.function.look", "match": "\\w+(?=\\()" },
+                { "name": "variable.other.look", "match": "(?<=\\$)\\w+" },
+                { "name": "markup.repeated.look", "match": "(\\w)\\1" }
+            ]
+        }"#);
+        assert!(g.errors().is_empty(), "{:?}", g.errors());
+        let (tokens, _) = g.tokenize_line("call( $x name", &RuleStack::default());
+        let scoped: Vec<(usize, &str)> = tokens.iter().map(|t| (t.start_index as usize, t.scopes.last().unwrap().as_str())).collect();
+        assert_eq!(scoped[0], (0, "entity.name.function.look"));
+        assert!(scoped.contains(&(7, "variable.other.look")));
+        // `name` is not followed by `(`, so lookahead keeps it unscoped.
+        assert_eq!(scoped.last().unwrap().1, "source.look");
+
+        let (tokens, _) = g.tokenize_line("a bb", &RuleStack::default());
+        assert_eq!(tokens.last().unwrap().scopes.last().unwrap(), "markup.repeated.look");
+    }
+}
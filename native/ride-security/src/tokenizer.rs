@@ -3,9 +3,18 @@
  *  Licensed under the MIT License. See License.txt in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
+//! RIDE Tokenizer
+//!
+//! Two modes:
+//! - Flat rule list (`add_rule`): first regex matching at the cursor wins, everything else is `text`
+//! - TextMate grammar (`load_grammar`): full `.tmLanguage.json` engine with a rule stack
+//!   carried across line ends (see `textmate.rs`)
+
 use napi_derive::napi;
 use std::collections::HashMap;
+use std::sync::Arc;
 use regex::Regex;
+use crate::textmate::{RawGrammar, RuleStack, TextMateGrammar};
 
 #[napi(object)]
 #[derive(Clone, Debug)]
@@ -23,9 +32,51 @@ pub struct TokenizerRule {
     pub token_type: String,
 }
 
+/// Opaque tokenizer state at a line end, handed back to `tokenize_line` for the next line.
+#[napi]
+#[derive(Clone, Default)]
+pub struct TokenizerState {
+    pub(crate) stack: RuleStack,
+}
+
+#[napi]
+impl TokenizerState {
+    #[napi]
+    pub fn equals(&self, other: &TokenizerState) -> bool {
+        self.stack == other.stack
+    }
+
+    #[napi(getter)]
+    pub fn scopes(&self) -> Vec<String> {
+        self.stack.scopes()
+    }
+}
+
 #[napi]
+pub struct TokenizeLineResult {
+    tokens: Vec<Token>,
+    end_state: TokenizerState,
+}
+
+#[napi]
+impl TokenizeLineResult {
+    #[napi(getter)]
+    pub fn tokens(&self) -> Vec<Token> {
+        self.tokens.clone()
+    }
+
+    #[napi(getter)]
+    pub fn end_state(&self) -> TokenizerState {
+        self.end_state.clone()
+    }
+}
+
+#[napi]
+#[derive(Clone)]
 pub struct Tokenizer {
     rules: Vec<(Regex, String)>,
+    grammars: HashMap<String, Arc<RawGrammar>>,
+    grammar: Option<Arc<TextMateGrammar>>,
 }
 
 #[napi]
@@ -34,6 +85,66 @@ impl Tokenizer {
     pub fn new() -> Self {
         Self {
             rules: Vec::new(),
+            grammars: HashMap::new(),
+            grammar: None,
+        }
+    }
+
+    /// Loads a `.tmLanguage.json` grammar and makes it the active one.
+    /// Returns the grammar's `scopeName`.
+    #[napi]
+    pub fn load_grammar(&mut self, grammar_json: String) -> napi::Result<String> {
+        let scope_name = self.register_grammar(grammar_json)?;
+        self.activate_grammar(scope_name.clone())?;
+        Ok(scope_name)
+    }
+
+    /// Registers a grammar that other grammars can include (embedded languages)
+    /// without changing the active grammar.
+    #[napi]
+    pub fn register_grammar(&mut self, grammar_json: String) -> napi::Result<String> {
+        let raw = RawGrammar::from_json(&grammar_json).map_err(napi::Error::from_reason)?;
+        let scope_name = raw.scope_name.clone();
+        self.grammars.insert(scope_name.clone(), Arc::new(raw));
+        if let Some(active) = self.grammar.as_ref().map(|g| g.scope_name().to_string()) {
+            self.activate_grammar(active)?;
+        }
+        Ok(scope_name)
+    }
+
+    /// Compiles a registered grammar and makes it the active one.
+    #[napi]
+    pub fn activate_grammar(&mut self, scope_name: String) -> napi::Result<()> {
+        let grammar = TextMateGrammar::compile(&scope_name, &self.grammars).map_err(napi::Error::from_reason)?;
+        self.grammar = Some(Arc::new(grammar));
+        Ok(())
+    }
+
+    /// Patterns that failed to compile or includes that did not resolve.
+    #[napi]
+    pub fn get_grammar_errors(&self) -> Vec<String> {
+        self.grammar.as_ref().map(|g| g.errors().to_vec()).unwrap_or_default()
+    }
+
+    #[napi]
+    pub fn initial_state(&self) -> TokenizerState {
+        TokenizerState::default()
+    }
+
+    #[napi]
+    pub fn tokenize_line(&self, line_content: String, state: Option<&TokenizerState>) -> TokenizeLineResult {
+        let initial = RuleStack::default();
+        let stack = state.map(|s| &s.stack).unwrap_or(&initial);
+        let (tokens, end) = self.tokenize_line_with_state(&line_content, stack);
+        TokenizeLineResult { tokens, end_state: TokenizerState { stack: end } }
+    }
+
+    /// Tokenizes one line from `state`. Flat-rule mode has no state, so the
+    /// returned stack is always the initial one.
+    pub fn tokenize_line_with_state(&self, line: &str, state: &RuleStack) -> (Vec<Token>, RuleStack) {
+        match &self.grammar {
+            Some(grammar) => grammar.tokenize_line(line, state),
+            None => (self.tokenize_with_rules(line), RuleStack::default()),
         }
     }
 
@@ -46,6 +157,10 @@ impl Tokenizer {
 
     #[napi]
     pub fn tokenize(&self, line_content: String) -> Vec<Token> {
+        self.tokenize_line_with_state(&line_content, &RuleStack::default()).0
+    }
+
+    fn tokenize_with_rules(&self, line_content: &str) -> Vec<Token> {
         let mut tokens = Vec::new();
         let mut pos = 0;
         let content_len = line_content.len();
@@ -87,6 +202,11 @@ impl Tokenizer {
 
     #[napi]
     pub fn tokenize_lines(&self, lines: Vec<String>) -> Vec<Vec<Token>> {
-        lines.into_iter().map(|l| self.tokenize(l)).collect()
+        let mut state = RuleStack::default();
+        lines.iter().map(|line| {
+            let (tokens, next) = self.tokenize_line_with_state(line, &state);
+            state = next;
+            tokens
+        }).collect()
     }
 }
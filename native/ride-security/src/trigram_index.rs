@@ -0,0 +1,539 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) RIDE Contributors. All rights reserved.
+ *  Licensed under the MIT License. See License.txt in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+//! RIDE Trigram Content Index
+//!
+//! Features:
+//! - Case-folded byte trigram postings for every text file in the workspace
+//! - Candidate narrowing from the literals a regex requires (prefix or suffix extraction)
+//! - Incremental updates from `fs_watcher` events with tombstoning and periodic compaction
+//! - Directory mtimes, so files created without a watcher event are still searched
+//! - Persistent MessagePack cache with staleness reporting until re-verified
+//! - Files it cannot index (large, UTF-16) are always kept as candidates
+
+use ignore::WalkBuilder;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use rayon::prelude::*;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use crate::fs_watcher::FsEvent;
+
+/// Files larger than this are not tokenized; they are searched on every query instead.
+const MAX_INDEXED_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+type Trigram = u32;
+
+#[napi(object)]
+pub struct TrigramIndexStatus {
+    pub root: Option<String>,
+    pub files: u32,
+    pub trigrams: u32,
+    /// The index may miss changes: it was loaded from cache, or a candidate changed on disk
+    /// without a watcher event. Cleared by `verify_trigram_index`.
+    pub stale: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexedDoc {
+    path: String,
+    mtime: f64,
+    size: f64,
+    /// `false` when the file was too large or not byte-searchable; it is always a candidate.
+    tokenized: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct TrigramIndex {
+    root: String,
+    /// Removed documents leave `None` tombstones until the next compaction.
+    docs: Vec<Option<IndexedDoc>>,
+    postings: HashMap<Trigram, Vec<u32>>,
+    /// Directory mtimes when indexed. A changed directory may hold files that arrived
+    /// without a watcher event.
+    #[serde(default)]
+    dirs: HashMap<String, f64>,
+    #[serde(skip)]
+    ids: HashMap<String, u32>,
+    /// See `TrigramIndexStatus::stale`.
+    #[serde(skip)]
+    stale: AtomicBool,
+}
+
+static TRIGRAM_INDEX: RwLock<Option<TrigramIndex>> = RwLock::new(None);
+
+/// ASCII-lowercases `bytes` and folds the only non-ASCII letters whose Unicode case folding
+/// is ASCII (KELVIN SIGN and LONG S), so literals of `(?i)` patterns find them too.
+fn fold_case(bytes: &[u8]) -> Vec<u8> {
+    let mut folded = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while let Some(&byte) = rest.first() {
+        rest = match rest {
+            [0xE2, 0x84, 0xAA, tail @ ..] => { folded.push(b'k'); tail }
+            [0xC5, 0xBF, tail @ ..] => { folded.push(b's'); tail }
+            [_, tail @ ..] => { folded.push(byte.to_ascii_lowercase()); tail }
+            [] => break,
+        };
+    }
+    folded
+}
+
+fn trigrams_of(bytes: &[u8]) -> Vec<Trigram> {
+    let mut trigrams: Vec<Trigram> = fold_case(bytes).windows(3)
+        .map(|w| u32::from_be_bytes([0, w[0], w[1], w[2]]))
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+fn dir_mtime(path: &Path) -> Option<f64> {
+    std::fs::metadata(path).ok().map(|m| metadata_stamp(&m).0)
+}
+
+fn metadata_stamp(meta: &std::fs::Metadata) -> (f64, f64) {
+    let mtime = meta.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0.0, |d| d.as_secs_f64());
+    (mtime, meta.len() as f64)
+}
+
+/// A file read for indexing, or `None` for binary files.
+type ReadDoc = Option<(IndexedDoc, Vec<Trigram>)>;
+
+/// Reads and tokenizes one file. Returns `None` for binary files, which search skips anyway.
+fn read_doc(path: &Path) -> ReadDoc {
+    let meta = std::fs::metadata(path).ok()?;
+    if !meta.is_file() {
+        return None;
+    }
+    let (mtime, size) = metadata_stamp(&meta);
+    let mut doc = IndexedDoc { path: path.to_string_lossy().to_string(), mtime, size, tokenized: false };
+    if meta.len() > MAX_INDEXED_FILE_SIZE {
+        return Some((doc, Vec::new()));
+    }
+    let bytes = std::fs::read(path).ok()?;
+    // Search transcodes UTF-16 files, so their raw bytes say nothing about matches.
+    if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+        return Some((doc, Vec::new()));
+    }
+    if bytes[..bytes.len().min(1024)].contains(&0) {
+        return None;
+    }
+    doc.tokenized = true;
+    Some((doc, trigrams_of(&bytes)))
+}
+
+impl TrigramIndex {
+    fn build(root: &str, files: &[PathBuf], dirs: &[PathBuf]) -> Self {
+        let docs: Vec<_> = files.par_iter().filter_map(|p| read_doc(p)).collect();
+        let mut index = Self { root: root.to_string(), ..Default::default() };
+        for (doc, trigrams) in docs {
+            index.insert(doc, trigrams);
+        }
+        index.dirs = dirs.par_iter().filter_map(|d| Some((d.to_string_lossy().to_string(), dir_mtime(d)?))).collect();
+        index
+    }
+
+    fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::SeqCst)
+    }
+
+    fn live_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn insert(&mut self, doc: IndexedDoc, trigrams: Vec<Trigram>) {
+        self.remove(&doc.path);
+        let id = self.docs.len() as u32;
+        // Ids only grow, so postings stay sorted.
+        for t in trigrams {
+            self.postings.entry(t).or_default().push(id);
+        }
+        self.ids.insert(doc.path.clone(), id);
+        self.docs.push(Some(doc));
+    }
+
+    fn remove(&mut self, path: &str) {
+        if let Some(id) = self.ids.remove(path) {
+            self.docs[id as usize] = None;
+        }
+        if self.docs.len() > 1024 && self.docs.len() > self.live_count() * 2 {
+            self.compact();
+        }
+    }
+
+    fn compact(&mut self) {
+        let mut remap = vec![u32::MAX; self.docs.len()];
+        let mut docs = Vec::with_capacity(self.live_count());
+        for (old, doc) in std::mem::take(&mut self.docs).into_iter().enumerate() {
+            if let Some(doc) = doc {
+                remap[old] = docs.len() as u32;
+                docs.push(Some(doc));
+            }
+        }
+        self.postings.retain(|_, ids| {
+            ids.retain_mut(|id| {
+                *id = remap[*id as usize];
+                *id != u32::MAX
+            });
+            !ids.is_empty()
+        });
+        self.docs = docs;
+        self.rebuild_ids();
+    }
+
+    fn rebuild_ids(&mut self) {
+        self.ids = self.docs.iter().enumerate()
+            .filter_map(|(id, doc)| doc.as_ref().map(|d| (d.path.clone(), id as u32)))
+            .collect();
+    }
+
+    /// Documents containing every trigram of `literal`, as a sorted id list.
+    fn docs_with(&self, literal: &[u8]) -> Vec<u32> {
+        let mut lists: Vec<&Vec<u32>> = Vec::new();
+        for t in trigrams_of(literal) {
+            match self.postings.get(&t) {
+                Some(ids) => lists.push(ids),
+                None => return Vec::new(),
+            }
+        }
+        lists.sort_by_key(|ids| ids.len());
+        let Some((first, rest)) = lists.split_first() else { return Vec::new() };
+        first.iter().copied().filter(|id| rest.iter().all(|ids| ids.binary_search(id).is_ok())).collect()
+    }
+
+    /// Files under `directory` that may match. `literals` are alternatives one of which every
+    /// match contains; `None` means no narrowing is possible.
+    fn candidates(&self, directory: &Path, literals: Option<&[Vec<u8>]>) -> Vec<&IndexedDoc> {
+        let mut selected = vec![literals.is_none(); self.docs.len()];
+        for literal in literals.unwrap_or_default() {
+            for id in self.docs_with(literal) {
+                selected[id as usize] = true;
+            }
+        }
+        self.docs.iter().zip(selected)
+            .filter_map(|(doc, selected)| doc.as_ref().filter(|d| selected || !d.tokenized))
+            .filter(|d| Path::new(&d.path).starts_with(directory))
+            .collect()
+    }
+
+    /// Files under `directory` that are not indexed although they may be searchable: the
+    /// new entries of every directory whose mtime changed since it was indexed.
+    fn unindexed_files(&self, directory: &Path) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for (dir, mtime) in &self.dirs {
+            let dir = Path::new(dir);
+            if !dir.starts_with(directory) || dir_mtime(dir) == Some(*mtime) {
+                continue;
+            }
+            let entries = WalkBuilder::new(dir).git_ignore(true).hidden(true).max_depth(Some(1)).build();
+            for entry in entries.filter_map(|e| e.ok()).filter(|e| e.depth() == 1) {
+                let key = entry.path().to_string_lossy().to_string();
+                match entry.file_type() {
+                    Some(t) if t.is_file() && !self.ids.contains_key(&key) => found.push(entry.into_path()),
+                    Some(t) if t.is_dir() && !self.dirs.contains_key(&key) => found.extend(walk_workspace(entry.path()).0),
+                    _ => {}
+                }
+            }
+        }
+        found
+    }
+
+    /// Candidate files for a search under `directory`, with the size and mtime they were
+    /// indexed at. `None` when the index does not cover `directory`.
+    fn candidate_files(&self, directory: &Path, pattern: &str, case_insensitive: bool) -> Option<Vec<(PathBuf, f64, f64)>> {
+        if !directory.starts_with(&self.root) || !self.dirs.keys().any(|d| Path::new(d) == directory) {
+            return None;
+        }
+        let literals = required_literals(pattern, case_insensitive);
+        let mut files: Vec<_> = self.candidates(directory, literals.as_deref()).into_iter()
+            .map(|d| (PathBuf::from(&d.path), d.mtime, d.size))
+            .collect();
+        let unindexed = self.unindexed_files(directory);
+        if !unindexed.is_empty() {
+            self.stale.store(true, Ordering::SeqCst);
+        }
+        files.extend(unindexed.into_iter().filter_map(|path| {
+            let (mtime, size) = metadata_stamp(&std::fs::metadata(&path).ok()?);
+            Some((path, mtime, size))
+        }));
+        Some(files)
+    }
+
+    /// Applies file watcher events. Returns the number of files reindexed.
+    fn apply_events(&mut self, events: Vec<FsEvent>) -> u32 {
+        let mut reindexed = 0;
+        for event in events {
+            if event.is_directory {
+                continue;
+            }
+            if let Some(old_path) = &event.old_path {
+                self.remove(old_path);
+            }
+            if event.event_type == "remove" {
+                self.remove(&event.path);
+                continue;
+            }
+            match read_doc(Path::new(&event.path)) {
+                Some((doc, trigrams)) => {
+                    self.insert(doc, trigrams);
+                    reindexed += 1;
+                }
+                None => self.remove(&event.path),
+            }
+        }
+        reindexed
+    }
+
+    /// Files of a fresh walk whose mtime or size differs from what was indexed.
+    fn changed_files(&self, files: &[PathBuf]) -> Vec<PathBuf> {
+        files.par_iter().filter(|p| {
+            let stamp = std::fs::metadata(p).ok().map(|m| metadata_stamp(&m));
+            let indexed = self.ids.get(p.to_string_lossy().as_ref())
+                .and_then(|&id| self.docs[id as usize].as_ref())
+                .map(|d| (d.mtime, d.size));
+            stamp != indexed
+        }).cloned().collect()
+    }
+
+    /// Stores the reread `docs`, drops files missing from the walk and records the current
+    /// directory mtimes. Returns the number of files reindexed or removed.
+    fn reconcile(&mut self, docs: Vec<(&PathBuf, ReadDoc)>, files: &[PathBuf], dirs: &[PathBuf]) -> u32 {
+        let mut count = 0;
+        for (path, doc) in docs {
+            let path = path.to_string_lossy();
+            match doc {
+                Some((doc, trigrams)) => self.insert(doc, trigrams),
+                None if self.ids.contains_key(path.as_ref()) => self.remove(&path),
+                // Binary files are never indexed.
+                None => continue,
+            }
+            count += 1;
+        }
+        let present: std::collections::HashSet<String> = files.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        let removed: Vec<String> = self.ids.keys().filter(|p| !present.contains(*p)).cloned().collect();
+        for path in &removed {
+            self.remove(path);
+        }
+        self.dirs = dirs.iter().filter_map(|d| Some((d.to_string_lossy().to_string(), dir_mtime(d)?))).collect();
+        self.stale.store(false, Ordering::SeqCst);
+        count + removed.len() as u32
+    }
+}
+
+/// Literals of which every match of `pattern` must contain one, case-folded to match the
+/// index. `None` when the pattern does not pin down literals of at least three bytes, or
+/// when it is case-insensitive with non-ASCII letters, whose Unicode folds the index does
+/// not mirror.
+fn required_literals(pattern: &str, case_insensitive: bool) -> Option<Vec<Vec<u8>>> {
+    if case_insensitive && pattern.chars().any(|c| !c.is_ascii() && c.is_alphabetic()) {
+        return None;
+    }
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+    [ExtractKind::Prefix, ExtractKind::Suffix].into_iter().find_map(|kind| {
+        let seq = Extractor::new().kind(kind).extract(&hir);
+        let mut literals: Vec<Vec<u8>> = Vec::new();
+        for literal in seq.literals()? {
+            let bytes = fold_case(literal.as_bytes());
+            if bytes.len() < 3 {
+                return None;
+            }
+            literals.push(bytes);
+        }
+        literals.sort();
+        literals.dedup();
+        Some(literals).filter(|l| !l.is_empty())
+    })
+}
+
+/// Candidate files for a search under `directory`, with the size and mtime they were indexed
+/// at. `None` when no index covers `directory`.
+pub(crate) fn candidate_files(directory: &str, pattern: &str, case_insensitive: bool) -> Option<Vec<(PathBuf, f64, f64)>> {
+    TRIGRAM_INDEX.read().unwrap().as_ref()?.candidate_files(Path::new(directory), pattern, case_insensitive)
+}
+
+/// Flags the index stale when a candidate no longer matches what was indexed.
+pub(crate) fn check_candidate(path: &Path, mtime: f64, size: f64) {
+    let current = std::fs::metadata(path).ok().map(|m| metadata_stamp(&m));
+    if current != Some((mtime, size)) && let Some(index) = TRIGRAM_INDEX.read().unwrap().as_ref() {
+        index.stale.store(true, Ordering::SeqCst);
+    }
+}
+
+pub(crate) fn is_stale() -> bool {
+    TRIGRAM_INDEX.read().unwrap().as_ref().is_some_and(TrigramIndex::is_stale)
+}
+
+/// Builds the index for `root` from an already collected walk of its files and directories.
+pub(crate) fn build_from_paths(root: &str, files: &[PathBuf], dirs: &[PathBuf]) {
+    *TRIGRAM_INDEX.write().unwrap() = Some(TrigramIndex::build(root, files, dirs));
+}
+
+/// Files and directories (including `root`) that search would visit.
+fn walk_workspace(root: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let (mut files, mut dirs) = (Vec::new(), Vec::new());
+    for entry in WalkBuilder::new(root).git_ignore(true).hidden(true).build().filter_map(|e| e.ok()) {
+        match entry.file_type() {
+            Some(t) if t.is_file() => files.push(entry.into_path()),
+            Some(t) if t.is_dir() => dirs.push(entry.into_path()),
+            _ => {}
+        }
+    }
+    (files, dirs)
+}
+
+#[napi]
+pub fn build_trigram_index(root: String) -> TrigramIndexStatus {
+    let (files, dirs) = walk_workspace(Path::new(&root));
+    build_from_paths(&root, &files, &dirs);
+    get_trigram_index_status()
+}
+
+#[napi]
+pub fn get_trigram_index_status() -> TrigramIndexStatus {
+    let guard = TRIGRAM_INDEX.read().unwrap();
+    TrigramIndexStatus {
+        root: guard.as_ref().map(|i| i.root.clone()),
+        files: guard.as_ref().map_or(0, |i| i.live_count() as u32),
+        trigrams: guard.as_ref().map_or(0, |i| i.postings.len() as u32),
+        stale: guard.as_ref().is_some_and(TrigramIndex::is_stale),
+    }
+}
+
+/// Applies file watcher events to the index. Returns the number of files reindexed.
+#[napi]
+pub fn update_trigram_index(events: Vec<FsEvent>) -> u32 {
+    TRIGRAM_INDEX.write().unwrap().as_mut().map_or(0, |index| index.apply_events(events))
+}
+
+/// Re-walks the root and reindexes files added, removed or changed since they were indexed,
+/// then clears the stale flag. Returns the number of files reindexed or removed.
+#[napi]
+pub fn verify_trigram_index() -> Result<u32> {
+    let no_index = || Error::from_reason("No trigram index to verify");
+    let root = TRIGRAM_INDEX.read().unwrap().as_ref().map(|i| i.root.clone()).ok_or_else(no_index)?;
+    let (files, dirs) = walk_workspace(Path::new(&root));
+    let changed = TRIGRAM_INDEX.read().unwrap().as_ref().ok_or_else(no_index)?.changed_files(&files);
+    let docs: Vec<_> = changed.par_iter().map(|p| (p, read_doc(p))).collect();
+
+    let mut guard = TRIGRAM_INDEX.write().unwrap();
+    let index = guard.as_mut().ok_or_else(no_index)?;
+    Ok(index.reconcile(docs, &files, &dirs))
+}
+
+#[napi]
+pub fn save_trigram_index_cache(cache_path: String) -> Result<()> {
+    let guard = TRIGRAM_INDEX.read().unwrap();
+    let index = guard.as_ref().ok_or_else(|| Error::from_reason("No trigram index to save"))?;
+
+    let encoded = rmp_serde::to_vec(index)
+        .map_err(|e| Error::from_reason(format!("Serialization error: {}", e)))?;
+
+    std::fs::write(cache_path, encoded)
+        .map_err(|e| Error::from_reason(format!("IO Error: {}", e)))?;
+
+    Ok(())
+}
+
+/// Loads a cached index. It is reported stale until `verify_trigram_index` runs, since files
+/// may have changed while nothing was watching.
+#[napi]
+pub fn load_trigram_index_cache(cache_path: String) -> Result<u32> {
+    let bytes = std::fs::read(&cache_path)
+        .map_err(|e| Error::from_reason(format!("IO Error: {}", e)))?;
+
+    let mut index: TrigramIndex = rmp_serde::from_slice(&bytes)
+        .map_err(|e| Error::from_reason(format!("Deserialization error: {}", e)))?;
+    index.rebuild_ids();
+    index.stale = AtomicBool::new(true);
+
+    let count = index.live_count() as u32;
+    *TRIGRAM_INDEX.write().unwrap() = Some(index);
+    Ok(count)
+}
+
+#[napi]
+pub fn drop_trigram_index() {
+    *TRIGRAM_INDEX.write().unwrap() = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_literals() {
+        let lits = |p: &str, ci: bool| required_literals(p, ci).map(|l| l.into_iter().map(|b| String::from_utf8(b).unwrap()).collect::<Vec<_>>());
+        assert_eq!(lits("fooBar", true), Some(vec!["foobar".to_string()]));
+        assert_eq!(lits(r"\bparse(Int|Float)\b", false), Some(vec!["parsefloat".to_string(), "parseint".to_string()]));
+        assert_eq!(lits(r".*_handler", false), Some(vec!["_handler".to_string()]));
+        assert_eq!(lits(r"\w+", false), None);
+        assert_eq!(lits("ab|cde", false), None);
+    }
+
+    #[test]
+    fn test_unicode_case_folding() {
+        assert_eq!(fold_case("\u{212A}ey \u{17F}ET".as_bytes()), b"key set");
+        assert_eq!(required_literals("stra\u{DF}e_x", true), None);
+        assert_eq!(required_literals("stra\u{DF}e_x", false), Some(vec!["stra\u{DF}e_x".as_bytes().to_vec()]));
+    }
+
+    /// Works on its own index rather than the process-wide one, so tests cannot race on it.
+    #[test]
+    fn test_index_candidates_and_updates() {
+        let root = std::env::temp_dir().join(format!("ride-trigram-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/a.rs"), "fn parse_config() {}\n").unwrap();
+        std::fs::write(root.join("src/b.rs"), "fn render() {}\n").unwrap();
+        std::fs::write(root.join("blob.bin"), [0u8, 1, 2, 3]).unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let (files, dirs) = walk_workspace(&root);
+        let mut index = TrigramIndex::build(&root_str, &files, &dirs);
+        assert_eq!(index.live_count(), 2);
+        let names = |index: &TrigramIndex, pattern: &str| {
+            let mut names: Vec<String> = index.candidate_files(&root, pattern, true).unwrap().into_iter()
+                .map(|(p, _, _)| p.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&index, "PARSE_config"), ["a.rs"]);
+        assert_eq!(names(&index, r"\w+"), ["a.rs", "b.rs"]);
+        assert!(index.candidate_files(&root.join("missing"), "x", false).is_none());
+
+        let b = root.join("src/b.rs").to_string_lossy().to_string();
+        std::fs::write(&b, "fn parse_config_again() {}\n").unwrap();
+        let event = FsEvent { event_type: "modify".into(), path: b, old_path: None, is_directory: false, timestamp_ms: 0.0 };
+        assert_eq!(index.apply_events(vec![event]), 1);
+        assert_eq!(names(&index, "parse_config"), ["a.rs", "b.rs"]);
+
+        // Files created without a watcher event show up through their directory's mtime.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        std::fs::create_dir_all(root.join("src/new")).unwrap();
+        std::fs::write(root.join("src/new/c.rs"), "fn other() {}\n").unwrap();
+        std::fs::write(root.join("src/d.rs"), "fn other() {}\n").unwrap();
+        assert_eq!(names(&index, "parse_config"), ["a.rs", "b.rs", "c.rs", "d.rs"]);
+        assert!(index.is_stale());
+
+        let mut loaded: TrigramIndex = rmp_serde::from_slice(&rmp_serde::to_vec(&index).unwrap()).unwrap();
+        loaded.rebuild_ids();
+        assert_eq!(loaded.live_count(), 2);
+        std::fs::remove_file(root.join("src/a.rs")).unwrap();
+        let (files, dirs) = walk_workspace(&root);
+        let changed = loaded.changed_files(&files);
+        let docs = changed.iter().map(|p| (p, read_doc(p))).collect();
+        assert_eq!(loaded.reconcile(docs, &files, &dirs), 3);
+        assert!(!loaded.is_stale());
+        assert_eq!(names(&loaded, "parse_config"), ["b.rs"]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
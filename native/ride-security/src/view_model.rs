@@ -15,9 +15,12 @@
 
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
-use crate::text_model::TextModel;
+use crate::text_model::{LineEdit, TextModel};
 use crate::range::Range;
+use crate::position::Position;
+use crate::line_projection::{self, InjectedText, ModelLineProjection, WrappingIndent};
 use std::collections::{HashMap, BTreeMap};
+use std::sync::Mutex;
 
 #[napi(object)]
 pub struct Viewport {
@@ -39,11 +42,68 @@ pub struct ViewCursor {
 pub struct ViewLineInfo {
     pub model_line_number: u32,
     pub is_folded: bool,
+    /// True for the continuation segments of a soft-wrapped model line.
     pub is_wrapped: bool,
     pub is_dirty: bool,
     pub content_preview: String,
 }
 
+/// View-line layout derived from the model. Edits re-project only the lines they touch;
+/// a view configuration, fold or injected text change rebuilds it.
+#[derive(Default)]
+struct ProjectionCache {
+    model_version_id: u32,
+    injected_text_version_id: u32,
+    valid: bool,
+    /// Non-trivial projection of each model line, by 0-based line index.
+    projections: Vec<Option<ModelLineProjection>>,
+    /// View lines of each model line when it is not folded away.
+    line_view_counts: Vec<u32>,
+    /// 0-based view line index of the first view line of each model line.
+    view_line_starts: Vec<u32>,
+    /// View lines per model line (0 for lines hidden by a fold).
+    view_line_counts: Vec<u32>,
+}
+
+impl ProjectionCache {
+    fn view_line_count(&self) -> u32 {
+        match (self.view_line_starts.last(), self.view_line_counts.last()) {
+            (Some(start), Some(count)) => start + count,
+            _ => 0,
+        }
+    }
+
+    fn projection(&self, model_line: u32) -> Option<&ModelLineProjection> {
+        self.projections.get(model_line as usize - 1)?.as_ref()
+    }
+
+    /// Model line (1-based) and wrapped segment shown on the 1-based `view_line`.
+    fn view_line_to_model(&self, view_line: u32) -> (u32, usize) {
+        let index = view_line.clamp(1, self.view_line_count().max(1)) - 1;
+        let model_index = self.view_line_starts.partition_point(|&s| s <= index).max(1) - 1;
+        (model_index as u32 + 1, (index - self.view_line_starts[model_index]) as usize)
+    }
+
+    /// Recomputes view line starts from the 0-based model line `from` on.
+    fn layout_from(&mut self, from: usize, folded_ranges: &BTreeMap<u32, u32>) {
+        let line_count = self.line_view_counts.len();
+        self.view_line_starts.truncate(from);
+        self.view_line_counts.truncate(from);
+        let mut next_view_line = match (self.view_line_starts.last(), self.view_line_counts.last()) {
+            (Some(start), Some(count)) => start + count,
+            _ => 0,
+        };
+        for index in from..line_count {
+            let line = index as u32 + 1;
+            let hidden = folded_ranges.range(..line).any(|(_, &end)| line <= end);
+            let count = if hidden { 0 } else { self.line_view_counts[index] };
+            self.view_line_starts.push(next_view_line);
+            self.view_line_counts.push(count);
+            next_view_line += count;
+        }
+    }
+}
+
 #[napi]
 pub struct ViewModel {
     model: TextModel,
@@ -53,8 +113,10 @@ pub struct ViewModel {
     char_width: f64,
     wrap_column: u32,
     tab_size: u32,
+    wrapping_indent: WrappingIndent,
     decorations: HashMap<String, Vec<Range>>, // ID -> Ranges
     dirty_lines: HashSet<u32>,
+    cache: Mutex<ProjectionCache>,
 }
 
 #[napi]
@@ -69,8 +131,10 @@ impl ViewModel {
             char_width: 8.5,
             wrap_column: 0, // 0 means no wrap
             tab_size: 4,
+            wrapping_indent: WrappingIndent::None,
             decorations: HashMap::new(),
             dirty_lines: HashSet::new(),
+            cache: Mutex::new(ProjectionCache::default()),
         }
     }
 
@@ -80,6 +144,13 @@ impl ViewModel {
         self.char_width = char_width;
         self.wrap_column = wrap_column;
         self.tab_size = tab_size;
+        self.invalidate();
+    }
+
+    #[napi]
+    pub fn set_wrapping_indent(&mut self, wrapping_indent: WrappingIndent) {
+        self.wrapping_indent = wrapping_indent;
+        self.invalidate();
     }
 
     #[napi]
@@ -102,62 +173,60 @@ impl ViewModel {
         if !is_contained {
             self.folded_ranges.insert(start_line, end_line);
             self.dirty_lines.insert(start_line);
+            self.invalidate();
         }
     }
 
     #[napi]
     pub fn unfold_all(&mut self) {
         self.folded_ranges.clear();
+        self.invalidate();
     }
 
+    /// Number of view lines: folded lines are hidden and wrapped lines count once per segment.
     #[napi]
     pub fn get_view_line_count(&self) -> u32 {
-        let model_count = self.model.line_count();
-        let mut hidden_count = 0;
-        for (&start, &end) in &self.folded_ranges {
-            hidden_count += end - start;
-        }
-        model_count - hidden_count
+        self.with_cache(|cache| cache.view_line_count())
     }
 
     #[napi]
     pub fn model_to_view_position(&self, model_line: u32, model_column: u32) -> ViewCursor {
-        let mut view_line = model_line;
-        for (&start, &end) in &self.folded_ranges {
-            if model_line > end {
-                view_line -= end - start;
-            } else if model_line > start {
-                // If in fold, collapsed to the start line
-                return ViewCursor {
-                    view_line: self.model_to_view_position(start, 1).view_line,
-                    view_column: 1,
-                    model_line,
-                    model_column: 1,
-                };
-            }
+        // A position inside a fold collapses onto the fold's start line.
+        if let Some((&start, _)) = self.folded_ranges.iter().find(|&(&s, &e)| model_line > s && model_line <= e) {
+            return ViewCursor {
+                view_line: self.model_to_view_position(start, 1).view_line,
+                view_column: 1,
+                model_line,
+                model_column: 1,
+            };
         }
 
-        // Handle tabs for view_column expansion
-        // In a real impl, we'd iterate over text to count expanded tabs
-        let view_column = model_column; // Simplified
-
-        ViewCursor {
-            view_line,
-            view_column,
-            model_line,
-            model_column,
-        }
+        self.with_cache(|cache| {
+            let line = model_line.clamp(1, cache.view_line_starts.len() as u32);
+            let (segment, view_column) = match cache.projection(line) {
+                Some(projection) => projection.model_to_view(model_column),
+                None => (0, model_column),
+            };
+            ViewCursor {
+                view_line: cache.view_line_starts[line as usize - 1] + segment as u32 + 1,
+                view_column,
+                model_line,
+                model_column,
+            }
+        })
     }
 
     #[napi]
-    pub fn view_position_to_model(&self, view_line: u32, view_column: u32) -> (u32, u32) {
-        let mut model_line = view_line;
-        for (&start, &end) in &self.folded_ranges {
-            if model_line > start {
-                model_line += end - start;
-            }
-        }
-        (model_line, view_column)
+    pub fn view_position_to_model(&self, view_line: u32, view_column: u32) -> Position {
+        self.with_cache(|cache| {
+            let (model_line, segment) = cache.view_line_to_model(view_line);
+            let column = match cache.projection(model_line) {
+                Some(projection) => projection.view_to_model(segment, view_column),
+                None => view_column,
+            };
+            let max_column = self.model.get_line_length(model_line) + 1;
+            Position::new(model_line, column.clamp(1, max_column))
+        })
     }
 
     #[napi]
@@ -170,20 +239,39 @@ impl ViewModel {
         let mut result = Vec::new();
 
         for v_line in start_view..=range_end {
-            let (m_line, _) = self.view_position_to_model(v_line, 1);
-            let content = self.model.get_line_content(m_line);
-
+            let (m_line, segment, content) = self.view_line_content(v_line);
             result.push(ViewLineInfo {
                 model_line_number: m_line,
-                is_folded: self.folded_ranges.contains_key(&m_line),
-                is_wrapped: false, // Placeholder
+                is_folded: segment == 0 && self.folded_ranges.contains_key(&m_line),
+                is_wrapped: segment > 0,
                 is_dirty: self.dirty_lines.contains(&m_line),
-                content_preview: if content.len() > 100 { content[..100].to_string() } else { content },
+                content_preview: if content.chars().count() > 100 { content.chars().take(100).collect() } else { content },
             });
         }
         result
     }
 
+    /// Moves a model position `delta` view lines up (negative) or down,
+    /// keeping its visible column. Steps through wrapped segments one at a time.
+    #[napi]
+    pub fn move_view_lines(&self, model_line: u32, model_column: u32, delta: i32) -> ViewCursor {
+        let from = self.model_to_view_position(model_line, model_column);
+        let tab_size = self.tab_size as usize;
+        let (_, _, from_content) = self.view_line_content(from.view_line);
+        let visible = line_projection::visible_column_at(&from_content, from.view_column as usize - 1, tab_size);
+
+        let target_line = (from.view_line as i64 + delta as i64).clamp(1, self.get_view_line_count().max(1) as i64) as u32;
+        let (_, _, target_content) = self.view_line_content(target_line);
+        let view_column = line_projection::char_count_at_visible_column(&target_content, visible, tab_size) as u32 + 1;
+        let model = self.view_position_to_model(target_line, view_column);
+        ViewCursor {
+            view_line: target_line,
+            view_column,
+            model_line: model.line_number,
+            model_column: model.column,
+        }
+    }
+
     #[napi]
     pub fn add_decoration(&mut self, type_id: String, range: Range) {
         self.decorations.entry(type_id).or_insert_with(Vec::new).push(range);
@@ -196,17 +284,253 @@ impl ViewModel {
 
     #[napi]
     pub fn hit_test(&self, x: f64, y: f64) -> ViewCursor {
-        let view_line = ((y + self.viewport.top) / self.line_height).floor() as u32 + 1;
-        let view_column = ((x + self.viewport.left) / self.char_width).floor() as u32 + 1;
+        let view_line = (((y + self.viewport.top) / self.line_height).floor() as u32 + 1).min(self.get_view_line_count().max(1));
+        let visible_column = ((x + self.viewport.left) / self.char_width).round().max(0.0) as usize;
 
-        let (m_line, _) = self.view_position_to_model(view_line, view_column);
+        // Pixels map to visible columns; tabs and wide characters span several.
+        let (_, _, content) = self.view_line_content(view_line);
+        let view_column = line_projection::char_count_at_visible_column(&content, visible_column, self.tab_size as usize) as u32 + 1;
+        let model = self.view_position_to_model(view_line, view_column);
         ViewCursor {
             view_line,
             view_column,
-            model_line: m_line,
-            model_column: view_column,
+            model_line: model.line_number,
+            model_column: model.column,
         }
     }
 }
 
+impl ViewModel {
+    fn invalidate(&mut self) {
+        self.cache.get_mut().unwrap().valid = false;
+    }
+
+    fn with_cache<R>(&self, f: impl FnOnce(&ProjectionCache) -> R) -> R {
+        let mut cache = self.cache.lock().unwrap();
+        let version = self.model.get_version_id();
+        if cache.injected_text_version_id != self.model.get_injected_text_version_id() {
+            cache.valid = false;
+        }
+        if cache.valid && cache.model_version_id != version {
+            match self.model.line_edits_between(cache.model_version_id, version) {
+                Some(edits) => self.apply_line_edits(&mut cache, &edits, version),
+                None => cache.valid = false,
+            }
+        }
+        if !cache.valid {
+            *cache = self.build_cache(version);
+        }
+        f(&cache)
+    }
+
+    /// Model line, wrapped segment and rendered content of a 1-based view line.
+    fn view_line_content(&self, view_line: u32) -> (u32, usize, String) {
+        self.with_cache(|cache| {
+            let (model_line, segment) = cache.view_line_to_model(view_line);
+            let content = match cache.projection(model_line) {
+                Some(projection) => projection.view_line_content(segment),
+                None => self.model.get_line_content(model_line),
+            };
+            (model_line, segment, content)
+        })
+    }
+
+    /// Lays out every model line.
+    fn build_cache(&self, model_version_id: u32) -> ProjectionCache {
+        let line_count = self.model.line_count() as usize;
+        let mut cache = ProjectionCache {
+            model_version_id,
+            injected_text_version_id: self.model.get_injected_text_version_id(),
+            valid: true,
+            ..Default::default()
+        };
+        for (count, projection) in self.project_lines(1, line_count as u32) {
+            cache.line_view_counts.push(count);
+            cache.projections.push(projection);
+        }
+        cache.layout_from(0, &self.folded_ranges);
+        cache
+    }
+
+    /// Replays `edits` on the cache's line structure, then re-projects only the lines they
+    /// touched and re-lays out view lines from the first one whose view line count changed.
+    fn apply_line_edits(&self, cache: &mut ProjectionCache, edits: &[LineEdit], model_version_id: u32) {
+        let mut dirty = vec![false; cache.line_view_counts.len()];
+        let mut layout_from = usize::MAX;
+        for edit in edits {
+            let start = edit.start_line_index.min(dirty.len());
+            let end = (start + edit.removed_line_breaks + 1).min(dirty.len());
+            let inserted = edit.inserted_line_breaks + 1;
+            if end - start == inserted {
+                // Same line count: keep the old view line counts to compare against.
+                dirty[start..end].fill(true);
+                continue;
+            }
+            dirty.splice(start..end, std::iter::repeat_n(true, inserted));
+            cache.line_view_counts.splice(start..end, std::iter::repeat_n(0, inserted));
+            cache.projections.splice(start..end, std::iter::repeat_with(|| None).take(inserted));
+            layout_from = layout_from.min(start);
+        }
+
+        let mut index = 0;
+        while index < dirty.len() {
+            if !dirty[index] {
+                index += 1;
+                continue;
+            }
+            let run_end = dirty[index..].iter().position(|d| !d).map_or(dirty.len(), |n| index + n);
+            let projected = self.project_lines(index as u32 + 1, run_end as u32);
+            for (line_index, (count, projection)) in (index..run_end).zip(projected) {
+                if cache.line_view_counts[line_index] != count {
+                    layout_from = layout_from.min(line_index);
+                }
+                cache.line_view_counts[line_index] = count;
+                cache.projections[line_index] = projection;
+            }
+            index = run_end;
+        }
+        if layout_from < usize::MAX {
+            cache.layout_from(layout_from.min(dirty.len()), &self.folded_ranges);
+        }
+        cache.model_version_id = model_version_id;
+    }
+
+    /// View line count and non-trivial projection of the 1-based lines `from..=to`.
+    /// Wrapping needs each line's content; without it only lines carrying injected text
+    /// are projected.
+    fn project_lines(&self, from: u32, to: u32) -> Vec<(u32, Option<ModelLineProjection>)> {
+        if from > to {
+            return Vec::new();
+        }
+        let mut injected: HashMap<u32, Vec<InjectedText>> = HashMap::new();
+        for decoration in self.model.get_lines_decorations(from, to) {
+            if let Some(content) = decoration.options.before_content {
+                injected.entry(decoration.range.start_line_number).or_default()
+                    .push(InjectedText { column: decoration.range.start_column, content });
+            }
+            if let Some(content) = decoration.options.after_content {
+                injected.entry(decoration.range.end_line_number).or_default()
+                    .push(InjectedText { column: decoration.range.end_column, content });
+            }
+        }
+        (from..=to).map(|line| {
+            let texts = injected.get(&line).map(Vec::as_slice).unwrap_or(&[]);
+            if self.wrap_column == 0 && texts.is_empty() {
+                return (1, None);
+            }
+            let projection = ModelLineProjection::compute(&self.model.get_line_content(line), texts, self.wrap_column, self.tab_size, self.wrapping_indent);
+            let count = projection.output_line_count() as u32;
+            (count, (!projection.is_trivial()).then_some(projection))
+        }).collect()
+    }
+}
+
 use std::collections::HashSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_model::{ModelDecoration, ModelDecorationOptions};
+    use crate::text_model_types::TrackedRangeStickiness;
+
+    #[test]
+    fn test_wrapped_lines_in_view_mapping() {
+        let model = TextModel::new("file:///doc.md".into(), "short\nthe quick brown fox jumps\nend".into());
+        let mut view = ViewModel::new(&model);
+        view.set_rendering_config(18.0, 8.0, 10, 4);
+
+        assert_eq!(view.get_view_line_count(), 5);
+        let cursor = view.model_to_view_position(2, 12);
+        assert_eq!((cursor.view_line, cursor.view_column), (3, 2));
+        assert_eq!(view.view_position_to_model(4, 1), Position::new(2, 21));
+        assert_eq!(view.view_position_to_model(5, 2), Position::new(3, 2));
+
+        // Moving down from the first segment lands on the second one, same column.
+        let moved = view.move_view_lines(2, 3, 1);
+        assert_eq!((moved.view_line, moved.model_line, moved.model_column), (3, 2, 13));
+
+        view.fold_range(1, 2);
+        assert_eq!(view.get_view_line_count(), 2);
+        assert_eq!(view.view_position_to_model(2, 1), Position::new(3, 1));
+    }
+
+    #[test]
+    fn test_inlay_hint_shifts_view_columns() {
+        let model = TextModel::new("file:///a.rs".into(), "let x = 1;".into());
+        model.delta_decorations(vec![], vec![ModelDecoration {
+            id: "hint".into(),
+            range: Range { start_line_number: 1, start_column: 6, end_line_number: 1, end_column: 6 },
+            options: ModelDecorationOptions {
+                stickiness: TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges,
+                class_name: None,
+                inline_class_name: None,
+                hover_message: None,
+                before_content: Some(": i32".into()),
+                after_content: None,
+            },
+        }]);
+        let view = ViewModel::new(&model);
+        assert_eq!(view.model_to_view_position(1, 8).view_column, 13);
+        assert_eq!(view.get_lines_in_viewport()[0].content_preview, "let x: i32 = 1;");
+        assert_eq!(view.hit_test(8.0 * 8.0, 0.0).model_column, 6);
+    }
+
+    #[test]
+    fn test_inlay_added_after_layout_reprojects() {
+        let model = TextModel::new("file:///a.rs".into(), "let value = compute();".into());
+        let mut view = ViewModel::new(&model);
+        view.set_rendering_config(18.0, 8.0, 24, 4);
+        assert_eq!(view.get_view_line_count(), 1);
+
+        let hint = |id: &str| ModelDecoration {
+            id: id.into(),
+            range: Range { start_line_number: 1, start_column: 10, end_line_number: 1, end_column: 10 },
+            options: ModelDecorationOptions {
+                stickiness: TrackedRangeStickiness::NeverGrowsWhenTypingAtEdges,
+                class_name: None,
+                inline_class_name: None,
+                hover_message: None,
+                before_content: Some(": Option<usize>".into()),
+                after_content: None,
+            },
+        };
+        let ids = model.delta_decorations(vec![], vec![hint("hint")]);
+        assert_eq!(view.get_view_line_count(), 2);
+        assert_eq!(view.model_to_view_position(1, 11).view_line, 2);
+
+        model.delta_decorations(ids, vec![]);
+        assert_eq!(view.get_view_line_count(), 1);
+        let cursor = view.model_to_view_position(1, 11);
+        assert_eq!((cursor.view_line, cursor.view_column), (1, 11));
+    }
+
+    #[test]
+    fn test_edits_update_layout_like_a_rebuild() {
+        use crate::text_model_types::{RangePod, SingleEditOperation};
+        let edit = |start: (u32, u32), end: (u32, u32), text: &str| SingleEditOperation {
+            range: RangePod { start_line_number: start.0, start_column: start.1, end_line_number: end.0, end_column: end.1 },
+            text: Some(text.into()),
+            force_move_markers: None,
+        };
+        let lines: Vec<String> = (0..40).map(|i| if i % 3 == 0 { format!("line {} with enough text to wrap", i) } else { format!("l{}", i) }).collect();
+        let model = TextModel::new("file:///a.txt".into(), lines.join("\n"));
+        let mut view = ViewModel::new(&model);
+        view.set_rendering_config(18.0, 8.0, 12, 4);
+        view.fold_range(30, 33);
+        view.get_view_line_count();
+
+        model.apply_edits(vec![edit((2, 1), (2, 1), "a much longer line that now wraps\nnew\n")]);
+        model.apply_edits(vec![edit((10, 1), (13, 1), ""), edit((20, 3), (20, 3), "!")]);
+        model.undo();
+        model.apply_edits(vec![edit((1, 1), (1, 5), "x")]);
+
+        let mut fresh = ViewModel::new(&model);
+        fresh.set_rendering_config(18.0, 8.0, 12, 4);
+        fresh.fold_range(30, 33);
+        assert_eq!(view.get_view_line_count(), fresh.get_view_line_count());
+        for view_line in 1..=fresh.get_view_line_count() {
+            assert_eq!(view.view_line_content(view_line), fresh.view_line_content(view_line), "view line {}", view_line);
+        }
+        assert!(model.line_edits_between(1, model.get_version_id()).is_some());
+    }
+}